@@ -0,0 +1,53 @@
+use tokio_stream::StreamExt;
+
+use miniredis::Server;
+use miniredis_client::{cmd, Client, Pipeline, Pool};
+
+#[tokio::main]
+async fn main() -> miniredis_client::Result<()> {
+    // Run against a throwaway in-process server.
+    let server = Server::builder().bind("127.0.0.1:0").start().await?;
+    let addr = server.local_addr().to_string();
+
+    // Tasks share connections through the pool instead of a manager task.
+    let pool = Pool::builder().max_size(2).build(addr.clone());
+
+    let setter = {
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            let mut client = pool.get().await?;
+            client.set("foo", "bar").await
+        })
+    };
+    setter.await??;
+
+    let mut client = pool.get().await?;
+    println!("GOT = {:?}", client.get("foo").await?);
+
+    // One round trip for several commands, replies come back in order.
+    let replies = Pipeline::new()
+        .add(cmd::set("counter", "1"))
+        .add(cmd::get("counter"))
+        .add(cmd::get("missing"))
+        .query(&mut client)
+        .await?;
+    println!("PIPELINE = {:?}", replies);
+
+    let replies = Pipeline::new()
+        .atomic()
+        .add(cmd::rename("counter", "renamed"))
+        .add(cmd::get("renamed"))
+        .query(&mut client)
+        .await?;
+    println!("TRANSACTION = {:?}", replies);
+
+    let subscriber = Client::connect(&addr).await?.subscribe(&["news"]).await?;
+    let mut messages = Box::pin(subscriber.into_stream());
+
+    client.publish("news", "hello").await?;
+    println!("MESSAGE = {:?}", messages.next().await);
+
+    drop(client);
+    server.shutdown().await;
+    Ok(())
+}
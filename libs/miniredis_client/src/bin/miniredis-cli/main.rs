@@ -0,0 +1,473 @@
+//! Command-line client for miniredis.
+//!
+//! ```text
+//! miniredis-cli [options] [command [arg ...]]
+//! ```
+//!
+//! Without a command, commands are read from stdin when it is not a terminal,
+//! or typed at an interactive prompt.
+//!
+//! Built with the `cli` feature:
+//!
+//! ```text
+//! cargo run -p miniredis_client --features cli --bin miniredis-cli
+//! ```
+
+mod output;
+mod split;
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{self, BufRead, IsTerminal};
+use std::path::PathBuf;
+use std::process;
+
+use miniredis_client::{cmd, Client, Cmd, Frame, Pipeline, ServerError};
+use output::Mode;
+
+const USAGE: &str = "\
+Usage: miniredis-cli [OPTIONS] [cmd [arg [arg ...]]]
+  -h <hostname>      Server hostname (default: 127.0.0.1).
+  -p <port>          Server port (default: 6379).
+  -n <db>            Database number.
+  --raw              Use raw formatting for replies (default when stdout is
+                     not a tty).
+  --no-raw           Force formatted output even when stdout is not a tty.
+  --pipe             Read commands from stdin, one per line, and send them
+                     pipelined. Meant for bulk imports.
+  --scan             List all keys using the SCAN command.
+  --pattern <pat>    Keys pattern when using --scan (default: *).
+  --count <count>    Keys asked for per SCAN call (default: 100).
+  --bigkeys          Sample keys looking for keys with many bytes.
+  --help             Output this help and exit.";
+
+/// Number of commands sent at once with `--pipe`.
+const PIPE_BATCH: usize = 1000;
+
+/// Number of the biggest keys listed by `--bigkeys`.
+const BIGKEYS_TOP: usize = 10;
+
+#[derive(Debug)]
+struct Options {
+    host: String,
+    port: u16,
+    db: usize,
+    mode: Mode,
+    pipe: bool,
+    scan: bool,
+    pattern: String,
+    count: usize,
+    bigkeys: bool,
+    command: Vec<String>,
+}
+
+#[tokio::main]
+async fn main() {
+    let options = match parse_options(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = run(options).await {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn parse_options(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        host: "127.0.0.1".to_string(),
+        port: 6379,
+        db: 0,
+        mode: if io::stdout().is_terminal() { Mode::Pretty } else { Mode::Raw },
+        pipe: false,
+        scan: false,
+        pattern: "*".to_string(),
+        count: 100,
+        bigkeys: false,
+        command: vec![],
+    };
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("missing value for '{}'", name))
+        };
+
+        match &arg[..] {
+            "-h" => options.host = value("-h")?,
+            "-p" => options.port = value("-p")?.parse().map_err(|_| "invalid port")?,
+            "-n" => options.db = value("-n")?.parse().map_err(|_| "invalid database")?,
+            "--raw" => options.mode = Mode::Raw,
+            "--no-raw" => options.mode = Mode::Pretty,
+            "--pipe" => options.pipe = true,
+            "--scan" => options.scan = true,
+            "--pattern" => options.pattern = value("--pattern")?,
+            "--count" => options.count = value("--count")?.parse().map_err(|_| "invalid count")?,
+            "--bigkeys" => options.bigkeys = true,
+            "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if arg.starts_with('-') => return Err(format!("unrecognized option '{}'", arg)),
+            _ => {
+                // Everything from the first argument on is the command.
+                options.command.push(arg);
+                options.command.extend(args);
+                break;
+            }
+        }
+    }
+
+    Ok(options)
+}
+
+async fn run(options: Options) -> miniredis_client::Result<()> {
+    let addr = format!("{}:{}", options.host, options.port);
+    let mut client = Client::connect(&addr)
+        .await
+        .map_err(|err| format!("Could not connect to miniredis at {}: {}", addr, err))?;
+
+    if options.db != 0 {
+        client.select(options.db).await?;
+    }
+
+    if options.scan {
+        scan(&mut client, &options.pattern, options.count).await
+    } else if options.bigkeys {
+        bigkeys(&mut client, options.count).await
+    } else if options.pipe {
+        pipe(&mut client).await
+    } else if !options.command.is_empty() {
+        let args = options.command.into_iter().map(String::into_bytes).collect();
+        run_command(client, args, options.mode).await
+    } else if !io::stdin().is_terminal() {
+        for line in io::stdin().lock().lines() {
+            match split::split_args(&line?) {
+                Some(args) if args.is_empty() => {}
+                Some(args) => {
+                    let reply = query(&mut client, args).await?;
+                    println!("{}", output::format(&reply, options.mode));
+                }
+                None => println!("Invalid argument(s)"),
+            }
+        }
+        Ok(())
+    } else {
+        repl(client, &options).await
+    }
+}
+
+/// Runs a single command and prints its reply. Commands that turn the
+/// connection into a stream, such as `SUBSCRIBE`, print until interrupted.
+async fn run_command(mut client: Client, args: Vec<Vec<u8>>, mode: Mode) -> miniredis_client::Result<()> {
+    match &args[0].to_ascii_lowercase()[..] {
+        b"subscribe" | b"psubscribe" => stream_messages(client, args, mode).await,
+        b"monitor" => stream_monitor(client).await,
+        _ => {
+            let reply = query(&mut client, args).await?;
+            println!("{}", output::format(&reply, mode));
+            Ok(())
+        }
+    }
+}
+
+/// Sends a command, error replies included in the returned frame.
+async fn query(client: &mut Client, args: Vec<Vec<u8>>) -> miniredis_client::Result<Frame> {
+    match client.query::<Frame>(to_cmd(args)).await {
+        Ok(frame) => Ok(frame),
+        Err(err) => match err.downcast::<ServerError>() {
+            Ok(err) => Ok(Frame::Error(err.0)),
+            Err(err) => Err(err),
+        },
+    }
+}
+
+fn to_cmd(args: Vec<Vec<u8>>) -> Cmd {
+    let mut args = args.into_iter();
+    let name = args.next().unwrap_or_default();
+    let mut cmd = Cmd::new(&String::from_utf8_lossy(&name));
+    for arg in args {
+        cmd = cmd.arg(&arg);
+    }
+    cmd
+}
+
+async fn repl(mut client: Client, options: &Options) -> miniredis_client::Result<()> {
+    let mut editor = DefaultEditor::new()?;
+    let history = history_path();
+    if let Some(history) = &history {
+        let _ = editor.load_history(history);
+    }
+
+    let mut db = options.db;
+    loop {
+        let prompt = match db {
+            0 => format!("{}:{}> ", options.host, options.port),
+            db => format!("{}:{}[{}]> ", options.host, options.port, db),
+        };
+
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err.into()),
+        };
+
+        let args = match split::split_args(&line) {
+            Some(args) if args.is_empty() => continue,
+            Some(args) => args,
+            None => {
+                println!("Invalid argument(s)");
+                continue;
+            }
+        };
+        let _ = editor.add_history_entry(line.as_str());
+
+        let name = args[0].to_ascii_lowercase();
+        match &name[..] {
+            b"quit" | b"exit" => break,
+            b"subscribe" | b"psubscribe" | b"monitor" => {
+                if let Some(history) = &history {
+                    let _ = editor.save_history(history);
+                }
+                return run_command(client, args, options.mode).await;
+            }
+            _ => {}
+        }
+
+        let selected = (name == b"select")
+            .then(|| args.get(1).and_then(|db| String::from_utf8_lossy(db).parse().ok()))
+            .flatten();
+
+        let reply = query(&mut client, args).await?;
+        if let (Some(selected), Frame::Simple(_)) = (selected, &reply) {
+            db = selected;
+        }
+        println!("{}", output::format(&reply, options.mode));
+    }
+
+    if let Some(history) = &history {
+        let _ = editor.save_history(history);
+    }
+    Ok(())
+}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".miniredis_cli_history"))
+}
+
+async fn stream_messages(client: Client, args: Vec<Vec<u8>>, mode: Mode) -> miniredis_client::Result<()> {
+    let pattern = args[0].eq_ignore_ascii_case(b"psubscribe");
+    let mut subscriber = if pattern {
+        client.psubscribe(&args[1..]).await?
+    } else {
+        client.subscribe(&args[1..]).await?
+    };
+
+    println!("Reading messages... (press Ctrl-C to quit)");
+    while let Some(message) = subscriber.next_message().await? {
+        let mut parts = vec![];
+        match message.pattern {
+            Some(pattern) => {
+                parts.push(Frame::Bulk("pmessage".into()));
+                parts.push(Frame::Bulk(pattern));
+            }
+            None => parts.push(Frame::Bulk("message".into())),
+        }
+        parts.push(Frame::Bulk(message.channel));
+        parts.push(Frame::Bulk(message.payload));
+
+        println!("{}", output::format(&Frame::Array(parts), mode));
+    }
+
+    Ok(())
+}
+
+async fn stream_monitor(client: Client) -> miniredis_client::Result<()> {
+    let mut monitor = client.monitor().await?;
+
+    println!("OK");
+    while let Some(line) = monitor.next_line().await? {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+/// One `SCAN` call from `cursor`, returning the next cursor and the keys.
+async fn scan_page(
+    client: &mut Client,
+    cursor: &str,
+    pattern: &str,
+    count: usize,
+) -> miniredis_client::Result<(String, Vec<Vec<u8>>)> {
+    let cmd = Cmd::new("SCAN")
+        .arg(&cursor)
+        .arg("MATCH")
+        .arg(pattern)
+        .arg("COUNT")
+        .arg(&count);
+    let parts: Vec<Frame> = client.query(cmd).await?;
+    scan_reply(parts)
+}
+
+fn scan_reply(parts: Vec<Frame>) -> miniredis_client::Result<(String, Vec<Vec<u8>>)> {
+    let mut parts = parts.into_iter();
+    match (parts.next(), parts.next()) {
+        (Some(Frame::Bulk(cursor)), Some(Frame::Array(keys))) => {
+            let keys = keys
+                .into_iter()
+                .map(|key| match key {
+                    Frame::Bulk(key) => Ok(key.to_vec()),
+                    _ => Err("unexpected SCAN reply"),
+                })
+                .collect::<Result<_, _>>()?;
+            Ok((String::from_utf8_lossy(&cursor).into_owned(), keys))
+        }
+        _ => Err("unexpected SCAN reply".into()),
+    }
+}
+
+async fn scan(client: &mut Client, pattern: &str, count: usize) -> miniredis_client::Result<()> {
+    let mut cursor = "0".to_string();
+    loop {
+        let (next, keys) = scan_page(client, &cursor, pattern, count).await?;
+        for key in keys {
+            println!("{}", String::from_utf8_lossy(&key));
+        }
+
+        if next == "0" {
+            return Ok(());
+        }
+        cursor = next;
+    }
+}
+
+/// Sizes every key page by page as `SCAN` returns them, keeping only the
+/// `BIGKEYS_TOP` biggest.
+async fn bigkeys(client: &mut Client, count: usize) -> miniredis_client::Result<()> {
+    let total_keys = client.dbsize().await?.max(1);
+
+    println!("# Scanning the entire keyspace to find biggest keys as well as");
+    println!("# average sizes per key type.");
+    println!();
+
+    let mut sampled = 0;
+    let mut key_bytes = 0;
+    let mut string_bytes = 0;
+    // The smallest of the biggest keys on top, to be replaced first.
+    let mut biggest: BinaryHeap<Reverse<(i64, Vec<u8>)>> = BinaryHeap::new();
+    let mut max = None;
+
+    let mut cursor = "0".to_string();
+    loop {
+        let (next, batch) = scan_page(client, &cursor, "*", count).await?;
+
+        let mut pipeline = Pipeline::new();
+        for key in &batch {
+            pipeline.add(cmd::Cmd::new("STRLEN").arg(key));
+        }
+
+        // Keys deleted since the scan report a length of 0, like absent keys.
+        for (key, reply) in batch.into_iter().zip(pipeline.query(client).await?) {
+            let len = match reply {
+                Frame::Integer(len) => len,
+                _ => continue,
+            };
+
+            sampled += 1;
+            key_bytes += key.len();
+            string_bytes += len;
+
+            if max.is_none_or(|max| len > max) {
+                println!(
+                    "[{:05.2}%] Biggest string found so far '{}' with {} bytes",
+                    sampled as f64 * 100.0 / total_keys as f64,
+                    output::quote(&key),
+                    len
+                );
+                max = Some(len);
+            }
+
+            if biggest.len() < BIGKEYS_TOP {
+                biggest.push(Reverse((len, key)));
+            } else if biggest.peek().is_some_and(|Reverse((min, _))| len > *min) {
+                biggest.pop();
+                biggest.push(Reverse((len, key)));
+            }
+        }
+
+        if next == "0" {
+            break;
+        }
+        cursor = next;
+    }
+
+    println!();
+    println!("-------- summary -------");
+    println!();
+    println!("Sampled {} keys in the keyspace!", sampled);
+    println!(
+        "Total key length in bytes is {} (avg len {:.2})",
+        key_bytes,
+        key_bytes as f64 / sampled.max(1) as f64
+    );
+    println!();
+    if !biggest.is_empty() {
+        for Reverse((len, key)) in biggest.into_sorted_vec() {
+            println!("Big string found '{}' has {} bytes", output::quote(&key), len);
+        }
+        println!();
+    }
+    println!(
+        "{} strings with {} bytes ({:.2}% of keys, avg size {:.2})",
+        sampled,
+        string_bytes,
+        if sampled > 0 { 100.0 } else { 0.0 },
+        string_bytes as f64 / sampled.max(1) as f64
+    );
+
+    Ok(())
+}
+
+/// Sends commands read from stdin in pipelined batches, printing error
+/// replies and a summary.
+async fn pipe(client: &mut Client) -> miniredis_client::Result<()> {
+    let mut errors = 0;
+    let mut replies = 0;
+    let mut pipeline = Pipeline::new();
+
+    let mut lines = io::stdin().lock().lines().enumerate().peekable();
+    while let Some((number, line)) = lines.next() {
+        match split::split_args(&line?) {
+            Some(args) if args.is_empty() => {}
+            Some(args) => {
+                pipeline.add(to_cmd(args));
+            }
+            None => {
+                eprintln!("line {}: Invalid argument(s)", number + 1);
+                errors += 1;
+            }
+        }
+
+        if pipeline.len() >= PIPE_BATCH || (lines.peek().is_none() && !pipeline.is_empty()) {
+            for reply in pipeline.query(client).await? {
+                if let Frame::Error(msg) = reply {
+                    eprintln!("{}", msg);
+                    errors += 1;
+                }
+                replies += 1;
+            }
+            pipeline = Pipeline::new();
+        }
+    }
+
+    println!("All data transferred. errors: {}, replies: {}", errors, replies);
+    Ok(())
+}
@@ -0,0 +1,110 @@
+//! Formatting of replies, either the way `redis-cli` shows them on a terminal
+//! or raw.
+
+use std::fmt::Write;
+
+use miniredis_client::Frame;
+
+/// How replies are printed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Types spelled out, strings quoted and escaped, arrays numbered.
+    Pretty,
+    /// Values as is, one per line.
+    Raw,
+}
+
+/// Formats `frame`, without a trailing newline.
+pub fn format(frame: &Frame, mode: Mode) -> String {
+    let mut out = String::new();
+    match mode {
+        Mode::Pretty => pretty(&mut out, frame, 0),
+        Mode::Raw => raw(&mut out, frame),
+    }
+    out
+}
+
+fn pretty(out: &mut String, frame: &Frame, indent: usize) {
+    match frame {
+        Frame::Simple(s) => out.push_str(s),
+        Frame::Error(msg) => {
+            let _ = write!(out, "(error) {}", msg);
+        }
+        Frame::Integer(n) => {
+            let _ = write!(out, "(integer) {}", n);
+        }
+        Frame::Bulk(bytes) => out.push_str(&quote(bytes)),
+        Frame::Null => out.push_str("(nil)"),
+        Frame::Array(items) | Frame::Push(items) if items.is_empty() => {
+            out.push_str("(empty array)")
+        }
+        Frame::Map(pairs) => pretty(out, &flatten(pairs), indent),
+        Frame::Array(items) | Frame::Push(items) => {
+            let width = items.len().to_string().len();
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push('\n');
+                    out.push_str(&" ".repeat(indent));
+                }
+
+                let label = format!("{:>width$}) ", i + 1, width = width);
+                out.push_str(&label);
+                pretty(out, item, indent + label.len());
+            }
+        }
+    }
+}
+
+fn raw(out: &mut String, frame: &Frame) {
+    match frame {
+        Frame::Simple(s) => out.push_str(s),
+        Frame::Error(msg) => out.push_str(msg),
+        Frame::Integer(n) => {
+            let _ = write!(out, "{}", n);
+        }
+        Frame::Bulk(bytes) => out.push_str(&String::from_utf8_lossy(bytes)),
+        Frame::Null => {}
+        Frame::Map(pairs) => raw(out, &flatten(pairs)),
+        Frame::Array(items) | Frame::Push(items) => {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push('\n');
+                }
+                raw(out, item);
+            }
+        }
+    }
+}
+
+/// Maps are shown as arrays of alternating keys and values, as they would be
+/// received over RESP2.
+fn flatten(pairs: &[(Frame, Frame)]) -> Frame {
+    Frame::Array(
+        pairs
+            .iter()
+            .flat_map(|(key, value)| [key.clone(), value.clone()])
+            .collect(),
+    )
+}
+
+/// Quotes `bytes`, escaping anything that is not printable ASCII.
+pub fn quote(bytes: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for &c in bytes {
+        match c {
+            b'\\' => out.push_str("\\\\"),
+            b'"' => out.push_str("\\\""),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            0x07 => out.push_str("\\a"),
+            0x08 => out.push_str("\\b"),
+            c if c.is_ascii_graphic() || c == b' ' => out.push(c as char),
+            c => {
+                let _ = write!(out, "\\x{:02x}", c);
+            }
+        }
+    }
+    out.push('"');
+    out
+}
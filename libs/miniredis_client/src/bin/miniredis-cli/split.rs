@@ -0,0 +1,88 @@
+//! Splits a command line into arguments, with the quoting rules of
+//! `redis-cli`.
+
+/// Splits `line` on whitespace.
+///
+/// * `"..."` may contain spaces and the escapes `\n`, `\r`, `\t`, `\b`,
+///   `\a`, `\\`, `\"` and `\xHH`.
+/// * `'...'` may contain spaces and `\'`, nothing else is escaped.
+///
+/// A closing quote must be followed by whitespace or the end of the line.
+/// `None` means the quoting is unbalanced.
+pub fn split_args(line: &str) -> Option<Vec<Vec<u8>>> {
+    let mut args = vec![];
+    let mut chars = line.as_bytes().iter().copied().peekable();
+
+    loop {
+        while chars.next_if(u8::is_ascii_whitespace).is_some() {}
+
+        let first = match chars.peek() {
+            Some(&c) => c,
+            None => return Some(args),
+        };
+
+        let mut arg = vec![];
+        match first {
+            b'"' => {
+                chars.next();
+                loop {
+                    match chars.next()? {
+                        b'"' => break,
+                        b'\\' => match chars.next()? {
+                            b'n' => arg.push(b'\n'),
+                            b'r' => arg.push(b'\r'),
+                            b't' => arg.push(b'\t'),
+                            b'b' => arg.push(0x08),
+                            b'a' => arg.push(0x07),
+                            b'x' => {
+                                let high = chars.next()?;
+                                let low = chars.next()?;
+                                match (hex(high), hex(low)) {
+                                    (Some(high), Some(low)) => arg.push(high << 4 | low),
+                                    // Not a valid escape, keep it as is.
+                                    _ => arg.extend_from_slice(&[b'\\', b'x', high, low]),
+                                }
+                            }
+                            c => arg.push(c),
+                        },
+                        c => arg.push(c),
+                    }
+                }
+                closing_quote(&mut chars)?;
+            }
+            b'\'' => {
+                chars.next();
+                loop {
+                    match chars.next()? {
+                        b'\'' => break,
+                        b'\\' if chars.peek() == Some(&b'\'') => {
+                            chars.next();
+                            arg.push(b'\'');
+                        }
+                        c => arg.push(c),
+                    }
+                }
+                closing_quote(&mut chars)?;
+            }
+            _ => {
+                while let Some(c) = chars.next_if(|c| !c.is_ascii_whitespace()) {
+                    arg.push(c);
+                }
+            }
+        }
+
+        args.push(arg);
+    }
+}
+
+fn closing_quote(chars: &mut std::iter::Peekable<impl Iterator<Item = u8>>) -> Option<()> {
+    match chars.peek() {
+        None => Some(()),
+        Some(c) if c.is_ascii_whitespace() => Some(()),
+        Some(_) => None,
+    }
+}
+
+fn hex(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|d| d as u8)
+}
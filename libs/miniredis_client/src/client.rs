@@ -0,0 +1,657 @@
+use bytes::{Bytes, BytesMut};
+use std::time::Duration;
+use tokio::net::{TcpStream, ToSocketAddrs};
+
+use miniredis_protocol::connection::Connection;
+use miniredis_protocol::frame::Frame;
+
+use crate::cmd::{self, Cmd, SetOptions, ToArg, TrackingOptions};
+use crate::reply::{self, FromFrame};
+use crate::subscriber::Subscriber;
+
+/// A connection to a miniredis server.
+///
+/// Requests are issued one at a time, each method waits for its reply. Use a
+/// `Pipeline` to send several commands in one round trip.
+#[derive(Debug)]
+pub struct Client {
+    connection: Connection,
+    /// Set once the connection failed.
+    broken: bool,
+    /// Replies not read yet. Non-zero outside of a request means a request
+    /// was cancelled halfway, leaving its reply on the connection.
+    in_flight: usize,
+}
+
+/// An entry of the slowlog, as returned by `SLOWLOG GET`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlowLogEntry {
+    pub id: i64,
+    /// Unix time the command was processed at, in seconds.
+    pub timestamp: i64,
+    pub duration: Duration,
+    pub args: Vec<Bytes>,
+    pub client_addr: String,
+    pub client_name: String,
+}
+
+/// Latest sample of a latency event, as returned by `LATENCY LATEST`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyEvent {
+    pub name: String,
+    /// Unix time of the latest sample, in seconds.
+    pub timestamp: i64,
+    pub latest_ms: i64,
+    pub max_ms: i64,
+}
+
+/// Stream of the commands processed by the server, see `Client::monitor`.
+#[derive(Debug)]
+pub struct Monitor {
+    client: Client,
+}
+
+impl Client {
+    /// Establishes a connection to the server at `addr`.
+    pub async fn connect<T: ToSocketAddrs>(addr: T) -> crate::Result<Client> {
+        let socket = TcpStream::connect(addr).await?;
+        socket.set_nodelay(true)?;
+
+        Ok(Client {
+            connection: Connection::new(socket),
+            broken: false,
+            in_flight: 0,
+        })
+    }
+
+    /// Sends any command and converts its reply.
+    ///
+    /// Error replies are returned as a `ServerError`.
+    pub async fn query<T: FromFrame>(&mut self, cmd: Cmd) -> crate::Result<T> {
+        self.send(vec![cmd]).await?;
+        T::from_frame(self.read_reply().await?)
+    }
+
+    /// `true` once the connection failed or a request was cancelled before
+    /// its reply was read. The client should then be dropped.
+    pub fn is_broken(&self) -> bool {
+        self.broken || self.in_flight > 0
+    }
+
+    /// Writes `cmds` out in a single write.
+    pub(crate) async fn send(&mut self, cmds: Vec<Cmd>) -> crate::Result<()> {
+        let mut buf = BytesMut::new();
+        self.in_flight += cmds.len();
+        for cmd in cmds {
+            cmd.into_frame().encode(&mut buf);
+        }
+
+        let res = self.connection.write_bytes(&[buf.freeze()]).await;
+        res.map_err(|err| self.fail(err.into()))
+    }
+
+    /// Reads the next reply, error replies included.
+    pub(crate) async fn read_reply(&mut self) -> crate::Result<Frame> {
+        match self.read_frame().await? {
+            Some(frame) => Ok(frame),
+            None => Err(self.fail("connection closed by server".into())),
+        }
+    }
+
+    /// Reads the next frame, `None` once the server closed the connection.
+    pub(crate) async fn read_frame(&mut self) -> crate::Result<Option<Frame>> {
+        match self.connection.read_frame().await {
+            Ok(Some(frame)) => {
+                self.in_flight = self.in_flight.saturating_sub(1);
+                Ok(Some(frame))
+            }
+            Ok(None) => {
+                self.broken = true;
+                Ok(None)
+            }
+            Err(err) => Err(self.fail(err)),
+        }
+    }
+
+    fn fail(&mut self, err: crate::Error) -> crate::Error {
+        self.broken = true;
+        err
+    }
+
+    pub async fn get(&mut self, key: impl ToArg) -> crate::Result<Option<Bytes>> {
+        self.query(cmd::get(key)).await
+    }
+
+    pub async fn set(&mut self, key: impl ToArg, value: impl ToArg) -> crate::Result<()> {
+        self.query(cmd::set(key, value)).await
+    }
+
+    /// Sets `key` to expire after `seconds`.
+    pub async fn set_ex(&mut self, key: impl ToArg, value: impl ToArg, seconds: u64) -> crate::Result<()> {
+        self.query(cmd::set_ex(key, value, seconds)).await
+    }
+
+    /// Returns the previous value with `options.get`. Otherwise returns
+    /// `OK` when the value was set and `None` when the condition failed.
+    pub async fn set_with(
+        &mut self,
+        key: impl ToArg,
+        value: impl ToArg,
+        options: &SetOptions,
+    ) -> crate::Result<Option<Bytes>> {
+        self.query(cmd::set_with(key, value, options)).await
+    }
+
+    pub async fn strlen(&mut self, key: impl ToArg) -> crate::Result<i64> {
+        self.query(cmd::strlen(key)).await
+    }
+
+    /// Sets the bit at `offset`, returning the bit's previous value.
+    pub async fn setbit(&mut self, key: impl ToArg, offset: u64, bit: u8) -> crate::Result<u8> {
+        Ok(self.query::<i64>(cmd::setbit(key, offset, bit)).await? as u8)
+    }
+
+    pub async fn getbit(&mut self, key: impl ToArg, offset: u64) -> crate::Result<u8> {
+        Ok(self.query::<i64>(cmd::getbit(key, offset)).await? as u8)
+    }
+
+    pub async fn bitcount(&mut self, key: impl ToArg, range: Option<(i64, i64)>) -> crate::Result<i64> {
+        self.query(cmd::bitcount(key, range)).await
+    }
+
+    pub async fn bitpos(
+        &mut self,
+        key: impl ToArg,
+        bit: u8,
+        start: Option<i64>,
+        end: Option<i64>,
+    ) -> crate::Result<i64> {
+        self.query(cmd::bitpos(key, bit, start, end)).await
+    }
+
+    /// Returns the length of the string stored at `dest`.
+    pub async fn bitop<K: ToArg>(&mut self, op: &str, dest: impl ToArg, keys: &[K]) -> crate::Result<i64> {
+        self.query(cmd::bitop(op, dest, keys)).await
+    }
+
+    /// Returns one value per `GET`, `SET` or `INCRBY` operation, `None` where
+    /// an increment overflowed with `OVERFLOW FAIL`.
+    pub async fn bitfield<A: ToArg>(&mut self, key: impl ToArg, ops: &[A]) -> crate::Result<Vec<Option<i64>>> {
+        self.query(cmd::bitfield(key, ops)).await
+    }
+
+    pub async fn bitfield_ro<A: ToArg>(&mut self, key: impl ToArg, ops: &[A]) -> crate::Result<Vec<i64>> {
+        self.query(cmd::bitfield_ro(key, ops)).await
+    }
+
+    /// Returns `true` if the estimated cardinality changed.
+    pub async fn pfadd<E: ToArg>(&mut self, key: impl ToArg, elements: &[E]) -> crate::Result<bool> {
+        self.query(cmd::pfadd(key, elements)).await
+    }
+
+    pub async fn pfcount<K: ToArg>(&mut self, keys: &[K]) -> crate::Result<i64> {
+        self.query(cmd::pfcount(keys)).await
+    }
+
+    pub async fn pfmerge<K: ToArg>(&mut self, dest: impl ToArg, sources: &[K]) -> crate::Result<()> {
+        self.query(cmd::pfmerge(dest, sources)).await
+    }
+
+    /// Switches the connection to database `db`.
+    pub async fn select(&mut self, db: usize) -> crate::Result<()> {
+        self.query(cmd::select(db)).await
+    }
+
+    pub async fn dbsize(&mut self) -> crate::Result<i64> {
+        self.query(cmd::dbsize()).await
+    }
+
+    pub async fn flushdb(&mut self, lazy: bool) -> crate::Result<()> {
+        self.query(cmd::flushdb(lazy)).await
+    }
+
+    pub async fn flushall(&mut self, lazy: bool) -> crate::Result<()> {
+        self.query(cmd::flushall(lazy)).await
+    }
+
+    pub async fn swapdb(&mut self, first: usize, second: usize) -> crate::Result<()> {
+        self.query(cmd::swapdb(first, second)).await
+    }
+
+    /// Returns the number of keys removed.
+    pub async fn del<K: ToArg>(&mut self, keys: &[K]) -> crate::Result<i64> {
+        self.query(cmd::del(keys)).await
+    }
+
+    pub async fn unlink<K: ToArg>(&mut self, keys: &[K]) -> crate::Result<i64> {
+        self.query(cmd::unlink(keys)).await
+    }
+
+    /// Returns the number of keys that exist.
+    pub async fn touch<K: ToArg>(&mut self, keys: &[K]) -> crate::Result<i64> {
+        self.query(cmd::touch(keys)).await
+    }
+
+    /// Returns `false` if `key` does not exist.
+    pub async fn expire(&mut self, key: impl ToArg, seconds: i64) -> crate::Result<bool> {
+        self.query(cmd::expire(key, seconds)).await
+    }
+
+    pub async fn pexpire(&mut self, key: impl ToArg, millis: i64) -> crate::Result<bool> {
+        self.query(cmd::pexpire(key, millis)).await
+    }
+
+    /// Expires `key` at the Unix time `at`, in seconds.
+    pub async fn expireat(&mut self, key: impl ToArg, at: i64) -> crate::Result<bool> {
+        self.query(cmd::expireat(key, at)).await
+    }
+
+    pub async fn pexpireat(&mut self, key: impl ToArg, at: i64) -> crate::Result<bool> {
+        self.query(cmd::pexpireat(key, at)).await
+    }
+
+    /// Returns the seconds left to live, -1 without a time to live and -2 if
+    /// `key` does not exist.
+    pub async fn ttl(&mut self, key: impl ToArg) -> crate::Result<i64> {
+        self.query(cmd::ttl(key)).await
+    }
+
+    pub async fn pttl(&mut self, key: impl ToArg) -> crate::Result<i64> {
+        self.query(cmd::pttl(key)).await
+    }
+
+    /// Returns the Unix time `key` expires at, in seconds, with the same
+    /// negative values as `ttl`.
+    pub async fn expiretime(&mut self, key: impl ToArg) -> crate::Result<i64> {
+        self.query(cmd::expiretime(key)).await
+    }
+
+    pub async fn pexpiretime(&mut self, key: impl ToArg) -> crate::Result<i64> {
+        self.query(cmd::pexpiretime(key)).await
+    }
+
+    /// Returns `false` if `key` had no time to live.
+    pub async fn persist(&mut self, key: impl ToArg) -> crate::Result<bool> {
+        self.query(cmd::persist(key)).await
+    }
+
+    pub async fn rename(&mut self, key: impl ToArg, new_key: impl ToArg) -> crate::Result<()> {
+        self.query(cmd::rename(key, new_key)).await
+    }
+
+    /// Returns `false` if `new_key` already existed.
+    pub async fn renamenx(&mut self, key: impl ToArg, new_key: impl ToArg) -> crate::Result<bool> {
+        self.query(cmd::renamenx(key, new_key)).await
+    }
+
+    /// Returns `false` if nothing was copied.
+    pub async fn copy(
+        &mut self,
+        source: impl ToArg,
+        dest: impl ToArg,
+        db: Option<usize>,
+        replace: bool,
+    ) -> crate::Result<bool> {
+        self.query(cmd::copy(source, dest, db, replace)).await
+    }
+
+    /// Moves `key` to database `db`, returning `false` if nothing was moved.
+    pub async fn move_key(&mut self, key: impl ToArg, db: usize) -> crate::Result<bool> {
+        self.query(cmd::move_key(key, db)).await
+    }
+
+    pub async fn randomkey(&mut self) -> crate::Result<Option<Bytes>> {
+        self.query(cmd::randomkey()).await
+    }
+
+    /// Returns the cursor to continue from, 0 once the scan is complete, and
+    /// a batch of keys.
+    pub async fn scan(
+        &mut self,
+        cursor: u64,
+        pattern: Option<&str>,
+        count: Option<usize>,
+        key_type: Option<&str>,
+    ) -> crate::Result<(u64, Vec<Bytes>)> {
+        let frame = self.query(cmd::scan(cursor, pattern, count, key_type)).await?;
+        let unexpected = reply::unexpected(&frame);
+        let [cursor, keys]: [Frame; 2] = Vec::from_frame(frame)?
+            .try_into()
+            .map_err(|_| unexpected)?;
+
+        let cursor = String::from_frame(cursor)?.parse()?;
+        Ok((cursor, FromFrame::from_frame(keys)?))
+    }
+
+    pub async fn keys(&mut self, pattern: impl ToArg) -> crate::Result<Vec<Bytes>> {
+        self.query(cmd::keys(pattern)).await
+    }
+
+    /// Returns the type of the value of `key`, `none` if it does not exist.
+    pub async fn key_type(&mut self, key: impl ToArg) -> crate::Result<String> {
+        self.query(cmd::key_type(key)).await
+    }
+
+    pub async fn object_encoding(&mut self, key: impl ToArg) -> crate::Result<Option<String>> {
+        self.query(cmd::object_encoding(key)).await
+    }
+
+    pub async fn object_refcount(&mut self, key: impl ToArg) -> crate::Result<Option<i64>> {
+        self.query(cmd::object_refcount(key)).await
+    }
+
+    /// Returns the bytes `key` takes, `None` if it does not exist.
+    pub async fn memory_usage(&mut self, key: impl ToArg, samples: Option<usize>) -> crate::Result<Option<i64>> {
+        self.query(cmd::memory_usage(key, samples)).await
+    }
+
+    /// Returns the serialized value of `key`, for `restore`.
+    pub async fn dump(&mut self, key: impl ToArg) -> crate::Result<Option<Bytes>> {
+        self.query(cmd::dump(key)).await
+    }
+
+    pub async fn restore(
+        &mut self,
+        key: impl ToArg,
+        ttl: i64,
+        payload: impl ToArg,
+        replace: bool,
+        absttl: bool,
+    ) -> crate::Result<()> {
+        self.query(cmd::restore(key, ttl, payload, replace, absttl)).await
+    }
+
+    /// Returns `false` if none of `keys` exist.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn migrate<K: ToArg>(
+        &mut self,
+        host: &str,
+        port: u16,
+        keys: &[K],
+        db: usize,
+        timeout: u64,
+        copy: bool,
+        replace: bool,
+    ) -> crate::Result<bool> {
+        let cmd = cmd::migrate(host, port, keys, db, timeout, copy, replace);
+        match self.query(cmd).await? {
+            Frame::Simple(status) => Ok(status != "NOKEY"),
+            frame => Err(reply::unexpected(&frame)),
+        }
+    }
+
+    pub async fn client_id(&mut self) -> crate::Result<i64> {
+        self.query(cmd::client_id()).await
+    }
+
+    pub async fn client_getname(&mut self) -> crate::Result<Option<String>> {
+        self.query(cmd::client_getname()).await
+    }
+
+    pub async fn client_setname(&mut self, name: impl ToArg) -> crate::Result<()> {
+        self.query(cmd::client_setname(name)).await
+    }
+
+    /// Turns client side caching on with `options`, or off with `None`.
+    ///
+    /// Invalidations are sent to the connection given as `redirect`, read
+    /// them there with a `Subscriber` on `__redis__:invalidate`.
+    pub async fn client_tracking(&mut self, options: Option<&TrackingOptions>) -> crate::Result<()> {
+        self.query(cmd::client_tracking(options)).await
+    }
+
+    pub async fn client_caching(&mut self, yes: bool) -> crate::Result<()> {
+        self.query(cmd::client_caching(yes)).await
+    }
+
+    /// Returns the id invalidations are redirected to, 0 when they are not
+    /// and -1 without tracking.
+    pub async fn client_getredir(&mut self) -> crate::Result<i64> {
+        self.query(cmd::client_getredir()).await
+    }
+
+    /// Returns the tracking `flags`, `redirect` and `prefixes`.
+    pub async fn client_trackinginfo(&mut self) -> crate::Result<Vec<(String, Frame)>> {
+        let frame = self.query(cmd::client_trackinginfo()).await?;
+        reply::pairs(frame)
+    }
+
+    /// Returns a description of the server.
+    ///
+    /// Switching to protocol 3 makes the server reply with RESP3 types,
+    /// which the typed methods do not all convert.
+    pub async fn hello(&mut self, protover: Option<i64>, setname: Option<&str>) -> crate::Result<Vec<(String, Frame)>> {
+        let frame = self.query(cmd::hello(protover, setname)).await?;
+        reply::pairs(frame)
+    }
+
+    /// Returns the parameters matching `pattern`, with their value.
+    pub async fn config_get(&mut self, pattern: &str) -> crate::Result<Vec<(String, String)>> {
+        let frame = self.query(cmd::config_get(pattern)).await?;
+        reply::pairs(frame)
+    }
+
+    pub async fn config_set(&mut self, name: &str, value: impl ToArg) -> crate::Result<()> {
+        self.query(cmd::config_set(name, value)).await
+    }
+
+    pub async fn slowlog_get(&mut self, count: Option<i64>) -> crate::Result<Vec<SlowLogEntry>> {
+        self.query(cmd::slowlog_get(count)).await
+    }
+
+    pub async fn slowlog_len(&mut self) -> crate::Result<i64> {
+        self.query(cmd::slowlog_len()).await
+    }
+
+    pub async fn slowlog_reset(&mut self) -> crate::Result<()> {
+        self.query(cmd::slowlog_reset()).await
+    }
+
+    pub async fn latency_latest(&mut self) -> crate::Result<Vec<LatencyEvent>> {
+        self.query(cmd::latency_latest()).await
+    }
+
+    /// Returns `(unix time, latency in milliseconds)` samples of `event`.
+    pub async fn latency_history(&mut self, event: &str) -> crate::Result<Vec<(i64, i64)>> {
+        let samples: Vec<Frame> = self.query(cmd::latency_history(event)).await?;
+        samples
+            .into_iter()
+            .map(|sample| {
+                let pair = reply::pairs(sample)?;
+                pair.into_iter().next().ok_or_else(|| "empty latency sample".into())
+            })
+            .collect()
+    }
+
+    /// Returns the number of events reset.
+    pub async fn latency_reset(&mut self, events: &[&str]) -> crate::Result<i64> {
+        self.query(cmd::latency_reset(events)).await
+    }
+
+    /// Returns `PONG`, or `msg` when given.
+    pub async fn ping(&mut self, msg: Option<&[u8]>) -> crate::Result<Bytes> {
+        self.query(cmd::ping(msg)).await
+    }
+
+    /// Returns the number of subscriptions that received the message.
+    pub async fn publish(&mut self, channel: impl ToArg, message: impl ToArg) -> crate::Result<i64> {
+        self.query(cmd::publish(channel, message)).await
+    }
+
+    pub async fn pubsub_channels(&mut self, pattern: Option<&str>) -> crate::Result<Vec<Bytes>> {
+        self.query(cmd::pubsub_channels(pattern)).await
+    }
+
+    pub async fn pubsub_numsub<C: ToArg>(&mut self, channels: &[C]) -> crate::Result<Vec<(Bytes, i64)>> {
+        let frame = self.query(cmd::pubsub_numsub(channels)).await?;
+        reply::pairs(frame)
+    }
+
+    pub async fn pubsub_numpat(&mut self) -> crate::Result<i64> {
+        self.query(cmd::pubsub_numpat()).await
+    }
+
+    pub async fn eval<K: ToArg, A: ToArg>(
+        &mut self,
+        script: impl ToArg,
+        keys: &[K],
+        args: &[A],
+    ) -> crate::Result<Frame> {
+        self.query(cmd::eval(script, keys, args)).await
+    }
+
+    pub async fn evalsha<K: ToArg, A: ToArg>(&mut self, sha: &str, keys: &[K], args: &[A]) -> crate::Result<Frame> {
+        self.query(cmd::evalsha(sha, keys, args)).await
+    }
+
+    pub async fn eval_ro<K: ToArg, A: ToArg>(
+        &mut self,
+        script: impl ToArg,
+        keys: &[K],
+        args: &[A],
+    ) -> crate::Result<Frame> {
+        self.query(cmd::eval_ro(script, keys, args)).await
+    }
+
+    pub async fn evalsha_ro<K: ToArg, A: ToArg>(&mut self, sha: &str, keys: &[K], args: &[A]) -> crate::Result<Frame> {
+        self.query(cmd::evalsha_ro(sha, keys, args)).await
+    }
+
+    pub async fn fcall<K: ToArg, A: ToArg>(&mut self, function: &str, keys: &[K], args: &[A]) -> crate::Result<Frame> {
+        self.query(cmd::fcall(function, keys, args)).await
+    }
+
+    pub async fn fcall_ro<K: ToArg, A: ToArg>(&mut self, function: &str, keys: &[K], args: &[A]) -> crate::Result<Frame> {
+        self.query(cmd::fcall_ro(function, keys, args)).await
+    }
+
+    /// Returns the SHA1 digest to run the script with `evalsha`.
+    pub async fn script_load(&mut self, script: impl ToArg) -> crate::Result<String> {
+        self.query(cmd::script_load(script)).await
+    }
+
+    pub async fn script_exists(&mut self, shas: &[&str]) -> crate::Result<Vec<bool>> {
+        self.query(cmd::script_exists(shas)).await
+    }
+
+    pub async fn script_flush(&mut self) -> crate::Result<()> {
+        self.query(cmd::script_flush()).await
+    }
+
+    /// Stops the running script, if it did not write yet.
+    pub async fn script_kill(&mut self) -> crate::Result<()> {
+        self.query(cmd::script_kill()).await
+    }
+
+    /// Returns the name of the library loaded.
+    pub async fn function_load(&mut self, code: impl ToArg, replace: bool) -> crate::Result<String> {
+        self.query(cmd::function_load(code, replace)).await
+    }
+
+    pub async fn function_delete(&mut self, library: &str) -> crate::Result<()> {
+        self.query(cmd::function_delete(library)).await
+    }
+
+    pub async fn function_flush(&mut self) -> crate::Result<()> {
+        self.query(cmd::function_flush()).await
+    }
+
+    /// Returns a description of each library matching `pattern`.
+    pub async fn function_list(&mut self, pattern: Option<&str>, with_code: bool) -> crate::Result<Vec<Frame>> {
+        self.query(cmd::function_list(pattern, with_code)).await
+    }
+
+    /// Stops the running function, if it did not write yet.
+    pub async fn function_kill(&mut self) -> crate::Result<()> {
+        self.query(cmd::function_kill()).await
+    }
+
+    /// Returns the name and version of each module loaded.
+    pub async fn module_list(&mut self) -> crate::Result<Vec<(String, i64)>> {
+        let modules: Vec<Frame> = self.query(cmd::module_list()).await?;
+        modules
+            .into_iter()
+            .map(|module| {
+                let fields: Vec<(String, Frame)> = reply::pairs(module)?;
+                let mut name = None;
+                let mut version = None;
+                for (field, value) in fields {
+                    match &field[..] {
+                        "name" => name = Some(String::from_frame(value)?),
+                        "ver" => version = Some(i64::from_frame(value)?),
+                        _ => {}
+                    }
+                }
+
+                name.zip(version).ok_or_else(|| "malformed module entry".into())
+            })
+            .collect()
+    }
+
+    /// Subscribes to `channels`, turning the connection into a `Subscriber`.
+    pub async fn subscribe<C: ToArg>(self, channels: &[C]) -> crate::Result<Subscriber> {
+        let mut subscriber = Subscriber::new(self);
+        subscriber.subscribe(channels).await?;
+        Ok(subscriber)
+    }
+
+    /// Subscribes to channels matching `patterns`, turning the connection into
+    /// a `Subscriber`.
+    pub async fn psubscribe<P: ToArg>(self, patterns: &[P]) -> crate::Result<Subscriber> {
+        let mut subscriber = Subscriber::new(self);
+        subscriber.psubscribe(patterns).await?;
+        Ok(subscriber)
+    }
+
+    /// Turns the connection into a stream of the commands processed by the
+    /// server.
+    pub async fn monitor(mut self) -> crate::Result<Monitor> {
+        self.query::<()>(cmd::monitor()).await?;
+        Ok(Monitor { client: self })
+    }
+}
+
+impl Monitor {
+    /// Returns the next command processed by the server, `None` once the
+    /// connection is closed.
+    pub async fn next_line(&mut self) -> crate::Result<Option<String>> {
+        match self.client.read_frame().await? {
+            Some(frame) => String::from_frame(frame).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl FromFrame for SlowLogEntry {
+    fn from_frame(frame: Frame) -> crate::Result<SlowLogEntry> {
+        let fields: Vec<Frame> = FromFrame::from_frame(frame)?;
+        let [id, timestamp, duration, args, client_addr, client_name]: [Frame; 6] = fields
+            .try_into()
+            .map_err(|_| "malformed slowlog entry")?;
+
+        Ok(SlowLogEntry {
+            id: i64::from_frame(id)?,
+            timestamp: i64::from_frame(timestamp)?,
+            duration: Duration::from_micros(i64::from_frame(duration)? as u64),
+            args: FromFrame::from_frame(args)?,
+            client_addr: String::from_frame(client_addr)?,
+            client_name: String::from_frame(client_name)?,
+        })
+    }
+}
+
+impl FromFrame for LatencyEvent {
+    fn from_frame(frame: Frame) -> crate::Result<LatencyEvent> {
+        let fields: Vec<Frame> = FromFrame::from_frame(frame)?;
+        let [name, timestamp, latest, max]: [Frame; 4] = fields
+            .try_into()
+            .map_err(|_| "malformed latency event")?;
+
+        Ok(LatencyEvent {
+            name: String::from_frame(name)?,
+            timestamp: i64::from_frame(timestamp)?,
+            latest_ms: i64::from_frame(latest)?,
+            max_ms: i64::from_frame(max)?,
+        })
+    }
+}
@@ -0,0 +1,702 @@
+//! Commands as sent over the wire, with a constructor for every command the
+//! server supports.
+//!
+//! The constructors are used by `Client`'s typed methods, and directly to
+//! fill a `Pipeline`.
+
+use bytes::Bytes;
+
+use miniredis_protocol::frame::Frame;
+
+/// A command name and its arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cmd {
+    args: Vec<Bytes>,
+}
+
+/// Values that can be sent as a command argument.
+pub trait ToArg {
+    fn to_arg(&self) -> Bytes;
+}
+
+impl Cmd {
+    pub fn new(name: &str) -> Cmd {
+        Cmd {
+            args: vec![Bytes::copy_from_slice(name.as_bytes())],
+        }
+    }
+
+    /// Appends an argument.
+    pub fn arg<T: ToArg + ?Sized>(mut self, arg: &T) -> Cmd {
+        self.args.push(arg.to_arg());
+        self
+    }
+
+    /// Appends every argument of `args`.
+    pub fn args<T: ToArg>(mut self, args: &[T]) -> Cmd {
+        self.args.extend(args.iter().map(ToArg::to_arg));
+        self
+    }
+
+    /// Name of the command, as given to `new`.
+    pub fn name(&self) -> &[u8] {
+        &self.args[0]
+    }
+
+    pub fn into_frame(self) -> Frame {
+        Frame::Array(self.args.into_iter().map(Frame::Bulk).collect())
+    }
+}
+
+impl ToArg for str {
+    fn to_arg(&self) -> Bytes {
+        Bytes::copy_from_slice(self.as_bytes())
+    }
+}
+
+impl ToArg for String {
+    fn to_arg(&self) -> Bytes {
+        Bytes::copy_from_slice(self.as_bytes())
+    }
+}
+
+impl ToArg for [u8] {
+    fn to_arg(&self) -> Bytes {
+        Bytes::copy_from_slice(self)
+    }
+}
+
+impl ToArg for Vec<u8> {
+    fn to_arg(&self) -> Bytes {
+        Bytes::copy_from_slice(self)
+    }
+}
+
+impl ToArg for Bytes {
+    fn to_arg(&self) -> Bytes {
+        self.clone()
+    }
+}
+
+impl ToArg for Condition {
+    fn to_arg(&self) -> Bytes {
+        Bytes::from_static(match self {
+            Condition::Nx => b"NX",
+            Condition::Xx => b"XX",
+            Condition::Gt => b"GT",
+            Condition::Lt => b"LT",
+        })
+    }
+}
+
+impl<T: ToArg + ?Sized> ToArg for &T {
+    fn to_arg(&self) -> Bytes {
+        (**self).to_arg()
+    }
+}
+
+macro_rules! int_args {
+    ($($ty:ty),*) => {
+        $(
+            impl ToArg for $ty {
+                fn to_arg(&self) -> Bytes {
+                    Bytes::from(self.to_string())
+                }
+            }
+        )*
+    };
+}
+
+int_args!(i32, i64, u8, u16, u32, u64, usize);
+
+pub fn get(key: impl ToArg) -> Cmd {
+    Cmd::new("GET").arg(&key)
+}
+
+pub fn set(key: impl ToArg, value: impl ToArg) -> Cmd {
+    Cmd::new("SET").arg(&key).arg(&value)
+}
+
+/// `SET key value EX seconds`
+pub fn set_ex(key: impl ToArg, value: impl ToArg, seconds: u64) -> Cmd {
+    set(key, value).arg("EX").arg(&seconds)
+}
+
+/// Options of `SET`, see `set_with`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SetOptions {
+    pub expiry: Option<Expiry>,
+    /// Only `Nx` and `Xx` apply to `SET`.
+    pub condition: Option<Condition>,
+    /// Replies with the previous value.
+    pub get: bool,
+}
+
+/// When a key set with `SET` expires.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Expiry {
+    /// `EX seconds`
+    Ex(u64),
+    /// `PX milliseconds`
+    Px(u64),
+    /// `EXAT unix-time-seconds`
+    ExAt(u64),
+    /// `PXAT unix-time-milliseconds`
+    PxAt(u64),
+    /// `KEEPTTL`
+    KeepTtl,
+}
+
+/// Condition of `SET` and of the `EXPIRE` family, passed as an argument.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Condition {
+    /// Only if the key, or its time to live, does not exist.
+    Nx,
+    /// Only if the key, or its time to live, exists.
+    Xx,
+    /// Only if the new expiration time is later.
+    Gt,
+    /// Only if the new expiration time is sooner.
+    Lt,
+}
+
+/// Options of `CLIENT TRACKING on`, see `client_tracking`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackingOptions {
+    /// Sends invalidations to the connection with this id.
+    pub redirect: Option<i64>,
+    /// Prefixes tracked in broadcasting mode.
+    pub prefixes: Vec<Bytes>,
+    pub bcast: bool,
+    pub optin: bool,
+    pub optout: bool,
+    pub noloop: bool,
+}
+
+/// `SET key value [NX|XX] [GET] [EX|PX|EXAT|PXAT time|KEEPTTL]`
+pub fn set_with(key: impl ToArg, value: impl ToArg, options: &SetOptions) -> Cmd {
+    let mut cmd = set(key, value);
+    if let Some(condition) = options.condition {
+        cmd = cmd.arg(&condition);
+    }
+    if options.get {
+        cmd = cmd.arg("GET");
+    }
+    match options.expiry {
+        Some(Expiry::Ex(seconds)) => cmd.arg("EX").arg(&seconds),
+        Some(Expiry::Px(millis)) => cmd.arg("PX").arg(&millis),
+        Some(Expiry::ExAt(at)) => cmd.arg("EXAT").arg(&at),
+        Some(Expiry::PxAt(at)) => cmd.arg("PXAT").arg(&at),
+        Some(Expiry::KeepTtl) => cmd.arg("KEEPTTL"),
+        None => cmd,
+    }
+}
+
+pub fn strlen(key: impl ToArg) -> Cmd {
+    Cmd::new("STRLEN").arg(&key)
+}
+
+pub fn setbit(key: impl ToArg, offset: u64, bit: u8) -> Cmd {
+    Cmd::new("SETBIT").arg(&key).arg(&offset).arg(&bit)
+}
+
+pub fn getbit(key: impl ToArg, offset: u64) -> Cmd {
+    Cmd::new("GETBIT").arg(&key).arg(&offset)
+}
+
+/// `BITCOUNT key [start end]`, the range is in bytes.
+pub fn bitcount(key: impl ToArg, range: Option<(i64, i64)>) -> Cmd {
+    let cmd = Cmd::new("BITCOUNT").arg(&key);
+    match range {
+        Some((start, end)) => cmd.arg(&start).arg(&end),
+        None => cmd,
+    }
+}
+
+/// `BITPOS key bit [start [end]]`, the range is in bytes.
+pub fn bitpos(key: impl ToArg, bit: u8, start: Option<i64>, end: Option<i64>) -> Cmd {
+    let mut cmd = Cmd::new("BITPOS").arg(&key).arg(&bit);
+    if let Some(start) = start {
+        cmd = cmd.arg(&start);
+        if let Some(end) = end {
+            cmd = cmd.arg(&end);
+        }
+    }
+    cmd
+}
+
+/// `BITOP op destkey key [key ...]`, `op` is one of AND, OR, XOR and NOT.
+pub fn bitop<K: ToArg>(op: &str, dest: impl ToArg, keys: &[K]) -> Cmd {
+    Cmd::new("BITOP").arg(op).arg(&dest).args(keys)
+}
+
+/// `BITFIELD key [GET|SET|INCRBY|OVERFLOW ...]`, sub-commands are passed as
+/// is.
+pub fn bitfield<A: ToArg>(key: impl ToArg, ops: &[A]) -> Cmd {
+    Cmd::new("BITFIELD").arg(&key).args(ops)
+}
+
+pub fn bitfield_ro<A: ToArg>(key: impl ToArg, ops: &[A]) -> Cmd {
+    Cmd::new("BITFIELD_RO").arg(&key).args(ops)
+}
+
+pub fn pfadd<E: ToArg>(key: impl ToArg, elements: &[E]) -> Cmd {
+    Cmd::new("PFADD").arg(&key).args(elements)
+}
+
+pub fn pfcount<K: ToArg>(keys: &[K]) -> Cmd {
+    Cmd::new("PFCOUNT").args(keys)
+}
+
+pub fn pfmerge<K: ToArg>(dest: impl ToArg, sources: &[K]) -> Cmd {
+    Cmd::new("PFMERGE").arg(&dest).args(sources)
+}
+
+pub fn select(db: usize) -> Cmd {
+    Cmd::new("SELECT").arg(&db)
+}
+
+pub fn dbsize() -> Cmd {
+    Cmd::new("DBSIZE")
+}
+
+pub fn flushdb(lazy: bool) -> Cmd {
+    flush("FLUSHDB", lazy)
+}
+
+pub fn flushall(lazy: bool) -> Cmd {
+    flush("FLUSHALL", lazy)
+}
+
+fn flush(name: &str, lazy: bool) -> Cmd {
+    let cmd = Cmd::new(name);
+    if lazy {
+        cmd.arg("ASYNC")
+    } else {
+        cmd
+    }
+}
+
+pub fn swapdb(first: usize, second: usize) -> Cmd {
+    Cmd::new("SWAPDB").arg(&first).arg(&second)
+}
+
+pub fn del<K: ToArg>(keys: &[K]) -> Cmd {
+    Cmd::new("DEL").args(keys)
+}
+
+pub fn unlink<K: ToArg>(keys: &[K]) -> Cmd {
+    Cmd::new("UNLINK").args(keys)
+}
+
+pub fn touch<K: ToArg>(keys: &[K]) -> Cmd {
+    Cmd::new("TOUCH").args(keys)
+}
+
+pub fn expire(key: impl ToArg, seconds: i64) -> Cmd {
+    Cmd::new("EXPIRE").arg(&key).arg(&seconds)
+}
+
+pub fn pexpire(key: impl ToArg, millis: i64) -> Cmd {
+    Cmd::new("PEXPIRE").arg(&key).arg(&millis)
+}
+
+/// `EXPIREAT key unix-time-seconds`
+pub fn expireat(key: impl ToArg, at: i64) -> Cmd {
+    Cmd::new("EXPIREAT").arg(&key).arg(&at)
+}
+
+/// `PEXPIREAT key unix-time-milliseconds`
+pub fn pexpireat(key: impl ToArg, at: i64) -> Cmd {
+    Cmd::new("PEXPIREAT").arg(&key).arg(&at)
+}
+
+pub fn ttl(key: impl ToArg) -> Cmd {
+    Cmd::new("TTL").arg(&key)
+}
+
+pub fn pttl(key: impl ToArg) -> Cmd {
+    Cmd::new("PTTL").arg(&key)
+}
+
+pub fn expiretime(key: impl ToArg) -> Cmd {
+    Cmd::new("EXPIRETIME").arg(&key)
+}
+
+pub fn pexpiretime(key: impl ToArg) -> Cmd {
+    Cmd::new("PEXPIRETIME").arg(&key)
+}
+
+pub fn persist(key: impl ToArg) -> Cmd {
+    Cmd::new("PERSIST").arg(&key)
+}
+
+pub fn rename(key: impl ToArg, new_key: impl ToArg) -> Cmd {
+    Cmd::new("RENAME").arg(&key).arg(&new_key)
+}
+
+pub fn renamenx(key: impl ToArg, new_key: impl ToArg) -> Cmd {
+    Cmd::new("RENAMENX").arg(&key).arg(&new_key)
+}
+
+/// `COPY source destination [DB db] [REPLACE]`
+pub fn copy(source: impl ToArg, dest: impl ToArg, db: Option<usize>, replace: bool) -> Cmd {
+    let mut cmd = Cmd::new("COPY").arg(&source).arg(&dest);
+    if let Some(db) = db {
+        cmd = cmd.arg("DB").arg(&db);
+    }
+    if replace {
+        cmd = cmd.arg("REPLACE");
+    }
+    cmd
+}
+
+/// `MOVE key db`
+pub fn move_key(key: impl ToArg, db: usize) -> Cmd {
+    Cmd::new("MOVE").arg(&key).arg(&db)
+}
+
+pub fn randomkey() -> Cmd {
+    Cmd::new("RANDOMKEY")
+}
+
+/// `SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]`
+pub fn scan(
+    cursor: u64,
+    pattern: Option<&str>,
+    count: Option<usize>,
+    key_type: Option<&str>,
+) -> Cmd {
+    let mut cmd = Cmd::new("SCAN").arg(&cursor);
+    if let Some(pattern) = pattern {
+        cmd = cmd.arg("MATCH").arg(pattern);
+    }
+    if let Some(count) = count {
+        cmd = cmd.arg("COUNT").arg(&count);
+    }
+    if let Some(key_type) = key_type {
+        cmd = cmd.arg("TYPE").arg(key_type);
+    }
+    cmd
+}
+
+pub fn keys(pattern: impl ToArg) -> Cmd {
+    Cmd::new("KEYS").arg(&pattern)
+}
+
+/// `TYPE key`
+pub fn key_type(key: impl ToArg) -> Cmd {
+    Cmd::new("TYPE").arg(&key)
+}
+
+pub fn object_encoding(key: impl ToArg) -> Cmd {
+    Cmd::new("OBJECT").arg("ENCODING").arg(&key)
+}
+
+pub fn object_refcount(key: impl ToArg) -> Cmd {
+    Cmd::new("OBJECT").arg("REFCOUNT").arg(&key)
+}
+
+/// `MEMORY USAGE key [SAMPLES count]`
+pub fn memory_usage(key: impl ToArg, samples: Option<usize>) -> Cmd {
+    let cmd = Cmd::new("MEMORY").arg("USAGE").arg(&key);
+    match samples {
+        Some(samples) => cmd.arg("SAMPLES").arg(&samples),
+        None => cmd,
+    }
+}
+
+pub fn dump(key: impl ToArg) -> Cmd {
+    Cmd::new("DUMP").arg(&key)
+}
+
+/// `RESTORE key ttl payload [REPLACE] [ABSTTL]`, a `ttl` of 0 keeps the
+/// expiration time recorded in the payload.
+pub fn restore(key: impl ToArg, ttl: i64, payload: impl ToArg, replace: bool, absttl: bool) -> Cmd {
+    let mut cmd = Cmd::new("RESTORE").arg(&key).arg(&ttl).arg(&payload);
+    if replace {
+        cmd = cmd.arg("REPLACE");
+    }
+    if absttl {
+        cmd = cmd.arg("ABSTTL");
+    }
+    cmd
+}
+
+/// `MIGRATE host port key|"" db timeout [COPY] [REPLACE] [KEYS key ...]`,
+/// with `timeout` in milliseconds. A single key is passed in place, more use
+/// `KEYS`.
+pub fn migrate<K: ToArg>(
+    host: &str,
+    port: u16,
+    keys: &[K],
+    db: usize,
+    timeout: u64,
+    copy: bool,
+    replace: bool,
+) -> Cmd {
+    let mut cmd = Cmd::new("MIGRATE").arg(host).arg(&port);
+    cmd = match keys {
+        [key] => cmd.arg(key),
+        _ => cmd.arg(""),
+    };
+    cmd = cmd.arg(&db).arg(&timeout);
+    if copy {
+        cmd = cmd.arg("COPY");
+    }
+    if replace {
+        cmd = cmd.arg("REPLACE");
+    }
+    if keys.len() > 1 {
+        cmd = cmd.arg("KEYS").args(keys);
+    }
+    cmd
+}
+
+pub fn client_id() -> Cmd {
+    Cmd::new("CLIENT").arg("ID")
+}
+
+pub fn client_getname() -> Cmd {
+    Cmd::new("CLIENT").arg("GETNAME")
+}
+
+pub fn client_setname(name: impl ToArg) -> Cmd {
+    Cmd::new("CLIENT").arg("SETNAME").arg(&name)
+}
+
+/// `CLIENT TRACKING on [REDIRECT id] [PREFIX prefix ...] [BCAST] [OPTIN]
+/// [OPTOUT] [NOLOOP]`, or `CLIENT TRACKING off` without options.
+pub fn client_tracking(options: Option<&TrackingOptions>) -> Cmd {
+    let cmd = Cmd::new("CLIENT").arg("TRACKING");
+    let options = match options {
+        Some(options) => options,
+        None => return cmd.arg("off"),
+    };
+
+    let mut cmd = cmd.arg("on");
+    if let Some(id) = options.redirect {
+        cmd = cmd.arg("REDIRECT").arg(&id);
+    }
+    for prefix in &options.prefixes {
+        cmd = cmd.arg("PREFIX").arg(prefix);
+    }
+    for (set, flag) in [
+        (options.bcast, "BCAST"),
+        (options.optin, "OPTIN"),
+        (options.optout, "OPTOUT"),
+        (options.noloop, "NOLOOP"),
+    ] {
+        if set {
+            cmd = cmd.arg(flag);
+        }
+    }
+    cmd
+}
+
+/// `CLIENT CACHING yes|no`
+pub fn client_caching(yes: bool) -> Cmd {
+    Cmd::new("CLIENT")
+        .arg("CACHING")
+        .arg(if yes { "yes" } else { "no" })
+}
+
+pub fn client_getredir() -> Cmd {
+    Cmd::new("CLIENT").arg("GETREDIR")
+}
+
+pub fn client_trackinginfo() -> Cmd {
+    Cmd::new("CLIENT").arg("TRACKINGINFO")
+}
+
+/// `HELLO [protover [SETNAME name]]`
+pub fn hello(protover: Option<i64>, setname: Option<&str>) -> Cmd {
+    let mut cmd = Cmd::new("HELLO");
+    if let Some(protover) = protover {
+        cmd = cmd.arg(&protover);
+    }
+    if let Some(name) = setname {
+        cmd = cmd.arg("SETNAME").arg(name);
+    }
+    cmd
+}
+
+pub fn config_get(pattern: &str) -> Cmd {
+    Cmd::new("CONFIG").arg("GET").arg(pattern)
+}
+
+pub fn config_set(name: &str, value: impl ToArg) -> Cmd {
+    Cmd::new("CONFIG").arg("SET").arg(name).arg(&value)
+}
+
+pub fn slowlog_get(count: Option<i64>) -> Cmd {
+    let cmd = Cmd::new("SLOWLOG").arg("GET");
+    match count {
+        Some(count) => cmd.arg(&count),
+        None => cmd,
+    }
+}
+
+pub fn slowlog_len() -> Cmd {
+    Cmd::new("SLOWLOG").arg("LEN")
+}
+
+pub fn slowlog_reset() -> Cmd {
+    Cmd::new("SLOWLOG").arg("RESET")
+}
+
+pub fn latency_latest() -> Cmd {
+    Cmd::new("LATENCY").arg("LATEST")
+}
+
+pub fn latency_history(event: &str) -> Cmd {
+    Cmd::new("LATENCY").arg("HISTORY").arg(event)
+}
+
+pub fn latency_reset(events: &[&str]) -> Cmd {
+    Cmd::new("LATENCY").arg("RESET").args(events)
+}
+
+pub fn monitor() -> Cmd {
+    Cmd::new("MONITOR")
+}
+
+pub fn ping(msg: Option<&[u8]>) -> Cmd {
+    let cmd = Cmd::new("PING");
+    match msg {
+        Some(msg) => cmd.arg(msg),
+        None => cmd,
+    }
+}
+
+pub fn publish(channel: impl ToArg, message: impl ToArg) -> Cmd {
+    Cmd::new("PUBLISH").arg(&channel).arg(&message)
+}
+
+pub fn subscribe<C: ToArg>(channels: &[C]) -> Cmd {
+    Cmd::new("SUBSCRIBE").args(channels)
+}
+
+pub fn psubscribe<P: ToArg>(patterns: &[P]) -> Cmd {
+    Cmd::new("PSUBSCRIBE").args(patterns)
+}
+
+pub fn unsubscribe<C: ToArg>(channels: &[C]) -> Cmd {
+    Cmd::new("UNSUBSCRIBE").args(channels)
+}
+
+pub fn punsubscribe<P: ToArg>(patterns: &[P]) -> Cmd {
+    Cmd::new("PUNSUBSCRIBE").args(patterns)
+}
+
+pub fn pubsub_channels(pattern: Option<&str>) -> Cmd {
+    let cmd = Cmd::new("PUBSUB").arg("CHANNELS");
+    match pattern {
+        Some(pattern) => cmd.arg(pattern),
+        None => cmd,
+    }
+}
+
+pub fn pubsub_numsub<C: ToArg>(channels: &[C]) -> Cmd {
+    Cmd::new("PUBSUB").arg("NUMSUB").args(channels)
+}
+
+pub fn pubsub_numpat() -> Cmd {
+    Cmd::new("PUBSUB").arg("NUMPAT")
+}
+
+/// `EVAL script numkeys [key ...] [arg ...]`
+pub fn eval<K: ToArg, A: ToArg>(script: impl ToArg, keys: &[K], args: &[A]) -> Cmd {
+    with_keys(Cmd::new("EVAL").arg(&script), keys, args)
+}
+
+pub fn evalsha<K: ToArg, A: ToArg>(sha: &str, keys: &[K], args: &[A]) -> Cmd {
+    with_keys(Cmd::new("EVALSHA").arg(sha), keys, args)
+}
+
+pub fn eval_ro<K: ToArg, A: ToArg>(script: impl ToArg, keys: &[K], args: &[A]) -> Cmd {
+    with_keys(Cmd::new("EVAL_RO").arg(&script), keys, args)
+}
+
+pub fn evalsha_ro<K: ToArg, A: ToArg>(sha: &str, keys: &[K], args: &[A]) -> Cmd {
+    with_keys(Cmd::new("EVALSHA_RO").arg(sha), keys, args)
+}
+
+/// `FCALL function numkeys [key ...] [arg ...]`
+pub fn fcall<K: ToArg, A: ToArg>(function: &str, keys: &[K], args: &[A]) -> Cmd {
+    with_keys(Cmd::new("FCALL").arg(function), keys, args)
+}
+
+pub fn fcall_ro<K: ToArg, A: ToArg>(function: &str, keys: &[K], args: &[A]) -> Cmd {
+    with_keys(Cmd::new("FCALL_RO").arg(function), keys, args)
+}
+
+fn with_keys<K: ToArg, A: ToArg>(cmd: Cmd, keys: &[K], args: &[A]) -> Cmd {
+    cmd.arg(&keys.len()).args(keys).args(args)
+}
+
+pub fn script_load(script: impl ToArg) -> Cmd {
+    Cmd::new("SCRIPT").arg("LOAD").arg(&script)
+}
+
+pub fn script_exists(shas: &[&str]) -> Cmd {
+    Cmd::new("SCRIPT").arg("EXISTS").args(shas)
+}
+
+pub fn script_flush() -> Cmd {
+    Cmd::new("SCRIPT").arg("FLUSH")
+}
+
+pub fn script_kill() -> Cmd {
+    Cmd::new("SCRIPT").arg("KILL")
+}
+
+/// `FUNCTION LOAD [REPLACE] code`
+pub fn function_load(code: impl ToArg, replace: bool) -> Cmd {
+    let cmd = Cmd::new("FUNCTION").arg("LOAD");
+    if replace {
+        cmd.arg("REPLACE").arg(&code)
+    } else {
+        cmd.arg(&code)
+    }
+}
+
+pub fn function_delete(library: &str) -> Cmd {
+    Cmd::new("FUNCTION").arg("DELETE").arg(library)
+}
+
+pub fn function_flush() -> Cmd {
+    Cmd::new("FUNCTION").arg("FLUSH")
+}
+
+/// `FUNCTION LIST [LIBRARYNAME pattern] [WITHCODE]`
+pub fn function_list(pattern: Option<&str>, with_code: bool) -> Cmd {
+    let mut cmd = Cmd::new("FUNCTION").arg("LIST");
+    if let Some(pattern) = pattern {
+        cmd = cmd.arg("LIBRARYNAME").arg(pattern);
+    }
+    if with_code {
+        cmd = cmd.arg("WITHCODE");
+    }
+    cmd
+}
+
+pub fn function_kill() -> Cmd {
+    Cmd::new("FUNCTION").arg("KILL")
+}
+
+pub fn module_list() -> Cmd {
+    Cmd::new("MODULE").arg("LIST")
+}
+
+pub fn multi() -> Cmd {
+    Cmd::new("MULTI")
+}
+
+pub fn exec() -> Cmd {
+    Cmd::new("EXEC")
+}
+
+pub fn discard() -> Cmd {
+    Cmd::new("DISCARD")
+}
@@ -0,0 +1,52 @@
+//! Async client for miniredis.
+//!
+//! * `Client` is a single connection with a typed method per command.
+//! * `Pipeline` sends a batch of commands in one round trip, optionally as a
+//!   `MULTI`/`EXEC` transaction.
+//! * `Subscriber` receives pub/sub messages, also as a `Stream`.
+//! * `Pool` hands out connections from a bounded, health checked pool.
+//!
+//! ```no_run
+//! # async fn run() -> miniredis_client::Result<()> {
+//! use miniredis_client::{cmd, Client, Pipeline};
+//!
+//! let mut client = Client::connect("127.0.0.1:6379").await?;
+//! client.set("foo", "bar").await?;
+//! assert_eq!(client.get("foo").await?.as_deref(), Some(&b"bar"[..]));
+//!
+//! let replies = Pipeline::new()
+//!     .atomic()
+//!     .add(cmd::set("a", "1"))
+//!     .add(cmd::get("a"))
+//!     .query(&mut client)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod client;
+pub use client::{Client, LatencyEvent, Monitor, SlowLogEntry};
+
+pub mod cmd;
+pub use cmd::{Cmd, Condition, Expiry, SetOptions, ToArg, TrackingOptions};
+
+pub mod pipeline;
+pub use pipeline::Pipeline;
+
+pub mod pool;
+pub use pool::{Pool, PooledClient};
+
+pub mod reply;
+pub use reply::{FromFrame, ServerError};
+
+pub mod subscriber;
+pub use subscriber::{Message, Subscriber};
+
+pub use miniredis_protocol::frame::Frame;
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// A specialized `Result` type for miniredis client operations.
+///
+/// This is defined as a convenience.
+pub type Result<T> = std::result::Result<T, Error>;
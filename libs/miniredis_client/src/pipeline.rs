@@ -0,0 +1,84 @@
+use miniredis_protocol::frame::Frame;
+
+use crate::client::Client;
+use crate::cmd::{self, Cmd};
+use crate::reply::{self, ServerError};
+
+/// A batch of commands sent in a single write, with all replies read back
+/// afterwards.
+///
+/// An atomic pipeline is wrapped in `MULTI`/`EXEC`, so its commands run as a
+/// transaction: all of them or none, without commands of other clients in
+/// between.
+#[derive(Debug, Default, Clone)]
+pub struct Pipeline {
+    cmds: Vec<Cmd>,
+    atomic: bool,
+}
+
+impl Pipeline {
+    pub fn new() -> Pipeline {
+        Pipeline::default()
+    }
+
+    /// Runs the pipeline as a transaction.
+    pub fn atomic(&mut self) -> &mut Pipeline {
+        self.atomic = true;
+        self
+    }
+
+    pub fn add(&mut self, cmd: Cmd) -> &mut Pipeline {
+        self.cmds.push(cmd);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.cmds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cmds.is_empty()
+    }
+
+    /// Sends the commands and returns one reply per command, in order.
+    ///
+    /// In a plain pipeline a failing command does not affect the others, its
+    /// reply is left as a `Frame::Error`. A transaction that could not be
+    /// queued fails as a whole with a `ServerError`.
+    pub async fn query(&self, client: &mut Client) -> crate::Result<Vec<Frame>> {
+        if !self.atomic {
+            client.send(self.cmds.clone()).await?;
+
+            let mut replies = Vec::with_capacity(self.cmds.len());
+            for _ in 0..self.cmds.len() {
+                replies.push(client.read_reply().await?);
+            }
+            return Ok(replies);
+        }
+
+        let mut cmds = Vec::with_capacity(self.cmds.len() + 2);
+        cmds.push(cmd::multi());
+        cmds.extend(self.cmds.iter().cloned());
+        cmds.push(cmd::exec());
+        client.send(cmds).await?;
+
+        // Every reply is read before reporting an error, so the connection
+        // stays in sync with the server.
+        let mut first_error = reply::check(client.read_reply().await?).err();
+        for _ in 0..self.cmds.len() {
+            if let Frame::Error(msg) = client.read_reply().await? {
+                first_error.get_or_insert_with(|| ServerError(msg).into());
+            }
+        }
+
+        let exec = client.read_reply().await?;
+        if let Some(err) = first_error {
+            return Err(err);
+        }
+
+        match reply::check(exec)? {
+            Frame::Array(replies) => Ok(replies),
+            frame => Err(reply::unexpected(&frame)),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time;
+
+use crate::client::Client;
+
+/// A bounded pool of connections to one server.
+///
+/// At most `max_size` connections exist at once, `get` waits for one to be
+/// returned when they are all in use. Connections that sat idle for longer
+/// than the health check interval are checked with a `PING` before being
+/// handed out, failed ones are replaced.
+///
+/// Connections are returned as they are: a client that ran `SELECT` should
+/// switch back before being dropped.
+#[derive(Debug, Clone)]
+pub struct Pool {
+    inner: Arc<Inner>,
+}
+
+/// Configures a `Pool`.
+#[derive(Debug, Clone)]
+pub struct Builder {
+    max_size: usize,
+    health_check_after: Duration,
+    connect_timeout: Duration,
+}
+
+/// A connection checked out of a `Pool`, returned to it on drop.
+#[derive(Debug)]
+pub struct PooledClient {
+    client: Option<Client>,
+    inner: Arc<Inner>,
+    _permit: OwnedSemaphorePermit,
+}
+
+#[derive(Debug)]
+struct Inner {
+    addr: String,
+    config: Builder,
+    permits: Arc<Semaphore>,
+    idle: Mutex<Vec<Idle>>,
+}
+
+#[derive(Debug)]
+struct Idle {
+    client: Client,
+    since: Instant,
+}
+
+impl Pool {
+    pub fn builder() -> Builder {
+        Builder {
+            max_size: 10,
+            health_check_after: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// A pool with the default settings.
+    pub fn new(addr: impl Into<String>) -> Pool {
+        Pool::builder().build(addr)
+    }
+
+    /// Checks a connection out, opening one if none is idle.
+    pub async fn get(&self) -> crate::Result<PooledClient> {
+        let permit = Arc::clone(&self.inner.permits).acquire_owned().await?;
+
+        let client = match self.idle_client().await {
+            Some(client) => client,
+            None => {
+                let connect = Client::connect(&self.inner.addr);
+                match time::timeout(self.inner.config.connect_timeout, connect).await {
+                    Ok(client) => client?,
+                    Err(_) => return Err("timed out connecting to server".into()),
+                }
+            }
+        };
+
+        Ok(PooledClient {
+            client: Some(client),
+            inner: Arc::clone(&self.inner),
+            _permit: permit,
+        })
+    }
+
+    /// Number of connections waiting in the pool.
+    pub fn idle(&self) -> usize {
+        self.inner.idle.lock().unwrap().len()
+    }
+
+    /// Pops idle connections until one passes its health check.
+    async fn idle_client(&self) -> Option<Client> {
+        loop {
+            let Idle { mut client, since } = self.inner.idle.lock().unwrap().pop()?;
+
+            if since.elapsed() < self.inner.config.health_check_after {
+                return Some(client);
+            }
+
+            if client.ping(None).await.is_ok() {
+                return Some(client);
+            }
+        }
+    }
+}
+
+impl Builder {
+    /// Maximum number of connections, idle or checked out.
+    pub fn max_size(mut self, max_size: usize) -> Builder {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Connections idle for at least this long are pinged before reuse.
+    /// Zero checks every connection on every checkout.
+    pub fn health_check_after(mut self, after: Duration) -> Builder {
+        self.health_check_after = after;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Builder {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    pub fn build(self, addr: impl Into<String>) -> Pool {
+        Pool {
+            inner: Arc::new(Inner {
+                addr: addr.into(),
+                permits: Arc::new(Semaphore::new(self.max_size)),
+                config: self,
+                idle: Mutex::new(vec![]),
+            }),
+        }
+    }
+}
+
+impl Deref for PooledClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledClient {
+    fn deref_mut(&mut self) -> &mut Client {
+        self.client.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledClient {
+    fn drop(&mut self) {
+        let client = self.client.take().unwrap();
+
+        // A failed connection is closed, the next checkout opens a new one.
+        if !client.is_broken() {
+            self.inner.idle.lock().unwrap().push(Idle {
+                client,
+                since: Instant::now(),
+            });
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Conversion of reply frames into Rust values.
+
+use bytes::Bytes;
+use std::fmt;
+
+use miniredis_protocol::frame::Frame;
+
+/// An error reply sent by the server, such as `ERR syntax error` or
+/// `WRONGTYPE ...`.
+///
+/// Any other error returned by the client means the connection failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerError(pub String);
+
+/// Values a reply frame can be converted into.
+pub trait FromFrame: Sized {
+    fn from_frame(frame: Frame) -> crate::Result<Self>;
+}
+
+impl ServerError {
+    /// The error code, e.g. `ERR` or `WRONGTYPE`.
+    pub fn code(&self) -> &str {
+        self.0.split(' ').next().unwrap_or_default()
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(fmt)
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+/// Turns error replies into `ServerError`, leaving other frames untouched.
+pub(crate) fn check(frame: Frame) -> crate::Result<Frame> {
+    match frame {
+        Frame::Error(msg) => Err(ServerError(msg).into()),
+        frame => Ok(frame),
+    }
+}
+
+pub(crate) fn unexpected(frame: &Frame) -> crate::Error {
+    format!("unexpected reply: {}", frame).into()
+}
+
+impl FromFrame for Frame {
+    fn from_frame(frame: Frame) -> crate::Result<Frame> {
+        check(frame)
+    }
+}
+
+impl FromFrame for () {
+    fn from_frame(frame: Frame) -> crate::Result<()> {
+        match check(frame)? {
+            Frame::Simple(_) => Ok(()),
+            frame => Err(unexpected(&frame)),
+        }
+    }
+}
+
+impl FromFrame for Bytes {
+    fn from_frame(frame: Frame) -> crate::Result<Bytes> {
+        match check(frame)? {
+            Frame::Bulk(bytes) => Ok(bytes),
+            Frame::Simple(s) => Ok(s.into()),
+            frame => Err(unexpected(&frame)),
+        }
+    }
+}
+
+impl FromFrame for String {
+    fn from_frame(frame: Frame) -> crate::Result<String> {
+        let bytes = Bytes::from_frame(frame)?;
+        Ok(String::from_utf8(bytes.to_vec())?)
+    }
+}
+
+impl FromFrame for i64 {
+    fn from_frame(frame: Frame) -> crate::Result<i64> {
+        match check(frame)? {
+            Frame::Integer(n) => Ok(n),
+            frame => Err(unexpected(&frame)),
+        }
+    }
+}
+
+impl FromFrame for bool {
+    fn from_frame(frame: Frame) -> crate::Result<bool> {
+        Ok(i64::from_frame(frame)? != 0)
+    }
+}
+
+impl<T: FromFrame> FromFrame for Option<T> {
+    fn from_frame(frame: Frame) -> crate::Result<Option<T>> {
+        match check(frame)? {
+            Frame::Null => Ok(None),
+            frame => T::from_frame(frame).map(Some),
+        }
+    }
+}
+
+impl<T: FromFrame> FromFrame for Vec<T> {
+    fn from_frame(frame: Frame) -> crate::Result<Vec<T>> {
+        match check(frame)? {
+            Frame::Array(items) => items.into_iter().map(T::from_frame).collect(),
+            frame => Err(unexpected(&frame)),
+        }
+    }
+}
+
+/// Reads a flat array of alternating names and values, as replied by
+/// `CONFIG GET` or `PUBSUB NUMSUB`, or a RESP3 map.
+pub(crate) fn pairs<A: FromFrame, B: FromFrame>(frame: Frame) -> crate::Result<Vec<(A, B)>> {
+    let items = match check(frame)? {
+        Frame::Array(items) if items.len() % 2 == 0 => items,
+        Frame::Map(entries) => {
+            return entries
+                .into_iter()
+                .map(|(a, b)| Ok((A::from_frame(a)?, B::from_frame(b)?)))
+                .collect()
+        }
+        frame => return Err(unexpected(&frame)),
+    };
+
+    let mut items = items.into_iter();
+    let mut pairs = vec![];
+    while let (Some(a), Some(b)) = (items.next(), items.next()) {
+        pairs.push((A::from_frame(a)?, B::from_frame(b)?));
+    }
+
+    Ok(pairs)
+}
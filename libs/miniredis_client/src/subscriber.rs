@@ -0,0 +1,169 @@
+use async_stream::try_stream;
+use bytes::Bytes;
+use std::collections::{HashSet, VecDeque};
+use tokio_stream::Stream;
+
+use miniredis_protocol::frame::Frame;
+
+use crate::client::Client;
+use crate::cmd::{self, Cmd, ToArg};
+use crate::reply::{self, FromFrame};
+
+/// A client in pub/sub mode, see `Client::subscribe`.
+///
+/// Subscriptions can be changed at any time, messages received while waiting
+/// for a confirmation are kept for `next_message`.
+#[derive(Debug)]
+pub struct Subscriber {
+    client: Client,
+    channels: HashSet<Bytes>,
+    patterns: HashSet<Bytes>,
+    pending: VecDeque<Message>,
+}
+
+/// A message published to a channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub channel: Bytes,
+    /// The pattern that matched, for subscriptions made with `psubscribe`.
+    pub pattern: Option<Bytes>,
+    pub payload: Bytes,
+}
+
+impl Subscriber {
+    pub(crate) fn new(client: Client) -> Subscriber {
+        Subscriber {
+            client,
+            channels: HashSet::new(),
+            patterns: HashSet::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Channels currently subscribed to.
+    pub fn channels(&self) -> impl Iterator<Item = &Bytes> {
+        self.channels.iter()
+    }
+
+    /// Patterns currently subscribed to.
+    pub fn patterns(&self) -> impl Iterator<Item = &Bytes> {
+        self.patterns.iter()
+    }
+
+    pub async fn subscribe<C: ToArg>(&mut self, channels: &[C]) -> crate::Result<()> {
+        self.change(cmd::subscribe(channels), channels.len()).await
+    }
+
+    pub async fn psubscribe<P: ToArg>(&mut self, patterns: &[P]) -> crate::Result<()> {
+        self.change(cmd::psubscribe(patterns), patterns.len()).await
+    }
+
+    /// Unsubscribes from `channels`, or from every channel when empty.
+    pub async fn unsubscribe<C: ToArg>(&mut self, channels: &[C]) -> crate::Result<()> {
+        let confirmations = match channels.len() {
+            0 => self.channels.len().max(1),
+            n => n,
+        };
+        self.change(cmd::unsubscribe(channels), confirmations).await
+    }
+
+    /// Unsubscribes from `patterns`, or from every pattern when empty.
+    pub async fn punsubscribe<P: ToArg>(&mut self, patterns: &[P]) -> crate::Result<()> {
+        let confirmations = match patterns.len() {
+            0 => self.patterns.len().max(1),
+            n => n,
+        };
+        self.change(cmd::punsubscribe(patterns), confirmations).await
+    }
+
+    /// Returns the next message, `None` once the connection is closed.
+    pub async fn next_message(&mut self) -> crate::Result<Option<Message>> {
+        loop {
+            if let Some(message) = self.pending.pop_front() {
+                return Ok(Some(message));
+            }
+
+            let frame = match self.client.read_frame().await? {
+                Some(frame) => frame,
+                None => return Ok(None),
+            };
+            self.handle(frame)?;
+        }
+    }
+
+    /// Converts the subscriber into a `Stream` of messages.
+    pub fn into_stream(mut self) -> impl Stream<Item = crate::Result<Message>> {
+        try_stream! {
+            while let Some(message) = self.next_message().await? {
+                yield message;
+            }
+        }
+    }
+
+    /// Sends a subscription change and waits for its confirmations.
+    async fn change(&mut self, cmd: Cmd, confirmations: usize) -> crate::Result<()> {
+        self.client.send(vec![cmd]).await?;
+
+        let mut confirmed = 0;
+        while confirmed < confirmations {
+            let frame = self.client.read_reply().await?;
+            if self.handle(frame)? {
+                confirmed += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles a frame pushed by the server, returning `true` for a
+    /// subscription change confirmation.
+    fn handle(&mut self, frame: Frame) -> crate::Result<bool> {
+        let unexpected = reply::unexpected(&frame);
+        let mut parts: Vec<Frame> = FromFrame::from_frame(frame)?;
+        if parts.is_empty() {
+            return Err(unexpected);
+        }
+
+        let kind = Bytes::from_frame(parts.remove(0))?;
+        let mut parts = parts.into_iter();
+        let mut next = || parts.next().ok_or_else(|| reply::unexpected(&Frame::Null));
+
+        match &kind[..] {
+            b"message" => {
+                let channel = Bytes::from_frame(next()?)?;
+                let payload = Bytes::from_frame(next()?)?;
+                self.pending.push_back(Message {
+                    channel,
+                    pattern: None,
+                    payload,
+                });
+                Ok(false)
+            }
+            b"pmessage" => {
+                let pattern = Bytes::from_frame(next()?)?;
+                let channel = Bytes::from_frame(next()?)?;
+                let payload = Bytes::from_frame(next()?)?;
+                self.pending.push_back(Message {
+                    channel,
+                    pattern: Some(pattern),
+                    payload,
+                });
+                Ok(false)
+            }
+            b"subscribe" | b"psubscribe" | b"unsubscribe" | b"punsubscribe" => {
+                // Unsubscribing while subscribed to nothing confirms with a
+                // nil channel.
+                if let Some(name) = Option::<Bytes>::from_frame(next()?)? {
+                    match &kind[..] {
+                        b"subscribe" => self.channels.insert(name),
+                        b"psubscribe" => self.patterns.insert(name),
+                        b"unsubscribe" => self.channels.remove(&name),
+                        _ => self.patterns.remove(&name),
+                    };
+                }
+                Ok(true)
+            }
+            _ => Err(unexpected),
+        }
+    }
+}
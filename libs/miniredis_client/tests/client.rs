@@ -0,0 +1,170 @@
+mod common;
+
+use bytes::Bytes;
+use miniredis_client::{cmd, Condition, Expiry, Frame, SetOptions};
+
+#[tokio::test]
+async fn set_options() {
+    let server = common::start().await;
+    let mut client = common::connect(&server).await;
+
+    let nx = SetOptions {
+        condition: Some(Condition::Nx),
+        ..SetOptions::default()
+    };
+    assert!(client.set_with("key", "a", &nx).await.unwrap().is_some());
+    assert_eq!(client.set_with("key", "b", &nx).await.unwrap(), None);
+
+    let get = SetOptions {
+        expiry: Some(Expiry::Ex(100)),
+        get: true,
+        ..SetOptions::default()
+    };
+    assert_eq!(
+        client.set_with("key", "c", &get).await.unwrap(),
+        Some(Bytes::from("a"))
+    );
+    assert!((90..=100).contains(&client.ttl("key").await.unwrap()));
+    assert_eq!(client.strlen("key").await.unwrap(), 1);
+
+    // Conditions are appended to the `EXPIRE` family as arguments.
+    let shorter = cmd::expire("key", 1000).arg(&Condition::Lt);
+    assert!(!client.query::<bool>(shorter).await.unwrap());
+    let later = client.expiretime("key").await.unwrap() + 100;
+    assert!(client.expireat("key", later).await.unwrap());
+    assert_eq!(client.expiretime("key").await.unwrap(), later);
+}
+
+#[tokio::test]
+async fn keyspace() {
+    let server = common::start().await;
+    let mut client = common::connect(&server).await;
+
+    for key in ["a", "b", "c"] {
+        client.set(key, "value").await.unwrap();
+    }
+
+    let mut keys = vec![];
+    let mut cursor = 0;
+    loop {
+        let (next, batch) = client.scan(cursor, Some("*"), Some(1), None).await.unwrap();
+        keys.extend(batch);
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+    keys.sort();
+    assert_eq!(keys, ["a", "b", "c"]);
+
+    assert_eq!(client.keys("[ab]").await.unwrap().len(), 2);
+    assert_eq!(client.key_type("a").await.unwrap(), "string");
+    assert_eq!(client.key_type("missing").await.unwrap(), "none");
+    assert!(client.object_encoding("a").await.unwrap().is_some());
+    assert_eq!(client.object_refcount("missing").await.unwrap(), None);
+    assert!(client.memory_usage("a", Some(5)).await.unwrap().unwrap() > 0);
+
+    let payload = client.dump("a").await.unwrap().unwrap();
+    client
+        .restore("copy", 0, payload, false, false)
+        .await
+        .unwrap();
+    assert_eq!(
+        client.get("copy").await.unwrap(),
+        Some(Bytes::from("value"))
+    );
+}
+
+#[tokio::test]
+async fn keys_are_migrated() {
+    let source = common::start().await;
+    let target = common::start().await;
+    let mut client = common::connect(&source).await;
+    let port = target.local_addr().port();
+
+    client.set("a", "1").await.unwrap();
+    client.set("b", "2").await.unwrap();
+    assert!(client
+        .migrate("127.0.0.1", port, &["a", "b"], 0, 1000, false, false)
+        .await
+        .unwrap());
+    assert!(!client
+        .migrate("127.0.0.1", port, &["a"], 0, 1000, false, false)
+        .await
+        .unwrap());
+
+    let mut other = common::connect(&target).await;
+    assert_eq!(other.dbsize().await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn connection_commands() {
+    let server = common::start().await;
+    let mut client = common::connect(&server).await;
+
+    assert!(client.client_id().await.unwrap() > 0);
+    assert_eq!(client.client_getname().await.unwrap(), None);
+    client.client_setname("worker").await.unwrap();
+    assert_eq!(
+        client.client_getname().await.unwrap().as_deref(),
+        Some("worker")
+    );
+
+    let hello = client.hello(Some(2), None).await.unwrap();
+    assert!(hello.contains(&("proto".to_string(), Frame::Integer(2))));
+
+    assert_eq!(client.client_getredir().await.unwrap(), -1);
+    let options = miniredis_client::TrackingOptions {
+        optin: true,
+        ..Default::default()
+    };
+    client.client_tracking(Some(&options)).await.unwrap();
+    client.client_caching(true).await.unwrap();
+    assert_eq!(client.client_getredir().await.unwrap(), 0);
+    let info = client.client_trackinginfo().await.unwrap();
+    assert_eq!(info[0].0, "flags");
+    client.client_tracking(None).await.unwrap();
+}
+
+#[tokio::test]
+async fn scripts_and_functions() {
+    let server = common::start().await;
+    let mut client = common::connect(&server).await;
+
+    let script = "return redis.call('SET', KEYS[1], ARGV[1])";
+    assert_eq!(
+        client.eval(script, &["key"], &["value"]).await.unwrap(),
+        Frame::Simple("OK".into())
+    );
+    let sha = client.script_load("return KEYS[1]").await.unwrap();
+    assert_eq!(
+        client.script_exists(&[&sha, "missing"]).await.unwrap(),
+        [true, false]
+    );
+    assert_eq!(
+        client
+            .evalsha_ro(&sha, &["key"], &[] as &[&str])
+            .await
+            .unwrap(),
+        Frame::Bulk("key".into())
+    );
+    client.script_flush().await.unwrap();
+    assert_eq!(
+        common::error_code(client.evalsha(&sha, &["key"], &[] as &[&str]).await),
+        "NOSCRIPT"
+    );
+
+    let library = "#!lua name=lib
+redis.register_function('echo', function(keys, args) return args[1] end)";
+    assert_eq!(client.function_load(library, false).await.unwrap(), "lib");
+    assert_eq!(
+        client.fcall("echo", &[] as &[&str], &["hi"]).await.unwrap(),
+        Frame::Bulk("hi".into())
+    );
+    assert_eq!(client.function_list(None, false).await.unwrap().len(), 1);
+    client.function_delete("lib").await.unwrap();
+    assert!(client.function_list(None, false).await.unwrap().is_empty());
+    assert_eq!(common::error_code(client.function_kill().await), "NOTBUSY");
+
+    assert!(client.module_list().await.unwrap().is_empty());
+}
@@ -0,0 +1,25 @@
+//! Helpers shared by the integration tests: an embedded server to run the
+//! client against.
+
+#![allow(dead_code)]
+
+use miniredis::server::{Server, ServerHandle};
+use miniredis_client::Client;
+
+/// Starts a server on a free port.
+pub async fn start() -> ServerHandle {
+    Server::builder().bind("127.0.0.1:0").start().await.unwrap()
+}
+
+pub async fn connect(server: &ServerHandle) -> Client {
+    Client::connect(server.local_addr()).await.unwrap()
+}
+
+/// Returns the code of the `ServerError` in `result`.
+pub fn error_code<T: std::fmt::Debug>(result: miniredis_client::Result<T>) -> String {
+    let err = result.expect_err("the command succeeded");
+    match err.downcast_ref::<miniredis_client::ServerError>() {
+        Some(err) => err.code().to_string(),
+        None => panic!("not a server error: {}", err),
+    }
+}
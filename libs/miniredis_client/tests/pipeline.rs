@@ -0,0 +1,73 @@
+mod common;
+
+use bytes::Bytes;
+use miniredis_client::{cmd, Frame, Pipeline};
+
+#[tokio::test]
+async fn replies_come_back_in_order() {
+    let server = common::start().await;
+    let mut client = common::connect(&server).await;
+
+    let replies = Pipeline::new()
+        .add(cmd::set("a", "1"))
+        .add(cmd::get("a"))
+        .add(cmd::Cmd::new("GET"))
+        .add(cmd::del(&["a", "b"]))
+        .add(cmd::get("a"))
+        .query(&mut client)
+        .await
+        .unwrap();
+
+    assert_eq!(replies.len(), 5);
+    assert_eq!(replies[0], Frame::Simple("OK".into()));
+    assert_eq!(replies[1], Frame::Bulk("1".into()));
+    assert!(matches!(&replies[2], Frame::Error(msg) if msg.starts_with("ERR wrong number")));
+    assert_eq!(replies[3], Frame::Integer(1));
+    assert_eq!(replies[4], Frame::Null);
+}
+
+#[tokio::test]
+async fn atomic_pipelines_run_as_a_transaction() {
+    let server = common::start().await;
+    let mut client = common::connect(&server).await;
+
+    let replies = Pipeline::new()
+        .atomic()
+        .add(cmd::set("a", "1"))
+        .add(cmd::get("a"))
+        .query(&mut client)
+        .await
+        .unwrap();
+    assert_eq!(
+        replies,
+        [Frame::Simple("OK".into()), Frame::Bulk("1".into())]
+    );
+
+    // A command that cannot be queued aborts the whole transaction, and the
+    // connection stays usable.
+    let result = Pipeline::new()
+        .atomic()
+        .add(cmd::set("a", "2"))
+        .add(cmd::Cmd::new("GET"))
+        .query(&mut client)
+        .await;
+    assert_eq!(common::error_code(result), "ERR");
+    assert!(!client.is_broken());
+    assert_eq!(client.get("a").await.unwrap(), Some(Bytes::from("1")));
+}
+
+#[tokio::test]
+async fn discarded_transactions_do_nothing() {
+    let server = common::start().await;
+    let mut client = common::connect(&server).await;
+
+    client.query::<()>(cmd::multi()).await.unwrap();
+    client.query::<()>(cmd::set("a", "1")).await.unwrap();
+    client.query::<()>(cmd::discard()).await.unwrap();
+
+    assert_eq!(client.get("a").await.unwrap(), None);
+    assert_eq!(
+        common::error_code(client.query::<Frame>(cmd::exec()).await),
+        "ERR"
+    );
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use std::time::Duration;
+use tokio::time;
+
+use miniredis_client::Pool;
+
+#[tokio::test]
+async fn checkouts_wait_at_max_size() {
+    let server = common::start().await;
+    let pool = Pool::builder()
+        .max_size(1)
+        .build(server.local_addr().to_string());
+
+    let mut first = pool.get().await.unwrap();
+    let id = first.client_id().await.unwrap();
+    assert!(time::timeout(Duration::from_millis(100), pool.get())
+        .await
+        .is_err());
+
+    // Once returned, the connection is handed to the next checkout.
+    let waiting = tokio::spawn({
+        let pool = pool.clone();
+        async move { pool.get().await.unwrap().client_id().await.unwrap() }
+    });
+    time::sleep(Duration::from_millis(50)).await;
+    drop(first);
+    assert_eq!(waiting.await.unwrap(), id);
+    assert_eq!(pool.idle(), 1);
+}
+
+#[tokio::test]
+async fn connections_failing_their_ping_are_replaced() {
+    let server = common::start().await;
+    let pool = Pool::builder()
+        .health_check_after(Duration::ZERO)
+        .build(server.local_addr().to_string());
+
+    let mut client = pool.get().await.unwrap();
+    let id = client.client_id().await.unwrap();
+    client.config_set("timeout", "1").await.unwrap();
+    drop(client);
+    assert_eq!(pool.idle(), 1);
+
+    // The server closes the idle connection, its ping fails on checkout.
+    time::sleep(Duration::from_millis(2500)).await;
+    let mut client = pool.get().await.unwrap();
+    assert_eq!(pool.idle(), 0);
+    client.config_set("timeout", "0").await.unwrap();
+    assert_ne!(client.client_id().await.unwrap(), id);
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use std::time::Duration;
+use tokio::time;
+use tokio_stream::StreamExt;
+
+use miniredis_client::Message;
+
+fn message(channel: &str, pattern: Option<&str>, payload: &str) -> Message {
+    Message {
+        channel: channel.to_string().into(),
+        pattern: pattern.map(|pattern| pattern.to_string().into()),
+        payload: payload.to_string().into(),
+    }
+}
+
+#[tokio::test]
+async fn messages_are_delivered() {
+    let server = common::start().await;
+    let mut publisher = common::connect(&server).await;
+    let mut subscriber = common::connect(&server)
+        .await
+        .subscribe(&["news"])
+        .await
+        .unwrap();
+    subscriber.psubscribe(&["sports.*"]).await.unwrap();
+
+    assert_eq!(publisher.publish("news", "one").await.unwrap(), 1);
+    assert_eq!(publisher.publish("sports.ski", "two").await.unwrap(), 1);
+    assert_eq!(
+        subscriber.next_message().await.unwrap(),
+        Some(message("news", None, "one"))
+    );
+    assert_eq!(
+        subscriber.next_message().await.unwrap(),
+        Some(message("sports.ski", Some("sports.*"), "two"))
+    );
+}
+
+#[tokio::test]
+async fn unsubscribed_channels_get_nothing() {
+    let server = common::start().await;
+    let mut publisher = common::connect(&server).await;
+    let mut subscriber = common::connect(&server)
+        .await
+        .subscribe(&["a", "b"])
+        .await
+        .unwrap();
+
+    subscriber.unsubscribe(&["a"]).await.unwrap();
+    assert_eq!(subscriber.channels().count(), 1);
+    assert_eq!(publisher.publish("a", "dropped").await.unwrap(), 0);
+    assert_eq!(publisher.publish("b", "kept").await.unwrap(), 1);
+    assert_eq!(
+        subscriber.next_message().await.unwrap(),
+        Some(message("b", None, "kept"))
+    );
+
+    subscriber.unsubscribe(&[] as &[&str]).await.unwrap();
+    assert_eq!(subscriber.channels().count(), 0);
+    assert_eq!(publisher.publish("b", "dropped").await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn subscribers_are_streams() {
+    let server = common::start().await;
+    let mut publisher = common::connect(&server).await;
+    let subscriber = common::connect(&server)
+        .await
+        .subscribe(&["news"])
+        .await
+        .unwrap();
+
+    for payload in ["one", "two", "three"] {
+        publisher.publish("news", payload).await.unwrap();
+    }
+
+    let stream = subscriber.into_stream();
+    tokio::pin!(stream);
+    let mut payloads = vec![];
+    while payloads.len() < 3 {
+        let message = time::timeout(Duration::from_secs(1), stream.next())
+            .await
+            .expect("no message was delivered")
+            .unwrap()
+            .unwrap();
+        payloads.push(message.payload);
+    }
+    assert_eq!(payloads, ["one", "two", "three"]);
+}
@@ -0,0 +1,119 @@
+use bytes::{Buf, Bytes, BytesMut};
+use std::io::{self, Cursor};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
+use tokio::net::TcpStream;
+
+use crate::frame::{Error, Frame};
+
+#[derive(Debug)]
+pub struct Connection {
+    // The `TcpStream`. It is decorated with a `BufWriter`, which provides write
+    // level buffering.
+    stream: BufWriter<TcpStream>,
+
+    // The buffer for reading frames.
+    buffer: BytesMut,
+
+    // Scratch space frames are encoded into before being written out.
+    out: BytesMut,
+
+    // Bytes of unparsed input allowed in `buffer` before the client is
+    // considered misbehaving.
+    query_buffer_limit: usize,
+
+    // Whether frames are written as RESP3 rather than RESP2.
+    resp3: bool,
+}
+
+impl Connection {
+    pub fn new(socket: TcpStream) -> Connection {
+        Connection {
+            stream: BufWriter::new(socket),
+            // Default to a 4KB read buffer, the same as miniminio.
+            buffer: BytesMut::with_capacity(4 * 1024),
+            out: BytesMut::with_capacity(4 * 1024),
+            query_buffer_limit: usize::MAX,
+            resp3: false,
+        }
+    }
+
+    pub fn set_query_buffer_limit(&mut self, limit: usize) {
+        self.query_buffer_limit = limit;
+    }
+
+    /// Switches the encoding of written frames, as negotiated with `HELLO`.
+    pub fn set_resp3(&mut self, resp3: bool) {
+        self.resp3 = resp3;
+    }
+
+    pub async fn read_frame(&mut self) -> crate::Result<Option<Frame>> {
+        loop {
+            if let Some(frame) = self.parse_frame()? {
+                return Ok(Some(frame));
+            }
+
+            if self.buffer.len() > self.query_buffer_limit {
+                return Err("client query buffer limit reached".into());
+            }
+
+            // On success, the number of bytes is returned. `0` indicates "end
+            // of stream"
+            if 0 == self.stream.read_buf(&mut self.buffer).await? {
+                // if no bytes are in the buffer then fine, if bytes then connection
+                // was abruptly killed.
+                if self.buffer.is_empty() {
+                    return Ok(None);
+                } else {
+                    return Err("connection reset by peer".into());
+                }
+            }
+        }
+    }
+
+    fn parse_frame(&mut self) -> crate::Result<Option<Frame>> {
+        let mut buf = Cursor::new(&self.buffer[..]);
+
+        match Frame::check(&mut buf) {
+            Ok(_) => {
+                let len = buf.position() as usize;
+                buf.set_position(0);
+                let frame = Frame::parse(&mut buf)?;
+                self.buffer.advance(len);
+                Ok(Some(frame))
+            }
+            Err(Error::Incomplete) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        self.encode(frame);
+        self.write_encoded().await
+    }
+
+    /// Encodes `frame` for a later `write_encoded`, returning its size so the
+    /// caller can check it against an output buffer limit first.
+    pub fn encode(&mut self, frame: &Frame) -> usize {
+        self.out.clear();
+        if self.resp3 {
+            frame.encode_resp3(&mut self.out);
+        } else {
+            frame.encode(&mut self.out);
+        }
+        self.out.len()
+    }
+
+    /// Writes out the frame passed to the last `encode`.
+    pub async fn write_encoded(&mut self) -> io::Result<()> {
+        self.stream.write_all(&self.out).await?;
+        self.stream.flush().await
+    }
+
+    /// Writes frames that were encoded elsewhere.
+    pub async fn write_bytes(&mut self, chunks: &[Bytes]) -> io::Result<()> {
+        for chunk in chunks {
+            self.stream.write_all(chunk).await?;
+        }
+        self.stream.flush().await
+    }
+}
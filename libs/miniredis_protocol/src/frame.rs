@@ -0,0 +1,452 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::convert::TryInto;
+use std::fmt;
+use std::io::Cursor;
+use std::num::TryFromIntError;
+use std::string::FromUtf8Error;
+
+pub const SIMPLE_BYTE: u8 = b'+';
+pub const ERROR_BYTE: u8 = b'-';
+pub const INTEGER_BYTE: u8 = b':';
+pub const BULK_BYTE: u8 = b'$';
+pub const ARRAY_BYTE: u8 = b'*';
+pub const NULL_BYTE: u8 = b'_';
+pub const MAP_BYTE: u8 = b'%';
+pub const PUSH_BYTE: u8 = b'>';
+
+pub const EOL_BYTE_ENCODING: &[u8; 2] = b"\r\n";
+pub const NULL_BYTE_ENCODING: &[u8; 2] = b"-1";
+
+/// A frame in the Redis serialization protocol.
+///
+/// Frames are RESP2 unless encoded with `encode_resp3`. The RESP3 only
+/// `Map` and `Push` types are sent as plain arrays to RESP2 clients.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Frame {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    Bulk(Bytes),
+    Null,
+    Array(Vec<Frame>),
+    Map(Vec<(Frame, Frame)>),
+    /// Out of band data, such as pub/sub messages and invalidations.
+    Push(Vec<Frame>),
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Not enough data is available to parse a frame
+    Incomplete,
+    /// Invalid frame encoding
+    Other(crate::Error),
+}
+
+impl Frame {
+    pub fn array() -> Frame {
+        Frame::Array(vec![])
+    }
+
+    pub fn ok() -> Frame {
+        Frame::Simple("OK".to_string())
+    }
+
+    pub fn push_bulk(&mut self, bytes: Bytes) {
+        match self {
+            Frame::Array(vec) => vec.push(Frame::Bulk(bytes)),
+            _ => panic!("not an array frame"),
+        }
+    }
+
+    pub fn push_int(&mut self, value: i64) {
+        match self {
+            Frame::Array(vec) => vec.push(Frame::Integer(value)),
+            _ => panic!("not an array frame"),
+        }
+    }
+
+    /// Checks if an entire frame can be decoded from `src`.
+    pub fn check(src: &mut Cursor<&[u8]>) -> Result<(), Error> {
+        match get_u8(src)? {
+            SIMPLE_BYTE | ERROR_BYTE => {
+                get_line(src)?;
+                Ok(())
+            }
+            INTEGER_BYTE => {
+                get_signed(src)?;
+                Ok(())
+            }
+            BULK_BYTE => {
+                if ERROR_BYTE == peek_u8(src)? {
+                    // Skip '-1\r\n'
+                    skip(src, NULL_BYTE_ENCODING.len() + EOL_BYTE_ENCODING.len())
+                } else {
+                    let len: usize = get_decimal(src)?.try_into()?;
+                    // skip that number of bytes + 2 (\r\n).
+                    let n = len
+                        .checked_add(EOL_BYTE_ENCODING.len())
+                        .ok_or("protocol error; invalid bulk length")?;
+                    skip(src, n)
+                }
+            }
+            ARRAY_BYTE => {
+                if ERROR_BYTE == peek_u8(src)? {
+                    return skip(src, NULL_BYTE_ENCODING.len() + EOL_BYTE_ENCODING.len());
+                }
+
+                let len = get_decimal(src)?;
+                for _ in 0..len {
+                    Frame::check(src)?;
+                }
+
+                Ok(())
+            }
+            NULL_BYTE => {
+                get_line(src)?;
+                Ok(())
+            }
+            MAP_BYTE => {
+                let len = get_decimal(src)?;
+                for _ in 0..len * 2 {
+                    Frame::check(src)?;
+                }
+
+                Ok(())
+            }
+            PUSH_BYTE => {
+                let len = get_decimal(src)?;
+                for _ in 0..len {
+                    Frame::check(src)?;
+                }
+
+                Ok(())
+            }
+            actual => Err(format!("protocol error; invalid frame type byte `{}`", actual).into()),
+        }
+    }
+
+    /// The frame has already been validated with `check`.
+    pub fn parse(src: &mut Cursor<&[u8]>) -> Result<Frame, Error> {
+        match get_u8(src)? {
+            SIMPLE_BYTE => {
+                let line = get_line(src)?.to_vec();
+                Ok(Frame::Simple(String::from_utf8(line)?))
+            }
+            ERROR_BYTE => {
+                let line = get_line(src)?.to_vec();
+                Ok(Frame::Error(String::from_utf8(line)?))
+            }
+            INTEGER_BYTE => Ok(Frame::Integer(get_signed(src)?)),
+            BULK_BYTE => {
+                if ERROR_BYTE == peek_u8(src)? {
+                    let line = get_line(src)?;
+                    if line != NULL_BYTE_ENCODING {
+                        return Err("protocol error; invalid frame format".into());
+                    }
+
+                    Ok(Frame::Null)
+                } else {
+                    let len = get_decimal(src)?.try_into()?;
+                    let n = len + EOL_BYTE_ENCODING.len();
+
+                    if src.remaining() < n {
+                        return Err(Error::Incomplete);
+                    }
+
+                    let data = Bytes::copy_from_slice(&src.chunk()[..len]);
+
+                    // skip that number of bytes + 2 (\r\n).
+                    skip(src, n)?;
+
+                    Ok(Frame::Bulk(data))
+                }
+            }
+            ARRAY_BYTE => {
+                if ERROR_BYTE == peek_u8(src)? {
+                    let line = get_line(src)?;
+                    if line != NULL_BYTE_ENCODING {
+                        return Err("protocol error; invalid frame format".into());
+                    }
+
+                    return Ok(Frame::Null);
+                }
+
+                let len = get_decimal(src)?.try_into()?;
+                let mut out = Vec::with_capacity(len);
+
+                for _ in 0..len {
+                    out.push(Frame::parse(src)?);
+                }
+
+                Ok(Frame::Array(out))
+            }
+            NULL_BYTE => {
+                get_line(src)?;
+                Ok(Frame::Null)
+            }
+            MAP_BYTE => {
+                let len = get_decimal(src)?.try_into()?;
+                let mut out = Vec::with_capacity(len);
+
+                for _ in 0..len {
+                    out.push((Frame::parse(src)?, Frame::parse(src)?));
+                }
+
+                Ok(Frame::Map(out))
+            }
+            PUSH_BYTE => {
+                let len = get_decimal(src)?.try_into()?;
+                let mut out = Vec::with_capacity(len);
+
+                for _ in 0..len {
+                    out.push(Frame::parse(src)?);
+                }
+
+                Ok(Frame::Push(out))
+            }
+            actual => Err(format!("protocol error; invalid frame type byte `{}`", actual).into()),
+        }
+    }
+
+    /// Appends the RESP2 wire encoding of the frame to `dst`.
+    ///
+    /// Unlike the connection, encoding into a buffer is synchronous so nested
+    /// arrays can be written recursively.
+    pub fn encode(&self, dst: &mut BytesMut) {
+        self.encode_as(dst, false)
+    }
+
+    /// Appends the RESP3 wire encoding of the frame to `dst`, for clients
+    /// that switched protocol with `HELLO 3`.
+    pub fn encode_resp3(&self, dst: &mut BytesMut) {
+        self.encode_as(dst, true)
+    }
+
+    fn encode_as(&self, dst: &mut BytesMut, resp3: bool) {
+        match self {
+            Frame::Simple(val) => {
+                dst.put_u8(SIMPLE_BYTE);
+                dst.put_slice(val.as_bytes());
+                dst.put_slice(EOL_BYTE_ENCODING);
+            }
+            Frame::Error(val) => {
+                dst.put_u8(ERROR_BYTE);
+                dst.put_slice(val.as_bytes());
+                dst.put_slice(EOL_BYTE_ENCODING);
+            }
+            Frame::Integer(val) => {
+                dst.put_u8(INTEGER_BYTE);
+                dst.put_slice(val.to_string().as_bytes());
+                dst.put_slice(EOL_BYTE_ENCODING);
+            }
+            Frame::Bulk(val) => {
+                dst.put_u8(BULK_BYTE);
+                dst.put_slice(val.len().to_string().as_bytes());
+                dst.put_slice(EOL_BYTE_ENCODING);
+                dst.put_slice(val);
+                dst.put_slice(EOL_BYTE_ENCODING);
+            }
+            Frame::Null if resp3 => {
+                dst.put_u8(NULL_BYTE);
+                dst.put_slice(EOL_BYTE_ENCODING);
+            }
+            Frame::Null => {
+                dst.put_u8(BULK_BYTE);
+                dst.put_slice(NULL_BYTE_ENCODING);
+                dst.put_slice(EOL_BYTE_ENCODING);
+            }
+            Frame::Array(val) => {
+                encode_len(dst, ARRAY_BYTE, val.len());
+                for frame in val {
+                    frame.encode_as(dst, resp3);
+                }
+            }
+            Frame::Map(val) => {
+                if resp3 {
+                    encode_len(dst, MAP_BYTE, val.len());
+                } else {
+                    encode_len(dst, ARRAY_BYTE, val.len() * 2);
+                }
+                for (key, value) in val {
+                    key.encode_as(dst, resp3);
+                    value.encode_as(dst, resp3);
+                }
+            }
+            Frame::Push(val) => {
+                encode_len(dst, if resp3 { PUSH_BYTE } else { ARRAY_BYTE }, val.len());
+                for frame in val {
+                    frame.encode_as(dst, resp3);
+                }
+            }
+        }
+    }
+}
+
+fn encode_len(dst: &mut BytesMut, type_byte: u8, len: usize) {
+    dst.put_u8(type_byte);
+    dst.put_slice(len.to_string().as_bytes());
+    dst.put_slice(EOL_BYTE_ENCODING);
+}
+
+fn peek_u8(src: &mut Cursor<&[u8]>) -> Result<u8, Error> {
+    if !src.has_remaining() {
+        return Err(Error::Incomplete);
+    }
+
+    Ok(src.chunk()[0])
+}
+
+fn get_u8(src: &mut Cursor<&[u8]>) -> Result<u8, Error> {
+    if !src.has_remaining() {
+        return Err(Error::Incomplete);
+    }
+
+    Ok(src.get_u8())
+}
+
+fn skip(src: &mut Cursor<&[u8]>, n: usize) -> Result<(), Error> {
+    if src.remaining() < n {
+        return Err(Error::Incomplete);
+    }
+
+    src.advance(n);
+    Ok(())
+}
+
+/// Read a new-line terminated decimal
+fn get_decimal(src: &mut Cursor<&[u8]>) -> Result<u64, Error> {
+    let line = get_line(src)?;
+
+    std::str::from_utf8(line)
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .ok_or_else(|| "protocol error; invalid frame format".into())
+}
+
+/// Read a new-line terminated, possibly negative, decimal
+fn get_signed(src: &mut Cursor<&[u8]>) -> Result<i64, Error> {
+    let line = get_line(src)?;
+
+    std::str::from_utf8(line)
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or_else(|| "protocol error; invalid frame format".into())
+}
+
+/// Find a line
+fn get_line<'a>(src: &mut Cursor<&'a [u8]>) -> Result<&'a [u8], Error> {
+    let start = src.position() as usize;
+    let buf = *src.get_ref();
+
+    if buf.len() < 2 {
+        return Err(Error::Incomplete);
+    }
+
+    // Scan to the second to last byte
+    for i in start..buf.len() - 1 {
+        if buf[i] == b'\r' && buf[i + 1] == b'\n' {
+            // We found a line, update the position to be *after* the \n
+            src.set_position((i + 2) as u64);
+            return Ok(&buf[start..i]);
+        }
+    }
+
+    Err(Error::Incomplete)
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use std::str;
+
+        match self {
+            Frame::Simple(res) => res.fmt(fmt),
+            Frame::Error(msg) => write!(fmt, "error: {}", msg),
+            Frame::Integer(num) => num.fmt(fmt),
+            Frame::Bulk(msg) => match str::from_utf8(msg) {
+                Ok(string) => string.fmt(fmt),
+                Err(_) => write!(fmt, "{:?}", msg),
+            },
+            Frame::Null => "(nil)".fmt(fmt),
+            Frame::Map(pairs) => {
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(fmt, " ")?;
+                    }
+                    write!(fmt, "{} {}", key, value)?;
+                }
+                Ok(())
+            }
+            Frame::Array(parts) | Frame::Push(parts) => {
+                for (i, part) in parts.iter().enumerate() {
+                    if i > 0 {
+                        write!(fmt, " ")?;
+                    }
+                    part.fmt(fmt)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<String> for Error {
+    fn from(src: String) -> Error {
+        Error::Other(src.into())
+    }
+}
+
+impl From<&str> for Error {
+    fn from(src: &str) -> Error {
+        src.to_string().into()
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(_src: FromUtf8Error) -> Error {
+        "protocol error; invalid frame format".into()
+    }
+}
+
+impl From<TryFromIntError> for Error {
+    fn from(_src: TryFromIntError) -> Error {
+        "protocol error; invalid frame format".into()
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Incomplete => "stream ended early".fmt(fmt),
+            Error::Other(err) => err.fmt(fmt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &[u8]) -> Result<Frame, Error> {
+        Frame::parse(&mut Cursor::new(input))
+    }
+
+    #[test]
+    fn unknown_type_byte_is_a_protocol_error() {
+        assert!(matches!(parse(b"?x\r\n"), Err(Error::Other(_))));
+        assert!(matches!(
+            Frame::check(&mut Cursor::new(&b"?x\r\n"[..])),
+            Err(Error::Other(_))
+        ));
+    }
+
+    #[test]
+    fn huge_bulk_length_is_a_protocol_error() {
+        let input = format!("${}\r\n", u64::MAX);
+        assert!(matches!(
+            Frame::check(&mut Cursor::new(input.as_bytes())),
+            Err(Error::Other(_))
+        ));
+    }
+}
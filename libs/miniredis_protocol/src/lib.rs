@@ -0,0 +1,13 @@
+//! The Redis serialization protocol, as spoken by miniredis: frames and a
+//! connection reading and writing them over TCP.
+//!
+//! Kept apart from the server so clients can speak it without building the
+//! whole server.
+
+pub mod connection;
+pub mod frame;
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// A specialized `Result` type for protocol operations.
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,110 @@
+//! Compares the shard models of `ShardedDB` on reads.
+//!
+//! ```text
+//! cargo bench -p shared_lib --bench sharded_db
+//! ```
+
+use std::hint::black_box;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use shared_lib::sharded_db::{ShardModel, ShardedDB};
+
+const MODELS: [ShardModel; 3] = [ShardModel::Mutex, ShardModel::RwLock, ShardModel::Owner];
+
+const NUM_SHARDS: usize = 10;
+
+const KEYS: usize = 10_000;
+
+/// Large enough for cloning values to show.
+const VALUE_SIZE: usize = 1024;
+
+/// Threads reading at once in `concurrent_reads`.
+const READERS: usize = 8;
+
+fn keys() -> Vec<String> {
+    (0..KEYS).map(|i| format!("key:{:012}", i)).collect()
+}
+
+fn filled<T>(model: ShardModel, keys: &[String], value: impl Fn() -> T) -> Arc<ShardedDB<String, T>>
+where
+    T: Clone + Send + 'static,
+{
+    let db = ShardedDB::with_model(NUM_SHARDS, model);
+    for key in keys {
+        db.insert(&key[..], value());
+    }
+    db
+}
+
+/// `get` cloning the value, against `get` of an `Arc` and reading in place
+/// with `with`, from a single thread.
+fn single_reader(c: &mut Criterion) {
+    let keys = keys();
+    let mut group = c.benchmark_group("single_reader");
+
+    for model in MODELS {
+        let db = filled(model, &keys, || vec![b'x'; VALUE_SIZE]);
+        let mut i = 0;
+        group.bench_function(BenchmarkId::new("get", format!("{:?}", model)), |b| {
+            b.iter(|| {
+                i = (i + 1) % KEYS;
+                black_box(db.get(&keys[i][..]).map(|value| value.len()))
+            })
+        });
+        group.bench_function(BenchmarkId::new("with", format!("{:?}", model)), |b| {
+            b.iter(|| {
+                i = (i + 1) % KEYS;
+                black_box(db.with(&keys[i][..], |value| value.map(|value| value.len())))
+            })
+        });
+
+        let db = filled(model, &keys, || Arc::new(vec![b'x'; VALUE_SIZE]));
+        group.bench_function(BenchmarkId::new("get_arc", format!("{:?}", model)), |b| {
+            b.iter(|| {
+                i = (i + 1) % KEYS;
+                black_box(db.get(&keys[i][..]).map(|value| value.len()))
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// `READERS` threads reading in place at once, where readers of a shard
+/// serialize on a mutex but not on a reader-writer lock.
+fn concurrent_reads(c: &mut Criterion) {
+    let keys = Arc::new(keys());
+    let mut group = c.benchmark_group("concurrent_reads");
+    group.throughput(Throughput::Elements(READERS as u64));
+
+    for model in MODELS {
+        let db = filled(model, &keys, || vec![b'x'; VALUE_SIZE]);
+        group.bench_function(format!("{:?}", model), |b| {
+            b.iter_custom(|iters| read_in_parallel(&db, &keys, iters))
+        });
+    }
+
+    group.finish();
+}
+
+/// Time for `READERS` threads to read `iters` keys each.
+fn read_in_parallel(db: &ShardedDB<String, Vec<u8>>, keys: &[String], iters: u64) -> Duration {
+    let start = Instant::now();
+    thread::scope(|scope| {
+        for reader in 0..READERS {
+            scope.spawn(move || {
+                for i in 0..iters as usize {
+                    let key = &keys[(i * READERS + reader) % KEYS][..];
+                    black_box(db.with(key, |value| value.map(|value| value.len())));
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+criterion_group!(benches, single_reader, concurrent_reads);
+criterion_main!(benches);
@@ -1,6 +1,5 @@
 use std::collections::HashMap;
 
-use uuid::Uuid;
 use bytes::Bytes;
 
 pub type UploadId = String;
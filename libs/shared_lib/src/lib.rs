@@ -1,2 +1,4 @@
 pub mod sharded_db;
+mod shard_owner;
+#[allow(dead_code)]
 pub mod client_model;
\ No newline at end of file
@@ -0,0 +1,305 @@
+//! Shards owned by threads, for `ShardModel::Owner`.
+//!
+//! The shards of every map are spread over a single pool of threads, one per
+//! core. The map of a shard lives on its thread and is only reached with
+//! messages: a job run against the map, its result sent back over a channel.
+//! Entries changed by a caller's closure are checked out instead, the shard
+//! running nothing else until they come back, which keeps read-modify-write
+//! updates atomic as a lock would. The other shards of the thread carry on
+//! meanwhile.
+//!
+//! Callers block until the reply comes back, tokio workers included, so the
+//! model is there to be compared with the locks in benchmarks rather than to
+//! serve clients.
+
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::OnceLock;
+use std::thread;
+
+/// The map of a shard, whatever its key and value types.
+type Map = Box<dyn Any + Send>;
+
+/// What a job panicked with, handed to the caller to panic with in turn.
+type Panic = Box<dyn Any + Send>;
+
+/// Work on the map of a shard, returning whether it checked entries out.
+type Task = Box<dyn FnOnce(&mut Map) -> bool + Send>;
+
+enum Job {
+    /// Adds the map of a new shard.
+    Create(u64, Map),
+    /// Runs once no entries of the shard are checked out.
+    Run(u64, Task),
+    /// Puts checked out entries back.
+    Return(u64, Task),
+    /// Drops the map of a shard whose handle is gone.
+    Remove(u64),
+}
+
+/// A shard as kept by its thread.
+struct Shard {
+    map: Map,
+    checked_out: bool,
+    /// Tasks sent while entries were checked out, in order.
+    waiting: VecDeque<Task>,
+}
+
+/// The threads owning shards, and where the next shard goes.
+struct Pool {
+    threads: Vec<Sender<Job>>,
+    next: AtomicUsize,
+}
+
+/// Handle to a shard, on one of the pool's threads. The shard is dropped
+/// along with its handle.
+#[derive(Debug)]
+pub(crate) struct Owner<K, T> {
+    id: u64,
+    jobs: Sender<Job>,
+    _map: PhantomData<fn() -> HashMap<K, T>>,
+}
+
+/// Entries taken out of a shard by `Owner::checkout`, in the order of their
+/// keys. They are put back when the checkout is dropped, those left `Some`
+/// stored.
+pub(crate) struct Checkout<K, T>
+where
+    K: Hash + Eq + Send + 'static,
+    T: Send + 'static,
+{
+    pub slots: Vec<Option<T>>,
+    keys: Vec<K>,
+    id: u64,
+    jobs: Sender<Job>,
+}
+
+impl<K, T> Owner<K, T>
+where
+    K: Hash + Eq + Send + 'static,
+    T: Send + 'static,
+{
+    /// Adds an empty shard to the next thread of the pool.
+    pub(crate) fn new() -> Owner<K, T> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        let pool = pool();
+        let thread = pool.next.fetch_add(1, Ordering::Relaxed) % pool.threads.len();
+        let owner = Owner {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            jobs: pool.threads[thread].clone(),
+            _map: PhantomData,
+        };
+        owner.send(Job::Create(owner.id, Box::new(HashMap::<K, T>::new())));
+        owner
+    }
+
+    /// Runs `f` on the owner thread and waits for its result. A panic of
+    /// `f` is resumed on the caller's thread.
+    pub(crate) fn run<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut HashMap<K, T>) -> R + Send + 'static,
+    ) -> R {
+        let (result, receiver) = mpsc::sync_channel(1);
+        self.send(Job::Run(
+            self.id,
+            Box::new(move |map| {
+                let map = downcast(map);
+                let _ = result.send(panic::catch_unwind(AssertUnwindSafe(|| f(map))));
+                false
+            }),
+        ));
+        wait(receiver)
+    }
+
+    /// Takes the entries of `keys` out of the shard, which runs nothing else
+    /// until they are put back.
+    pub(crate) fn checkout(&self, keys: Vec<K>) -> Checkout<K, T> {
+        let (result, receiver) = mpsc::sync_channel(1);
+        self.send(Job::Run(
+            self.id,
+            Box::new(move |map| {
+                let map = downcast(map);
+                let slots = match panic::catch_unwind(AssertUnwindSafe(|| {
+                    keys.iter().map(|key| map.remove(key)).collect()
+                })) {
+                    Ok(slots) => slots,
+                    Err(payload) => {
+                        let _ = result.send(Err(payload));
+                        return false;
+                    }
+                };
+                // Put back at once if the caller is gone.
+                match result.send(Ok((keys, slots))) {
+                    Ok(()) => true,
+                    Err(mpsc::SendError(Ok((keys, slots)))) => {
+                        restore(map, keys, slots);
+                        false
+                    }
+                    Err(mpsc::SendError(Err(_))) => false,
+                }
+            }),
+        ));
+
+        let (keys, slots) = wait(receiver);
+        Checkout {
+            slots,
+            keys,
+            id: self.id,
+            jobs: self.jobs.clone(),
+        }
+    }
+
+    fn send(&self, job: Job) {
+        self.jobs.send(job).expect("shard thread exited");
+    }
+}
+
+impl<K, T> Drop for Owner<K, T> {
+    fn drop(&mut self) {
+        let _ = self.jobs.send(Job::Remove(self.id));
+    }
+}
+
+impl<K, T> Drop for Checkout<K, T>
+where
+    K: Hash + Eq + Send + 'static,
+    T: Send + 'static,
+{
+    fn drop(&mut self) {
+        let keys = mem::take(&mut self.keys);
+        let slots = mem::take(&mut self.slots);
+        let task: Task = Box::new(move |map| {
+            restore(downcast(map), keys, slots);
+            false
+        });
+        let _ = self.jobs.send(Job::Return(self.id, task));
+    }
+}
+
+impl Shard {
+    fn run(&mut self, task: Task) {
+        if self.checked_out {
+            self.waiting.push_back(task);
+            return;
+        }
+        self.checked_out = run_task(&mut self.map, task);
+    }
+
+    /// Puts entries back, then runs the tasks that waited for them until one
+    /// checks entries out again.
+    fn give_back(&mut self, task: Task) {
+        run_task(&mut self.map, task);
+        self.checked_out = false;
+
+        while !self.checked_out {
+            match self.waiting.pop_front() {
+                Some(task) => self.checked_out = run_task(&mut self.map, task),
+                None => break,
+            }
+        }
+    }
+}
+
+/// The pool, started on first use.
+fn pool() -> &'static Pool {
+    static POOL: OnceLock<Pool> = OnceLock::new();
+
+    POOL.get_or_init(|| {
+        let size = thread::available_parallelism().map_or(1, |n| n.get());
+        let threads = (0..size)
+            .map(|index| {
+                let (jobs, queue) = mpsc::channel();
+                thread::Builder::new()
+                    .name(format!("shard-owner-{}", index))
+                    .spawn(move || serve(queue))
+                    .expect("failed to spawn shard thread");
+                jobs
+            })
+            .collect();
+
+        Pool {
+            threads,
+            next: AtomicUsize::new(0),
+        }
+    })
+}
+
+fn serve(queue: Receiver<Job>) {
+    let mut shards = HashMap::new();
+
+    for job in queue {
+        match job {
+            Job::Create(id, map) => {
+                let shard = Shard {
+                    map,
+                    checked_out: false,
+                    waiting: VecDeque::new(),
+                };
+                shards.insert(id, shard);
+            }
+            Job::Run(id, task) => {
+                if let Some(shard) = shards.get_mut(&id) {
+                    shard.run(task);
+                }
+            }
+            Job::Return(id, task) => {
+                if let Some(shard) = shards.get_mut(&id) {
+                    shard.give_back(task);
+                }
+            }
+            Job::Remove(id) => {
+                shards.remove(&id);
+            }
+        }
+    }
+}
+
+/// Waits for the result of a job, resuming its panic if it panicked.
+fn wait<R>(receiver: Receiver<Result<R, Panic>>) -> R {
+    match receiver.recv() {
+        Ok(Ok(result)) => result,
+        Ok(Err(payload)) => panic::resume_unwind(payload),
+        Err(_) => panic!("shard thread exited"),
+    }
+}
+
+/// Runs `task`, a panic only failing the caller waiting for it rather than
+/// every shard of the thread. Jobs with a caller hand it their panic, those
+/// putting entries back have none so theirs is reported here.
+fn run_task(map: &mut Map, task: Task) -> bool {
+    match panic::catch_unwind(AssertUnwindSafe(|| task(map))) {
+        Ok(checked_out) => checked_out,
+        Err(payload) => {
+            eprintln!("shard job panicked: {}", panic_message(&payload));
+            false
+        }
+    }
+}
+
+fn panic_message(payload: &Panic) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(msg) => msg,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("non-string payload", String::as_str),
+    }
+}
+
+fn downcast<K: 'static, T: 'static>(map: &mut Map) -> &mut HashMap<K, T> {
+    map.downcast_mut().expect("shard of another type")
+}
+
+fn restore<K: Hash + Eq, T>(map: &mut HashMap<K, T>, keys: Vec<K>, slots: Vec<Option<T>>) {
+    for (key, slot) in keys.into_iter().zip(slots) {
+        if let Some(value) = slot {
+            map.insert(key, value);
+        }
+    }
+}
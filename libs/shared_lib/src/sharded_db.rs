@@ -1,41 +1,672 @@
-use std::hash::{Hash, Hasher};
-use std::collections::hash_map::DefaultHasher;
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::collections::hash_map::{DefaultHasher, RandomState};
 
-use std::sync::{Arc, Mutex};
+use std::mem;
+use std::ops::DerefMut;
+use std::sync::{Arc, Mutex, RwLock};
 use std::collections::{HashMap};
 
-type ShardedMap<T> = Arc<Vec<Mutex<HashMap<String, T>>>>;
+use crate::shard_owner::Owner;
 
-#[derive(Clone)]
-pub struct ShardedDB<T> {
-    db: ShardedMap<T>,
+type ShardedMap<K, T> = Arc<Shards<K, T>>;
+
+/// Scan cursors keep the shard index in their top byte and a position within
+/// the shard below it.
+const SCAN_SHARD_SHIFT: u32 = 56;
+const SCAN_POSITION_MASK: u64 = (1 << SCAN_SHARD_SHIFT) - 1;
+
+/// How the shards of a `ShardedDB` are reached.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShardModel {
+    /// Each shard is behind a `Mutex`, locked by whichever thread uses it.
+    #[default]
+    Mutex,
+    /// Each shard is behind a `RwLock`, so readers of a shard run side by
+    /// side and only writers take it exclusively. Suits read-heavy loads.
+    RwLock,
+    /// Each shard is owned by one of a pool of threads, one per core and
+    /// shared by every map, and every operation is a message sent to it.
+    /// Callers block until the reply comes back, as they would on a contended
+    /// lock, tokio workers included: the model is meant for benchmarks.
+    Owner,
 }
 
-impl<T: std::clone::Clone> ShardedDB<T> {
-    pub fn new(num_shards: usize) -> Arc<Self> {
-        let mut db =  Vec::with_capacity(num_shards);
-        for _ in 0..num_shards {
-            db.push(Mutex::new(HashMap::new()));
+impl std::str::FromStr for ShardModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ShardModel, String> {
+        match &s.to_lowercase()[..] {
+            "mutex" => Ok(ShardModel::Mutex),
+            "rwlock" => Ok(ShardModel::RwLock),
+            "owner" => Ok(ShardModel::Owner),
+            _ => Err(format!("unknown shard model '{}', expected mutex, rwlock or owner", s)),
         }
+    }
+}
+
+#[derive(Debug)]
+enum Shards<K, T> {
+    Locked(Vec<Mutex<HashMap<K, T>>>),
+    Shared(Vec<RwLock<HashMap<K, T>>>),
+    Owned(Vec<Owner<K, T>>),
+}
+
+/// A map split into shards, each behind its own lock or owned by its own
+/// thread, see `ShardModel`.
+///
+/// Keys are of any hashable type `K`, and are looked up by any borrowed form
+/// of it as with `HashMap`, e.g. `&str` for `String` keys or `&[u8]` for
+/// `Bytes` keys. Methods storing a key they were not given owned turn the
+/// borrowed form into `K` with `ToOwned` and `Into`, as do lookups with the
+/// `Owner` model, whose messages carry their key.
+///
+/// `get` returns a clone of the value. Values that are costly to clone are
+/// best stored as `Arc<T>`, or borrowed in place with `with`.
+#[derive(Clone, Debug)]
+pub struct ShardedDB<K, T> {
+    db: ShardedMap<K, T>,
+}
+
+impl<K, T> ShardedDB<K, T>
+where
+    K: Hash + Eq + Clone + Send + 'static,
+    T: Clone + Send + 'static,
+{
+    pub fn new(num_shards: usize) -> Arc<Self> {
+        Self::with_model(num_shards, ShardModel::Mutex)
+    }
+
+    /// Panics if `num_shards` is 0, as no shard could hold a key.
+    pub fn with_model(num_shards: usize, model: ShardModel) -> Arc<Self> {
+        assert!(num_shards > 0, "a ShardedDB needs at least one shard");
+        let db = match model {
+            ShardModel::Mutex => {
+                let mut db =  Vec::with_capacity(num_shards);
+                for _ in 0..num_shards {
+                    db.push(Mutex::new(HashMap::new()));
+                }
+                Shards::Locked(db)
+            }
+            ShardModel::RwLock => {
+                Shards::Shared((0..num_shards).map(|_| RwLock::new(HashMap::new())).collect())
+            }
+            ShardModel::Owner => Shards::Owned((0..num_shards).map(|_| Owner::new()).collect()),
+        };
 
         Arc::new(Self { db: Arc::new(db), })
     }
 
-    pub fn insert(&self, key: &str, value: T) {
+    pub fn model(&self) -> ShardModel {
+        match &*self.db {
+            Shards::Locked(_) => ShardModel::Mutex,
+            Shards::Shared(_) => ShardModel::RwLock,
+            Shards::Owned(_) => ShardModel::Owner,
+        }
+    }
+
+    pub fn insert<Q>(&self, key: &Q, value: T)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned + ?Sized,
+        Q::Owned: Into<K>,
+    {
+        let shard_index = self.get_key_shard(key);
+        let key = key.to_owned().into();
+        match &*self.db {
+            Shards::Locked(shards) => {
+                shards[shard_index].lock().unwrap().insert(key, value);
+            }
+            Shards::Shared(shards) => {
+                shards[shard_index].write().unwrap().insert(key, value);
+            }
+            Shards::Owned(owners) => owners[shard_index].run(move |shard| {
+                shard.insert(key, value);
+            }),
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<T>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned + ?Sized,
+        Q::Owned: Into<K>,
+    {
+        let shard_index = self.get_key_shard(key);
+        match &*self.db {
+            Shards::Locked(shards) => shards[shard_index].lock().unwrap().get(key).cloned(),
+            Shards::Shared(shards) => shards[shard_index].read().unwrap().get(key).cloned(),
+            Shards::Owned(owners) => {
+                let key: K = key.to_owned().into();
+                owners[shard_index].run(move |shard| shard.get::<K>(&key).cloned())
+            }
+        }
+    }
+
+    /// Runs `f` against the value of `key` where it is stored, so it is read
+    /// without being cloned. `None` when the key is absent.
+    ///
+    /// The shard stays locked for reading for the duration of `f`, or the
+    /// entry checked out with the `Owner` model, so `f` should be short and
+    /// must not use the map.
+    pub fn with<Q, R>(&self, key: &Q, f: impl FnOnce(Option<&T>) -> R) -> R
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned + ?Sized,
+        Q::Owned: Into<K>,
+    {
+        let shard_index = self.get_key_shard(key);
+        match &*self.db {
+            Shards::Locked(shards) => f(shards[shard_index].lock().unwrap().get(key)),
+            Shards::Shared(shards) => f(shards[shard_index].read().unwrap().get(key)),
+            Shards::Owned(owners) => {
+                let checkout = owners[shard_index].checkout(vec![key.to_owned().into()]);
+                f(checkout.slots[0].as_ref())
+            }
+        }
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<T>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned + ?Sized,
+        Q::Owned: Into<K>,
+    {
+        let shard_index = self.get_key_shard(key);
+        match &*self.db {
+            Shards::Locked(shards) => shards[shard_index].lock().unwrap().remove(key),
+            Shards::Shared(shards) => shards[shard_index].write().unwrap().remove(key),
+            Shards::Owned(owners) => {
+                let key: K = key.to_owned().into();
+                owners[shard_index].run(move |shard| shard.remove::<K>(&key))
+            }
+        }
+    }
+
+    /// Runs `f` against the slot for `key` while holding the shard lock, so a
+    /// read-modify-write cannot interleave with other writers of the shard.
+    /// With the `Owner` model the entry is checked out of the shard instead,
+    /// the shard running nothing else until it comes back.
+    ///
+    /// The slot is `None` when the key is absent. Leaving `None` behind removes
+    /// the key, leaving `Some` stores the value. With the locks, an existing
+    /// value is updated where it is stored, a default value standing in while
+    /// `f` has it.
+    pub fn update<Q, R>(&self, key: &Q, f: impl FnOnce(&mut Option<T>) -> R) -> R
+    where
+        T: Default,
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned + ?Sized,
+        Q::Owned: Into<K>,
+    {
         let shard_index = self.get_key_shard(key);
-        let mut shard = self.db[shard_index].lock().unwrap();
-        shard.insert(key.to_string(), value);
+        match &*self.db {
+            Shards::Locked(shards) => update_in(&mut shards[shard_index].lock().unwrap(), key, f),
+            Shards::Shared(shards) => update_in(&mut shards[shard_index].write().unwrap(), key, f),
+            Shards::Owned(owners) => {
+                let mut checkout = owners[shard_index].checkout(vec![key.to_owned().into()]);
+                f(&mut checkout.slots[0])
+            }
+        }
     }
 
-    pub fn get(&self, key: &str) -> Option<T> {
+    /// Same as `update` for two distinct keys, with both shards locked for the
+    /// duration of `f`.
+    ///
+    /// Shards are always locked in index order, so concurrent calls cannot
+    /// deadlock each other. With the `Owner` model the two entries are
+    /// checked out of their shards in that same order.
+    pub fn update_pair<Q, R>(
+        &self,
+        first: &Q,
+        second: &Q,
+        f: impl FnOnce(&mut Option<T>, &mut Option<T>) -> R,
+    ) -> R
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned + ?Sized,
+        Q::Owned: Into<K>,
+    {
+        assert!(first != second, "update_pair needs two distinct keys");
+
+        let first_index = self.get_key_shard(first);
+        let second_index = self.get_key_shard(second);
+
+        let first = (first_index, first);
+        let second = (second_index, second);
+        match &*self.db {
+            Shards::Locked(shards) => {
+                update_locked_pair(shards, |shard| shard.lock().unwrap(), first, second, f)
+            }
+            Shards::Shared(shards) => {
+                update_locked_pair(shards, |shard| shard.write().unwrap(), first, second, f)
+            }
+            Shards::Owned(owners) => update_owned_pair(owners, first, second, f),
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned + ?Sized,
+        Q::Owned: Into<K>,
+    {
         let shard_index = self.get_key_shard(key);
-        let shard = self.db[shard_index].lock().unwrap();
-        shard.get(key).cloned()
+        match &*self.db {
+            Shards::Locked(shards) => shards[shard_index].lock().unwrap().contains_key(key),
+            Shards::Shared(shards) => shards[shard_index].read().unwrap().contains_key(key),
+            Shards::Owned(owners) => {
+                let key: K = key.to_owned().into();
+                owners[shard_index].run(move |shard| shard.contains_key::<K>(&key))
+            }
+        }
+    }
+
+    /// Number of keys across all shards.
+    ///
+    /// Shards are counted one after the other, so the total is only exact
+    /// when there are no concurrent writers.
+    pub fn len(&self) -> usize {
+        (0..self.num_shards()).map(|i| self.with_shard(i, |shard| shard.len())).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..self.num_shards()).all(|i| self.with_shard(i, |shard| shard.is_empty()))
+    }
+
+    pub fn clear(&self) {
+        match &*self.db {
+            Shards::Locked(shards) => shards.iter().for_each(|shard| shard.lock().unwrap().clear()),
+            Shards::Shared(shards) => shards.iter().for_each(|shard| shard.write().unwrap().clear()),
+            Shards::Owned(owners) => owners.iter().for_each(|owner| owner.run(|shard| shard.clear())),
+        }
+    }
+
+    /// Returns a random key, or `None` when the map is empty.
+    ///
+    /// A random shard is picked first, so keys in sparsely populated shards
+    /// are somewhat more likely to come up.
+    pub fn random_key(&self) -> Option<K> {
+        let num_shards = self.num_shards();
+        let start = random_index(num_shards);
+
+        for i in 0..num_shards {
+            let key = self.with_shard((start + i) % num_shards, |shard| {
+                shard.keys().nth(random_index(shard.len().max(1))).cloned()
+            });
+            if key.is_some() {
+                return key;
+            }
+        }
+
+        None
+    }
+
+    /// Every key, in no particular order.
+    pub fn keys(&self) -> Vec<K> {
+        (0..self.num_shards())
+            .flat_map(|i| self.with_shard(i, |shard| shard.keys().cloned().collect::<Vec<_>>()))
+            .collect()
+    }
+
+    /// Returns about `count` keys starting at `cursor`, with the cursor to
+    /// continue from. Iteration starts and ends with cursor 0.
+    ///
+    /// Keys are visited shard by shard, in the order of their hash, so a key
+    /// present for the whole iteration is returned exactly once no matter how
+    /// the map changes in between. Only one shard is locked at a time.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<K>) {
+        let num_shards = self.num_shards();
+        assert!(num_shards <= 1 << (64 - SCAN_SHARD_SHIFT), "too many shards to scan");
+        let count = count.max(1);
+
+        let mut shard_index = (cursor >> SCAN_SHARD_SHIFT) as usize;
+        let mut from = cursor & SCAN_POSITION_MASK;
+        let mut keys = vec![];
+
+        while shard_index < num_shards && keys.len() < count {
+            let wanted = count - keys.len();
+            let (found, rest) = self.with_shard(shard_index, move |shard| scan_shard(shard, from, wanted));
+            keys.extend(found);
+
+            if let Some(position) = rest {
+                let cursor = ((shard_index as u64) << SCAN_SHARD_SHIFT) | position;
+                return (cursor, keys);
+            }
+
+            shard_index += 1;
+            from = 0;
+        }
+
+        if shard_index >= num_shards {
+            (0, keys)
+        } else {
+            ((shard_index as u64) << SCAN_SHARD_SHIFT, keys)
+        }
+    }
+
+    fn num_shards(&self) -> usize {
+        match &*self.db {
+            Shards::Locked(shards) => shards.len(),
+            Shards::Shared(shards) => shards.len(),
+            Shards::Owned(owners) => owners.len(),
+        }
     }
 
-    fn get_key_shard(&self, key: &str) -> usize{
-        let mut s = DefaultHasher::new();
-        key.hash(&mut s);
-        (s.finish() as usize) % self.db.len()
+    /// Runs `f` against the shard at `index`, under its lock or on its thread.
+    fn with_shard<R: Send + 'static>(
+        &self,
+        index: usize,
+        f: impl FnOnce(&HashMap<K, T>) -> R + Send + 'static,
+    ) -> R {
+        match &*self.db {
+            Shards::Locked(shards) => f(&shards[index].lock().unwrap()),
+            Shards::Shared(shards) => f(&shards[index].read().unwrap()),
+            Shards::Owned(owners) => owners[index].run(|shard| f(shard)),
+        }
+    }
+
+    fn get_key_shard<Q: Hash + ?Sized>(&self, key: &Q) -> usize{
+        (hash_key(key) as usize) % self.num_shards()
+    }
+}
+
+/// `update` of a locked shard. The key is only looked up again when `f`
+/// removes it, or stores a value for a key that was absent.
+fn update_in<K, Q, T, R>(shard: &mut HashMap<K, T>, key: &Q, f: impl FnOnce(&mut Option<T>) -> R) -> R
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: Hash + Eq + ToOwned + ?Sized,
+    Q::Owned: Into<K>,
+    T: Default,
+{
+    match shard.get_mut(key) {
+        Some(value) => {
+            let mut slot = Some(mem::take(value));
+            let result = f(&mut slot);
+            match slot {
+                Some(slot) => *value = slot,
+                None => {
+                    shard.remove(key);
+                }
+            }
+            result
+        }
+        None => {
+            let mut slot = None;
+            let result = f(&mut slot);
+            if let Some(value) = slot {
+                shard.insert(key.to_owned().into(), value);
+            }
+            result
+        }
+    }
+}
+
+/// `update_pair` for the models with locks, `lock` locking a shard for
+/// writing.
+fn update_locked_pair<'a, S, G, K, Q, T, R>(
+    shards: &'a [S],
+    lock: impl Fn(&'a S) -> G,
+    (first_index, first): (usize, &Q),
+    (second_index, second): (usize, &Q),
+    f: impl FnOnce(&mut Option<T>, &mut Option<T>) -> R,
+) -> R
+where
+    G: DerefMut<Target = HashMap<K, T>>,
+    K: Hash + Eq + Borrow<Q>,
+    Q: Hash + Eq + ToOwned + ?Sized,
+    Q::Owned: Into<K>,
+{
+    if first_index == second_index {
+        let mut shard = lock(&shards[first_index]);
+        let (first_stored, mut first_slot) = take(&mut shard, first);
+        let (second_stored, mut second_slot) = take(&mut shard, second);
+
+        let result = f(&mut first_slot, &mut second_slot);
+        restore(&mut shard, first, first_stored, first_slot);
+        restore(&mut shard, second, second_stored, second_slot);
+        return result;
+    }
+
+    let low = lock(&shards[first_index.min(second_index)]);
+    let high = lock(&shards[first_index.max(second_index)]);
+    let (mut first_shard, mut second_shard) = if first_index < second_index {
+        (low, high)
+    } else {
+        (high, low)
+    };
+
+    let (first_stored, mut first_slot) = take(&mut first_shard, first);
+    let (second_stored, mut second_slot) = take(&mut second_shard, second);
+
+    let result = f(&mut first_slot, &mut second_slot);
+    restore(&mut first_shard, first, first_stored, first_slot);
+    restore(&mut second_shard, second, second_stored, second_slot);
+    result
+}
+
+/// `update_pair` for the `Owner` model.
+fn update_owned_pair<K, Q, T, R>(
+    owners: &[Owner<K, T>],
+    (first_index, first): (usize, &Q),
+    (second_index, second): (usize, &Q),
+    f: impl FnOnce(&mut Option<T>, &mut Option<T>) -> R,
+) -> R
+where
+    K: Hash + Eq + Borrow<Q> + Send + 'static,
+    Q: ToOwned + ?Sized,
+    Q::Owned: Into<K>,
+    T: Send + 'static,
+{
+    if first_index == second_index {
+        let keys = vec![first.to_owned().into(), second.to_owned().into()];
+        let mut checkout = owners[first_index].checkout(keys);
+        let (first_slot, second_slot) = checkout.slots.split_at_mut(1);
+        return f(&mut first_slot[0], &mut second_slot[0]);
+    }
+
+    // Shards are checked out in index order, as `update_pair` locks them.
+    let (low, high) = if first_index < second_index {
+        ((first_index, first), (second_index, second))
+    } else {
+        ((second_index, second), (first_index, first))
+    };
+    let mut low_checkout = owners[low.0].checkout(vec![low.1.to_owned().into()]);
+    let mut high_checkout = owners[high.0].checkout(vec![high.1.to_owned().into()]);
+
+    let (low_slot, high_slot) = (&mut low_checkout.slots[0], &mut high_checkout.slots[0]);
+    if first_index < second_index {
+        f(low_slot, high_slot)
+    } else {
+        f(high_slot, low_slot)
+    }
+}
+
+/// Keys of `shard` in the range of scan positions starting at `from`, along
+/// with the position to continue from when the range did not reach the end.
+///
+/// The range is sized for about `wanted` keys given how many the shard holds,
+/// so a call takes one pass over the shard without sorting it. Ranges follow
+/// each other whatever their size, so the cursor stays valid as the shard
+/// grows or shrinks.
+fn scan_shard<K: Hash + Clone, T>(shard: &HashMap<K, T>, from: u64, wanted: usize) -> (Vec<K>, Option<u64>) {
+    let span = ((SCAN_POSITION_MASK / shard.len().max(1) as u64).saturating_mul(wanted as u64)).max(1);
+    let end = from.saturating_add(span);
+
+    let keys = shard
+        .keys()
+        .filter(|key| (from..end).contains(&scan_position(*key)))
+        .cloned()
+        .collect();
+    (keys, (end <= SCAN_POSITION_MASK).then_some(end))
+}
+
+/// Hashes the same whether given a key or a borrowed form of it, as `Borrow`
+/// requires.
+fn hash_key<Q: Hash + ?Sized>(key: &Q) -> u64 {
+    let mut s = DefaultHasher::new();
+    key.hash(&mut s);
+    s.finish()
+}
+
+/// Position of `key` within its shard for `scan`. Never 0, so a cursor into
+/// the first shard cannot be mistaken for the start or end of a scan.
+fn scan_position<Q: Hash + ?Sized>(key: &Q) -> u64 {
+    (hash_key(key) >> (64 - SCAN_SHARD_SHIFT)).max(1)
+}
+
+/// Removes `key` from `shard` for `update`, keeping the stored key so it is
+/// put back without being copied again.
+fn take<K, Q, T>(shard: &mut HashMap<K, T>, key: &Q) -> (Option<K>, Option<T>)
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+{
+    match shard.remove_entry(key) {
+        Some((stored, value)) => (Some(stored), Some(value)),
+        None => (None, None),
+    }
+}
+
+/// Stores what `update` left in the slot of `key`, under the key `take`
+/// removed when there was one.
+fn restore<K, Q, T>(shard: &mut HashMap<K, T>, key: &Q, stored: Option<K>, slot: Option<T>)
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: ToOwned + ?Sized,
+    Q::Owned: Into<K>,
+{
+    if let Some(value) = slot {
+        let key = stored.unwrap_or_else(|| key.to_owned().into());
+        shard.insert(key, value);
     }
-}
\ No newline at end of file
+}
+
+/// Cheap source of randomness, every `RandomState` is seeded differently.
+fn random_index(len: usize) -> usize {
+    let mut s = RandomState::new().build_hasher();
+    s.write_usize(len);
+    (s.finish() as usize) % len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn scan_returns_keys_present_throughout_once() {
+        for model in [ShardModel::Mutex, ShardModel::RwLock, ShardModel::Owner] {
+            let db: Arc<ShardedDB<String, u32>> = ShardedDB::with_model(4, model);
+            for i in 0..1000 {
+                db.insert(&format!("key:{}", i)[..], i);
+            }
+
+            let mut seen = HashSet::new();
+            let mut cursor = 0;
+            let mut added = 0;
+            loop {
+                let (next, keys) = db.scan(cursor, 10);
+                for key in keys {
+                    assert!(seen.insert(key), "{:?} returned twice", model);
+                }
+                // Keys added along the way may or may not be returned.
+                db.insert(&format!("new:{}", added)[..], added);
+                added += 1;
+
+                if next == 0 {
+                    break;
+                }
+                cursor = next;
+            }
+
+            assert!((0..1000).all(|i| seen.contains(&format!("key:{}", i))));
+        }
+    }
+
+    #[test]
+    fn update_inserts_changes_and_removes() {
+        for model in [ShardModel::Mutex, ShardModel::RwLock, ShardModel::Owner] {
+            let db: Arc<ShardedDB<String, u32>> = ShardedDB::with_model(4, model);
+
+            db.update("key", |slot| {
+                assert_eq!(*slot, None);
+                *slot = Some(1);
+            });
+            db.update("key", |slot| *slot = slot.map(|value| value + 1));
+            assert_eq!(db.get("key"), Some(2), "{:?}", model);
+            assert_eq!(db.update("key", |slot| *slot), Some(2), "{:?}", model);
+            assert_eq!(db.get("key"), Some(2), "{:?}", model);
+
+            db.update("key", |slot| *slot = None);
+            assert_eq!(db.get("key"), None, "{:?}", model);
+            assert_eq!(db.len(), 0, "{:?}", model);
+        }
+    }
+
+    #[test]
+    fn owner_checkouts_are_put_back_when_the_caller_panics() {
+        let db: Arc<ShardedDB<String, u32>> = ShardedDB::with_model(4, ShardModel::Owner);
+        db.insert("key", 1);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            db.update("key", |slot| {
+                *slot = Some(2);
+                panic!("update failed");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(db.get("key"), Some(2));
+    }
+
+    #[test]
+    fn owner_thread_panics_reach_the_caller() {
+        #[derive(Debug)]
+        struct Unclonable;
+
+        impl Clone for Unclonable {
+            fn clone(&self) -> Unclonable {
+                panic!("cloned");
+            }
+        }
+
+        let db: Arc<ShardedDB<String, Unclonable>> = ShardedDB::with_model(1, ShardModel::Owner);
+        db.insert("key", Unclonable);
+
+        let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| db.get("key")))
+            .unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"cloned"));
+        // The thread carries on.
+        assert!(db.contains_key("key"));
+    }
+
+    #[test]
+    fn owner_threads_serve_other_shards_during_a_checkout() {
+        let db: Arc<ShardedDB<String, u32>> = ShardedDB::with_model(1, ShardModel::Owner);
+        // Enough maps for some to share the thread of `db`.
+        let others: Vec<Arc<ShardedDB<String, u32>>> =
+            (0..64).map(|_| ShardedDB::with_model(1, ShardModel::Owner)).collect();
+
+        db.update("key", |slot| {
+            *slot = Some(1);
+            for other in &others {
+                other.insert("key", 2);
+                assert_eq!(other.get("key"), Some(2));
+            }
+        });
+        assert_eq!(db.get("key"), Some(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one shard")]
+    fn zero_shards_are_refused() {
+        ShardedDB::<String, u32>::new(0);
+    }
+
+    #[test]
+    fn scan_of_an_empty_map_ends_at_once() {
+        let db: Arc<ShardedDB<String, u32>> = ShardedDB::new(4);
+        assert_eq!(db.scan(0, 10), (0, vec![]));
+    }
+}
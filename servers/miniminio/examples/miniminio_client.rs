@@ -1,5 +1,3 @@
-pub mod test_message;
-
 use bytes::Bytes;
 use mini_redis::client;
 use tokio::sync::mpsc::channel;
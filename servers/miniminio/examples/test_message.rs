@@ -1,7 +1,4 @@
-use miniminio::client::{self, MiniMinioClient};
-use miniminio::protocol::message::Message;
-use miniminio::protocol::connection::Connection;
-use tokio::net::TcpStream;
+use miniminio::client;
 
 const ADDR: &str = "127.0.0.1:6378";
 
@@ -1,7 +1,9 @@
+use bytes::Bytes;
 use shared_lib::client_model::UploadId;
 use tokio::net::ToSocketAddrs;
 use tokio::net::TcpStream;
-use crate::{operations::create_mutlipart_upload::CreateMultipartUploadRequest, protocol::{connection::Connection, message}};
+use crate::operations::{complete_multipart_upload::CompleteMultipartUploadRequest, create_mutlipart_upload::CreateMultipartUploadRequest, upload_part::UploadPartRequest};
+use crate::protocol::{connection::Connection, message::Message};
 
 
 pub struct MiniMinioClient {
@@ -11,21 +13,41 @@ pub struct MiniMinioClient {
 impl MiniMinioClient {
     pub async fn create_mutlipart_upload(&mut self, bucket: &str, key: &str, version: &str) -> crate::Result<UploadId> {
         let mpu= CreateMultipartUploadRequest::new(bucket, key, version);
-        let message = mpu.to_message();
-        self.connection.write_message(&message).await?;
-       
+        self.request(mpu.into_message()).await?;
+
         let upload_id = uuid::Uuid::new_v4().to_string();
-        return Ok(upload_id);
+        Ok(upload_id)
+    }
+
+    pub async fn upload_part(&mut self, upload_id: &str, part_number: u32, bytes: Bytes) -> crate::Result<()> {
+        let part = UploadPartRequest::new(upload_id, part_number, bytes);
+        self.request(part.into_message()).await?;
+        Ok(())
+    }
 
-        // let res = self.connection.read_message().await?;
+    pub async fn complete_multipart_upload(&mut self, upload_id: &str, part_order: Vec<String>) -> crate::Result<()> {
+        let complete = CompleteMultipartUploadRequest::new(upload_id, part_order);
+        self.request(complete.into_message()).await?;
+        Ok(())
+    }
+
+    /// Sends a request and waits for the server's reply.
+    async fn request(&mut self, message: Message) -> crate::Result<Message> {
+        self.connection.write_message(&message).await?;
+
+        match self.connection.read_message().await? {
+            Some(reply) => Ok(reply),
+            None => Err("connection closed by server".into()),
+        }
     }
 
 }
 
 
 pub async fn connect<T: ToSocketAddrs>(addr: T) -> crate::Result<MiniMinioClient> {
-    let socket = TcpStream::connect(addr).await.unwrap();
+    let socket = TcpStream::connect(addr).await?;
+    socket.set_nodelay(true)?;
     let connection = Connection::new(socket);
 
     Ok(MiniMinioClient { connection })
-}
\ No newline at end of file
+}
@@ -1,10 +1,12 @@
 pub mod client;
 pub mod operations;
 pub mod protocol;
+pub mod server;
+pub use server::Server;
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 
 /// A specialized `Result` type for mini-redis operations.
 ///
 /// This is defined as a convenience.
-pub type Result<T> = std::result::Result<T, Error>;
\ No newline at end of file
+pub type Result<T> = std::result::Result<T, Error>;
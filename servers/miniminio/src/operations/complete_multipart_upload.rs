@@ -0,0 +1,41 @@
+use bytes::Bytes;
+
+use crate::protocol::{message::Message, parser::MessageParser};
+
+#[derive(Clone, Debug)]
+pub struct CompleteMultipartUploadRequest {
+    upload_id: String,
+    part_order: Vec<String>,
+}
+
+impl CompleteMultipartUploadRequest {
+
+    pub fn new(upload_id: impl ToString, part_order: Vec<String>) -> CompleteMultipartUploadRequest {
+        CompleteMultipartUploadRequest {
+            upload_id: upload_id.to_string(),
+            part_order,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn parse_message(parse: &mut MessageParser) -> crate::Result<CompleteMultipartUploadRequest> {
+        // CompleteMultipartUpload has already been consumed.
+        let upload_id = parse.next_string()?;
+        let mut part_order = vec![];
+        while let Ok(part) = parse.next_string() {
+            part_order.push(part);
+        }
+
+        Ok(CompleteMultipartUploadRequest{upload_id, part_order})
+    }
+
+    pub(crate) fn into_message(self) -> Message {
+        let mut message = Message::array();
+        message.push_bulk(Bytes::from("CompleteMultipartUpload".as_bytes()));
+        message.push_bulk(Bytes::from(self.upload_id.into_bytes()));
+        for part in self.part_order {
+            message.push_bulk(Bytes::from(part.into_bytes()));
+        }
+        message
+    }
+}
@@ -19,6 +19,7 @@ impl CreateMultipartUploadRequest {
         }
     }
 
+    #[allow(dead_code)]
     pub(crate) fn parse_message(parse: &mut MessageParser) -> crate::Result<CreateMultipartUploadRequest> {
         // CreateMultipartUploadRequest has already been consumed.
         let bucket = parse.next_string()?;
@@ -28,7 +29,7 @@ impl CreateMultipartUploadRequest {
         Ok(CreateMultipartUploadRequest{bucket, key, version})
     } 
 
-    pub(crate) fn to_message(self) -> Message {
+    pub(crate) fn into_message(self) -> Message {
         let mut message = Message::array();
         message.push_bulk(Bytes::from("CreateMultiPartUpload".as_bytes()));
         message.push_bulk(Bytes::from(self.bucket.into_bytes()));
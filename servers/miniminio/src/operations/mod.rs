@@ -1 +1,3 @@
-pub mod create_mutlipart_upload;
\ No newline at end of file
+pub mod complete_multipart_upload;
+pub mod create_mutlipart_upload;
+pub mod upload_part;
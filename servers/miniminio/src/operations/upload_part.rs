@@ -0,0 +1,40 @@
+use bytes::Bytes;
+
+use crate::protocol::{message::Message, parser::MessageParser};
+
+#[derive(Clone, Debug)]
+pub struct UploadPartRequest {
+    upload_id: String,
+    part_number: u32,
+    bytes: Bytes,
+}
+
+impl UploadPartRequest {
+
+    pub fn new(upload_id: impl ToString, part_number: u32, bytes: Bytes) -> UploadPartRequest {
+        UploadPartRequest {
+            upload_id: upload_id.to_string(),
+            part_number,
+            bytes,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn parse_message(parse: &mut MessageParser) -> crate::Result<UploadPartRequest> {
+        // UploadPart has already been consumed.
+        let upload_id = parse.next_string()?;
+        let part_number = parse.next_string()?.parse()?;
+        let bytes = parse.next_bytes()?;
+
+        Ok(UploadPartRequest{upload_id, part_number, bytes})
+    }
+
+    pub(crate) fn into_message(self) -> Message {
+        let mut message = Message::array();
+        message.push_bulk(Bytes::from("UploadPart".as_bytes()));
+        message.push_bulk(Bytes::from(self.upload_id.into_bytes()));
+        message.push_bulk(Bytes::from(self.part_number.to_string().into_bytes()));
+        message.push_bulk(self.bytes);
+        message
+    }
+}
@@ -91,7 +91,7 @@ impl Connection {
             Message::Bulk(val) => {
                 self.stream.write_u8(BULK_BYTE).await?;
                 self.write_decimal(val.len() as u64).await?;
-                self.stream.write_all(&val).await?;
+                self.stream.write_all(val).await?;
                 self.stream.write_all(EOL_BYTE_ENCODING).await?;
             }
             Message::Null => {
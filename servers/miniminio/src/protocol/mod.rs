@@ -1,3 +1,4 @@
 pub mod connection;
 pub mod message;
+#[allow(dead_code)]
 pub mod parser;
\ No newline at end of file
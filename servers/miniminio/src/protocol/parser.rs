@@ -32,7 +32,7 @@ impl MessageParser {
 
     pub(crate) fn next_string(&mut self) -> Result<String, ParserError> {
         match self.next()? {
-            Message::Simple(S) => Ok(S),
+            Message::Simple(s) => Ok(s),
             Message::Bulk(data) => str::from_utf8(&data[..])
                 .map(|s| s.to_string())
                 .map_err(|_| "protocol error; invalid string".into()),
@@ -0,0 +1,159 @@
+use std::future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+use tokio::task::{JoinHandle, JoinSet};
+
+use shared_lib::{client_model::{DataStoreServiceSchema, ObjectLocation}, sharded_db::{ShardModel, ShardedDB}};
+
+use crate::protocol::connection::Connection;
+
+/// Address the server binds to unless told otherwise.
+pub const DEFAULT_ADDR: &str = "127.0.0.1:6378";
+
+/// Number of shards of each store.
+const NUM_SHARDS: usize = 10;
+
+/// Entry point for running miniminio in process.
+///
+/// ```no_run
+/// # async fn run() -> miniminio::Result<()> {
+/// use miniminio::server::Server;
+///
+/// let server = Server::builder().bind("127.0.0.1:0").start().await?;
+/// println!("listening on {}", server.local_addr());
+/// server.shutdown().await;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Server;
+
+/// Configures a server before starting it.
+#[derive(Debug)]
+pub struct Builder {
+    addr: String,
+    shard_model: ShardModel,
+}
+
+/// A running server. Dropping the handle leaves the server running, call
+/// `shutdown` to stop it.
+#[derive(Debug)]
+pub struct ServerHandle {
+    local_addr: SocketAddr,
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+/// Stores shared by every connection.
+struct Stores {
+    data_store: Arc<ShardedDB<String, DataStoreServiceSchema>>,
+    object_store: Arc<ShardedDB<String, ObjectLocation>>,
+}
+
+impl Server {
+    pub fn builder() -> Builder {
+        Builder {
+            addr: DEFAULT_ADDR.to_string(),
+            shard_model: ShardModel::default(),
+        }
+    }
+}
+
+impl Builder {
+    /// Sets the address to listen on. Port `0` picks a free port, see
+    /// `ServerHandle::local_addr` for the one picked.
+    pub fn bind(mut self, addr: impl Into<String>) -> Builder {
+        self.addr = addr.into();
+        self
+    }
+
+    /// Whether the shards of the stores are locked by the connections using
+    /// them or owned by a thread each, see `ShardModel`.
+    pub fn shard_model(mut self, model: ShardModel) -> Builder {
+        self.shard_model = model;
+        self
+    }
+
+    /// Binds the listener and starts accepting connections in the background.
+    pub async fn start(self) -> crate::Result<ServerHandle> {
+        let listener = TcpListener::bind(&self.addr).await?;
+        let local_addr = listener.local_addr()?;
+        let (shutdown, shutdown_rx) = oneshot::channel();
+
+        let stores = Stores {
+            data_store: ShardedDB::<String, DataStoreServiceSchema>::with_model(NUM_SHARDS, self.shard_model),
+            object_store: ShardedDB::<String, ObjectLocation>::with_model(NUM_SHARDS, self.shard_model),
+        };
+
+        let task = tokio::spawn(run(listener, stores, shutdown_rx));
+
+        Ok(ServerHandle {
+            local_addr,
+            shutdown,
+            task,
+        })
+    }
+}
+
+impl ServerHandle {
+    /// Address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stops accepting connections, closes the open ones and waits for the
+    /// server to wind down.
+    pub async fn shutdown(self) {
+        let _ = self.shutdown.send(());
+        let _ = self.task.await;
+    }
+}
+
+async fn run(listener: TcpListener, stores: Stores, shutdown: oneshot::Receiver<()>) {
+    let shutdown = shutdown_requested(shutdown);
+    tokio::pin!(shutdown);
+    let mut connections = JoinSet::new();
+
+    loop {
+        tokio::select! {
+            res = listener.accept() => match res {
+                Ok((socket, _)) => {
+                    let data_store_clone = Arc::clone(&stores.data_store);
+                    let object_store_clone = Arc::clone(&stores.object_store);
+                    connections.spawn(async move {
+                        // A connection failing only affects that client.
+                        let _ = process(socket, data_store_clone, object_store_clone).await;
+                    });
+                }
+                Err(err) => eprintln!("accept error: {}", err),
+            },
+            // Reap finished connections so the set does not grow forever.
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            _ = &mut shutdown => break,
+        }
+    }
+
+    connections.shutdown().await;
+}
+
+/// Completes once `ServerHandle::shutdown` is called. A handle dropped
+/// without it closes the channel instead, which leaves the server running.
+async fn shutdown_requested(shutdown: oneshot::Receiver<()>) {
+    if shutdown.await.is_err() {
+        future::pending::<()>().await;
+    }
+}
+
+async fn process(socket: TcpStream, _data_store: Arc<ShardedDB<String, DataStoreServiceSchema>>, _object_store: Arc<ShardedDB<String, ObjectLocation>>) -> crate::Result<()> {
+    socket.set_nodelay(true)?;
+    let mut connection = Connection::new(socket);
+
+    // Messages are echoed back until operations are wired up to the stores.
+    while let Some(message) = connection.read_message().await? {
+        connection.write_message(&message).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use bytes::BytesMut;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time;
+
+use miniredis::protocol::connection::Connection;
+use miniredis::protocol::frame::Frame;
+
+/// Requests written to a backend at once, at most.
+const MAX_BATCH: usize = 512;
+
+/// A miniredis instance keys are sharded to.
+#[derive(Debug)]
+pub(crate) struct Backend {
+    pub(crate) addr: String,
+    requests: mpsc::UnboundedSender<Request>,
+    /// Requests and health checks failed in a row.
+    failures: AtomicU32,
+    /// Set while the backend is left out of the ring.
+    ejected: AtomicBool,
+    eject_after: u32,
+}
+
+#[derive(Debug)]
+struct Request {
+    frame: Frame,
+    reply: oneshot::Sender<crate::Result<Frame>>,
+}
+
+impl Backend {
+    /// Creates the backend along with the task pipelining its requests,
+    /// which connects on the first one.
+    pub(crate) fn start(addr: String, eject_after: u32, timeout: Duration) -> Arc<Backend> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let backend = Arc::new(Backend {
+            addr,
+            requests: tx,
+            failures: AtomicU32::new(0),
+            ejected: AtomicBool::new(false),
+            eject_after,
+        });
+
+        tokio::spawn(Arc::clone(&backend).run(rx, timeout));
+        backend
+    }
+
+    pub(crate) fn is_ejected(&self) -> bool {
+        self.ejected.load(Ordering::Relaxed)
+    }
+
+    /// Queues `frame`, whose reply is received once read. Error replies
+    /// are passed on, errors reaching the backend are returned as such.
+    pub(crate) fn send(&self, frame: Frame) -> oneshot::Receiver<crate::Result<Frame>> {
+        let (reply, rx) = oneshot::channel();
+        // The task only ends with the process.
+        let _ = self.requests.send(Request { frame, reply });
+        rx
+    }
+
+    /// Writes out queued requests in batches, handing over replies as they
+    /// are read. A failed batch fails its requests and closes the
+    /// connection, a late reply could otherwise be taken for the next one.
+    async fn run(
+        self: Arc<Self>,
+        mut requests: mpsc::UnboundedReceiver<Request>,
+        timeout: Duration,
+    ) {
+        let mut connection = None;
+
+        while let Some(first) = requests.recv().await {
+            let mut batch = vec![first];
+            while batch.len() < MAX_BATCH {
+                match requests.try_recv() {
+                    Ok(request) => batch.push(request),
+                    Err(_) => break,
+                }
+            }
+
+            let mut replies = batch.into_iter();
+            let res = time::timeout(timeout, self.exchange(&mut connection, &mut replies)).await;
+            let err = match res {
+                Ok(Ok(())) => {
+                    self.succeeded();
+                    continue;
+                }
+                Ok(Err(err)) => err.to_string(),
+                Err(_) => "request timed out".to_string(),
+            };
+
+            connection = None;
+            self.failed();
+            for request in replies {
+                let _ =
+                    request.reply.send(Err(
+                        format!("backend {} unavailable: {}", self.addr, err).into()
+                    ));
+            }
+        }
+    }
+
+    async fn exchange(
+        &self,
+        connection: &mut Option<Connection>,
+        batch: &mut std::vec::IntoIter<Request>,
+    ) -> crate::Result<()> {
+        if connection.is_none() {
+            let socket = TcpStream::connect(&self.addr).await?;
+            socket.set_nodelay(true)?;
+            *connection = Some(Connection::new(socket));
+        }
+        let connection = connection.as_mut().unwrap();
+
+        let mut buf = BytesMut::new();
+        for request in batch.as_slice() {
+            request.frame.encode(&mut buf);
+        }
+        connection.write_bytes(&[buf.freeze()]).await?;
+
+        while !batch.as_slice().is_empty() {
+            let frame = match connection.read_frame().await? {
+                Some(frame) => frame,
+                None => return Err("connection closed by backend".into()),
+            };
+            // Only taken off the batch once its reply is in.
+            let request = batch.next().unwrap();
+            let _ = request.reply.send(Ok(frame));
+        }
+        Ok(())
+    }
+
+    fn succeeded(&self) {
+        self.failures.store(0, Ordering::Relaxed);
+        if self.ejected.swap(false, Ordering::Relaxed) {
+            println!("+backend {} restored", self.addr);
+        }
+    }
+
+    fn failed(&self) {
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= self.eject_after && !self.ejected.swap(true, Ordering::Relaxed) {
+            println!("-backend {} ejected after {} failures", self.addr, failures);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Proxy sharding keys across miniredis instances, for clients that only
+//! know how to talk to a single server.
+//!
+//! ```text
+//! miniproxy --backends 127.0.0.1:7001,127.0.0.1:7002,127.0.0.1:7003
+//! ```
+//!
+//! Keys are spread over the backends by consistent hashing, so adding or
+//! removing a backend only moves the keys it gains or loses. As with Redis
+//! Cluster, only the part of a key between `{` and the next `}` is hashed
+//! when not empty, for keys that must live together.
+//!
+//! Commands on a single key go to the backend holding it. `DEL`, `UNLINK`
+//! and `TOUCH` are split per backend and their counts summed, `DBSIZE`,
+//! `KEYS`, `FLUSHDB`, `FLUSHALL`, `SCRIPT` and `FUNCTION` go to every
+//! backend. Other commands on several keys are refused unless the keys are
+//! on the same backend. Commands depending on the connection, such as
+//! `SELECT`, `MULTI` or `SUBSCRIBE`, are not supported.
+//!
+//! Each backend has a single connection, requests of every client being
+//! pipelined on it. A backend failing `--eject-after` requests or health
+//! checks in a row is ejected, its keys going to the next backend on the
+//! ring, until it answers health checks again.
+
+mod backend;
+mod proxy;
+mod ring;
+mod server;
+
+use std::process;
+use std::time::Duration;
+
+use proxy::{Options, Proxy};
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+const USAGE: &str = "\
+Usage: miniproxy --backends <addr,...> [OPTIONS]
+
+Options:
+  --bind <host:port>            Address to accept clients on (default: 127.0.0.1:22121).
+  --backends <addr,...>         miniredis instances to shard keys across.
+  --eject-after <n>             Failures in a row after which a backend is
+                                ejected (default: 3).
+  --timeout <ms>                Time given to a backend to reply (default: 2000).";
+
+#[tokio::main]
+async fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            process::exit(1);
+        }
+    };
+
+    let bind = options.bind.clone();
+    let proxy = Proxy::new(options);
+    proxy.start();
+
+    if let Err(err) = server::run(&bind, proxy).await {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> std::result::Result<Options, String> {
+    let mut options = Options {
+        bind: "127.0.0.1:22121".to_string(),
+        backends: vec![],
+        eject_after: 3,
+        timeout: Duration::from_millis(2000),
+    };
+
+    while let Some(name) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for '{}'", name))?;
+
+        match &name[..] {
+            "--bind" => options.bind = value,
+            "--backends" => {
+                options.backends = value
+                    .split(',')
+                    .filter(|addr| !addr.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }
+            "--eject-after" => options.eject_after = parse_number(&name, &value)?,
+            "--timeout" => options.timeout = Duration::from_millis(parse_number(&name, &value)?),
+            _ => return Err(format!("unknown option '{}'", name)),
+        }
+    }
+
+    if options.backends.is_empty() {
+        return Err("no backends to shard keys across, see --backends".to_string());
+    }
+    if options.eject_after == 0 {
+        return Err("'--eject-after' must be at least 1".to_string());
+    }
+
+    Ok(options)
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> std::result::Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for '{}'", value, name))
+}
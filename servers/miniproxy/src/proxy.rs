@@ -0,0 +1,428 @@
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time;
+
+use miniredis::protocol::frame::Frame;
+use miniredis::protocol::parse::Parse;
+
+use crate::backend::Backend;
+use crate::ring::Ring;
+
+/// How often backends are sent a `PING`, ejected ones included.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_millis(1000);
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub bind: String,
+    pub backends: Vec<String>,
+    /// Failures in a row after which a backend is ejected.
+    pub eject_after: u32,
+    /// Time given to a backend to reply.
+    pub timeout: Duration,
+}
+
+/// Routes commands to the backends holding their keys.
+#[derive(Debug)]
+pub struct Proxy {
+    backends: Vec<Arc<Backend>>,
+    ring: Ring,
+}
+
+/// The reply to a command, once the backends it was sent to replied.
+pub(crate) type Reply = Pin<Box<dyn Future<Output = Frame> + Send>>;
+
+/// Where a command is sent.
+enum Route {
+    /// Answered by the proxy.
+    Local,
+    /// To the backend holding the key at this position.
+    Key(usize),
+    /// To the backend holding the keys, which must be the same one. Keyless
+    /// commands go to the backend holding their first argument.
+    Keys(Vec<Bytes>),
+    /// Split into one command per backend, summing the counts replied.
+    SplitSum,
+    /// To every backend not ejected.
+    All(Merge),
+    Unsupported,
+}
+
+/// How the replies of every backend are merged.
+enum Merge {
+    Sum,
+    Concat,
+    /// The reply of the first backend, unless another one failed.
+    First,
+}
+
+impl Proxy {
+    pub fn new(options: Options) -> Arc<Proxy> {
+        let backends = options
+            .backends
+            .iter()
+            .map(|addr| Backend::start(addr.clone(), options.eject_after, options.timeout))
+            .collect();
+
+        Arc::new(Proxy {
+            backends,
+            ring: Ring::new(&options.backends),
+        })
+    }
+
+    /// Starts checking on the backends, which is how ejected ones come back.
+    pub fn start(self: &Arc<Self>) {
+        for backend in &self.backends {
+            let backend = Arc::clone(backend);
+            tokio::spawn(async move {
+                let mut interval = time::interval(HEALTH_CHECK_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    let ping = Frame::Array(vec![Frame::Bulk("PING".into())]);
+                    let _ = backend.send(ping).await;
+                }
+            });
+        }
+    }
+
+    /// Sends a command to the backends it concerns. The returned reply
+    /// does not borrow the proxy, so that replies of pipelined commands can
+    /// be waited for while the next ones are sent.
+    pub(crate) fn dispatch(&self, frame: Frame) -> Reply {
+        match self.try_dispatch(frame) {
+            Ok(reply) => reply,
+            Err(err) => ready(error_frame(&err)),
+        }
+    }
+
+    fn try_dispatch(&self, frame: Frame) -> crate::Result<Reply> {
+        let args = command_args(frame.clone())?;
+        let name = String::from_utf8_lossy(&args[0]).to_lowercase();
+
+        let reply = match route(&name, &args)? {
+            Route::Local => ready(local(&args)?),
+            Route::Key(i) => {
+                let key = args.get(i).ok_or_else(|| arity_error(&name))?;
+                wait(self.backends[self.backend_for(key)?].send(frame))
+            }
+            Route::Keys(keys) => {
+                let hashed = if keys.is_empty() {
+                    &args[1..2]
+                } else {
+                    &keys[..]
+                };
+                let backend = self.backend_for(&hashed[0])?;
+                for key in &hashed[1..] {
+                    if self.backend_for(key)? != backend {
+                        return Err(
+                            "CROSSSLOT Keys in request don't hash to the same backend".into()
+                        );
+                    }
+                }
+                wait(self.backends[backend].send(frame))
+            }
+            Route::SplitSum => {
+                let mut keys_by_backend: HashMap<usize, Vec<Frame>> = HashMap::new();
+                for key in &args[1..] {
+                    keys_by_backend
+                        .entry(self.backend_for(key)?)
+                        .or_insert_with(|| vec![Frame::Bulk(args[0].clone())])
+                        .push(Frame::Bulk(key.clone()));
+                }
+
+                let replies = keys_by_backend
+                    .into_iter()
+                    .map(|(backend, parts)| self.backends[backend].send(Frame::Array(parts)))
+                    .collect();
+                merge(replies, Merge::Sum)
+            }
+            Route::All(how) => {
+                let replies: Vec<_> = self
+                    .backends
+                    .iter()
+                    .filter(|backend| !backend.is_ejected())
+                    .map(|backend| backend.send(frame.clone()))
+                    .collect();
+                if replies.is_empty() {
+                    return Err("no backend available".into());
+                }
+                merge(replies, how)
+            }
+            Route::Unsupported => {
+                return Err(format!("command '{}' is not supported by the proxy", name).into())
+            }
+        };
+        Ok(reply)
+    }
+
+    /// Index of the backend holding `key`.
+    fn backend_for(&self, key: &[u8]) -> crate::Result<usize> {
+        self.ring
+            .lookup(key, |i| !self.backends[i].is_ejected())
+            .ok_or_else(|| "no backend available".into())
+    }
+}
+
+/// Where `name` goes, from the commands of miniredis.
+fn route(name: &str, args: &[Bytes]) -> crate::Result<Route> {
+    let route = match name {
+        "ping" => Route::Local,
+        "get" | "set" | "setbit" | "getbit" | "bitcount" | "bitpos" | "bitfield"
+        | "bitfield_ro" | "pfadd" | "expire" | "pexpire" | "expireat" | "pexpireat" | "ttl"
+        | "pttl" | "expiretime" | "pexpiretime" | "persist" | "type" | "strlen" => Route::Key(1),
+        "del" | "unlink" | "touch" => {
+            if args.len() < 2 {
+                return Err(arity_error(name));
+            }
+            Route::SplitSum
+        }
+        "pfcount" | "pfmerge" => Route::Keys(keys(name, args, 1, args.len())?),
+        "rename" | "renamenx" | "copy" => Route::Keys(keys(name, args, 1, 3)?),
+        "bitop" => Route::Keys(keys(name, args, 2, args.len())?),
+        "eval" | "evalsha" | "eval_ro" | "evalsha_ro" | "fcall" | "fcall_ro" => {
+            let numkeys = args
+                .get(2)
+                .and_then(|arg| std::str::from_utf8(arg).ok()?.parse::<usize>().ok())
+                .ok_or_else(|| arity_error(name))?;
+            let keys = args.get(3..3 + numkeys).ok_or_else(|| arity_error(name))?;
+            Route::Keys(keys.to_vec())
+        }
+        "dbsize" => Route::All(Merge::Sum),
+        "keys" => Route::All(Merge::Concat),
+        "flushdb" | "flushall" | "script" | "function" => Route::All(Merge::First),
+        _ => Route::Unsupported,
+    };
+    Ok(route)
+}
+
+/// Arguments `start..end`, the keys of commands taking at least one.
+fn keys(name: &str, args: &[Bytes], start: usize, end: usize) -> crate::Result<Vec<Bytes>> {
+    match args.get(start..end) {
+        Some(keys) if !keys.is_empty() => Ok(keys.to_vec()),
+        _ => Err(arity_error(name)),
+    }
+}
+
+fn local(args: &[Bytes]) -> crate::Result<Frame> {
+    match args {
+        [_] => Ok(Frame::Simple("PONG".to_string())),
+        [_, message] => Ok(Frame::Bulk(message.clone())),
+        _ => Err(arity_error("ping")),
+    }
+}
+
+/// The command name and arguments of a command frame.
+fn command_args(frame: Frame) -> crate::Result<Vec<Bytes>> {
+    let mut parse = Parse::new(frame)?;
+    let args = (0..parse.remaining())
+        .map(|_| parse.next_bytes())
+        .collect::<Result<Vec<_>, _>>()?;
+    if args.is_empty() {
+        return Err("empty command".into());
+    }
+    Ok(args)
+}
+
+fn wait(reply: oneshot::Receiver<crate::Result<Frame>>) -> Reply {
+    Box::pin(async move { received(reply.await) })
+}
+
+fn merge(replies: Vec<oneshot::Receiver<crate::Result<Frame>>>, how: Merge) -> Reply {
+    Box::pin(async move {
+        let mut frames = Vec::with_capacity(replies.len());
+        for reply in replies {
+            match received(reply.await) {
+                Frame::Error(err) => return Frame::Error(err),
+                frame => frames.push(frame),
+            }
+        }
+
+        match how {
+            Merge::Sum => {
+                let mut sum = 0;
+                for frame in frames {
+                    match frame {
+                        Frame::Integer(n) => sum += n,
+                        frame => return Frame::Error(format!("ERR unexpected reply: {}", frame)),
+                    }
+                }
+                Frame::Integer(sum)
+            }
+            Merge::Concat => Frame::Array(
+                frames
+                    .into_iter()
+                    .flat_map(|frame| match frame {
+                        Frame::Array(parts) => parts,
+                        frame => vec![frame],
+                    })
+                    .collect(),
+            ),
+            Merge::First => frames.swap_remove(0),
+        }
+    })
+}
+
+/// Turns a backend reply, or the failure to get one, into the frame sent
+/// to the client.
+fn received(reply: Result<crate::Result<Frame>, oneshot::error::RecvError>) -> Frame {
+    match reply {
+        Ok(Ok(frame)) => frame,
+        Ok(Err(err)) => error_frame(&err),
+        Err(_) => Frame::Error("ERR request dropped".to_string()),
+    }
+}
+
+fn ready(frame: Frame) -> Reply {
+    Box::pin(future::ready(frame))
+}
+
+fn arity_error(name: &str) -> crate::Error {
+    format!("wrong number of arguments for '{}' command", name).into()
+}
+
+/// Errors already carrying a code, such as `CROSSSLOT`, are sent as they
+/// are, others as `ERR`.
+fn error_frame(err: &crate::Error) -> Frame {
+    let msg = err.to_string();
+    if msg.starts_with(|c: char| c.is_ascii_uppercase()) {
+        Frame::Error(msg)
+    } else {
+        Frame::Error(format!("ERR {}", msg))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miniredis::server::{Server, ServerHandle};
+
+    /// A proxy in front of `backends` embedded servers.
+    async fn start(backends: usize) -> (Arc<Proxy>, Vec<ServerHandle>) {
+        let mut servers = vec![];
+        for _ in 0..backends {
+            servers.push(Server::builder().bind("127.0.0.1:0").start().await.unwrap());
+        }
+
+        let proxy = Proxy::new(Options {
+            bind: "127.0.0.1:0".to_string(),
+            backends: servers
+                .iter()
+                .map(|server| server.local_addr().to_string())
+                .collect(),
+            eject_after: 3,
+            timeout: Duration::from_secs(1),
+        });
+        (proxy, servers)
+    }
+
+    fn command(args: &[&str]) -> Frame {
+        Frame::Array(
+            args.iter()
+                .map(|arg| Frame::Bulk(Bytes::copy_from_slice(arg.as_bytes())))
+                .collect(),
+        )
+    }
+
+    /// Keys `key:0`, `key:1`... up to one held by each backend.
+    fn spread_keys(proxy: &Proxy) -> Vec<String> {
+        let mut keys = vec![];
+        let mut seen = vec![false; proxy.backends.len()];
+        for i in 0.. {
+            let key = format!("key:{}", i);
+            let backend = proxy.backend_for(key.as_bytes()).unwrap();
+            keys.push(key);
+            seen[backend] = true;
+            if seen.iter().all(|&seen| seen) {
+                return keys;
+            }
+        }
+        unreachable!()
+    }
+
+    #[tokio::test]
+    async fn keys_across_backends_are_refused() {
+        let (proxy, _servers) = start(3).await;
+        let keys = spread_keys(&proxy);
+        let (first, last) = (&keys[0][..], &keys[keys.len() - 1][..]);
+
+        for args in [
+            vec!["RENAME", first, last],
+            vec!["PFCOUNT", first, last],
+            vec!["BITOP", "AND", "dest", first, last],
+            vec!["EVAL", "return 1", "2", first, last],
+        ] {
+            match proxy.dispatch(command(&args)).await {
+                Frame::Error(msg) => assert!(msg.starts_with("CROSSSLOT"), "{}", msg),
+                frame => panic!("{:?} replied {:?}", args, frame),
+            }
+        }
+
+        // Keys sharing a hash tag are held together.
+        assert_eq!(
+            proxy
+                .dispatch(command(&["PFCOUNT", "{tag}a", "{tag}b"]))
+                .await,
+            Frame::Integer(0)
+        );
+    }
+
+    #[tokio::test]
+    async fn multi_key_deletes_are_split_and_summed() {
+        let (proxy, _servers) = start(3).await;
+        let keys = spread_keys(&proxy);
+        for key in &keys {
+            proxy.dispatch(command(&["SET", key, "value"])).await;
+        }
+
+        let mut args = vec!["TOUCH"];
+        args.extend(keys.iter().map(String::as_str));
+        args.push("missing");
+        assert_eq!(
+            proxy.dispatch(command(&args)).await,
+            Frame::Integer(keys.len() as i64)
+        );
+
+        let (unlinked, deleted) = keys.split_at(keys.len() / 2);
+        let mut args = vec!["UNLINK"];
+        args.extend(unlinked.iter().map(String::as_str));
+        assert_eq!(
+            proxy.dispatch(command(&args)).await,
+            Frame::Integer(unlinked.len() as i64)
+        );
+        let mut args = vec!["DEL"];
+        args.extend(keys.iter().map(String::as_str));
+        assert_eq!(
+            proxy.dispatch(command(&args)).await,
+            Frame::Integer(deleted.len() as i64)
+        );
+    }
+
+    #[tokio::test]
+    async fn keyspace_commands_merge_every_backend() {
+        let (proxy, _servers) = start(3).await;
+        let keys = spread_keys(&proxy);
+        for key in &keys {
+            proxy.dispatch(command(&["SET", key, "value"])).await;
+        }
+
+        assert_eq!(
+            proxy.dispatch(command(&["DBSIZE"])).await,
+            Frame::Integer(keys.len() as i64)
+        );
+        let mut listed = match proxy.dispatch(command(&["KEYS", "*"])).await {
+            Frame::Array(keys) => keys,
+            frame => panic!("unexpected reply {:?}", frame),
+        };
+        listed.sort_by_key(|key| key.to_string());
+        let mut expected: Vec<Frame> = keys
+            .iter()
+            .map(|key| Frame::Bulk(key.clone().into()))
+            .collect();
+        expected.sort_by_key(|key| key.to_string());
+        assert_eq!(listed, expected);
+    }
+}
@@ -0,0 +1,157 @@
+/// Points each backend gets on the ring. More points spread the keys more
+/// evenly, the same count as ketama.
+const POINTS_PER_BACKEND: usize = 160;
+
+/// A consistent hashing ring: every backend owns the keys hashing between
+/// its points and the previous ones.
+#[derive(Debug)]
+pub(crate) struct Ring {
+    /// Sorted by position, each with the index of its backend.
+    points: Vec<(u64, usize)>,
+}
+
+impl Ring {
+    /// Places the backends on the ring by address, so that a backend keeps
+    /// its keys whatever other backends are added or removed.
+    pub(crate) fn new(addrs: &[String]) -> Ring {
+        let mut points: Vec<(u64, usize)> = addrs
+            .iter()
+            .enumerate()
+            .flat_map(|(backend, addr)| {
+                (0..POINTS_PER_BACKEND)
+                    .map(move |i| (hash(format!("{}-{}", addr, i).as_bytes()), backend))
+            })
+            .collect();
+        points.sort_unstable();
+
+        Ring { points }
+    }
+
+    /// The backend holding `key`: the first one from the key onwards that
+    /// `is_live`, or `None` when none is.
+    pub(crate) fn lookup(&self, key: &[u8], is_live: impl Fn(usize) -> bool) -> Option<usize> {
+        let position = hash(hash_tag(key));
+        let start = self.points.partition_point(|&(point, _)| point < position);
+
+        // Past the last point, keys belong to the first one.
+        self.points[start..]
+            .iter()
+            .chain(&self.points[..start])
+            .map(|&(_, backend)| backend)
+            .find(|&backend| is_live(backend))
+    }
+}
+
+/// The part of `key` that is hashed: what lies between the first `{` and
+/// the next `}` when not empty, the whole key otherwise.
+fn hash_tag(key: &[u8]) -> &[u8] {
+    if let Some(open) = key.iter().position(|&b| b == b'{') {
+        let rest = &key[open + 1..];
+        if let Some(len) = rest.iter().position(|&b| b == b'}') {
+            if len > 0 {
+                return &rest[..len];
+            }
+        }
+    }
+    key
+}
+
+/// FNV-1a, with the finalizer of MurmurHash3 so that similar keys, and
+/// the points of a backend, land far apart.
+fn hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+    hash ^ (hash >> 33)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(backends: usize) -> Ring {
+        let addrs: Vec<String> = (0..backends)
+            .map(|i| format!("10.0.0.{}:6379", i))
+            .collect();
+        Ring::new(&addrs)
+    }
+
+    fn keys() -> impl Iterator<Item = Vec<u8>> {
+        (0..30_000).map(|i| format!("key:{}", i).into_bytes())
+    }
+
+    #[test]
+    fn keys_are_spread_evenly() {
+        let ring = ring(3);
+        let mut counts = [0; 3];
+        for key in keys() {
+            counts[ring.lookup(&key, |_| true).unwrap()] += 1;
+        }
+
+        for count in counts {
+            assert!((8_000..12_000).contains(&count), "{:?}", counts);
+        }
+    }
+
+    #[test]
+    fn only_the_keys_of_an_ejected_backend_move() {
+        let ring = ring(3);
+        let before: Vec<usize> = keys()
+            .map(|key| ring.lookup(&key, |_| true).unwrap())
+            .collect();
+
+        for (key, &owner) in keys().zip(&before) {
+            let moved = ring.lookup(&key, |backend| backend != 1).unwrap();
+            if owner == 1 {
+                assert_ne!(moved, 1);
+            } else {
+                assert_eq!(moved, owner);
+            }
+        }
+
+        // Once back, the backend gets its keys back.
+        let after: Vec<usize> = keys()
+            .map(|key| ring.lookup(&key, |_| true).unwrap())
+            .collect();
+        assert_eq!(before, after);
+        assert_eq!(ring.lookup(b"key", |_| false), None);
+    }
+
+    #[test]
+    fn backends_keep_their_keys_when_another_is_added() {
+        let addrs: Vec<String> = (0..4).map(|i| format!("10.0.0.{}:6379", i)).collect();
+        let small = Ring::new(&addrs[..3]);
+        let large = Ring::new(&addrs);
+
+        for key in keys() {
+            let owner = large.lookup(&key, |_| true).unwrap();
+            if owner != 3 {
+                assert_eq!(small.lookup(&key, |_| true), Some(owner));
+            }
+        }
+    }
+
+    #[test]
+    fn hash_tags() {
+        assert_eq!(hash_tag(b"{user:1}:name"), b"user:1");
+        assert_eq!(hash_tag(b"name:{user:1}"), b"user:1");
+        assert_eq!(hash_tag(b"{a}{b}"), b"a");
+        // Empty and unclosed tags hash the whole key.
+        assert_eq!(hash_tag(b"{}user:1"), b"{}user:1");
+        assert_eq!(hash_tag(b"{user:1"), b"{user:1");
+        assert_eq!(hash_tag(b"user:1}"), b"user:1}");
+
+        let ring = ring(3);
+        assert_eq!(
+            ring.lookup(b"{user:1}:name", |_| true),
+            ring.lookup(b"user:1", |_| true)
+        );
+    }
+}
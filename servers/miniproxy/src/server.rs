@@ -0,0 +1,73 @@
+use std::collections::VecDeque;
+use std::future;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+
+use miniredis::protocol::connection::Connection;
+use miniredis::protocol::frame::Frame;
+
+use crate::proxy::{Proxy, Reply};
+
+/// Commands of a client sent on before their replies are written back, at
+/// most.
+const MAX_PENDING: usize = 1024;
+
+/// Accepts clients until the process ends.
+pub async fn run(bind: &str, proxy: Arc<Proxy>) -> crate::Result<()> {
+    let listener = TcpListener::bind(bind).await?;
+    println!("miniproxy listening on {}", bind);
+
+    loop {
+        match listener.accept().await {
+            Ok((socket, _)) => {
+                tokio::spawn(serve(socket, Arc::clone(&proxy)));
+            }
+            // Running out of file descriptors and similar errors only affect
+            // the connection being accepted.
+            Err(err) => eprintln!("accept error: {}", err),
+        }
+    }
+}
+
+/// Serves a client. Commands are sent on as soon as they are read, so the
+/// ones a client pipelines are pipelined to the backends as well, while
+/// replies are written back in order.
+async fn serve(socket: TcpStream, proxy: Arc<Proxy>) {
+    let _ = socket.set_nodelay(true);
+    let mut connection = Connection::new(socket);
+    let mut pending: VecDeque<Reply> = VecDeque::new();
+    let mut closed = false;
+
+    loop {
+        tokio::select! {
+            res = connection.read_frame(), if !closed && pending.len() < MAX_PENDING => match res {
+                Ok(Some(frame)) => pending.push_back(proxy.dispatch(frame)),
+                // Commands already read are still replied to.
+                Ok(None) => closed = true,
+                Err(err) => {
+                    let _ = connection.write_frame(&Frame::Error(format!("ERR {}", err))).await;
+                    return;
+                }
+            },
+            reply = next_reply(&mut pending) => {
+                pending.pop_front();
+                if connection.write_frame(&reply).await.is_err() {
+                    return;
+                }
+            }
+        }
+
+        if closed && pending.is_empty() {
+            return;
+        }
+    }
+}
+
+/// Waits for the reply to the oldest pending command, forever when there
+/// is none.
+async fn next_reply(pending: &mut VecDeque<Reply>) -> Frame {
+    match pending.front_mut() {
+        Some(reply) => reply.await,
+        None => future::pending().await,
+    }
+}
@@ -0,0 +1,88 @@
+use bytes::Bytes;
+
+use miniredis::module::{CommandFlags, Context, Module, Registry, ValueType};
+use miniredis::protocol::frame::Frame;
+use miniredis::server::DEFAULT_ADDR;
+use miniredis::Server;
+
+/// A counter stored as a native integer rather than a string.
+#[derive(Debug, Clone, Default)]
+struct Counter(i64);
+
+impl ValueType for Counter {
+    const NAME: &'static str = "counter";
+
+    fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Counter>()
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.0.to_le_bytes().to_vec()
+    }
+
+    fn load(data: &[u8]) -> miniredis::Result<Counter> {
+        Ok(Counter(i64::from_le_bytes(data.try_into()?)))
+    }
+}
+
+struct Counters;
+
+impl Module for Counters {
+    fn name(&self) -> &str {
+        "counters"
+    }
+
+    fn register(&self, registry: &mut Registry) -> miniredis::Result<()> {
+        registry.value_type::<Counter>()?;
+
+        // COUNTER.INCRBY key [increment]
+        registry.command(
+            "counter.incrby",
+            CommandFlags::WRITE,
+            |ctx: &mut Context, args: Vec<Bytes>| {
+                let key = ctx.key(args.first())?;
+                let by = match args.get(1) {
+                    Some(arg) => std::str::from_utf8(arg)?
+                        .parse::<i64>()
+                        .map_err(|_| "ERR value is not an integer or out of range")?,
+                    None => 1,
+                };
+
+                let value = ctx.update(&key, |slot: &mut Option<Counter>| {
+                    let counter = slot.get_or_insert_with(Counter::default);
+                    counter.0 += by;
+                    counter.0
+                })?;
+                ctx.notify("counter.incrby", &key);
+                Ok(Frame::Integer(value))
+            },
+        )?;
+
+        // COUNTER.GET key
+        registry.command(
+            "counter.get",
+            CommandFlags::READ_ONLY,
+            |ctx: &mut Context, args: Vec<Bytes>| {
+                let key = ctx.key(args.first())?;
+                Ok(ctx
+                    .get::<Counter>(&key)?
+                    .map_or(Frame::Null, |counter| Frame::Integer(counter.0)))
+            },
+        )
+    }
+}
+
+#[tokio::main]
+async fn main() -> miniredis::Result<()> {
+    let server = Server::builder()
+        .bind(DEFAULT_ADDR)
+        .module(Counters)
+        .start()
+        .await?;
+
+    println!("Listening on {}", server.local_addr());
+
+    tokio::signal::ctrl_c().await?;
+    server.shutdown().await;
+    Ok(())
+}
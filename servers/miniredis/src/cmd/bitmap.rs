@@ -0,0 +1,707 @@
+use bytes::Bytes;
+
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::{Parse, ParseError};
+use crate::db::Db;
+use crate::notify::EventClass;
+
+/// Strings are capped at 512MB, so bit offsets have to fit in 2^32 bits.
+const MAX_BIT_OFFSET: u64 = (1 << 32) - 1;
+
+const BIT_OFFSET_ERR: &str = "ERR bit offset is not an integer or out of range";
+const BIT_VALUE_ERR: &str = "ERR bit is not an integer or out of range";
+const BITFIELD_TYPE_ERR: &str =
+    "ERR Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.";
+
+/// Sets or clears the bit at `offset` in the string stored at `key`.
+///
+/// The string is grown and zero padded so that `offset` is addressable.
+/// Replies with the bit previously stored at `offset`.
+#[derive(Debug)]
+pub struct SetBit {
+    key: Bytes,
+    offset: u64,
+    value: u8,
+}
+
+/// Returns the bit at `offset` in the string stored at `key`.
+///
+/// Offsets past the end of the string, and missing keys, read as 0.
+#[derive(Debug)]
+pub struct GetBit {
+    key: Bytes,
+    offset: u64,
+}
+
+/// Counts the set bits in the string stored at `key`, optionally restricted to
+/// a `start`/`end` range expressed in bytes or bits.
+#[derive(Debug)]
+pub struct BitCount {
+    key: Bytes,
+    range: Option<(i64, i64, Unit)>,
+}
+
+/// Returns the position of the first bit set to 1 or 0 in the string stored
+/// at `key`, optionally restricted to a range.
+#[derive(Debug)]
+pub struct BitPos {
+    key: Bytes,
+    bit: u8,
+    start: Option<i64>,
+    end: Option<i64>,
+    unit: Unit,
+}
+
+/// Performs a bitwise operation between strings and stores the result at
+/// `destination`.
+///
+/// Shorter inputs are treated as if they were zero padded to the length of
+/// the longest one.
+#[derive(Debug)]
+pub struct BitOp {
+    op: Op,
+    destination: Bytes,
+    keys: Vec<Bytes>,
+}
+
+/// Treats the string stored at `key` as an array of arbitrarily sized
+/// integers, running each `GET`, `SET` and `INCRBY` sub-command in order.
+///
+/// `BITFIELD_RO` is the same command restricted to `GET`.
+#[derive(Debug)]
+pub struct BitField {
+    key: Bytes,
+    ops: Vec<FieldOp>,
+    read_only: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Unit {
+    Byte,
+    Bit,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Encoding {
+    signed: bool,
+    bits: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Overflow {
+    Wrap,
+    Sat,
+    Fail,
+}
+
+#[derive(Debug)]
+enum FieldOp {
+    Get {
+        encoding: Encoding,
+        offset: u64,
+    },
+    Set {
+        encoding: Encoding,
+        offset: u64,
+        value: i64,
+        overflow: Overflow,
+    },
+    IncrBy {
+        encoding: Encoding,
+        offset: u64,
+        increment: i64,
+        overflow: Overflow,
+    },
+}
+
+impl SetBit {
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        std::slice::from_ref(&self.key)
+    }
+
+    /// The `SETBIT` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SetBit> {
+        let key = parse.next_bytes()?;
+        let offset = next_bit_offset(parse)?;
+        let value = match parse.next_int() {
+            Ok(value @ (0 | 1)) => value as u8,
+            Err(ParseError::EndOfStream) => return Err(ParseError::EndOfStream.into()),
+            _ => return Err(BIT_VALUE_ERR.into()),
+        };
+
+        Ok(SetBit { key, offset, value })
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        let previous = db.update(&self.key, |mut slot| {
+            let mut buf = slot.as_ref().map(|v| v.to_vec()).unwrap_or_default();
+            grow_to_bits(&mut buf, self.offset + 1);
+
+            let previous = get_bit(&buf, self.offset);
+            set_bit(&mut buf, self.offset, self.value);
+            *slot = Some(Bytes::from(buf));
+            previous
+        });
+
+        let previous = match previous {
+            Ok(previous) => previous,
+            Err(err) => return Frame::Error(err.to_string()),
+        };
+
+        db.notify(EventClass::String, "setbit", &self.key);
+        Frame::Integer(previous as i64)
+    }
+}
+
+impl GetBit {
+    /// The `GETBIT` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<GetBit> {
+        let key = parse.next_bytes()?;
+        let offset = next_bit_offset(parse)?;
+
+        Ok(GetBit { key, offset })
+    }
+
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        std::slice::from_ref(&self.key)
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        match db.get(&self.key) {
+            Ok(value) => {
+                let bit = value.map_or(0, |value| get_bit(&value, self.offset));
+                Frame::Integer(bit as i64)
+            }
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+}
+
+impl BitCount {
+    /// The `BITCOUNT` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<BitCount> {
+        let key = parse.next_bytes()?;
+
+        let range = match parse.remaining() {
+            0 => None,
+            1 => return Err("ERR syntax error".into()),
+            _ => {
+                let start = parse.next_int()?;
+                let end = parse.next_int()?;
+                Some((start, end, next_unit(parse)?))
+            }
+        };
+
+        Ok(BitCount { key, range })
+    }
+
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        std::slice::from_ref(&self.key)
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        let value = match db.get(&self.key) {
+            Ok(Some(value)) => value,
+            Ok(None) => return Frame::Integer(0),
+            Err(err) => return Frame::Error(err.to_string()),
+        };
+
+        let (start, end, unit) = self.range.unwrap_or((0, -1, Unit::Byte));
+        let count = match bit_range(value.len(), start, end, unit) {
+            Some((first, last)) => count_bits(&value, first, last),
+            None => 0,
+        };
+
+        Frame::Integer(count as i64)
+    }
+}
+
+impl BitPos {
+    /// The `BITPOS` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<BitPos> {
+        let key = parse.next_bytes()?;
+        let bit = match parse.next_int() {
+            Ok(bit @ (0 | 1)) => bit as u8,
+            Err(ParseError::EndOfStream) => return Err(ParseError::EndOfStream.into()),
+            _ => return Err("ERR The bit argument must be 1 or 0.".into()),
+        };
+
+        let start = if parse.is_empty() { None } else { Some(parse.next_int()?) };
+        let end = if parse.is_empty() { None } else { Some(parse.next_int()?) };
+        let unit = next_unit(parse)?;
+
+        Ok(BitPos {
+            key,
+            bit,
+            start,
+            end,
+            unit,
+        })
+    }
+
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        std::slice::from_ref(&self.key)
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        // A missing key is an empty string: there is no set bit, and the first
+        // clear bit is at the very start.
+        let value = match db.get(&self.key) {
+            Ok(Some(value)) => value,
+            Ok(None) => return Frame::Integer(if self.bit == 1 { -1 } else { 0 }),
+            Err(err) => return Frame::Error(err.to_string()),
+        };
+
+        let start = self.start.unwrap_or(0);
+        let end = self.end.unwrap_or(-1);
+        let (first, last) = match bit_range(value.len(), start, end, self.unit) {
+            Some(range) => range,
+            None => return Frame::Integer(-1),
+        };
+
+        match find_bit(&value, self.bit, first, last) {
+            Some(pos) => Frame::Integer(pos as i64),
+            // Without an explicit end the string is considered padded with
+            // zeros on the right, so the first clear bit is just past it.
+            None if self.bit == 0 && self.end.is_none() => {
+                Frame::Integer(((last / 8 + 1) * 8) as i64)
+            }
+            None => Frame::Integer(-1),
+        }
+    }
+}
+
+impl BitOp {
+    pub(crate) fn keys(&self) -> Vec<Bytes> {
+        std::iter::once(&self.destination).chain(&self.keys).cloned().collect()
+    }
+
+    /// The `BITOP` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<BitOp> {
+        let op = match &parse.next_string()?.to_uppercase()[..] {
+            "AND" => Op::And,
+            "OR" => Op::Or,
+            "XOR" => Op::Xor,
+            "NOT" => Op::Not,
+            _ => return Err("ERR syntax error".into()),
+        };
+
+        let destination = parse.next_bytes()?;
+        let mut keys = vec![parse.next_bytes()?];
+        while !parse.is_empty() {
+            keys.push(parse.next_bytes()?);
+        }
+
+        if matches!(op, Op::Not) && keys.len() != 1 {
+            return Err("ERR BITOP NOT must be called with a single source key.".into());
+        }
+
+        Ok(BitOp {
+            op,
+            destination,
+            keys,
+        })
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        let sources: Result<Vec<Bytes>, _> = self
+            .keys
+            .iter()
+            .map(|key| db.get(key).map(Option::unwrap_or_default))
+            .collect();
+        let sources = match sources {
+            Ok(sources) => sources,
+            Err(err) => return Frame::Error(err.to_string()),
+        };
+        let len = sources.iter().map(|s| s.len()).max().unwrap_or(0);
+
+        let byte_at = |source: &Bytes, i: usize| source.get(i).copied().unwrap_or(0);
+        let result: Vec<u8> = (0..len)
+            .map(|i| {
+                let mut bytes = sources.iter().map(|source| byte_at(source, i));
+                let first = bytes.next().unwrap_or(0);
+                match self.op {
+                    Op::And => bytes.fold(first, |acc, b| acc & b),
+                    Op::Or => bytes.fold(first, |acc, b| acc | b),
+                    Op::Xor => bytes.fold(first, |acc, b| acc ^ b),
+                    Op::Not => !first,
+                }
+            })
+            .collect();
+
+        // An empty result deletes the destination, like any other empty
+        // string produced by a bit operation.
+        if result.is_empty() {
+            if db.remove(&self.destination) {
+                db.notify(EventClass::Generic, "del", &self.destination);
+            }
+        } else {
+            db.insert(&self.destination, Bytes::from(result));
+            db.notify(EventClass::String, "set", &self.destination);
+        }
+
+        Frame::Integer(len as i64)
+    }
+}
+
+impl BitField {
+    /// The `BITFIELD` or `BITFIELD_RO` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse, read_only: bool) -> crate::Result<BitField> {
+        let key = parse.next_bytes()?;
+        let mut ops = vec![];
+        let mut overflow = Overflow::Wrap;
+
+        while !parse.is_empty() {
+            let sub_command = parse.next_string()?.to_uppercase();
+            if read_only && sub_command != "GET" {
+                return Err("ERR BITFIELD_RO only supports the GET subcommand".into());
+            }
+
+            match &sub_command[..] {
+                "GET" => {
+                    let encoding = next_encoding(parse)?;
+                    let offset = next_field_offset(parse, encoding)?;
+                    ops.push(FieldOp::Get { encoding, offset });
+                }
+                "SET" => {
+                    let encoding = next_encoding(parse)?;
+                    let offset = next_field_offset(parse, encoding)?;
+                    let value = parse.next_int()?;
+                    ops.push(FieldOp::Set {
+                        encoding,
+                        offset,
+                        value,
+                        overflow,
+                    });
+                }
+                "INCRBY" => {
+                    let encoding = next_encoding(parse)?;
+                    let offset = next_field_offset(parse, encoding)?;
+                    let increment = parse.next_int()?;
+                    ops.push(FieldOp::IncrBy {
+                        encoding,
+                        offset,
+                        increment,
+                        overflow,
+                    });
+                }
+                "OVERFLOW" => {
+                    overflow = match &parse.next_string()?.to_uppercase()[..] {
+                        "WRAP" => Overflow::Wrap,
+                        "SAT" => Overflow::Sat,
+                        "FAIL" => Overflow::Fail,
+                        _ => return Err("ERR Invalid OVERFLOW type specified".into()),
+                    };
+                }
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+
+        Ok(BitField {
+            key,
+            ops,
+            read_only,
+        })
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        if self.read_only {
+            "bitfield_ro"
+        } else {
+            "bitfield"
+        }
+    }
+
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        std::slice::from_ref(&self.key)
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        // The string is grown to fit every write up front, even writes that
+        // end up failing on overflow.
+        let write_end = self
+            .ops
+            .iter()
+            .filter_map(|op| match op {
+                FieldOp::Get { .. } => None,
+                FieldOp::Set {
+                    encoding, offset, ..
+                }
+                | FieldOp::IncrBy {
+                    encoding, offset, ..
+                } => Some(offset + encoding.bits as u64),
+            })
+            .max();
+
+        let replies = match write_end {
+            None => match db.get(&self.key) {
+                Ok(value) => {
+                    let mut buf = value.unwrap_or_default().to_vec();
+                    self.ops.iter().map(|op| op.apply(&mut buf)).collect()
+                }
+                Err(err) => return Frame::Error(err.to_string()),
+            },
+            Some(write_end) => {
+                let replies = db.update(&self.key, |mut slot| {
+                    let mut buf = slot.as_ref().map(|v| v.to_vec()).unwrap_or_default();
+                    grow_to_bits(&mut buf, write_end);
+
+                    let replies = self.ops.iter().map(|op| op.apply(&mut buf)).collect();
+                    *slot = Some(Bytes::from(buf));
+                    replies
+                });
+
+                let replies = match replies {
+                    Ok(replies) => replies,
+                    Err(err) => return Frame::Error(err.to_string()),
+                };
+
+                db.notify(EventClass::String, "setbit", &self.key);
+                replies
+            }
+        };
+
+        Frame::Array(replies)
+    }
+}
+
+impl FieldOp {
+    fn apply(&self, buf: &mut [u8]) -> Frame {
+        match *self {
+            FieldOp::Get { encoding, offset } => Frame::Integer(encoding.read(buf, offset)),
+            FieldOp::Set {
+                encoding,
+                offset,
+                value,
+                overflow,
+            } => {
+                // Unsigned fields take the value as its two's complement bit
+                // pattern, so -1 saturates to the maximum rather than to 0.
+                let wanted = if encoding.signed {
+                    value as i128
+                } else {
+                    value as u64 as i128
+                };
+
+                match encoding.fit(wanted, overflow) {
+                    Some(value) => {
+                        let previous = encoding.read(buf, offset);
+                        encoding.write(buf, offset, value);
+                        Frame::Integer(previous)
+                    }
+                    None => Frame::Null,
+                }
+            }
+            FieldOp::IncrBy {
+                encoding,
+                offset,
+                increment,
+                overflow,
+            } => {
+                let wanted = encoding.read(buf, offset) as i128 + increment as i128;
+
+                match encoding.fit(wanted, overflow) {
+                    Some(value) => {
+                        encoding.write(buf, offset, value);
+                        Frame::Integer(value as i64)
+                    }
+                    None => Frame::Null,
+                }
+            }
+        }
+    }
+}
+
+impl Encoding {
+    fn range(&self) -> (i128, i128) {
+        if self.signed {
+            (-(1 << (self.bits - 1)), (1 << (self.bits - 1)) - 1)
+        } else {
+            (0, (1 << self.bits) - 1)
+        }
+    }
+
+    /// Brings `value` into the range of the encoding according to the
+    /// overflow policy, `None` meaning the operation must not be performed.
+    fn fit(&self, value: i128, overflow: Overflow) -> Option<i128> {
+        let (min, max) = self.range();
+        if (min..=max).contains(&value) {
+            return Some(value);
+        }
+
+        match overflow {
+            Overflow::Wrap => Some((value - min).rem_euclid(1 << self.bits) + min),
+            Overflow::Sat => Some(value.clamp(min, max)),
+            Overflow::Fail => None,
+        }
+    }
+
+    fn read(&self, buf: &[u8], offset: u64) -> i64 {
+        let mut raw = 0u64;
+        for i in 0..self.bits as u64 {
+            raw = (raw << 1) | get_bit(buf, offset + i) as u64;
+        }
+
+        // Sign extend signed fields by moving their sign bit to the top and
+        // back with an arithmetic shift.
+        if self.signed {
+            let shift = 64 - self.bits;
+            ((raw << shift) as i64) >> shift
+        } else {
+            raw as i64
+        }
+    }
+
+    fn write(&self, buf: &mut [u8], offset: u64, value: i128) {
+        let raw = value as u64;
+        for i in 0..self.bits as u64 {
+            let bit = (raw >> (self.bits as u64 - 1 - i)) & 1;
+            set_bit(buf, offset + i, bit as u8);
+        }
+    }
+}
+
+/// Reads a bit offset argument, bounded by the maximum string size.
+fn next_bit_offset(parse: &mut Parse) -> crate::Result<u64> {
+    match parse.next_int() {
+        Ok(offset) if (0..=MAX_BIT_OFFSET as i64).contains(&offset) => Ok(offset as u64),
+        Err(ParseError::EndOfStream) => Err(ParseError::EndOfStream.into()),
+        _ => Err(BIT_OFFSET_ERR.into()),
+    }
+}
+
+/// Reads the optional `BYTE | BIT` modifier closing a range.
+fn next_unit(parse: &mut Parse) -> crate::Result<Unit> {
+    if parse.is_empty() {
+        return Ok(Unit::Byte);
+    }
+
+    match &parse.next_string()?.to_uppercase()[..] {
+        "BYTE" => Ok(Unit::Byte),
+        "BIT" => Ok(Unit::Bit),
+        _ => Err("ERR syntax error".into()),
+    }
+}
+
+/// Reads a bitfield type such as `i8` or `u16`.
+fn next_encoding(parse: &mut Parse) -> crate::Result<Encoding> {
+    let encoding = parse.next_string()?;
+    let (signed, bits) = match encoding.split_at_checked(1) {
+        Some(("i" | "I", bits)) => (true, bits),
+        Some(("u" | "U", bits)) => (false, bits),
+        _ => return Err(BITFIELD_TYPE_ERR.into()),
+    };
+
+    match bits.parse::<u32>() {
+        Ok(bits @ 1..=64) if signed => Ok(Encoding { signed, bits }),
+        Ok(bits @ 1..=63) if !signed => Ok(Encoding { signed, bits }),
+        _ => Err(BITFIELD_TYPE_ERR.into()),
+    }
+}
+
+/// Reads a bitfield offset, either absolute or, when prefixed with `#`, in
+/// multiples of the field width.
+fn next_field_offset(parse: &mut Parse, encoding: Encoding) -> crate::Result<u64> {
+    let offset = parse.next_string()?;
+    let (multiplier, digits) = match offset.strip_prefix('#') {
+        Some(digits) => (encoding.bits as i64, digits),
+        None => (1, &offset[..]),
+    };
+
+    digits
+        .parse::<i64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .filter(|&offset| offset >= 0 && offset as u64 + encoding.bits as u64 <= MAX_BIT_OFFSET + 1)
+        .map(|offset| offset as u64)
+        .ok_or_else(|| BIT_OFFSET_ERR.into())
+}
+
+/// Resolves a possibly negative `start`/`end` pair against a string of `len`
+/// bytes into an inclusive range of bit positions, `None` if it is empty.
+fn bit_range(len: usize, start: i64, end: i64, unit: Unit) -> Option<(u64, u64)> {
+    let total = match unit {
+        Unit::Byte => len as i64,
+        Unit::Bit => len as i64 * 8,
+    };
+
+    let start = if start < 0 { (total + start).max(0) } else { start };
+    let end = if end < 0 { (total + end).max(0) } else { end };
+    let end = end.min(total - 1);
+
+    if start > end {
+        return None;
+    }
+
+    match unit {
+        Unit::Byte => Some((start as u64 * 8, end as u64 * 8 + 7)),
+        Unit::Bit => Some((start as u64, end as u64)),
+    }
+}
+
+/// Masks the byte at `index` down to the bits within `first..=last`.
+fn masked_byte(byte: u8, index: u64, first: u64, last: u64) -> u8 {
+    let mut byte = byte;
+    if index == first / 8 {
+        byte &= 0xff >> (first % 8);
+    }
+    if index == last / 8 {
+        byte &= 0xff << (7 - last % 8);
+    }
+    byte
+}
+
+fn count_bits(buf: &[u8], first: u64, last: u64) -> u64 {
+    (first / 8..=last / 8)
+        .map(|i| masked_byte(buf[i as usize], i, first, last).count_ones() as u64)
+        .sum()
+}
+
+fn find_bit(buf: &[u8], bit: u8, first: u64, last: u64) -> Option<u64> {
+    (first / 8..=last / 8).find_map(|i| {
+        // Searching for a clear bit is searching for a set bit in the
+        // complement.
+        let byte = if bit == 1 { buf[i as usize] } else { !buf[i as usize] };
+        match masked_byte(byte, i, first, last) {
+            0 => None,
+            byte => Some(i * 8 + byte.leading_zeros() as u64),
+        }
+    })
+}
+
+fn grow_to_bits(buf: &mut Vec<u8>, bits: u64) {
+    let len = bits.div_ceil(8) as usize;
+    if buf.len() < len {
+        buf.resize(len, 0);
+    }
+}
+
+/// Bit 0 is the most significant bit of the first byte.
+fn get_bit(buf: &[u8], offset: u64) -> u8 {
+    match buf.get((offset / 8) as usize) {
+        Some(byte) => (byte >> (7 - offset % 8)) & 1,
+        None => 0,
+    }
+}
+
+fn set_bit(buf: &mut [u8], offset: u64, bit: u8) {
+    let mask = 1 << (7 - offset % 8);
+    let byte = &mut buf[(offset / 8) as usize];
+    if bit == 1 {
+        *byte |= mask;
+    } else {
+        *byte &= !mask;
+    }
+}
@@ -0,0 +1,277 @@
+use bytes::Bytes;
+
+use crate::cmd::config::unknown_subcommand;
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+use crate::session::Session;
+use crate::state::ServerState;
+use crate::tracking::TrackingOptions;
+
+/// Inspects and configures the connection of the client sending it.
+#[derive(Debug)]
+pub enum ClientCmd {
+    /// `CLIENT ID`
+    Id,
+    /// `CLIENT GETNAME`
+    GetName,
+    /// `CLIENT SETNAME name`
+    SetName(Bytes),
+    /// `CLIENT TRACKING on|off [REDIRECT id] [PREFIX prefix ...] [BCAST]
+    /// [OPTIN] [OPTOUT] [NOLOOP]`, `None` when turning tracking off.
+    Tracking(Option<TrackingOptions>),
+    /// `CLIENT CACHING yes|no`
+    Caching(bool),
+    /// `CLIENT GETREDIR`
+    GetRedir,
+    /// `CLIENT TRACKINGINFO`
+    TrackingInfo,
+}
+
+impl ClientCmd {
+    /// The `CLIENT` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<ClientCmd> {
+        let sub_command = parse.next_string()?.to_lowercase();
+
+        match &sub_command[..] {
+            "id" => Ok(ClientCmd::Id),
+            "getname" => Ok(ClientCmd::GetName),
+            "setname" => Ok(ClientCmd::SetName(parse.next_bytes()?)),
+            "tracking" => parse_tracking(parse),
+            "caching" => match &parse.next_string()?.to_lowercase()[..] {
+                "yes" => Ok(ClientCmd::Caching(true)),
+                "no" => Ok(ClientCmd::Caching(false)),
+                _ => Err("ERR syntax error".into()),
+            },
+            "getredir" => Ok(ClientCmd::GetRedir),
+            "trackinginfo" => Ok(ClientCmd::TrackingInfo),
+            _ => Err(unknown_subcommand(&sub_command, "CLIENT").into()),
+        }
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        match self {
+            ClientCmd::Caching(_) => "client|caching",
+            _ => "client",
+        }
+    }
+
+    pub(crate) fn apply(self, state: &ServerState, session: &mut Session) -> Frame {
+        match self {
+            ClientCmd::Id => Frame::Integer(session.id as i64),
+            ClientCmd::GetName => session.name.clone().map_or(Frame::Null, Frame::Bulk),
+            ClientCmd::SetName(name) => match set_name(session, name) {
+                Ok(()) => Frame::ok(),
+                Err(err) => Frame::Error(err.to_string()),
+            },
+            ClientCmd::Tracking(None) => {
+                state.tracking.disable(session.id);
+                session.tracking = None;
+                Frame::ok()
+            }
+            ClientCmd::Tracking(Some(options)) => match enable_tracking(state, session, options) {
+                Ok(()) => Frame::ok(),
+                Err(err) => Frame::Error(err.to_string()),
+            },
+            ClientCmd::Caching(yes) => {
+                let allowed = match &session.tracking {
+                    Some(options) if yes => options.optin,
+                    Some(options) => options.optout,
+                    None => false,
+                };
+
+                if !allowed {
+                    let (answer, mode) = if yes {
+                        ("YES", "OPTIN")
+                    } else {
+                        ("NO", "OPTOUT")
+                    };
+                    return Frame::Error(format!(
+                        "ERR CLIENT CACHING {} is only valid when tracking is enabled in {} mode.",
+                        answer, mode
+                    ));
+                }
+
+                session.caching = Some(yes);
+                Frame::ok()
+            }
+            ClientCmd::GetRedir => match &session.tracking {
+                None => Frame::Integer(-1),
+                Some(options) => Frame::Integer(options.redirect.unwrap_or(0) as i64),
+            },
+            ClientCmd::TrackingInfo => tracking_info(state, session),
+        }
+    }
+}
+
+/// Only names that show up in one piece in `CLIENT LIST` are accepted.
+pub(crate) fn set_name(session: &mut Session, name: Bytes) -> crate::Result<()> {
+    if name.iter().any(|&c| !c.is_ascii_graphic()) {
+        return Err(
+            "ERR Client names cannot contain spaces, newlines or special characters.".into(),
+        );
+    }
+
+    session.name = Some(name).filter(|name| !name.is_empty());
+    Ok(())
+}
+
+fn parse_tracking(parse: &mut Parse) -> crate::Result<ClientCmd> {
+    let on = match &parse.next_string()?.to_lowercase()[..] {
+        "on" => true,
+        "off" => false,
+        _ => return Err("ERR syntax error".into()),
+    };
+
+    let mut options = TrackingOptions::default();
+    while !parse.is_empty() {
+        match &parse.next_string()?.to_uppercase()[..] {
+            "REDIRECT" => {
+                let id = parse.next_int()?;
+                options.redirect = Some(u64::try_from(id).map_err(|_| "ERR Invalid client ID")?);
+            }
+            "PREFIX" => options.prefixes.push(parse.next_bytes()?),
+            "BCAST" => options.bcast = true,
+            "OPTIN" => options.optin = true,
+            "OPTOUT" => options.optout = true,
+            "NOLOOP" => options.noloop = true,
+            _ => return Err("ERR syntax error".into()),
+        }
+    }
+
+    Ok(ClientCmd::Tracking(Some(options).filter(|_| on)))
+}
+
+/// Turns tracking on, or updates its options when it already is.
+fn enable_tracking(
+    state: &ServerState,
+    session: &mut Session,
+    mut options: TrackingOptions,
+) -> crate::Result<()> {
+    if !options.prefixes.is_empty() && !options.bcast {
+        return Err("ERR PREFIX option requires BCAST mode to be enabled".into());
+    }
+
+    if options.optin && options.optout {
+        return Err("ERR You can't use both OPTIN and OPTOUT".into());
+    }
+
+    if options.bcast && (options.optin || options.optout) {
+        return Err("ERR OPTIN and OPTOUT are not compatible with BCAST".into());
+    }
+
+    if let Some(current) = &session.tracking {
+        if current.bcast != options.bcast {
+            return Err("ERR You can't switch BCAST mode on/off before disabling tracking for this client, and then re-enabling it with a different mode.".into());
+        }
+
+        if current.optin != options.optin || current.optout != options.optout {
+            return Err("ERR You can't switch OPTIN/OPTOUT mode before disabling tracking for this client, and then re-enabling it with a different mode.".into());
+        }
+    }
+
+    if let Some(redirect) = options.redirect {
+        if redirect != session.id && !state.tracking.is_connected(redirect) {
+            return Err("ERR The client ID you want redirect to does not exist".into());
+        }
+    }
+
+    // Broadcasting without a prefix covers every key.
+    if options.bcast {
+        let current = session
+            .tracking
+            .as_ref()
+            .map_or(&[][..], |current| &current.prefixes[..]);
+        if options.prefixes.is_empty() && current.is_empty() {
+            options.prefixes.push(Bytes::new());
+        }
+
+        check_prefixes(current, &options.prefixes)?;
+        let mut prefixes = current.to_vec();
+        for prefix in options.prefixes {
+            if !prefixes.contains(&prefix) {
+                prefixes.push(prefix);
+            }
+        }
+        options.prefixes = prefixes;
+    }
+
+    state.tracking.enable(session.id, &options);
+    session.tracking = Some(options);
+    Ok(())
+}
+
+/// A key may only match one prefix of a client, so it gets a single
+/// invalidation.
+fn check_prefixes(current: &[Bytes], added: &[Bytes]) -> crate::Result<()> {
+    for (i, prefix) in added.iter().enumerate() {
+        let others = current
+            .iter()
+            .chain(&added[..i])
+            .filter(|other| *other != prefix);
+        for other in others {
+            if prefix.starts_with(&other[..]) || other.starts_with(&prefix[..]) {
+                return Err(format!(
+                    "ERR Prefix '{}' overlaps with an existing prefix '{}'. Prefixes for a single client must not overlap.",
+                    String::from_utf8_lossy(prefix),
+                    String::from_utf8_lossy(other)
+                )
+                .into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn tracking_info(state: &ServerState, session: &Session) -> Frame {
+    let mut flags = vec![];
+    let (redirect, prefixes) = match &session.tracking {
+        None => {
+            flags.push("off");
+            (-1, vec![])
+        }
+        Some(options) => {
+            flags.push("on");
+            for (set, flag) in [
+                (options.bcast, "bcast"),
+                (options.optin, "optin"),
+                (options.optout, "optout"),
+                (options.noloop, "noloop"),
+                (session.caching == Some(true), "caching-yes"),
+                (session.caching == Some(false), "caching-no"),
+            ] {
+                if set {
+                    flags.push(flag);
+                }
+            }
+
+            if let Some(redirect) = options.redirect {
+                if !state.tracking.is_connected(redirect) {
+                    flags.push("broken_redirect");
+                }
+            }
+
+            (
+                options.redirect.unwrap_or(0) as i64,
+                options.prefixes.clone(),
+            )
+        }
+    };
+
+    Frame::Map(vec![
+        (
+            Frame::Bulk("flags".into()),
+            Frame::Array(
+                flags
+                    .into_iter()
+                    .map(|flag| Frame::Bulk(flag.into()))
+                    .collect(),
+            ),
+        ),
+        (Frame::Bulk("redirect".into()), Frame::Integer(redirect)),
+        (
+            Frame::Bulk("prefixes".into()),
+            Frame::Array(prefixes.into_iter().map(Frame::Bulk).collect()),
+        ),
+    ])
+}
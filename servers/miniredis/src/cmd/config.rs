@@ -0,0 +1,84 @@
+use crate::config;
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+
+/// Reads or changes server parameters at runtime.
+#[derive(Debug)]
+pub enum Config {
+    /// `CONFIG GET pattern [pattern ...]`
+    Get(Vec<String>),
+    /// `CONFIG SET parameter value [parameter value ...]`
+    Set(Vec<(String, String)>),
+}
+
+impl Config {
+    /// The `CONFIG` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Config> {
+        let sub_command = parse.next_string()?.to_lowercase();
+
+        match &sub_command[..] {
+            "get" => {
+                let mut patterns = vec![parse.next_string()?.to_lowercase()];
+                while !parse.is_empty() {
+                    patterns.push(parse.next_string()?.to_lowercase());
+                }
+
+                Ok(Config::Get(patterns))
+            }
+            "set" => {
+                let mut params = vec![];
+                loop {
+                    let name = parse.next_string()?.to_lowercase();
+                    let value = parse.next_string()?;
+                    params.push((name, value));
+
+                    if parse.is_empty() {
+                        break;
+                    }
+                }
+
+                Ok(Config::Set(params))
+            }
+            _ => Err(unknown_subcommand(&sub_command, "CONFIG").into()),
+        }
+    }
+
+    pub(crate) fn apply(self, config: &config::Config) -> Frame {
+        match self {
+            Config::Get(patterns) => {
+                let mut reply = Frame::array();
+                let mut seen = vec![];
+
+                for pattern in &patterns {
+                    for (name, value) in config.get_matching(pattern) {
+                        if !seen.contains(&name) {
+                            seen.push(name);
+                            reply.push_bulk(name.into());
+                            reply.push_bulk(value.into());
+                        }
+                    }
+                }
+
+                reply
+            }
+            Config::Set(params) => {
+                for (name, value) in &params {
+                    if let Err(err) = config.set(name, value) {
+                        return Frame::Error(err);
+                    }
+                }
+
+                Frame::ok()
+            }
+        }
+    }
+}
+
+/// Error for a sub-command a container command such as `CONFIG` does not
+/// know about.
+pub(crate) fn unknown_subcommand(sub_command: &str, command: &str) -> String {
+    format!(
+        "ERR unknown subcommand '{}'. Try {} HELP.",
+        sub_command, command
+    )
+}
@@ -0,0 +1,133 @@
+use crate::db::Databases;
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::{Parse, ParseError};
+use crate::session::Session;
+
+const DB_INDEX_ERR: &str = "ERR DB index is out of range";
+
+/// Selects the logical database used by the connection's later commands.
+#[derive(Debug)]
+pub struct Select {
+    index: i64,
+}
+
+/// Returns the number of keys in the selected database.
+#[derive(Debug)]
+pub struct DbSize;
+
+/// Removes every key of the selected database, or of all databases for
+/// `FLUSHALL`.
+///
+/// `ASYNC` frees the old keys in the background.
+#[derive(Debug)]
+pub struct Flush {
+    all: bool,
+    lazy: bool,
+}
+
+/// Swaps two databases, so clients connected to one see the other's data.
+#[derive(Debug)]
+pub struct SwapDb {
+    first: i64,
+    second: i64,
+}
+
+impl Select {
+    /// The `SELECT` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Select> {
+        let index = parse.next_int()?;
+
+        Ok(Select { index })
+    }
+
+    pub(crate) fn apply(self, dbs: &Databases, session: &mut Session) -> Frame {
+        match db_index(dbs, self.index) {
+            Some(index) => {
+                session.db = index;
+                Frame::ok()
+            }
+            None => Frame::Error(DB_INDEX_ERR.to_string()),
+        }
+    }
+}
+
+impl DbSize {
+    /// The `DBSIZE` string has already been consumed.
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<DbSize> {
+        Ok(DbSize)
+    }
+
+    pub(crate) fn apply(self, dbs: &Databases, session: &Session) -> Frame {
+        Frame::Integer(dbs.get(session.db).len() as i64)
+    }
+}
+
+impl Flush {
+    /// The `FLUSHDB` or `FLUSHALL` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse, all: bool) -> crate::Result<Flush> {
+        let lazy = if parse.is_empty() {
+            false
+        } else {
+            match &parse.next_string()?.to_uppercase()[..] {
+                "ASYNC" => true,
+                "SYNC" => false,
+                _ => return Err("ERR syntax error".into()),
+            }
+        };
+
+        Ok(Flush { all, lazy })
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        if self.all {
+            "flushall"
+        } else {
+            "flushdb"
+        }
+    }
+
+    pub(crate) fn apply(self, dbs: &Databases, session: &Session) -> Frame {
+        if self.all {
+            dbs.flush_all(self.lazy);
+        } else {
+            dbs.flush(session.db, self.lazy);
+        }
+
+        Frame::ok()
+    }
+}
+
+impl SwapDb {
+    /// The `SWAPDB` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SwapDb> {
+        let first = next_index(parse, "ERR invalid first DB index")?;
+        let second = next_index(parse, "ERR invalid second DB index")?;
+
+        Ok(SwapDb { first, second })
+    }
+
+    pub(crate) fn apply(self, dbs: &Databases) -> Frame {
+        match (db_index(dbs, self.first), db_index(dbs, self.second)) {
+            (Some(first), Some(second)) => {
+                dbs.swap(first, second);
+                Frame::ok()
+            }
+            _ => Frame::Error(DB_INDEX_ERR.to_string()),
+        }
+    }
+}
+
+/// Reads a database index, with a command specific message for values that
+/// are not integers.
+pub(crate) fn next_index(parse: &mut Parse, msg: &str) -> crate::Result<i64> {
+    match parse.next_int() {
+        Ok(index) => Ok(index),
+        Err(ParseError::EndOfStream) => Err(ParseError::EndOfStream.into()),
+        Err(_) => Err(msg.into()),
+    }
+}
+
+/// Checks a client supplied index against the configured databases.
+pub(crate) fn db_index(dbs: &Databases, index: i64) -> Option<usize> {
+    usize::try_from(index).ok().filter(|&index| index < dbs.len())
+}
@@ -0,0 +1,357 @@
+use bytes::{Buf, Bytes, BytesMut};
+use std::future::Future;
+use std::io::Cursor;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time;
+
+use crate::cmd::databases::next_index;
+use crate::db::{self, Db};
+use crate::dump;
+use crate::locks::Scope;
+use crate::module::Modules;
+use crate::notify::EventClass;
+use crate::protocol::frame::{self, Frame};
+use crate::protocol::parse::Parse;
+use crate::session::Session;
+use crate::state::ServerState;
+
+/// Longest a `MIGRATE` waits on each step of the exchange with the target,
+/// whatever timeout it is given.
+const MAX_MIGRATE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Serializes the value of `key` for `RESTORE`, see `dump`.
+#[derive(Debug)]
+pub struct Dump {
+    key: Bytes,
+}
+
+/// Creates `key` from a `DUMP` payload.
+///
+/// A `ttl` of 0 keeps the expiration time recorded in the payload, any other
+/// one replaces it: milliseconds from now, or a Unix time in milliseconds
+/// with `ABSTTL`.
+#[derive(Debug)]
+pub struct Restore {
+    key: Bytes,
+    ttl: i64,
+    payload: Bytes,
+    replace: bool,
+    absttl: bool,
+}
+
+/// Moves keys to another instance: they are restored there, then deleted
+/// here unless `COPY` is given.
+///
+/// Unlike in Redis, other commands keep running while the target is waited
+/// on, the keys' locks are only held to read them and to delete them. Keys
+/// written in between are left here, as are keys the target restored over
+/// them when migrating to this same instance.
+#[derive(Debug)]
+pub struct Migrate {
+    host: String,
+    port: u16,
+    db: i64,
+    timeout: Duration,
+    copy: bool,
+    replace: bool,
+    keys: Vec<Bytes>,
+}
+
+impl Dump {
+    /// The `DUMP` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Dump> {
+        let key = parse.next_bytes()?;
+
+        Ok(Dump { key })
+    }
+
+    /// Keys the command reads, remembered for clients tracking them.
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        std::slice::from_ref(&self.key)
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        match db.get_entry(&self.key) {
+            Some(entry) => Frame::Bulk(dump::serialize(&entry)),
+            None => Frame::Null,
+        }
+    }
+}
+
+impl Restore {
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        std::slice::from_ref(&self.key)
+    }
+
+    /// The `RESTORE` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Restore> {
+        let key = parse.next_bytes()?;
+        let ttl = parse.next_int()?;
+        let payload = parse.next_bytes()?;
+        let mut replace = false;
+        let mut absttl = false;
+
+        while !parse.is_empty() {
+            match &parse.next_string()?.to_uppercase()[..] {
+                "REPLACE" => replace = true,
+                "ABSTTL" => absttl = true,
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+
+        if ttl < 0 {
+            return Err("ERR Invalid TTL value, must be >= 0".into());
+        }
+
+        Ok(Restore {
+            key,
+            ttl,
+            payload,
+            replace,
+            absttl,
+        })
+    }
+
+    pub(crate) fn apply(self, db: &Db, modules: &Modules) -> Frame {
+        let mut entry = match dump::deserialize(&self.payload, modules) {
+            Ok(entry) => entry,
+            Err(err) => return super::error_frame(&err),
+        };
+
+        if self.ttl > 0 {
+            let ttl = self.ttl as u64;
+            entry.expires_at = Some(if self.absttl {
+                ttl
+            } else {
+                db::now_ms().saturating_add(ttl)
+            });
+        }
+
+        // A key restored past its expiration time is not created, though it
+        // still replaces the current one.
+        let expired = entry.is_expired(db::now_ms());
+        let restored = db.update_entry(&self.key, |mut slot| {
+            if slot.is_some() && !self.replace {
+                return Err(Frame::Error(
+                    "BUSYKEY Target key name already exists.".to_string(),
+                ));
+            }
+
+            *slot = (!expired).then_some(entry);
+            Ok(slot.is_some())
+        });
+
+        match restored {
+            Ok(true) => {
+                db.notify(EventClass::Generic, "restore", &self.key);
+                Frame::ok()
+            }
+            Ok(false) => Frame::ok(),
+            Err(frame) => frame,
+        }
+    }
+}
+
+impl Migrate {
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    /// The `MIGRATE` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Migrate> {
+        let host = parse.next_string()?;
+        let port = parse
+            .next_string()?
+            .parse()
+            .map_err(|_| "ERR Invalid port")?;
+        let key = parse.next_bytes()?;
+        let db = next_index(parse, "ERR value is not an integer or out of range")?;
+        let timeout = parse.next_int()?;
+
+        let mut copy = false;
+        let mut replace = false;
+        let mut keys = vec![];
+        while !parse.is_empty() {
+            match &parse.next_string()?.to_uppercase()[..] {
+                "COPY" => copy = true,
+                "REPLACE" => replace = true,
+                "KEYS" => {
+                    if !key.is_empty() {
+                        return Err("ERR When using MIGRATE KEYS option, the key argument must be set to the empty string".into());
+                    }
+                    while !parse.is_empty() {
+                        keys.push(parse.next_bytes()?);
+                    }
+                }
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+        if keys.is_empty() {
+            keys.push(key);
+        }
+
+        // As in Redis, a timeout that is not positive means a second.
+        let timeout = match u64::try_from(timeout) {
+            Ok(timeout) if timeout > 0 => Duration::from_millis(timeout).min(MAX_MIGRATE_TIMEOUT),
+            _ => Duration::from_secs(1),
+        };
+
+        Ok(Migrate {
+            host,
+            port,
+            db,
+            timeout,
+            copy,
+            replace,
+            keys,
+        })
+    }
+
+    /// Runs on its own rather than through `Command::apply`, releasing the
+    /// locks of the keys while waiting on the target.
+    pub(crate) async fn apply(self, state: &ServerState, session: &Session) -> Frame {
+        let db = state.dbs.get(session.db);
+        let scope = Scope::Keys(self.keys.clone());
+
+        let (requests, migrated) = state.run_shared(scope.clone(), || self.requests(&db)).await;
+        if migrated.is_empty() {
+            return Frame::Simple("NOKEY".to_string());
+        }
+
+        let replies = match self.exchange(&requests).await {
+            Ok(replies) => replies,
+            Err(err) => {
+                return Frame::Error(format!("IOERR error or timeout {} target instance", err))
+            }
+        };
+
+        state
+            .run_shared(scope, || {
+                let mut error = None;
+                for (i, reply) in replies.into_iter().enumerate() {
+                    match reply {
+                        Frame::Error(msg) => {
+                            error.get_or_insert(msg);
+                        }
+                        // The reply to `SELECT`.
+                        _ if i == 0 => {}
+                        // Keys restored on the target are deleted even when
+                        // others failed, they would be found twice otherwise.
+                        _ if !self.copy => {
+                            let (key, cas) = &migrated[i - 1];
+                            if remove_unchanged(&db, key, *cas) {
+                                db.notify(EventClass::Generic, "del", key);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+
+                match error {
+                    Some(msg) => {
+                        Frame::Error(format!("ERR Target instance replied with error: {}", msg))
+                    }
+                    None => Frame::ok(),
+                }
+            })
+            .await
+    }
+
+    /// A `SELECT` followed by a `RESTORE` per key found, along with the keys
+    /// found and the `cas` of their entries.
+    ///
+    /// The time to live is sent relative to now, the clocks of both
+    /// instances may differ.
+    fn requests(&self, db: &Db) -> (Vec<Frame>, Vec<(Bytes, u64)>) {
+        let now = db::now_ms();
+        let mut migrated = vec![];
+        let mut requests = vec![command(&["SELECT".into(), self.db.to_string().into()])];
+
+        for key in &self.keys {
+            let entry = match db.get_entry(key) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let ttl = entry
+                .expires_at
+                .map_or(0, |at| at.saturating_sub(now).max(1));
+
+            let mut args = vec![
+                Bytes::from_static(b"RESTORE"),
+                key.clone(),
+                ttl.to_string().into(),
+                dump::serialize(&entry),
+            ];
+            if self.replace {
+                args.push(Bytes::from_static(b"REPLACE"));
+            }
+            requests.push(command(&args));
+            migrated.push((key.clone(), entry.cas));
+        }
+
+        (requests, migrated)
+    }
+
+    /// Sends `requests` to the target in a single write and reads back as
+    /// many replies, each step within the timeout. Errors name the step that
+    /// failed.
+    async fn exchange(&self, requests: &[Frame]) -> Result<Vec<Frame>, &'static str> {
+        let connect = TcpStream::connect((&self.host[..], self.port));
+        let mut socket = self.within(connect).await.ok_or("connecting to")?;
+        let _ = socket.set_nodelay(true);
+
+        let mut out = BytesMut::new();
+        for request in requests {
+            request.encode(&mut out);
+        }
+        let write = socket.write_all(&out);
+        self.within(write).await.ok_or("writing to")?;
+
+        let mut replies = Vec::with_capacity(requests.len());
+        let mut buf = BytesMut::with_capacity(4 * 1024);
+        while replies.len() < requests.len() {
+            let mut cursor = Cursor::new(&buf[..]);
+            match Frame::check(&mut cursor) {
+                Ok(()) => {
+                    let len = cursor.position() as usize;
+                    cursor.set_position(0);
+                    let reply = Frame::parse(&mut cursor).map_err(|_| "reading from")?;
+                    buf.advance(len);
+                    replies.push(reply);
+                }
+                Err(frame::Error::Incomplete) => {
+                    match self.within(socket.read_buf(&mut buf)).await {
+                        Some(0) | None => return Err("reading from"),
+                        Some(_) => {}
+                    }
+                }
+                Err(_) => return Err("reading from"),
+            }
+        }
+        Ok(replies)
+    }
+
+    /// The outcome of `io`, `None` if it failed or took longer than the
+    /// timeout.
+    async fn within<T>(&self, io: impl Future<Output = std::io::Result<T>>) -> Option<T> {
+        time::timeout(self.timeout, io).await.ok()?.ok()
+    }
+}
+
+/// Removes `key` if its entry is still the one with `cas`, returning whether
+/// it was.
+fn remove_unchanged(db: &Db, key: &[u8], cas: u64) -> bool {
+    db.update_entry(key, |mut slot| {
+        let unchanged = slot.as_ref().is_some_and(|entry| entry.cas == cas);
+        if unchanged {
+            *slot = None;
+        }
+        unchanged
+    })
+}
+
+fn command(args: &[Bytes]) -> Frame {
+    Frame::Array(args.iter().cloned().map(Frame::Bulk).collect())
+}
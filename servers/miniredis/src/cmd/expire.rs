@@ -0,0 +1,228 @@
+use bytes::Bytes;
+
+use crate::db::{self, Db};
+use crate::notify::EventClass;
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+
+/// Sets the time to live of `key`, replying with 1 if it was set.
+///
+/// Covers `EXPIRE` and `PEXPIRE`, relative to now, and `EXPIREAT` and
+/// `PEXPIREAT`, taking a Unix time. A time in the past deletes the key.
+#[derive(Debug)]
+pub struct Expire {
+    key: Bytes,
+    time: i64,
+    millis: bool,
+    at: bool,
+    conditions: Conditions,
+}
+
+/// Returns the time to live of `key`, -1 if it has none and -2 if it does
+/// not exist.
+///
+/// Covers `TTL` and `PTTL`, and `EXPIRETIME` and `PEXPIRETIME` which return
+/// the Unix time the key expires at instead.
+#[derive(Debug)]
+pub struct Ttl {
+    key: Bytes,
+    millis: bool,
+    at: bool,
+}
+
+/// Removes the time to live of `key`, replying with 1 if it had one.
+#[derive(Debug)]
+pub struct Persist {
+    key: Bytes,
+}
+
+/// Conditions for `EXPIRE` to change the time to live.
+#[derive(Debug, Default)]
+struct Conditions {
+    /// The key has no time to live.
+    nx: bool,
+    /// The key has a time to live.
+    xx: bool,
+    /// The new time to live is later than the current one.
+    gt: bool,
+    /// The new time to live is sooner than the current one.
+    lt: bool,
+}
+
+impl Expire {
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        std::slice::from_ref(&self.key)
+    }
+
+    /// The `EXPIRE`, `PEXPIRE`, `EXPIREAT` or `PEXPIREAT` string has already
+    /// been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse, millis: bool, at: bool) -> crate::Result<Expire> {
+        let key = parse.next_bytes()?;
+        let time = parse.next_int()?;
+        let mut conditions = Conditions::default();
+
+        while !parse.is_empty() {
+            match &parse.next_string()?.to_uppercase()[..] {
+                "NX" => conditions.nx = true,
+                "XX" => conditions.xx = true,
+                "GT" => conditions.gt = true,
+                "LT" => conditions.lt = true,
+                option => return Err(format!("ERR Unsupported option {}", option).into()),
+            }
+        }
+
+        if conditions.nx && (conditions.xx || conditions.gt || conditions.lt) {
+            return Err("ERR NX and XX, GT or LT options at the same time are not compatible".into());
+        }
+        if conditions.gt && conditions.lt {
+            return Err("ERR GT and LT options at the same time are not compatible".into());
+        }
+
+        Ok(Expire {
+            key,
+            time,
+            millis,
+            at,
+            conditions,
+        })
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        match (self.millis, self.at) {
+            (false, false) => "expire",
+            (true, false) => "pexpire",
+            (false, true) => "expireat",
+            (true, true) => "pexpireat",
+        }
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        let now = db::now_ms() as i64;
+        let scale = if self.millis { 1 } else { 1000 };
+        let offset = if self.at { 0 } else { now };
+
+        let deadline = match self
+            .time
+            .checked_mul(scale)
+            .and_then(|millis| millis.checked_add(offset))
+        {
+            Some(deadline) => deadline,
+            None => {
+                return Frame::Error(format!(
+                    "ERR invalid expire time in '{}' command",
+                    self.get_name()
+                ))
+            }
+        };
+
+        let conditions = &self.conditions;
+        let outcome = db.update_entry(&self.key, |mut slot| {
+            // Keys without a time to live count as never expiring for `GT`
+            // and `LT`.
+            let current = slot.as_ref()?.expires_at.map(|at| at as i64);
+            let refused = (conditions.nx && current.is_some())
+                || (conditions.xx && current.is_none())
+                || (conditions.gt && current.is_none_or(|at| deadline <= at))
+                || (conditions.lt && current.is_some_and(|at| deadline >= at));
+
+            if refused {
+                return Some(false);
+            }
+
+            if deadline <= now {
+                *slot = None;
+            } else {
+                slot.as_mut()?.expires_at = Some(deadline as u64);
+            }
+            Some(true)
+        });
+
+        match outcome {
+            Some(true) => {
+                let event = if deadline <= now { "del" } else { "expire" };
+                db.notify(EventClass::Generic, event, &self.key);
+                Frame::Integer(1)
+            }
+            _ => Frame::Integer(0),
+        }
+    }
+}
+
+impl Ttl {
+    /// The `TTL`, `PTTL`, `EXPIRETIME` or `PEXPIRETIME` string has already
+    /// been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse, millis: bool, at: bool) -> crate::Result<Ttl> {
+        let key = parse.next_bytes()?;
+
+        Ok(Ttl { key, millis, at })
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        match (self.millis, self.at) {
+            (false, false) => "ttl",
+            (true, false) => "pttl",
+            (false, true) => "expiretime",
+            (true, true) => "pexpiretime",
+        }
+    }
+
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        std::slice::from_ref(&self.key)
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        let entry = match db.get_entry(&self.key) {
+            Some(entry) => entry,
+            None => return Frame::Integer(-2),
+        };
+
+        let expires_at = match entry.expires_at {
+            Some(at) => at,
+            None => return Frame::Integer(-1),
+        };
+
+        let millis = if self.at {
+            expires_at
+        } else {
+            expires_at.saturating_sub(db::now_ms())
+        };
+
+        if self.millis {
+            Frame::Integer(millis as i64)
+        } else if self.at {
+            Frame::Integer((millis / 1000) as i64)
+        } else {
+            // Rounded to the closest second, as Redis does.
+            Frame::Integer(((millis + 500) / 1000) as i64)
+        }
+    }
+}
+
+impl Persist {
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        std::slice::from_ref(&self.key)
+    }
+
+    /// The `PERSIST` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Persist> {
+        let key = parse.next_bytes()?;
+
+        Ok(Persist { key })
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        let persisted = db.update_entry(&self.key, |mut slot| {
+            if slot.as_ref().is_none_or(|entry| entry.expires_at.is_none()) {
+                return false;
+            }
+            slot.as_mut().is_some_and(|entry| entry.expires_at.take().is_some())
+        });
+
+        if persisted {
+            db.notify(EventClass::Generic, "persist", &self.key);
+            Frame::Integer(1)
+        } else {
+            Frame::Integer(0)
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use bytes::Bytes;
+
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+use crate::db::Db;
+
+/// Get the value of key.
+///
+/// If the key does not exist the special value nil is returned.
+#[derive(Debug)]
+pub struct Get {
+    key: Bytes,
+}
+
+impl Get {
+    pub fn new(key: impl Into<Bytes>) -> Get {
+        Get {
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// The `GET` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Get> {
+        let key = parse.next_bytes()?;
+
+        Ok(Get { key })
+    }
+
+    /// Keys the command reads, remembered for clients tracking them.
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        std::slice::from_ref(&self.key)
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        match db.get(&self.key) {
+            Ok(Some(value)) => Frame::Bulk(value),
+            Ok(None) => Frame::Null,
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use bytes::Bytes;
+
+use crate::cmd::client::set_name;
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+use crate::session::Session;
+
+/// Switches the connection to the given protocol version, 2 or 3, and replies
+/// with a description of the server.
+#[derive(Debug)]
+pub struct Hello {
+    protover: Option<i64>,
+    setname: Option<Bytes>,
+}
+
+impl Hello {
+    /// The `HELLO` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Hello> {
+        let protover = if parse.is_empty() {
+            None
+        } else {
+            Some(
+                parse
+                    .next_int()
+                    .map_err(|_| "ERR Protocol version is not an integer or out of range")?,
+            )
+        };
+
+        let mut setname = None;
+        while !parse.is_empty() {
+            match &parse.next_string()?.to_uppercase()[..] {
+                "SETNAME" => setname = Some(parse.next_bytes()?),
+                option => {
+                    return Err(format!("ERR Syntax error in HELLO option '{}'", option).into())
+                }
+            }
+        }
+
+        Ok(Hello { protover, setname })
+    }
+
+    pub(crate) fn apply(self, session: &mut Session) -> Frame {
+        let resp3 = match self.protover {
+            None => session.outbox.is_resp3(),
+            Some(2) => false,
+            Some(3) => true,
+            Some(_) => return Frame::Error("NOPROTO unsupported protocol version".to_string()),
+        };
+
+        if let Some(name) = self.setname {
+            if let Err(err) = set_name(session, name) {
+                return Frame::Error(err.to_string());
+            }
+        }
+
+        session.outbox.set_resp3(resp3);
+
+        // Client libraries check the server name and version to pick the
+        // features they use, so miniredis presents itself as the Redis
+        // release whose commands it follows.
+        let field = |name: &'static str, value| (Frame::Bulk(name.into()), value);
+        Frame::Map(vec![
+            field("server", Frame::Bulk("redis".into())),
+            field("version", Frame::Bulk("7.0.0".into())),
+            field("proto", Frame::Integer(if resp3 { 3 } else { 2 })),
+            field("id", Frame::Integer(session.id as i64)),
+            field("mode", Frame::Bulk("standalone".into())),
+            field("role", Frame::Bulk("master".into())),
+            field("modules", Frame::Array(vec![])),
+        ])
+    }
+}
@@ -0,0 +1,197 @@
+use bytes::Bytes;
+
+use crate::hyperloglog::{Error, HyperLogLog};
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+use crate::db::Db;
+use crate::notify::EventClass;
+
+/// Adds the elements to the HyperLogLog stored at `key`, creating it if
+/// needed.
+///
+/// Replies 1 if the estimated cardinality may have changed, 0 otherwise.
+#[derive(Debug)]
+pub struct PfAdd {
+    key: Bytes,
+    elements: Vec<Bytes>,
+}
+
+/// Returns the estimated cardinality of the HyperLogLog stored at `key`, or
+/// of the union of several of them.
+#[derive(Debug)]
+pub struct PfCount {
+    keys: Vec<Bytes>,
+}
+
+/// Merges the source HyperLogLogs into `destination`, which estimates the
+/// union of all of them afterwards.
+#[derive(Debug)]
+pub struct PfMerge {
+    destination: Bytes,
+    sources: Vec<Bytes>,
+}
+
+impl PfAdd {
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        std::slice::from_ref(&self.key)
+    }
+
+    /// The `PFADD` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<PfAdd> {
+        let key = parse.next_bytes()?;
+        let mut elements = vec![];
+        while !parse.is_empty() {
+            elements.push(parse.next_bytes()?);
+        }
+
+        Ok(PfAdd { key, elements })
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        let updated = db.update(&self.key, |mut slot| {
+            let (mut hll, mut updated) = match &*slot {
+                Some(value) => (HyperLogLog::decode(value)?, false),
+                None => (HyperLogLog::new(), true),
+            };
+
+            for element in &self.elements {
+                updated |= hll.add(element);
+            }
+
+            if updated {
+                *slot = Some(hll.encode());
+            }
+            Ok(updated)
+        })
+        .unwrap_or(Err(Error::WrongType));
+
+        if let Ok(true) = updated {
+            db.notify(EventClass::String, "pfadd", &self.key);
+        }
+
+        match updated {
+            Ok(updated) => Frame::Integer(updated as i64),
+            Err(err) => error(err),
+        }
+    }
+}
+
+impl PfCount {
+    /// The `PFCOUNT` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<PfCount> {
+        let mut keys = vec![parse.next_bytes()?];
+        while !parse.is_empty() {
+            keys.push(parse.next_bytes()?);
+        }
+
+        Ok(PfCount { keys })
+    }
+
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        let count = match &self.keys[..] {
+            [key] => count_one(db, key),
+            keys => count_union(db, keys),
+        };
+
+        match count {
+            Ok(count) => Frame::Integer(count as i64),
+            Err(err) => error(err),
+        }
+    }
+}
+
+impl PfMerge {
+    pub(crate) fn keys(&self) -> Vec<Bytes> {
+        std::iter::once(&self.destination).chain(&self.sources).cloned().collect()
+    }
+
+    /// The `PFMERGE` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<PfMerge> {
+        let destination = parse.next_bytes()?;
+        let mut sources = vec![];
+        while !parse.is_empty() {
+            sources.push(parse.next_bytes()?);
+        }
+
+        Ok(PfMerge {
+            destination,
+            sources,
+        })
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        let sources = match load_all(db, &self.sources) {
+            Ok(sources) => sources,
+            Err(err) => return error(err),
+        };
+
+        let merged = db.update(&self.destination, |mut slot| {
+            let mut hll = match &*slot {
+                Some(value) => HyperLogLog::decode(value)?,
+                None => HyperLogLog::new(),
+            };
+
+            for source in &sources {
+                hll.merge(source);
+            }
+
+            *slot = Some(hll.encode());
+            Ok(())
+        })
+        .unwrap_or(Err(Error::WrongType));
+
+        match merged {
+            Ok(()) => {
+                db.notify(EventClass::String, "pfadd", &self.destination);
+                Frame::ok()
+            }
+            Err(err) => error(err),
+        }
+    }
+}
+
+/// Counting a single key refreshes the cardinality cached in its header, so
+/// repeated counts of an unchanged HLL skip the estimation.
+fn count_one(db: &Db, key: &[u8]) -> Result<u64, Error> {
+    db.update(key, |mut slot| {
+        let value = match &*slot {
+            Some(value) => value,
+            None => return Ok(0),
+        };
+
+        if let Some(count) = HyperLogLog::cached_count(value)? {
+            return Ok(count);
+        }
+
+        let mut hll = HyperLogLog::decode(value)?;
+        let count = hll.count();
+        *slot = Some(hll.encode());
+        Ok(count)
+    })
+    .unwrap_or(Err(Error::WrongType))
+}
+
+fn count_union(db: &Db, keys: &[Bytes]) -> Result<u64, Error> {
+    let mut union = HyperLogLog::new();
+    for hll in load_all(db, keys)? {
+        union.merge(&hll);
+    }
+
+    Ok(union.count())
+}
+
+/// Decodes the HLLs stored at `keys`, skipping missing keys.
+fn load_all(db: &Db, keys: &[Bytes]) -> Result<Vec<HyperLogLog>, Error> {
+    keys.iter()
+        .filter_map(|key| db.get(key).map_err(|_| Error::WrongType).transpose())
+        .map(|value| HyperLogLog::decode(&value?))
+        .collect()
+}
+
+fn error(err: Error) -> Frame {
+    Frame::Error(err.to_string())
+}
@@ -0,0 +1,418 @@
+use bytes::Bytes;
+
+use crate::cmd::databases::{db_index, next_index};
+use crate::db::{self, Databases, Db, Entry, Slot};
+use crate::notify::EventClass;
+use crate::pattern;
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+use crate::session::Session;
+
+const SAME_OBJECT_ERR: &str = "ERR source and destination objects are the same";
+
+/// Removes the given keys, replying with the number of keys removed.
+///
+/// `UNLINK` is accepted as an alias: values are plain strings, so there is
+/// nothing worth reclaiming in the background.
+#[derive(Debug)]
+pub struct Del {
+    keys: Vec<Bytes>,
+    unlink: bool,
+}
+
+/// Counts how many of the given keys exist.
+#[derive(Debug)]
+pub struct Touch {
+    keys: Vec<Bytes>,
+}
+
+/// Renames `key` to `new_key`, overwriting it unless `nx` is set.
+#[derive(Debug)]
+pub struct Rename {
+    key: Bytes,
+    new_key: Bytes,
+    nx: bool,
+}
+
+/// Copies the value of `source` to `destination`, possibly in another
+/// database.
+#[derive(Debug)]
+pub struct Copy {
+    source: Bytes,
+    destination: Bytes,
+    db: Option<i64>,
+    replace: bool,
+}
+
+/// Moves `key` from the selected database to another one, unless the
+/// destination already holds it.
+#[derive(Debug)]
+pub struct Move {
+    key: Bytes,
+    db: i64,
+}
+
+/// Returns a random key of the selected database.
+#[derive(Debug)]
+pub struct RandomKey;
+
+/// Iterates the keys of the selected database a few at a time.
+///
+/// `MATCH` and `TYPE` filter the keys of each batch after they were picked,
+/// so a call may return fewer than `COUNT` keys, or none, before the
+/// iteration is over.
+#[derive(Debug)]
+pub struct Scan {
+    cursor: u64,
+    pattern: Option<Bytes>,
+    count: usize,
+    key_type: Option<String>,
+}
+
+/// Returns every key matching a glob-style pattern.
+#[derive(Debug)]
+pub struct Keys {
+    pattern: Bytes,
+}
+
+/// Returns the type of the value stored at `key`, `none` if it is absent.
+#[derive(Debug)]
+pub struct Type {
+    key: Bytes,
+}
+
+impl Del {
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    /// The `DEL` or `UNLINK` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse, unlink: bool) -> crate::Result<Del> {
+        let keys = next_keys(parse)?;
+
+        Ok(Del { keys, unlink })
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        if self.unlink {
+            "unlink"
+        } else {
+            "del"
+        }
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        let removed = self
+            .keys
+            .iter()
+            .filter(|key| db.remove(key))
+            .inspect(|key| db.notify(EventClass::Generic, "del", key))
+            .count();
+
+        Frame::Integer(removed as i64)
+    }
+}
+
+impl Touch {
+    /// The `TOUCH` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Touch> {
+        let keys = next_keys(parse)?;
+
+        Ok(Touch { keys })
+    }
+
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        let found = self.keys.iter().filter(|key| db.contains_key(key)).count();
+
+        Frame::Integer(found as i64)
+    }
+}
+
+impl Rename {
+    pub(crate) fn keys(&self) -> Vec<Bytes> {
+        vec![self.key.clone(), self.new_key.clone()]
+    }
+
+    /// The `RENAME` or `RENAMENX` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse, nx: bool) -> crate::Result<Rename> {
+        let key = parse.next_bytes()?;
+        let new_key = parse.next_bytes()?;
+
+        Ok(Rename { key, new_key, nx })
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        if self.nx {
+            "renamenx"
+        } else {
+            "rename"
+        }
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        const NO_SUCH_KEY: &str = "ERR no such key";
+
+        if self.key == self.new_key {
+            return match (db.contains_key(&self.key), self.nx) {
+                (false, _) => Frame::Error(NO_SUCH_KEY.to_string()),
+                (true, false) => Frame::ok(),
+                (true, true) => Frame::Integer(0),
+            };
+        }
+
+        let renamed = db.update_pair(&self.key, &self.new_key, |mut from, mut to| {
+            if from.is_none() {
+                return Err(Frame::Error(NO_SUCH_KEY.to_string()));
+            }
+
+            if self.nx && to.is_some() {
+                return Err(Frame::Integer(0));
+            }
+
+            *to = from.take();
+            Ok(())
+        });
+
+        if let Err(frame) = renamed {
+            return frame;
+        }
+
+        db.notify(EventClass::Generic, "rename_from", &self.key);
+        db.notify(EventClass::Generic, "rename_to", &self.new_key);
+        if self.nx {
+            Frame::Integer(1)
+        } else {
+            Frame::ok()
+        }
+    }
+}
+
+impl Copy {
+    pub(crate) fn keys(&self) -> Vec<Bytes> {
+        vec![self.source.clone(), self.destination.clone()]
+    }
+
+    /// The `COPY` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Copy> {
+        let source = parse.next_bytes()?;
+        let destination = parse.next_bytes()?;
+        let mut db = None;
+        let mut replace = false;
+
+        while !parse.is_empty() {
+            match &parse.next_string()?.to_uppercase()[..] {
+                "DB" => db = Some(next_index(parse, "ERR value is not an integer or out of range")?),
+                "REPLACE" => replace = true,
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+
+        Ok(Copy {
+            source,
+            destination,
+            db,
+            replace,
+        })
+    }
+
+    pub(crate) fn apply(self, dbs: &Databases, session: &Session) -> Frame {
+        let target = match self.db {
+            None => session.db,
+            Some(index) => match db_index(dbs, index) {
+                Some(index) => index,
+                None => return Frame::Error("ERR DB index is out of range".to_string()),
+            },
+        };
+
+        // The time to live is copied along with the value.
+        let copy = |from: Slot<Entry>, mut to: Slot<Entry>| {
+            if from.is_none() || (to.is_some() && !self.replace) {
+                return false;
+            }
+
+            to.clone_from(&from);
+            true
+        };
+
+        let source = dbs.get(session.db);
+        let destination = dbs.get(target);
+        let copied = if target != session.db {
+            db::update_across(&source, &self.source, &destination, &self.destination, copy)
+        } else if self.source == self.destination {
+            return Frame::Error(SAME_OBJECT_ERR.to_string());
+        } else {
+            source.update_pair(&self.source, &self.destination, copy)
+        };
+
+        if copied {
+            destination.notify(EventClass::Generic, "copy_to", &self.destination);
+        }
+        Frame::Integer(copied as i64)
+    }
+}
+
+impl Move {
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        std::slice::from_ref(&self.key)
+    }
+
+    /// The `MOVE` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Move> {
+        let key = parse.next_bytes()?;
+        let db = parse.next_int()?;
+
+        Ok(Move { key, db })
+    }
+
+    pub(crate) fn apply(self, dbs: &Databases, session: &Session) -> Frame {
+        let target = match db_index(dbs, self.db) {
+            Some(target) => target,
+            None => return Frame::Error("ERR DB index is out of range".to_string()),
+        };
+
+        if target == session.db {
+            return Frame::Error(SAME_OBJECT_ERR.to_string());
+        }
+
+        let source = dbs.get(session.db);
+        let destination = dbs.get(target);
+        let moved = db::update_across(&source, &self.key, &destination, &self.key, |mut from, mut to| {
+            if from.is_none() || to.is_some() {
+                return false;
+            }
+
+            *to = from.take();
+            true
+        });
+
+        if moved {
+            source.notify(EventClass::Generic, "move_from", &self.key);
+            destination.notify(EventClass::Generic, "move_to", &self.key);
+        }
+        Frame::Integer(moved as i64)
+    }
+}
+
+impl RandomKey {
+    /// The `RANDOMKEY` string has already been consumed.
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<RandomKey> {
+        Ok(RandomKey)
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        match db.random_key() {
+            Some(key) => Frame::Bulk(key),
+            None => Frame::Null,
+        }
+    }
+}
+
+impl Scan {
+    /// The `SCAN` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Scan> {
+        let cursor = parse
+            .next_string()?
+            .parse()
+            .map_err(|_| "ERR invalid cursor")?;
+
+        let mut scan = Scan {
+            cursor,
+            pattern: None,
+            count: 10,
+            key_type: None,
+        };
+
+        while !parse.is_empty() {
+            match &parse.next_string()?.to_lowercase()[..] {
+                "match" => scan.pattern = Some(parse.next_bytes()?),
+                "count" => {
+                    let count = parse.next_int()?;
+                    if count < 1 {
+                        return Err("ERR syntax error".into());
+                    }
+                    scan.count = count as usize;
+                }
+                "type" => scan.key_type = Some(parse.next_string()?.to_lowercase()),
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+
+        Ok(scan)
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        let (cursor, keys) = db.scan(self.cursor, self.count);
+
+        // Every value is a string.
+        let type_matches = self.key_type.as_deref().is_none_or(|t| t == "string");
+
+        let keys = keys
+            .into_iter()
+            .filter(|_| type_matches)
+            .filter(|key| {
+                self.pattern
+                    .as_deref()
+                    .is_none_or(|pattern| pattern::matches(pattern, key))
+            })
+            .map(Frame::Bulk)
+            .collect();
+
+        Frame::Array(vec![
+            Frame::Bulk(cursor.to_string().into()),
+            Frame::Array(keys),
+        ])
+    }
+}
+
+impl Keys {
+    /// The `KEYS` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Keys> {
+        let pattern = parse.next_bytes()?;
+
+        Ok(Keys { pattern })
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        Frame::Array(
+            db.keys()
+                .into_iter()
+                .filter(|key| pattern::matches(&self.pattern, key))
+                .map(Frame::Bulk)
+                .collect(),
+        )
+    }
+}
+
+impl Type {
+    /// The `TYPE` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Type> {
+        let key = parse.next_bytes()?;
+
+        Ok(Type { key })
+    }
+
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        std::slice::from_ref(&self.key)
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        match db.get_entry(&self.key) {
+            Some(entry) => Frame::Simple(entry.value.type_name().to_string()),
+            None => Frame::Simple("none".to_string()),
+        }
+    }
+}
+
+/// Reads one or more keys until the end of the command.
+fn next_keys(parse: &mut Parse) -> crate::Result<Vec<Bytes>> {
+    let mut keys = vec![parse.next_bytes()?];
+    while !parse.is_empty() {
+        keys.push(parse.next_bytes()?);
+    }
+
+    Ok(keys)
+}
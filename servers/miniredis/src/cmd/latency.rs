@@ -0,0 +1,41 @@
+use crate::cmd::config::unknown_subcommand;
+use crate::latency::LatencyMonitor;
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+
+/// Reports latency spikes sampled by the latency monitor.
+#[derive(Debug)]
+pub enum Latency {
+    Latest,
+    History(String),
+    Reset(Vec<String>),
+}
+
+impl Latency {
+    /// The `LATENCY` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Latency> {
+        let sub_command = parse.next_string()?.to_lowercase();
+
+        match &sub_command[..] {
+            "latest" => Ok(Latency::Latest),
+            "history" => Ok(Latency::History(parse.next_string()?)),
+            "reset" => {
+                let mut events = vec![];
+                while !parse.is_empty() {
+                    events.push(parse.next_string()?);
+                }
+
+                Ok(Latency::Reset(events))
+            }
+            _ => Err(unknown_subcommand(&sub_command, "LATENCY").into()),
+        }
+    }
+
+    pub(crate) fn apply(self, monitor: &LatencyMonitor) -> Frame {
+        match self {
+            Latency::Latest => Frame::Array(monitor.latest()),
+            Latency::History(event) => Frame::Array(monitor.history(&event)),
+            Latency::Reset(events) => Frame::Integer(monitor.reset(&events) as i64),
+        }
+    }
+}
@@ -0,0 +1,690 @@
+mod bitmap;
+pub use bitmap::{BitCount, BitField, BitOp, BitPos, GetBit, SetBit};
+
+mod client;
+pub use client::ClientCmd;
+
+mod config;
+pub(crate) use config::unknown_subcommand;
+pub use config::Config;
+
+mod databases;
+pub use databases::{DbSize, Flush, Select, SwapDb};
+
+mod dump;
+pub use dump::{Dump, Migrate, Restore};
+
+mod expire;
+pub use expire::{Expire, Persist, Ttl};
+
+mod get;
+pub use get::Get;
+
+mod hello;
+pub use hello::Hello;
+
+mod hyperloglog;
+pub use hyperloglog::{PfAdd, PfCount, PfMerge};
+
+mod keyspace;
+pub use keyspace::{Copy, Del, Keys, Move, RandomKey, Rename, Scan, Touch, Type};
+
+mod latency;
+pub use latency::Latency;
+
+mod module;
+pub use module::{ModuleCmd, ModuleCommand};
+
+mod monitor;
+pub use monitor::Monitor;
+
+mod object;
+pub use object::{Memory, Object};
+
+mod ping;
+pub use ping::Ping;
+
+mod pubsub;
+pub(crate) use pubsub::unsubscribe_all;
+pub use pubsub::{PubSubCmd, Publish, Subscribe, Unsubscribe};
+
+mod scripting;
+pub use scripting::{Eval, FCall, FunctionCmd, ScriptCmd};
+
+mod set;
+pub use set::Set;
+
+mod slowlog;
+pub use slowlog::SlowLog;
+
+mod strlen;
+pub use strlen::Strlen;
+
+mod transaction;
+pub use transaction::{Discard, Exec, Multi};
+
+mod unknown;
+pub use unknown::Unknown;
+
+use bytes::Bytes;
+
+use crate::locks::Scope;
+use crate::module::Modules;
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::{Parse, ParseError};
+use crate::session::Session;
+use crate::state::ServerState;
+
+/// Reply to `MIGRATE` queued in a transaction, which cannot wait on another
+/// instance.
+pub(crate) const MIGRATE_IN_TRANSACTION: &str = "ERR MIGRATE is not allowed in transactions";
+
+/// Enumeration of supported Redis commands.
+///
+/// Methods called on `Command` are delegated to the command implementation.
+#[derive(Debug)]
+pub enum Command {
+    Get(Get),
+    Set(Set),
+    SetBit(SetBit),
+    GetBit(GetBit),
+    BitCount(BitCount),
+    BitPos(BitPos),
+    BitOp(BitOp),
+    BitField(BitField),
+    PfAdd(PfAdd),
+    PfCount(PfCount),
+    PfMerge(PfMerge),
+    Select(Select),
+    DbSize(DbSize),
+    Flush(Flush),
+    SwapDb(SwapDb),
+    Del(Del),
+    Touch(Touch),
+    Expire(Expire),
+    Ttl(Ttl),
+    Persist(Persist),
+    Rename(Rename),
+    Copy(Copy),
+    Move(Move),
+    RandomKey(RandomKey),
+    Scan(Scan),
+    Keys(Keys),
+    Type(Type),
+    Strlen(Strlen),
+    Object(Object),
+    Memory(Memory),
+    Dump(Dump),
+    Restore(Restore),
+    Migrate(Migrate),
+    Config(Config),
+    SlowLog(SlowLog),
+    Latency(Latency),
+    Monitor(Monitor),
+    Ping(Ping),
+    Hello(Hello),
+    Client(ClientCmd),
+    Publish(Publish),
+    Subscribe(Subscribe),
+    Unsubscribe(Unsubscribe),
+    PubSub(PubSubCmd),
+    Eval(Eval),
+    FCall(FCall),
+    Script(ScriptCmd),
+    Function(FunctionCmd),
+    Module(ModuleCmd),
+    ModuleCommand(ModuleCommand),
+    Multi(Multi),
+    Exec(Exec),
+    Discard(Discard),
+    Unknown(Unknown),
+}
+
+impl Command {
+    /// Parse a command from a received frame.
+    ///
+    /// The `Frame` must represent a Redis command supported by miniredis, or
+    /// registered by one of `modules`, and be the array variant.
+    pub fn from_frame(frame: Frame, modules: &Modules) -> crate::Result<Command> {
+        let mut parse = Parse::new(frame)?;
+
+        // All redis commands begin with the command name as a string. The name
+        // is read and converted to lower cases in order to do case sensitive
+        // matching.
+        let command_name = parse.next_string()?.to_lowercase();
+
+        Self::parse_command(&command_name, &mut parse, modules)
+            .map_err(|err| arity_error(&command_name, err))
+    }
+
+    fn parse_command(
+        command_name: &str,
+        parse: &mut Parse,
+        modules: &Modules,
+    ) -> crate::Result<Command> {
+        let command = match command_name {
+            "get" => Command::Get(Get::parse_frames(parse)?),
+            "set" => Command::Set(Set::parse_frames(parse)?),
+            "setbit" => Command::SetBit(SetBit::parse_frames(parse)?),
+            "getbit" => Command::GetBit(GetBit::parse_frames(parse)?),
+            "bitcount" => Command::BitCount(BitCount::parse_frames(parse)?),
+            "bitpos" => Command::BitPos(BitPos::parse_frames(parse)?),
+            "bitop" => Command::BitOp(BitOp::parse_frames(parse)?),
+            "bitfield" => Command::BitField(BitField::parse_frames(parse, false)?),
+            "bitfield_ro" => Command::BitField(BitField::parse_frames(parse, true)?),
+            "pfadd" => Command::PfAdd(PfAdd::parse_frames(parse)?),
+            "pfcount" => Command::PfCount(PfCount::parse_frames(parse)?),
+            "pfmerge" => Command::PfMerge(PfMerge::parse_frames(parse)?),
+            "select" => Command::Select(Select::parse_frames(parse)?),
+            "dbsize" => Command::DbSize(DbSize::parse_frames(parse)?),
+            "flushdb" => Command::Flush(Flush::parse_frames(parse, false)?),
+            "flushall" => Command::Flush(Flush::parse_frames(parse, true)?),
+            "swapdb" => Command::SwapDb(SwapDb::parse_frames(parse)?),
+            "del" => Command::Del(Del::parse_frames(parse, false)?),
+            "unlink" => Command::Del(Del::parse_frames(parse, true)?),
+            "touch" => Command::Touch(Touch::parse_frames(parse)?),
+            "expire" => Command::Expire(Expire::parse_frames(parse, false, false)?),
+            "pexpire" => Command::Expire(Expire::parse_frames(parse, true, false)?),
+            "expireat" => Command::Expire(Expire::parse_frames(parse, false, true)?),
+            "pexpireat" => Command::Expire(Expire::parse_frames(parse, true, true)?),
+            "ttl" => Command::Ttl(Ttl::parse_frames(parse, false, false)?),
+            "pttl" => Command::Ttl(Ttl::parse_frames(parse, true, false)?),
+            "expiretime" => Command::Ttl(Ttl::parse_frames(parse, false, true)?),
+            "pexpiretime" => Command::Ttl(Ttl::parse_frames(parse, true, true)?),
+            "persist" => Command::Persist(Persist::parse_frames(parse)?),
+            "rename" => Command::Rename(Rename::parse_frames(parse, false)?),
+            "renamenx" => Command::Rename(Rename::parse_frames(parse, true)?),
+            "copy" => Command::Copy(Copy::parse_frames(parse)?),
+            "move" => Command::Move(Move::parse_frames(parse)?),
+            "randomkey" => Command::RandomKey(RandomKey::parse_frames(parse)?),
+            "scan" => Command::Scan(Scan::parse_frames(parse)?),
+            "keys" => Command::Keys(Keys::parse_frames(parse)?),
+            "type" => Command::Type(Type::parse_frames(parse)?),
+            "strlen" => Command::Strlen(Strlen::parse_frames(parse)?),
+            "object" => Command::Object(Object::parse_frames(parse)?),
+            "memory" => Command::Memory(Memory::parse_frames(parse)?),
+            "dump" => Command::Dump(Dump::parse_frames(parse)?),
+            "restore" => Command::Restore(Restore::parse_frames(parse)?),
+            "migrate" => Command::Migrate(Migrate::parse_frames(parse)?),
+            "client" => Command::Client(ClientCmd::parse_frames(parse)?),
+            "hello" => Command::Hello(Hello::parse_frames(parse)?),
+            "config" => Command::Config(Config::parse_frames(parse)?),
+            "slowlog" => Command::SlowLog(SlowLog::parse_frames(parse)?),
+            "latency" => Command::Latency(Latency::parse_frames(parse)?),
+            "monitor" => Command::Monitor(Monitor::parse_frames(parse)?),
+            "ping" => Command::Ping(Ping::parse_frames(parse)?),
+            "publish" => Command::Publish(Publish::parse_frames(parse)?),
+            "subscribe" => Command::Subscribe(Subscribe::parse_frames(parse, false)?),
+            "psubscribe" => Command::Subscribe(Subscribe::parse_frames(parse, true)?),
+            "unsubscribe" => Command::Unsubscribe(Unsubscribe::parse_frames(parse, false)?),
+            "punsubscribe" => Command::Unsubscribe(Unsubscribe::parse_frames(parse, true)?),
+            "pubsub" => Command::PubSub(PubSubCmd::parse_frames(parse)?),
+            "eval" => Command::Eval(Eval::parse_frames(parse, false, false)?),
+            "evalsha" => Command::Eval(Eval::parse_frames(parse, true, false)?),
+            "eval_ro" => Command::Eval(Eval::parse_frames(parse, false, true)?),
+            "evalsha_ro" => Command::Eval(Eval::parse_frames(parse, true, true)?),
+            "fcall" => Command::FCall(FCall::parse_frames(parse, false)?),
+            "fcall_ro" => Command::FCall(FCall::parse_frames(parse, true)?),
+            "script" => Command::Script(ScriptCmd::parse_frames(parse)?),
+            "function" => Command::Function(FunctionCmd::parse_frames(parse)?),
+            "module" => Command::Module(ModuleCmd::parse_frames(parse)?),
+            "multi" => Command::Multi(Multi::parse_frames(parse)?),
+            "exec" => Command::Exec(Exec::parse_frames(parse)?),
+            "discard" => Command::Discard(Discard::parse_frames(parse)?),
+            _ => match modules.command(command_name) {
+                Some(spec) => Command::ModuleCommand(ModuleCommand::parse_frames(spec, parse)?),
+                None => {
+                    // The command is not recognized and an Unknown command is
+                    // returned.
+                    //
+                    // `return` is called here to skip the `finish()` call
+                    // below. As the command is not recognized, there is most
+                    // likely unconsumed fields remaining in the `Parse`
+                    // instance.
+                    return Ok(Command::Unknown(Unknown::new(command_name)));
+                }
+            },
+        };
+
+        // Check if there is any remaining unconsumed fields in the `Parse`
+        // value. If fields remain, this indicates an unexpected frame format
+        // and an error is returned.
+        parse.finish()?;
+
+        Ok(command)
+    }
+
+    /// Apply the command on behalf of the client of `session`, returning the
+    /// reply.
+    ///
+    /// Subscription commands reply through the session's outbox instead, and
+    /// return `None`.
+    pub fn apply(self, state: &ServerState, session: &mut Session) -> Option<Frame> {
+        use Command::*;
+
+        if let Some(options) = &session.tracking {
+            // Remembered before running the command, so a write landing in
+            // between still invalidates what the client is about to cache.
+            if options.tracks_reads(session.caching) {
+                state.tracking.remember(session.id, self.read_keys());
+            }
+        }
+
+        let dbs = &state.dbs;
+        let db = &dbs.get(session.db);
+        let response = match self {
+            Get(cmd) => cmd.apply(db),
+            Set(cmd) => cmd.apply(db),
+            SetBit(cmd) => cmd.apply(db),
+            GetBit(cmd) => cmd.apply(db),
+            BitCount(cmd) => cmd.apply(db),
+            BitPos(cmd) => cmd.apply(db),
+            BitOp(cmd) => cmd.apply(db),
+            BitField(cmd) => cmd.apply(db),
+            PfAdd(cmd) => cmd.apply(db),
+            PfCount(cmd) => cmd.apply(db),
+            PfMerge(cmd) => cmd.apply(db),
+            Select(cmd) => cmd.apply(dbs, session),
+            DbSize(cmd) => cmd.apply(dbs, session),
+            Flush(cmd) => cmd.apply(dbs, session),
+            SwapDb(cmd) => cmd.apply(dbs),
+            Del(cmd) => cmd.apply(db),
+            Touch(cmd) => cmd.apply(db),
+            Expire(cmd) => cmd.apply(db),
+            Ttl(cmd) => cmd.apply(db),
+            Persist(cmd) => cmd.apply(db),
+            Rename(cmd) => cmd.apply(db),
+            Copy(cmd) => cmd.apply(dbs, session),
+            Move(cmd) => cmd.apply(dbs, session),
+            RandomKey(cmd) => cmd.apply(db),
+            Scan(cmd) => cmd.apply(db),
+            Keys(cmd) => cmd.apply(db),
+            Type(cmd) => cmd.apply(db),
+            Strlen(cmd) => cmd.apply(db),
+            Object(cmd) => cmd.apply(db),
+            Memory(cmd) => cmd.apply(db),
+            Dump(cmd) => cmd.apply(db),
+            Restore(cmd) => cmd.apply(db, &state.modules),
+            // Runs on its own from `ServerState::execute`, it is refused in
+            // transactions and cannot be called from scripts.
+            Migrate(_) => Frame::Error(MIGRATE_IN_TRANSACTION.to_string()),
+            Config(cmd) => cmd.apply(&state.config),
+            SlowLog(cmd) => cmd.apply(&state.slowlog),
+            Latency(cmd) => cmd.apply(&state.latency),
+            Monitor(cmd) => cmd.apply(&state.monitors, session),
+            Ping(cmd) => cmd.apply(session),
+            Hello(cmd) => cmd.apply(session),
+            Client(cmd) => cmd.apply(state, session),
+            Publish(cmd) => cmd.apply(state),
+            Subscribe(cmd) => {
+                cmd.apply(state, session);
+                return None;
+            }
+            Unsubscribe(cmd) => {
+                cmd.apply(state, session);
+                return None;
+            }
+            PubSub(cmd) => cmd.apply(state),
+            Eval(cmd) => cmd.apply(state, session),
+            FCall(cmd) => cmd.apply(state, session),
+            Script(cmd) => cmd.apply(&state.scripts),
+            Function(cmd) => cmd.apply(&state.scripts),
+            Module(cmd) => cmd.apply(&state.modules),
+            ModuleCommand(cmd) => cmd.apply(state, session),
+            Multi(cmd) => cmd.apply(session),
+            Exec(cmd) => cmd.apply(state, session),
+            Discard(cmd) => cmd.apply(session),
+            Unknown(cmd) => cmd.apply(),
+        };
+
+        Some(response)
+    }
+
+    /// Returns the command name
+    pub fn get_name(&self) -> &str {
+        match self {
+            Command::Get(_) => "get",
+            Command::Set(_) => "set",
+            Command::SetBit(_) => "setbit",
+            Command::GetBit(_) => "getbit",
+            Command::BitCount(_) => "bitcount",
+            Command::BitPos(_) => "bitpos",
+            Command::BitOp(_) => "bitop",
+            Command::BitField(cmd) => cmd.get_name(),
+            Command::PfAdd(_) => "pfadd",
+            Command::PfCount(_) => "pfcount",
+            Command::PfMerge(_) => "pfmerge",
+            Command::Select(_) => "select",
+            Command::DbSize(_) => "dbsize",
+            Command::Flush(cmd) => cmd.get_name(),
+            Command::SwapDb(_) => "swapdb",
+            Command::Del(cmd) => cmd.get_name(),
+            Command::Touch(_) => "touch",
+            Command::Expire(cmd) => cmd.get_name(),
+            Command::Ttl(cmd) => cmd.get_name(),
+            Command::Persist(_) => "persist",
+            Command::Rename(cmd) => cmd.get_name(),
+            Command::Copy(_) => "copy",
+            Command::Move(_) => "move",
+            Command::RandomKey(_) => "randomkey",
+            Command::Scan(_) => "scan",
+            Command::Keys(_) => "keys",
+            Command::Type(_) => "type",
+            Command::Strlen(_) => "strlen",
+            Command::Object(_) => "object",
+            Command::Memory(_) => "memory",
+            Command::Dump(_) => "dump",
+            Command::Restore(_) => "restore",
+            Command::Migrate(_) => "migrate",
+            Command::Config(_) => "config",
+            Command::SlowLog(_) => "slowlog",
+            Command::Latency(_) => "latency",
+            Command::Monitor(_) => "monitor",
+            Command::Ping(_) => "ping",
+            Command::Hello(_) => "hello",
+            Command::Client(cmd) => cmd.get_name(),
+            Command::Publish(_) => "publish",
+            Command::Subscribe(cmd) => cmd.get_name(),
+            Command::Unsubscribe(cmd) => cmd.get_name(),
+            Command::PubSub(_) => "pubsub",
+            Command::Eval(cmd) => cmd.get_name(),
+            Command::FCall(cmd) => cmd.get_name(),
+            Command::Script(_) => "script",
+            Command::Function(_) => "function",
+            Command::Module(_) => "module",
+            Command::ModuleCommand(cmd) => cmd.get_name(),
+            Command::Multi(_) => "multi",
+            Command::Exec(_) => "exec",
+            Command::Discard(_) => "discard",
+            Command::Unknown(cmd) => cmd.get_name(),
+        }
+    }
+
+    /// Administrative and unknown commands are kept out of `MONITOR` output,
+    /// as in Redis.
+    pub fn is_monitored(&self) -> bool {
+        !matches!(
+            self,
+            Command::Config(_)
+                | Command::SlowLog(_)
+                | Command::Latency(_)
+                | Command::Monitor(_)
+                | Command::Unknown(_)
+        )
+    }
+
+    /// Commands that control a transaction rather than being queued in it.
+    pub fn is_transaction_control(&self) -> bool {
+        matches!(
+            self,
+            Command::Multi(_) | Command::Exec(_) | Command::Discard(_)
+        )
+    }
+
+    /// Commands running other commands, which hold the locks of their keys
+    /// exclusively so no other client's command on them runs in between, see
+    /// `lock_scope`.
+    pub fn is_atomic(&self) -> bool {
+        matches!(
+            self,
+            Command::Exec(_) | Command::Eval(_) | Command::FCall(_)
+        )
+    }
+
+    /// Keys the command is kept apart on from transactions and scripts, see
+    /// `locks`. `EXEC` covers the keys of the commands it runs, which the
+    /// command itself does not know about.
+    ///
+    /// Scripts are only isolated on the keys they declare.
+    pub fn lock_scope(&self) -> Scope {
+        let keys = match self {
+            Command::Get(cmd) => cmd.keys().to_vec(),
+            Command::Set(cmd) => cmd.keys().to_vec(),
+            Command::SetBit(cmd) => cmd.keys().to_vec(),
+            Command::GetBit(cmd) => cmd.keys().to_vec(),
+            Command::BitCount(cmd) => cmd.keys().to_vec(),
+            Command::BitPos(cmd) => cmd.keys().to_vec(),
+            Command::BitOp(cmd) => cmd.keys(),
+            Command::BitField(cmd) => cmd.keys().to_vec(),
+            Command::PfAdd(cmd) => cmd.keys().to_vec(),
+            Command::PfCount(cmd) => cmd.keys().to_vec(),
+            Command::PfMerge(cmd) => cmd.keys(),
+            Command::Del(cmd) => cmd.keys().to_vec(),
+            Command::Touch(cmd) => cmd.keys().to_vec(),
+            Command::Expire(cmd) => cmd.keys().to_vec(),
+            Command::Ttl(cmd) => cmd.keys().to_vec(),
+            Command::Persist(cmd) => cmd.keys().to_vec(),
+            Command::Rename(cmd) => cmd.keys(),
+            Command::Copy(cmd) => cmd.keys(),
+            Command::Move(cmd) => cmd.keys().to_vec(),
+            Command::Type(cmd) => cmd.keys().to_vec(),
+            Command::Strlen(cmd) => cmd.keys().to_vec(),
+            Command::Object(cmd) => cmd.keys().to_vec(),
+            Command::Memory(cmd) => cmd.keys().to_vec(),
+            Command::Dump(cmd) => cmd.keys().to_vec(),
+            Command::Restore(cmd) => cmd.keys().to_vec(),
+            Command::Migrate(cmd) => cmd.keys().to_vec(),
+            Command::Eval(cmd) => cmd.keys().to_vec(),
+            Command::FCall(cmd) => cmd.keys().to_vec(),
+            // Module commands do not tell which keys they use.
+            Command::DbSize(_)
+            | Command::Flush(_)
+            | Command::SwapDb(_)
+            | Command::RandomKey(_)
+            | Command::Scan(_)
+            | Command::Keys(_)
+            | Command::ModuleCommand(_) => return Scope::All,
+            _ => return Scope::None,
+        };
+        Scope::Keys(keys)
+    }
+
+    /// Commands that change the dataset, refused in read-only scripts.
+    pub fn is_write(&self) -> bool {
+        match self {
+            Command::BitField(cmd) => !cmd.is_read_only(),
+            Command::ModuleCommand(cmd) => cmd.is_write(),
+            cmd => matches!(
+                cmd,
+                Command::Set(_)
+                    | Command::SetBit(_)
+                    | Command::BitOp(_)
+                    | Command::PfAdd(_)
+                    | Command::PfMerge(_)
+                    | Command::Flush(_)
+                    | Command::SwapDb(_)
+                    | Command::Del(_)
+                    | Command::Expire(_)
+                    | Command::Persist(_)
+                    | Command::Rename(_)
+                    | Command::Copy(_)
+                    | Command::Move(_)
+                    | Command::Restore(_)
+                    | Command::Migrate(_)
+            ),
+        }
+    }
+
+    /// Commands whose effects are replicated through the Raft log: writes,
+    /// and scripts and functions that may write.
+    pub fn is_replicated(&self) -> bool {
+        match self {
+            Command::Eval(cmd) => !cmd.is_read_only(),
+            Command::FCall(cmd) => !cmd.is_read_only(),
+            Command::Function(cmd) => matches!(
+                cmd,
+                FunctionCmd::Load { .. } | FunctionCmd::Delete(_) | FunctionCmd::Flush
+            ),
+            cmd => cmd.is_write(),
+        }
+    }
+
+    /// Commands about the connection or this server rather than the dataset,
+    /// which any member of a Raft group runs without consulting the others.
+    pub fn is_node_local(&self) -> bool {
+        matches!(
+            self,
+            Command::Select(_)
+                | Command::Config(_)
+                | Command::SlowLog(_)
+                | Command::Latency(_)
+                | Command::Monitor(_)
+                | Command::Ping(_)
+                | Command::Hello(_)
+                | Command::Client(_)
+                | Command::Publish(_)
+                | Command::Subscribe(_)
+                | Command::Unsubscribe(_)
+                | Command::PubSub(_)
+                | Command::Script(_)
+                | Command::Module(_)
+                | Command::Unknown(_)
+        )
+    }
+
+    /// Commands administering the server or its clients rather than reading
+    /// or writing keys, along with those dropping whole databases.
+    pub fn is_admin(&self) -> bool {
+        matches!(
+            self,
+            Command::Flush(_)
+                | Command::SwapDb(_)
+                | Command::Config(_)
+                | Command::SlowLog(_)
+                | Command::Latency(_)
+                | Command::Monitor(_)
+                | Command::Client(_)
+                | Command::Script(_)
+                | Command::Function(_)
+                | Command::Module(_)
+        )
+    }
+
+    /// Commands scripts and module commands may call. Commands changing the
+    /// state of the connection, running other commands or waiting on another
+    /// instance are left out.
+    pub fn is_callable(&self) -> bool {
+        !matches!(
+            self,
+            Command::Monitor(_)
+                | Command::Subscribe(_)
+                | Command::Unsubscribe(_)
+                | Command::Hello(_)
+                | Command::Client(_)
+                | Command::Eval(_)
+                | Command::FCall(_)
+                | Command::Script(_)
+                | Command::Function(_)
+                | Command::Multi(_)
+                | Command::Exec(_)
+                | Command::Discard(_)
+                | Command::Migrate(_)
+        )
+    }
+
+    /// Commands that may grow the dataset, refused while over `maxmemory`
+    /// with nothing left to evict.
+    pub fn is_denied_when_oom(&self) -> bool {
+        match self {
+            Command::BitField(cmd) => !cmd.is_read_only(),
+            Command::ModuleCommand(cmd) => cmd.is_denied_when_oom(),
+            cmd => matches!(
+                cmd,
+                Command::Set(_)
+                    | Command::SetBit(_)
+                    | Command::BitOp(_)
+                    | Command::PfAdd(_)
+                    | Command::PfMerge(_)
+                    | Command::Copy(_)
+                    | Command::Restore(_)
+            ),
+        }
+    }
+
+    /// Commands a client may still send once subscribed to channels or
+    /// patterns.
+    pub fn is_allowed_when_subscribed(&self) -> bool {
+        matches!(
+            self,
+            Command::Ping(_)
+                | Command::Subscribe(_)
+                | Command::Unsubscribe(_)
+                | Command::Hello(_)
+        )
+    }
+
+    /// Commands other clients may still send while a script is busy, see
+    /// `scripting`.
+    pub fn is_allowed_when_busy(&self) -> bool {
+        matches!(
+            self,
+            Command::Script(ScriptCmd::Kill) | Command::Function(FunctionCmd::Kill)
+        )
+    }
+
+    /// `CLIENT CACHING`, whose answer applies to the command after it.
+    pub fn is_client_caching(&self) -> bool {
+        matches!(self, Command::Client(ClientCmd::Caching(_)))
+    }
+
+    /// Keys read by the command, which clients tracking keys may cache.
+    fn read_keys(&self) -> &[Bytes] {
+        match self {
+            Command::Get(cmd) => cmd.keys(),
+            Command::GetBit(cmd) => cmd.keys(),
+            Command::BitCount(cmd) => cmd.keys(),
+            Command::BitPos(cmd) => cmd.keys(),
+            Command::BitField(cmd) => cmd.keys(),
+            Command::PfCount(cmd) => cmd.keys(),
+            Command::Touch(cmd) => cmd.keys(),
+            Command::Ttl(cmd) => cmd.keys(),
+            Command::Type(cmd) => cmd.keys(),
+            Command::Strlen(cmd) => cmd.keys(),
+            Command::Dump(cmd) => cmd.keys(),
+            _ => &[],
+        }
+    }
+}
+
+/// Whether `name` is a command of miniredis itself, which modules may not
+/// register again.
+pub(crate) fn is_builtin(name: &str) -> bool {
+    // Parsing without arguments tells built-in commands, failing for lack of
+    // arguments or not, apart from unknown ones.
+    let mut parse = match Parse::new(Frame::Array(vec![])) {
+        Ok(parse) => parse,
+        Err(_) => return false,
+    };
+
+    !matches!(
+        Command::parse_command(name, &mut parse, &Modules::default()),
+        Ok(Command::Unknown(_))
+    )
+}
+
+/// Turns an error into the reply sent to the client.
+///
+/// Errors raised by commands already carry a Redis error code (`ERR`,
+/// `WRONGTYPE`, ...), anything else is reported as a generic `ERR`.
+pub fn error_frame(err: &crate::Error) -> Frame {
+    let msg = err.to_string();
+
+    if msg.starts_with(|c: char| c.is_ascii_uppercase()) {
+        Frame::Error(msg)
+    } else {
+        Frame::Error(format!("ERR {}", msg))
+    }
+}
+
+/// Running out of arguments while parsing a command means the client sent the
+/// wrong number of them.
+fn arity_error(command_name: &str, err: crate::Error) -> crate::Error {
+    match err.downcast_ref::<ParseError>() {
+        Some(ParseError::EndOfStream) => format!(
+            "ERR wrong number of arguments for '{}' command",
+            command_name
+        )
+        .into(),
+        _ => err,
+    }
+}
@@ -0,0 +1,91 @@
+use bytes::Bytes;
+use std::sync::Arc;
+
+use crate::cmd::config::unknown_subcommand;
+use crate::cmd::error_frame;
+use crate::module::{Context, ModuleCommandSpec, Modules};
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+use crate::session::Session;
+use crate::state::ServerState;
+
+/// A command registered by a module, see `module::Registry::command`.
+#[derive(Debug)]
+pub struct ModuleCommand {
+    spec: Arc<ModuleCommandSpec>,
+    args: Vec<Bytes>,
+}
+
+/// Lists the modules the server was started with.
+///
+/// Modules are registered in process, `MODULE LOAD` and `MODULE UNLOAD` are
+/// not supported.
+#[derive(Debug)]
+pub enum ModuleCmd {
+    /// `MODULE LIST`
+    List,
+}
+
+impl ModuleCommand {
+    /// The command name has already been consumed.
+    pub(crate) fn parse_frames(
+        spec: Arc<ModuleCommandSpec>,
+        parse: &mut Parse,
+    ) -> crate::Result<ModuleCommand> {
+        let mut args = Vec::with_capacity(parse.remaining());
+        while !parse.is_empty() {
+            args.push(parse.next_bytes()?);
+        }
+
+        Ok(ModuleCommand { spec, args })
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        &self.spec.name
+    }
+
+    pub(crate) fn is_write(&self) -> bool {
+        self.spec.flags.write
+    }
+
+    pub(crate) fn is_denied_when_oom(&self) -> bool {
+        self.spec.flags.deny_oom
+    }
+
+    pub(crate) fn apply(self, state: &ServerState, session: &mut Session) -> Frame {
+        let mut ctx = Context::new(state, session);
+        match self.spec.call(&mut ctx, self.args) {
+            Ok(frame) => frame,
+            Err(err) => error_frame(&err),
+        }
+    }
+}
+
+impl ModuleCmd {
+    /// The `MODULE` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<ModuleCmd> {
+        let sub_command = parse.next_string()?.to_lowercase();
+
+        match &sub_command[..] {
+            "list" => Ok(ModuleCmd::List),
+            _ => Err(unknown_subcommand(&sub_command, "MODULE").into()),
+        }
+    }
+
+    pub(crate) fn apply(self, modules: &Modules) -> Frame {
+        match self {
+            ModuleCmd::List => Frame::Array(
+                modules
+                    .list()
+                    .iter()
+                    .map(|(name, version)| {
+                        Frame::Map(vec![
+                            (Frame::Bulk("name".into()), Frame::Bulk(name.clone().into())),
+                            (Frame::Bulk("ver".into()), Frame::Integer(*version)),
+                        ])
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
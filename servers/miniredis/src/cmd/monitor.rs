@@ -0,0 +1,26 @@
+use crate::monitor::Monitors;
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+use crate::session::Session;
+
+/// Streams every command processed by the server back to this connection.
+///
+/// The connection keeps accepting commands, the monitored lines are written
+/// in between replies.
+#[derive(Debug)]
+pub struct Monitor;
+
+impl Monitor {
+    /// The `MONITOR` string has already been consumed.
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Monitor> {
+        Ok(Monitor)
+    }
+
+    pub(crate) fn apply(self, monitors: &Monitors, session: &mut Session) -> Frame {
+        if session.monitor.is_none() {
+            session.monitor = Some(monitors.subscribe());
+        }
+
+        Frame::ok()
+    }
+}
@@ -0,0 +1,87 @@
+use bytes::Bytes;
+
+use crate::cmd::config::unknown_subcommand;
+use crate::db::Db;
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+
+/// Inspects how the value of a key is stored.
+#[derive(Debug)]
+pub enum Object {
+    /// `OBJECT ENCODING key`, see `Value::encoding`.
+    Encoding(Bytes),
+    /// `OBJECT REFCOUNT key`, always 1: values are not shared between keys.
+    RefCount(Bytes),
+}
+
+/// `MEMORY USAGE key [SAMPLES count]`, the approximate number of bytes the
+/// key and its value take, as counted towards `maxmemory`.
+///
+/// `SAMPLES` is accepted for compatibility, sizes are exact for every type.
+#[derive(Debug)]
+pub struct Memory {
+    key: Bytes,
+}
+
+impl Object {
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        match self {
+            Object::Encoding(key) | Object::RefCount(key) => std::slice::from_ref(key),
+        }
+    }
+
+    /// The `OBJECT` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Object> {
+        let sub_command = parse.next_string()?.to_lowercase();
+
+        match &sub_command[..] {
+            "encoding" => Ok(Object::Encoding(parse.next_bytes()?)),
+            "refcount" => Ok(Object::RefCount(parse.next_bytes()?)),
+            _ => Err(unknown_subcommand(&sub_command, "OBJECT").into()),
+        }
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        match self {
+            Object::Encoding(key) => match db.get_entry(&key) {
+                Some(entry) => Frame::Bulk(entry.value.encoding().into()),
+                None => Frame::Null,
+            },
+            Object::RefCount(key) if db.contains_key(&key) => Frame::Integer(1),
+            Object::RefCount(_) => Frame::Null,
+        }
+    }
+}
+
+impl Memory {
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        std::slice::from_ref(&self.key)
+    }
+
+    /// The `MEMORY` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Memory> {
+        let sub_command = parse.next_string()?.to_lowercase();
+        if sub_command != "usage" {
+            return Err(unknown_subcommand(&sub_command, "MEMORY").into());
+        }
+
+        let key = parse.next_bytes()?;
+        while !parse.is_empty() {
+            match &parse.next_string()?.to_uppercase()[..] {
+                "SAMPLES" => {
+                    parse.next_int()?;
+                }
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+
+        Ok(Memory { key })
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        match db.memory_usage(&self.key) {
+            Some(size) => Frame::Integer(size as i64),
+            None => Frame::Null,
+        }
+    }
+}
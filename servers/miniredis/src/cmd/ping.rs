@@ -0,0 +1,39 @@
+use bytes::Bytes;
+
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+use crate::session::Session;
+
+/// Returns PONG if no argument is provided, otherwise return a copy of the
+/// argument as a bulk.
+///
+/// Subscribed RESP2 clients get the pub/sub reply format,
+/// `["pong", message]`.
+#[derive(Debug, Default)]
+pub struct Ping {
+    msg: Option<Bytes>,
+}
+
+impl Ping {
+    /// The `PING` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Ping> {
+        let msg = if parse.is_empty() {
+            None
+        } else {
+            Some(parse.next_bytes()?)
+        };
+
+        Ok(Ping { msg })
+    }
+
+    pub(crate) fn apply(self, session: &Session) -> Frame {
+        match (self.msg, session.is_subscribed() && !session.outbox.is_resp3()) {
+            (None, false) => Frame::Simple("PONG".to_string()),
+            (Some(msg), false) => Frame::Bulk(msg),
+            (msg, true) => Frame::Array(vec![
+                Frame::Bulk("pong".into()),
+                Frame::Bulk(msg.unwrap_or_default()),
+            ]),
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use bytes::Bytes;
+
+use crate::cmd::config::unknown_subcommand;
+use crate::config::ClientClass;
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+use crate::session::Session;
+use crate::state::ServerState;
+
+/// Posts a message to the given channel, replying with the number of
+/// subscriptions that received it.
+#[derive(Debug)]
+pub struct Publish {
+    channel: Bytes,
+    message: Bytes,
+}
+
+/// Subscribes the client to channels, or with `PSUBSCRIBE` to glob-style
+/// patterns of channels.
+///
+/// One confirmation is sent per channel, through the client's outbox so they
+/// stay ordered with the messages that follow.
+#[derive(Debug)]
+pub struct Subscribe {
+    channels: Vec<Bytes>,
+    pattern: bool,
+}
+
+/// Unsubscribes the client from channels or patterns, or from all of them when
+/// none are given.
+#[derive(Debug)]
+pub struct Unsubscribe {
+    channels: Vec<Bytes>,
+    pattern: bool,
+}
+
+/// Introspects the pub/sub subsystem.
+#[derive(Debug)]
+pub enum PubSubCmd {
+    /// `PUBSUB CHANNELS [pattern]`
+    Channels(Option<Bytes>),
+    /// `PUBSUB NUMSUB [channel ...]`
+    NumSub(Vec<Bytes>),
+    /// `PUBSUB NUMPAT`
+    NumPat,
+}
+
+impl Publish {
+    /// The `PUBLISH` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Publish> {
+        let channel = parse.next_bytes()?;
+        let message = parse.next_bytes()?;
+
+        Ok(Publish { channel, message })
+    }
+
+    pub(crate) fn apply(self, state: &ServerState) -> Frame {
+        let limit = state.config.client_output_buffer_limit(ClientClass::PubSub);
+        let receivers = state.pubsub.publish(&self.channel, &self.message, limit);
+
+        Frame::Integer(receivers as i64)
+    }
+}
+
+impl Subscribe {
+    /// The `SUBSCRIBE` or `PSUBSCRIBE` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse, pattern: bool) -> crate::Result<Subscribe> {
+        let mut channels = vec![parse.next_bytes()?];
+        while !parse.is_empty() {
+            channels.push(parse.next_bytes()?);
+        }
+
+        Ok(Subscribe { channels, pattern })
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        if self.pattern {
+            "psubscribe"
+        } else {
+            "subscribe"
+        }
+    }
+
+    pub(crate) fn apply(self, state: &ServerState, session: &mut Session) {
+        let limit = state.config.client_output_buffer_limit(ClientClass::PubSub);
+        let name = self.get_name().to_string();
+
+        for channel in self.channels {
+            if self.pattern {
+                if session.patterns.insert(channel.clone()) {
+                    state.pubsub.psubscribe(channel.clone(), session.id, &session.outbox);
+                }
+            } else if session.channels.insert(channel.clone()) {
+                state.pubsub.subscribe(channel.clone(), session.id, &session.outbox);
+            }
+
+            let confirmation = confirmation(&name, Frame::Bulk(channel), session);
+            session.outbox.push(&confirmation, limit);
+        }
+    }
+}
+
+impl Unsubscribe {
+    /// The `UNSUBSCRIBE` or `PUNSUBSCRIBE` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse, pattern: bool) -> crate::Result<Unsubscribe> {
+        let mut channels = vec![];
+        while !parse.is_empty() {
+            channels.push(parse.next_bytes()?);
+        }
+
+        Ok(Unsubscribe { channels, pattern })
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        if self.pattern {
+            "punsubscribe"
+        } else {
+            "unsubscribe"
+        }
+    }
+
+    pub(crate) fn apply(self, state: &ServerState, session: &mut Session) {
+        let limit = state.config.client_output_buffer_limit(ClientClass::PubSub);
+        let name = self.get_name().to_string();
+
+        let channels = match self.channels.is_empty() {
+            true if self.pattern => session.patterns.iter().cloned().collect(),
+            true => session.channels.iter().cloned().collect(),
+            false => self.channels,
+        };
+
+        // Unsubscribing from everything while subscribed to nothing still
+        // gets a confirmation.
+        if channels.is_empty() {
+            session.outbox.push(&confirmation(&name, Frame::Null, session), limit);
+        }
+
+        for channel in channels {
+            if self.pattern {
+                session.patterns.remove(&channel);
+                state.pubsub.punsubscribe(&channel, session.id);
+            } else {
+                session.channels.remove(&channel);
+                state.pubsub.unsubscribe(&channel, session.id);
+            }
+
+            session.outbox.push(&confirmation(&name, Frame::Bulk(channel), session), limit);
+        }
+    }
+}
+
+impl PubSubCmd {
+    /// The `PUBSUB` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<PubSubCmd> {
+        let sub_command = parse.next_string()?.to_lowercase();
+
+        match &sub_command[..] {
+            "channels" if parse.is_empty() => Ok(PubSubCmd::Channels(None)),
+            "channels" => Ok(PubSubCmd::Channels(Some(parse.next_bytes()?))),
+            "numsub" => {
+                let mut channels = vec![];
+                while !parse.is_empty() {
+                    channels.push(parse.next_bytes()?);
+                }
+
+                Ok(PubSubCmd::NumSub(channels))
+            }
+            "numpat" => Ok(PubSubCmd::NumPat),
+            _ => Err(unknown_subcommand(&sub_command, "PUBSUB").into()),
+        }
+    }
+
+    pub(crate) fn apply(self, state: &ServerState) -> Frame {
+        match self {
+            PubSubCmd::Channels(pattern) => Frame::Array(
+                state
+                    .pubsub
+                    .channels(pattern.as_deref())
+                    .into_iter()
+                    .map(Frame::Bulk)
+                    .collect(),
+            ),
+            PubSubCmd::NumSub(channels) => {
+                let mut reply = Frame::array();
+                for channel in channels {
+                    let count = state.pubsub.num_subscribers(&channel);
+                    reply.push_bulk(channel);
+                    reply.push_int(count as i64);
+                }
+
+                reply
+            }
+            PubSubCmd::NumPat => Frame::Integer(state.pubsub.num_patterns() as i64),
+        }
+    }
+}
+
+/// Drops every subscription of a client that is going away.
+pub(crate) fn unsubscribe_all(state: &ServerState, session: &mut Session) {
+    for channel in session.channels.drain() {
+        state.pubsub.unsubscribe(&channel, session.id);
+    }
+
+    for pattern in session.patterns.drain() {
+        state.pubsub.punsubscribe(&pattern, session.id);
+    }
+}
+
+fn confirmation(kind: &str, channel: Frame, session: &Session) -> Frame {
+    Frame::Push(vec![
+        Frame::Bulk(Bytes::copy_from_slice(kind.as_bytes())),
+        channel,
+        Frame::Integer(session.subscriptions() as i64),
+    ])
+}
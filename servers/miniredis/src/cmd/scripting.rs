@@ -0,0 +1,362 @@
+use bytes::Bytes;
+
+use crate::cmd::config::unknown_subcommand;
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+use crate::scripting::{self, Scripts};
+use crate::session::Session;
+use crate::state::ServerState;
+
+/// Runs a Lua script with the given keys and arguments, available to it as
+/// `KEYS` and `ARGV`.
+///
+/// Covers `EVAL`, `EVALSHA` running a script loaded before, and their `_RO`
+/// variants refusing write commands.
+#[derive(Debug)]
+pub struct Eval {
+    /// The script, or its SHA1 for `EVALSHA`.
+    script: Bytes,
+    sha: bool,
+    read_only: bool,
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+}
+
+/// Calls a function loaded with `FUNCTION LOAD`, with the given keys and
+/// arguments. `FCALL_RO` only calls functions flagged `no-writes`.
+#[derive(Debug)]
+pub struct FCall {
+    function: String,
+    read_only: bool,
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+}
+
+/// Manages the script cache.
+#[derive(Debug)]
+pub enum ScriptCmd {
+    /// `SCRIPT LOAD script`
+    Load(Bytes),
+    /// `SCRIPT EXISTS sha1 [sha1 ...]`
+    Exists(Vec<String>),
+    /// `SCRIPT FLUSH [ASYNC|SYNC]`
+    Flush,
+    /// `SCRIPT KILL`
+    Kill,
+}
+
+/// Manages function libraries.
+#[derive(Debug)]
+pub enum FunctionCmd {
+    /// `FUNCTION LOAD [REPLACE] code`
+    Load { code: Bytes, replace: bool },
+    /// `FUNCTION DELETE library`
+    Delete(String),
+    /// `FUNCTION FLUSH [ASYNC|SYNC]`
+    Flush,
+    /// `FUNCTION LIST [LIBRARYNAME pattern] [WITHCODE]`
+    List {
+        pattern: Option<String>,
+        with_code: bool,
+    },
+    /// `FUNCTION KILL`
+    Kill,
+}
+
+impl Eval {
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    /// The `EVAL`, `EVALSHA`, `EVAL_RO` or `EVALSHA_RO` string has already
+    /// been consumed.
+    pub(crate) fn parse_frames(
+        parse: &mut Parse,
+        sha: bool,
+        read_only: bool,
+    ) -> crate::Result<Eval> {
+        let script = parse.next_bytes()?;
+        let (keys, args) = parse_keys_and_args(parse)?;
+
+        Ok(Eval {
+            script,
+            sha,
+            read_only,
+            keys,
+            args,
+        })
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        match (self.sha, self.read_only) {
+            (false, false) => "eval",
+            (true, false) => "evalsha",
+            (false, true) => "eval_ro",
+            (true, true) => "evalsha_ro",
+        }
+    }
+
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// The caller holds the script's keys exclusively, so the commands
+    /// called by the script are not interleaved with other clients'
+    /// commands on them.
+    pub(crate) fn apply(self, state: &ServerState, session: &mut Session) -> Frame {
+        let (sha, body) = if self.sha {
+            match state.scripts.get(&String::from_utf8_lossy(&self.script)) {
+                Some(body) => (String::from_utf8_lossy(&self.script).to_lowercase(), body),
+                None => {
+                    return Frame::Error(
+                        "NOSCRIPT No matching script. Please use EVAL.".to_string(),
+                    )
+                }
+            }
+        } else {
+            // Scripts run with `EVAL` may be run again with `EVALSHA`.
+            (state.scripts.load(self.script.clone()), self.script)
+        };
+
+        scripting::eval(
+            state,
+            session,
+            &body,
+            &sha,
+            self.keys,
+            self.args,
+            self.read_only,
+        )
+    }
+}
+
+impl FCall {
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    /// The `FCALL` or `FCALL_RO` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse, read_only: bool) -> crate::Result<FCall> {
+        let function = parse.next_string()?;
+        let (keys, args) = parse_keys_and_args(parse)?;
+
+        Ok(FCall {
+            function,
+            read_only,
+            keys,
+            args,
+        })
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        if self.read_only {
+            "fcall_ro"
+        } else {
+            "fcall"
+        }
+    }
+
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// The caller holds the function's keys exclusively, as for `EVAL`.
+    pub(crate) fn apply(self, state: &ServerState, session: &mut Session) -> Frame {
+        let library = match state.scripts.function(&self.function) {
+            Some(library) => library,
+            None => return Frame::Error("ERR Function not found".to_string()),
+        };
+        let function = library.function(&self.function).unwrap();
+
+        if self.read_only && !function.is_read_only() {
+            return Frame::Error(
+                "ERR Can not execute a script with write flag using *_ro command.".to_string(),
+            );
+        }
+
+        scripting::fcall(
+            state,
+            session,
+            &library,
+            function,
+            self.keys,
+            self.args,
+            self.read_only,
+        )
+    }
+}
+
+impl ScriptCmd {
+    /// The `SCRIPT` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<ScriptCmd> {
+        let sub_command = parse.next_string()?.to_lowercase();
+
+        match &sub_command[..] {
+            "load" => Ok(ScriptCmd::Load(parse.next_bytes()?)),
+            "exists" => {
+                let mut shas = vec![parse.next_string()?];
+                while !parse.is_empty() {
+                    shas.push(parse.next_string()?);
+                }
+
+                Ok(ScriptCmd::Exists(shas))
+            }
+            "flush" => {
+                parse_flush_mode(parse)?;
+                Ok(ScriptCmd::Flush)
+            }
+            "kill" => Ok(ScriptCmd::Kill),
+            _ => Err(unknown_subcommand(&sub_command, "SCRIPT").into()),
+        }
+    }
+
+    pub(crate) fn apply(self, scripts: &Scripts) -> Frame {
+        match self {
+            ScriptCmd::Load(body) => Frame::Bulk(scripts.load(body).into()),
+            ScriptCmd::Exists(shas) => Frame::Array(
+                shas.iter()
+                    .map(|sha| Frame::Integer(scripts.contains(sha) as i64))
+                    .collect(),
+            ),
+            ScriptCmd::Flush => {
+                scripts.flush();
+                Frame::ok()
+            }
+            ScriptCmd::Kill => scripts.kill(false),
+        }
+    }
+}
+
+impl FunctionCmd {
+    /// The `FUNCTION` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<FunctionCmd> {
+        let sub_command = parse.next_string()?.to_lowercase();
+
+        match &sub_command[..] {
+            "load" => {
+                let mut arg = parse.next_bytes()?;
+                let replace = arg.eq_ignore_ascii_case(b"replace") && !parse.is_empty();
+                if replace {
+                    arg = parse.next_bytes()?;
+                }
+
+                Ok(FunctionCmd::Load { code: arg, replace })
+            }
+            "delete" => Ok(FunctionCmd::Delete(parse.next_string()?)),
+            "flush" => {
+                parse_flush_mode(parse)?;
+                Ok(FunctionCmd::Flush)
+            }
+            "list" => {
+                let mut pattern = None;
+                let mut with_code = false;
+                while !parse.is_empty() {
+                    match &parse.next_string()?.to_uppercase()[..] {
+                        "LIBRARYNAME" => pattern = Some(parse.next_string()?),
+                        "WITHCODE" => with_code = true,
+                        option => return Err(format!("ERR Unknown argument {}", option).into()),
+                    }
+                }
+
+                Ok(FunctionCmd::List { pattern, with_code })
+            }
+            "kill" => Ok(FunctionCmd::Kill),
+            _ => Err(unknown_subcommand(&sub_command, "FUNCTION").into()),
+        }
+    }
+
+    pub(crate) fn apply(self, scripts: &Scripts) -> Frame {
+        match self {
+            FunctionCmd::Load { code, replace } => match scripts.load_library(code, replace) {
+                Ok(name) => Frame::Bulk(name.into()),
+                Err(err) => Frame::Error(err.to_string()),
+            },
+            FunctionCmd::Delete(name) => {
+                if scripts.delete_library(&name) {
+                    Frame::ok()
+                } else {
+                    Frame::Error("ERR Library not found".to_string())
+                }
+            }
+            FunctionCmd::Flush => {
+                scripts.flush_libraries();
+                Frame::ok()
+            }
+            FunctionCmd::List { pattern, with_code } => Frame::Array(
+                scripts
+                    .libraries(pattern.as_deref())
+                    .into_iter()
+                    .map(|library| {
+                        let functions = library
+                            .functions
+                            .into_iter()
+                            .map(|function| {
+                                let flags = function
+                                    .flags
+                                    .into_iter()
+                                    .map(|flag| Frame::Bulk(flag.into()))
+                                    .collect();
+                                Frame::Map(vec![
+                                    field("name", Frame::Bulk(function.name.into())),
+                                    field("description", Frame::Null),
+                                    field("flags", Frame::Array(flags)),
+                                ])
+                            })
+                            .collect();
+
+                        let mut fields = vec![
+                            field("library_name", Frame::Bulk(library.name.into())),
+                            field("engine", Frame::Bulk("LUA".into())),
+                            field("functions", Frame::Array(functions)),
+                        ];
+                        if with_code {
+                            fields.push(field("library_code", Frame::Bulk(library.code)));
+                        }
+                        Frame::Map(fields)
+                    })
+                    .collect(),
+            ),
+            FunctionCmd::Kill => scripts.kill(true),
+        }
+    }
+}
+
+/// Parses `numkeys key [key ...] arg [arg ...]`.
+fn parse_keys_and_args(parse: &mut Parse) -> crate::Result<(Vec<Bytes>, Vec<Bytes>)> {
+    let numkeys = parse.next_int()?;
+    if numkeys < 0 {
+        return Err("ERR Number of keys can't be negative".into());
+    }
+    if numkeys as usize > parse.remaining() {
+        return Err("ERR Number of keys can't be greater than number of args".into());
+    }
+
+    let mut keys = Vec::with_capacity(numkeys as usize);
+    for _ in 0..numkeys {
+        keys.push(parse.next_bytes()?);
+    }
+
+    let mut args = vec![];
+    while !parse.is_empty() {
+        args.push(parse.next_bytes()?);
+    }
+
+    Ok((keys, args))
+}
+
+/// Flushing is always synchronous, the `ASYNC` and `SYNC` modes are
+/// accepted for compatibility.
+fn parse_flush_mode(parse: &mut Parse) -> crate::Result<()> {
+    if !parse.is_empty() {
+        match &parse.next_string()?.to_uppercase()[..] {
+            "ASYNC" | "SYNC" => {}
+            _ => return Err("ERR syntax error".into()),
+        }
+    }
+
+    Ok(())
+}
+
+fn field(name: &'static str, value: Frame) -> (Frame, Frame) {
+    (Frame::Bulk(name.into()), value)
+}
@@ -0,0 +1,164 @@
+use bytes::Bytes;
+
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+use crate::db::{self, Db, Entry, Value, WrongType};
+use crate::notify::EventClass;
+
+/// Set `key` to hold the string `value`.
+///
+/// If `key` already holds a value, it is overwritten and its time to live is
+/// discarded, unless `KEEPTTL` is given.
+#[derive(Debug)]
+pub struct Set {
+    key: Bytes,
+    value: Bytes,
+    expire: Option<SetExpire>,
+    condition: Option<Condition>,
+    get: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SetExpire {
+    /// Milliseconds from now.
+    After(u64),
+    /// Unix time in milliseconds.
+    At(u64),
+    Keep,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Condition {
+    /// Only set keys that do not exist.
+    Nx,
+    /// Only set keys that already exist.
+    Xx,
+}
+
+impl Set {
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        std::slice::from_ref(&self.key)
+    }
+
+    pub fn new(key: impl Into<Bytes>, value: Bytes) -> Set {
+        Set {
+            key: key.into(),
+            value,
+            expire: None,
+            condition: None,
+            get: false,
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn value(&self) -> &Bytes {
+        &self.value
+    }
+
+    /// The `SET` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Set> {
+        let key = parse.next_bytes()?;
+        let value = parse.next_bytes()?;
+        let mut set = Set::new(key, value);
+
+        while !parse.is_empty() {
+            let option = parse.next_string()?.to_uppercase();
+            let (expire, condition) = match &option[..] {
+                "NX" => (None, Some(Condition::Nx)),
+                "XX" => (None, Some(Condition::Xx)),
+                "GET" if !set.get => {
+                    set.get = true;
+                    continue;
+                }
+                "KEEPTTL" => (Some(SetExpire::Keep), None),
+                "EX" | "PX" | "EXAT" | "PXAT" => {
+                    let millis = next_expire_millis(parse, &option)?;
+                    match &option[..] {
+                        "EX" | "PX" => (Some(SetExpire::After(millis)), None),
+                        _ => (Some(SetExpire::At(millis)), None),
+                    }
+                }
+                _ => return Err("ERR syntax error".into()),
+            };
+
+            // Each kind of option may only be given once.
+            if (expire.is_some() && set.expire.is_some())
+                || (condition.is_some() && set.condition.is_some())
+            {
+                return Err("ERR syntax error".into());
+            }
+            set.expire = set.expire.or(expire);
+            set.condition = set.condition.or(condition);
+        }
+
+        Ok(set)
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        let expires_at = match self.expire {
+            Some(SetExpire::After(millis)) => Some(db::now_ms().saturating_add(millis)),
+            Some(SetExpire::At(at)) => Some(at),
+            _ => None,
+        };
+
+        let result = db.update_entry(&self.key, |mut slot| {
+            // With `GET` the previous value has to be a string, or nothing
+            // is set.
+            let previous = match slot.as_ref().map(|entry| &entry.value) {
+                Some(Value::Module(_)) if self.get => return Err(WrongType),
+                value => value.and_then(Value::as_string),
+            };
+            let allowed = match self.condition {
+                Some(Condition::Nx) => slot.is_none(),
+                Some(Condition::Xx) => slot.is_some(),
+                None => true,
+            };
+
+            if allowed {
+                let expires_at = match self.expire {
+                    Some(SetExpire::Keep) => slot.as_ref().and_then(|entry| entry.expires_at),
+                    _ => expires_at,
+                };
+                let mut entry = Entry::new(Value::String(self.value));
+                entry.expires_at = expires_at;
+                *slot = Some(entry);
+            }
+            Ok((allowed, previous))
+        });
+
+        let (written, previous) = match result {
+            Ok(result) => result,
+            Err(err) => return Frame::Error(err.to_string()),
+        };
+
+        if written {
+            db.notify(EventClass::String, "set", &self.key);
+            if expires_at.is_some() {
+                db.notify(EventClass::Generic, "expire", &self.key);
+            }
+        }
+
+        match (self.get, written) {
+            (true, _) => previous.map_or(Frame::Null, Frame::Bulk),
+            (false, true) => Frame::ok(),
+            (false, false) => Frame::Null,
+        }
+    }
+}
+
+/// Reads the time given to `EX`, `PX`, `EXAT` or `PXAT`, in milliseconds.
+fn next_expire_millis(parse: &mut Parse, option: &str) -> crate::Result<u64> {
+    const INVALID: &str = "ERR invalid expire time in 'set' command";
+
+    let time = parse.next_int()?;
+    let scale = if option.starts_with('E') { 1000 } else { 1 };
+
+    u64::try_from(time)
+        .ok()
+        .filter(|&time| time > 0)
+        .and_then(|time| time.checked_mul(scale))
+        .ok_or_else(|| INVALID.into())
+}
@@ -0,0 +1,50 @@
+use crate::cmd::config::unknown_subcommand;
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+use crate::slowlog;
+
+/// Number of entries `SLOWLOG GET` returns without an explicit count.
+const DEFAULT_COUNT: usize = 10;
+
+/// Reads or resets the slowlog.
+#[derive(Debug)]
+pub enum SlowLog {
+    /// `SLOWLOG GET [count]`, a negative count returns every entry.
+    Get(Option<i64>),
+    Len,
+    Reset,
+}
+
+impl SlowLog {
+    /// The `SLOWLOG` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SlowLog> {
+        let sub_command = parse.next_string()?.to_lowercase();
+
+        match &sub_command[..] {
+            "get" if parse.is_empty() => Ok(SlowLog::Get(None)),
+            "get" => Ok(SlowLog::Get(Some(parse.next_int()?))),
+            "len" => Ok(SlowLog::Len),
+            "reset" => Ok(SlowLog::Reset),
+            _ => Err(unknown_subcommand(&sub_command, "SLOWLOG").into()),
+        }
+    }
+
+    pub(crate) fn apply(self, log: &slowlog::SlowLog) -> Frame {
+        match self {
+            SlowLog::Get(count) => {
+                let count = match count {
+                    None => DEFAULT_COUNT,
+                    Some(count) if count < 0 => usize::MAX,
+                    Some(count) => count as usize,
+                };
+
+                Frame::Array(log.frames(count))
+            }
+            SlowLog::Len => Frame::Integer(log.len() as i64),
+            SlowLog::Reset => {
+                log.reset();
+                Frame::ok()
+            }
+        }
+    }
+}
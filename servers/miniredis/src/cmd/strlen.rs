@@ -0,0 +1,31 @@
+use bytes::Bytes;
+
+use crate::db::Db;
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+
+/// Returns the length of the string stored at `key`, 0 if it is absent.
+#[derive(Debug)]
+pub struct Strlen {
+    key: Bytes,
+}
+
+impl Strlen {
+    /// The `STRLEN` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Strlen> {
+        let key = parse.next_bytes()?;
+
+        Ok(Strlen { key })
+    }
+
+    pub(crate) fn keys(&self) -> &[Bytes] {
+        std::slice::from_ref(&self.key)
+    }
+
+    pub(crate) fn apply(self, db: &Db) -> Frame {
+        match db.get(&self.key) {
+            Ok(value) => Frame::Integer(value.map_or(0, |value| value.len()) as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+}
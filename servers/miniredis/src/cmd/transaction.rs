@@ -0,0 +1,77 @@
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+use crate::session::{Session, Transaction};
+use crate::state::ServerState;
+
+/// Marks the start of a transaction. Later commands are queued until `EXEC`.
+#[derive(Debug)]
+pub struct Multi;
+
+/// Runs the queued commands of a transaction, without any other command
+/// running in between, and replies with an array of their replies.
+#[derive(Debug)]
+pub struct Exec;
+
+/// Throws away the queued commands of a transaction.
+#[derive(Debug)]
+pub struct Discard;
+
+impl Multi {
+    /// The `MULTI` string has already been consumed.
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Multi> {
+        Ok(Multi)
+    }
+
+    pub(crate) fn apply(self, session: &mut Session) -> Frame {
+        if session.transaction.is_some() {
+            return Frame::Error("ERR MULTI calls can not be nested".to_string());
+        }
+
+        session.transaction = Some(Transaction::default());
+        Frame::ok()
+    }
+}
+
+impl Exec {
+    /// The `EXEC` string has already been consumed.
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Exec> {
+        Ok(Exec)
+    }
+
+    /// The caller holds the server's exclusive lock, so the queued commands
+    /// are not interleaved with other clients' commands.
+    pub(crate) fn apply(self, state: &ServerState, session: &mut Session) -> Frame {
+        let transaction = match session.transaction.take() {
+            Some(transaction) => transaction,
+            None => return Frame::Error("ERR EXEC without MULTI".to_string()),
+        };
+
+        if transaction.failed {
+            return Frame::Error(
+                "EXECABORT Transaction discarded because of previous errors.".to_string(),
+            );
+        }
+
+        Frame::Array(
+            transaction
+                .commands
+                .into_iter()
+                .map(|cmd| cmd.apply(state, session).unwrap_or(Frame::Null))
+                .collect(),
+        )
+    }
+}
+
+impl Discard {
+    /// The `DISCARD` string has already been consumed.
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Discard> {
+        Ok(Discard)
+    }
+
+    pub(crate) fn apply(self, session: &mut Session) -> Frame {
+        match session.transaction.take() {
+            Some(_) => Frame::ok(),
+            None => Frame::Error("ERR DISCARD without MULTI".to_string()),
+        }
+    }
+}
@@ -0,0 +1,442 @@
+use std::sync::atomic::{AtomicI64, AtomicU32, AtomicU64, AtomicU8, Ordering};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::db::DEFAULT_DATABASES;
+use crate::logging::LogLevel;
+use crate::notify;
+use crate::pattern;
+
+/// Runtime configuration, readable and writable with `CONFIG GET`/`CONFIG SET`
+/// or set on the command line as `--name value`.
+///
+/// Values are atomics so connections can read them on every command without
+/// taking a lock.
+#[derive(Debug)]
+pub struct Config {
+    /// Commands running for at least this many microseconds are logged to the
+    /// slowlog. Negative disables the slowlog, 0 logs every command.
+    slowlog_log_slower_than: AtomicI64,
+    /// Number of entries kept in the slowlog.
+    slowlog_max_len: AtomicU64,
+    /// Events lasting at least this many milliseconds are sampled by the
+    /// latency monitor, 0 disables it.
+    latency_monitor_threshold: AtomicU64,
+    /// Connections accepted at once, later ones are refused.
+    maxclients: AtomicU64,
+    /// Seconds a client may stay idle before being disconnected, 0 disables
+    /// the timeout. Subscribers and monitors are never timed out.
+    timeout: AtomicU64,
+    /// Seconds between TCP keepalive probes of idle connections, 0 disables
+    /// them. Only applies to connections accepted afterwards.
+    tcp_keepalive: AtomicU64,
+    /// Bytes of unparsed input a client may send before being disconnected.
+    client_query_buffer_limit: AtomicU64,
+    /// Output buffer limits of each class of client.
+    client_output_buffer_limit: RwLock<[BufferLimit; 2]>,
+    /// Classes of keyspace events published to subscribers, see `notify`.
+    notify_keyspace_events: AtomicU32,
+    /// Bytes of keys and values the databases may hold before keys are
+    /// evicted, 0 for no limit.
+    maxmemory: AtomicU64,
+    /// How keys are picked for eviction once over `maxmemory`.
+    maxmemory_policy: RwLock<EvictionPolicy>,
+    /// Milliseconds a script may run before other clients get `BUSY`
+    /// replies and it may be killed. Also known as `lua-time-limit`.
+    busy_reply_threshold: AtomicU64,
+    /// Least important messages written to the server log, see `logging`.
+    loglevel: AtomicU8,
+    /// Origin allowed to call the HTTP gateway from a browser, `*` for any.
+    /// Empty, the default, sends no CORS headers so pages served from
+    /// elsewhere cannot read replies or send JSON.
+    http_cors_allow_origin: RwLock<String>,
+}
+
+/// Kinds of client that get their own output buffer limit.
+///
+/// There is no replica class: Raft members only send each other requests
+/// of bounded size and wait for the replies, nothing is queued for them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClientClass {
+    /// Clients in the regular request/response mode.
+    Normal,
+    /// Clients subscribed to channels or patterns.
+    PubSub,
+}
+
+/// Keys evicted to get back under `maxmemory`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EvictionPolicy {
+    /// Nothing is evicted, commands that would grow the dataset fail.
+    NoEviction,
+    /// Any key, picked at random.
+    AllKeysRandom,
+    /// Keys with a time to live, the closest to expiring first.
+    VolatileTtl,
+}
+
+/// Limit on the data queued for a client that it has not read yet.
+///
+/// A client is disconnected as soon as it passes the `hard` limit, or after
+/// staying over the `soft` limit for `soft_seconds`. Zero disables a limit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BufferLimit {
+    pub hard: u64,
+    pub soft: u64,
+    pub soft_seconds: u64,
+}
+
+impl Config {
+    /// Names of every parameter, in the order `CONFIG GET *` lists them.
+    const NAMES: &'static [&'static str] = &[
+        "slowlog-log-slower-than",
+        "slowlog-max-len",
+        "latency-monitor-threshold",
+        "maxclients",
+        "timeout",
+        "tcp-keepalive",
+        "client-query-buffer-limit",
+        "client-output-buffer-limit",
+        "notify-keyspace-events",
+        "maxmemory",
+        "maxmemory-policy",
+        "busy-reply-threshold",
+        "loglevel",
+        "http-cors-allow-origin",
+        "databases",
+        // The former name of `busy-reply-threshold`.
+        "lua-time-limit",
+    ];
+
+    pub fn slowlog_log_slower_than(&self) -> i64 {
+        self.slowlog_log_slower_than.load(Ordering::Relaxed)
+    }
+
+    pub fn slowlog_max_len(&self) -> usize {
+        self.slowlog_max_len.load(Ordering::Relaxed) as usize
+    }
+
+    pub fn latency_monitor_threshold(&self) -> u64 {
+        self.latency_monitor_threshold.load(Ordering::Relaxed)
+    }
+
+    pub fn maxclients(&self) -> usize {
+        self.maxclients.load(Ordering::Relaxed) as usize
+    }
+
+    /// Idle timeout, `None` when disabled.
+    pub fn timeout(&self) -> Option<Duration> {
+        match self.timeout.load(Ordering::Relaxed) {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    /// Keepalive interval, `None` when disabled.
+    pub fn tcp_keepalive(&self) -> Option<Duration> {
+        match self.tcp_keepalive.load(Ordering::Relaxed) {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    pub fn client_query_buffer_limit(&self) -> usize {
+        self.client_query_buffer_limit.load(Ordering::Relaxed) as usize
+    }
+
+    pub fn client_output_buffer_limit(&self, class: ClientClass) -> BufferLimit {
+        self.client_output_buffer_limit.read().unwrap()[class as usize]
+    }
+
+    /// Flags of the keyspace event classes to publish.
+    pub fn notify_keyspace_events(&self) -> u32 {
+        self.notify_keyspace_events.load(Ordering::Relaxed)
+    }
+
+    pub fn maxmemory(&self) -> usize {
+        self.maxmemory.load(Ordering::Relaxed) as usize
+    }
+
+    pub fn maxmemory_policy(&self) -> EvictionPolicy {
+        *self.maxmemory_policy.read().unwrap()
+    }
+
+    pub fn busy_reply_threshold(&self) -> Duration {
+        Duration::from_millis(self.busy_reply_threshold.load(Ordering::Relaxed))
+    }
+
+    pub fn loglevel(&self) -> LogLevel {
+        LogLevel::from_index(self.loglevel.load(Ordering::Relaxed)).unwrap_or_default()
+    }
+
+    /// Origin sent in `Access-Control-Allow-Origin`, `None` when CORS is off.
+    pub fn http_cors_allow_origin(&self) -> Option<String> {
+        let origin = self.http_cors_allow_origin.read().unwrap();
+        Some(origin.clone()).filter(|origin| !origin.is_empty())
+    }
+
+    /// Returns the value of the parameter `name`, `None` if there is no such
+    /// parameter.
+    pub fn get(&self, name: &str) -> Option<String> {
+        let value = match name {
+            "slowlog-log-slower-than" => self.slowlog_log_slower_than().to_string(),
+            "slowlog-max-len" => self.slowlog_max_len().to_string(),
+            "latency-monitor-threshold" => self.latency_monitor_threshold().to_string(),
+            "maxclients" => self.maxclients().to_string(),
+            "timeout" => self.timeout.load(Ordering::Relaxed).to_string(),
+            "tcp-keepalive" => self.tcp_keepalive.load(Ordering::Relaxed).to_string(),
+            "client-query-buffer-limit" => self.client_query_buffer_limit().to_string(),
+            "client-output-buffer-limit" => {
+                let limits = self.client_output_buffer_limit.read().unwrap();
+                [ClientClass::Normal, ClientClass::PubSub]
+                    .iter()
+                    .map(|&class| {
+                        let limit = limits[class as usize];
+                        format!(
+                            "{} {} {} {}",
+                            class.name(),
+                            limit.hard,
+                            limit.soft,
+                            limit.soft_seconds
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+            "notify-keyspace-events" => notify::format_flags(self.notify_keyspace_events()),
+            "maxmemory" => self.maxmemory().to_string(),
+            "maxmemory-policy" => self.maxmemory_policy().name().to_string(),
+            "busy-reply-threshold" | "lua-time-limit" => {
+                self.busy_reply_threshold.load(Ordering::Relaxed).to_string()
+            }
+            "loglevel" => self.loglevel().name().to_string(),
+            "http-cors-allow-origin" => self.http_cors_allow_origin.read().unwrap().clone(),
+            "databases" => DEFAULT_DATABASES.to_string(),
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    /// Returns every parameter whose name matches the glob-style `pattern`,
+    /// with its value.
+    pub fn get_matching(&self, pattern: &str) -> Vec<(&'static str, String)> {
+        Self::NAMES
+            .iter()
+            .filter(|name| pattern::matches(pattern.as_bytes(), name.as_bytes()))
+            .filter_map(|name| self.get(name).map(|value| (*name, value)))
+            .collect()
+    }
+
+    /// Updates the parameter `name`, the error is the reply sent back to the
+    /// client.
+    pub fn set(&self, name: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("ERR Invalid argument '{}' for CONFIG SET '{}'", value, name);
+
+        match name {
+            "slowlog-log-slower-than" => {
+                let value = value.parse().map_err(|_| invalid())?;
+                self.slowlog_log_slower_than.store(value, Ordering::Relaxed);
+            }
+            "slowlog-max-len" => {
+                let value = value.parse().map_err(|_| invalid())?;
+                self.slowlog_max_len.store(value, Ordering::Relaxed);
+            }
+            "latency-monitor-threshold" => {
+                let value = value.parse().map_err(|_| invalid())?;
+                self.latency_monitor_threshold.store(value, Ordering::Relaxed);
+            }
+            "maxclients" => {
+                let value = value.parse().ok().filter(|&n| n > 0).ok_or_else(invalid)?;
+                self.maxclients.store(value, Ordering::Relaxed);
+            }
+            "timeout" => {
+                let value = value.parse().map_err(|_| invalid())?;
+                self.timeout.store(value, Ordering::Relaxed);
+            }
+            "tcp-keepalive" => {
+                let value = value.parse().map_err(|_| invalid())?;
+                self.tcp_keepalive.store(value, Ordering::Relaxed);
+            }
+            "client-query-buffer-limit" => {
+                let value = parse_memory(value).ok_or_else(invalid)?;
+                self.client_query_buffer_limit.store(value, Ordering::Relaxed);
+            }
+            "client-output-buffer-limit" => {
+                let updates = parse_output_buffer_limits(value).ok_or_else(invalid)?;
+                let mut limits = self.client_output_buffer_limit.write().unwrap();
+                for (class, limit) in updates {
+                    limits[class as usize] = limit;
+                }
+            }
+            "notify-keyspace-events" => {
+                let value = notify::parse_flags(value).ok_or_else(invalid)?;
+                self.notify_keyspace_events.store(value, Ordering::Relaxed);
+            }
+            "maxmemory" => {
+                let value = parse_memory(value).ok_or_else(invalid)?;
+                self.maxmemory.store(value, Ordering::Relaxed);
+            }
+            "maxmemory-policy" => {
+                let value = EvictionPolicy::from_name(&value.to_lowercase()).ok_or_else(invalid)?;
+                *self.maxmemory_policy.write().unwrap() = value;
+            }
+            "busy-reply-threshold" | "lua-time-limit" => {
+                let value = value.parse().map_err(|_| invalid())?;
+                self.busy_reply_threshold.store(value, Ordering::Relaxed);
+            }
+            "loglevel" => {
+                let value = LogLevel::from_name(&value.to_lowercase()).ok_or_else(invalid)?;
+                self.loglevel.store(value as u8, Ordering::Relaxed);
+            }
+            "http-cors-allow-origin" => {
+                // The value ends up in a response header.
+                if value.bytes().any(|b| b.is_ascii_control()) {
+                    return Err(invalid());
+                }
+                *self.http_cors_allow_origin.write().unwrap() = value.to_string();
+            }
+            // Only reported, every server has the same number of databases.
+            "databases" => {
+                return Err(format!(
+                    "ERR CONFIG SET failed (possibly related to argument '{}') - can't set immutable config",
+                    name
+                ))
+            }
+            _ => return Err(format!("ERR Unknown option or number of arguments for CONFIG SET - '{}'", name)),
+        }
+
+        Ok(())
+    }
+
+    /// Applies `--name value` pairs, as passed on the command line.
+    pub fn apply_args(&self, args: impl IntoIterator<Item = String>) -> Result<(), String> {
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let name = match arg.strip_prefix("--") {
+                Some(name) => name.to_lowercase(),
+                None => return Err(format!("unexpected argument '{}'", arg)),
+            };
+
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for '--{}'", name))?;
+            self.set(&name, &value)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    /// The Redis defaults.
+    fn default() -> Config {
+        const MB: u64 = 1024 * 1024;
+
+        Config {
+            slowlog_log_slower_than: AtomicI64::new(10_000),
+            slowlog_max_len: AtomicU64::new(128),
+            latency_monitor_threshold: AtomicU64::new(0),
+            maxclients: AtomicU64::new(10_000),
+            timeout: AtomicU64::new(0),
+            tcp_keepalive: AtomicU64::new(300),
+            client_query_buffer_limit: AtomicU64::new(1024 * MB),
+            client_output_buffer_limit: RwLock::new([
+                BufferLimit {
+                    hard: 0,
+                    soft: 0,
+                    soft_seconds: 0,
+                },
+                BufferLimit {
+                    hard: 32 * MB,
+                    soft: 8 * MB,
+                    soft_seconds: 60,
+                },
+            ]),
+            notify_keyspace_events: AtomicU32::new(0),
+            maxmemory: AtomicU64::new(0),
+            maxmemory_policy: RwLock::new(EvictionPolicy::NoEviction),
+            busy_reply_threshold: AtomicU64::new(5000),
+            loglevel: AtomicU8::new(LogLevel::Notice as u8),
+            http_cors_allow_origin: RwLock::new(String::new()),
+        }
+    }
+}
+
+impl ClientClass {
+    fn name(&self) -> &'static str {
+        match self {
+            ClientClass::Normal => "normal",
+            ClientClass::PubSub => "pubsub",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<ClientClass> {
+        match name {
+            "normal" => Some(ClientClass::Normal),
+            "pubsub" => Some(ClientClass::PubSub),
+            _ => None,
+        }
+    }
+}
+
+impl EvictionPolicy {
+    fn name(&self) -> &'static str {
+        match self {
+            EvictionPolicy::NoEviction => "noeviction",
+            EvictionPolicy::AllKeysRandom => "allkeys-random",
+            EvictionPolicy::VolatileTtl => "volatile-ttl",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<EvictionPolicy> {
+        match name {
+            "noeviction" => Some(EvictionPolicy::NoEviction),
+            "allkeys-random" => Some(EvictionPolicy::AllKeysRandom),
+            "volatile-ttl" => Some(EvictionPolicy::VolatileTtl),
+            _ => None,
+        }
+    }
+}
+
+/// Parses `<class> <hard> <soft> <soft seconds>` groups, e.g.
+/// `pubsub 32mb 8mb 60`.
+fn parse_output_buffer_limits(value: &str) -> Option<Vec<(ClientClass, BufferLimit)>> {
+    let words: Vec<&str> = value.split_whitespace().collect();
+    if words.is_empty() || !words.len().is_multiple_of(4) {
+        return None;
+    }
+
+    words
+        .chunks(4)
+        .map(|group| {
+            let class = ClientClass::from_name(&group[0].to_lowercase())?;
+            let limit = BufferLimit {
+                hard: parse_memory(group[1])?,
+                soft: parse_memory(group[2])?,
+                soft_seconds: group[3].parse().ok()?,
+            };
+            Some((class, limit))
+        })
+        .collect()
+}
+
+/// Parses a byte count with an optional unit, e.g. `100`, `64kb` or `1gb`.
+fn parse_memory(value: &str) -> Option<u64> {
+    let value = value.to_lowercase();
+    let digits = value.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+
+    let unit = match &value[digits.len()..] {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+
+    digits.parse::<u64>().ok()?.checked_mul(unit)
+}
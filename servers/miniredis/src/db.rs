@@ -0,0 +1,729 @@
+use bytes::Bytes;
+use shared_lib::sharded_db::{ShardModel, ShardedDB};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::EvictionPolicy;
+use crate::module::ModuleValue;
+use crate::notify::{EventClass, Notifier};
+use crate::tracking::Tracking;
+
+/// Number of logical databases, the same default as Redis.
+pub const DEFAULT_DATABASES: usize = 16;
+
+/// Rough bookkeeping cost of a key beyond its name and value, counted towards
+/// `maxmemory`.
+const ENTRY_OVERHEAD: usize = 64;
+
+/// Source of `Entry::cas`, shared by every database so entries moved between
+/// databases keep unique tokens.
+static NEXT_CAS: AtomicU64 = AtomicU64::new(1);
+
+/// A value with its expiration time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub value: Value,
+    /// Unix time in milliseconds at which the key expires, `None` for keys
+    /// without a time to live.
+    pub expires_at: Option<u64>,
+    /// Opaque flags stored along with the value by memcached clients, 0 for
+    /// values written by Redis commands.
+    pub flags: u32,
+    /// Token of the last change to the entry, for memcached `cas`. Assigned
+    /// by the database whenever the entry changes, whatever is set here is
+    /// overwritten.
+    pub cas: u64,
+}
+
+/// What a key holds.
+#[derive(Debug, Clone)]
+pub enum Value {
+    String(Bytes),
+    /// A string holding the decimal form of an integer, stored as the
+    /// integer rather than in a buffer of its own. Strings are turned into
+    /// integers by the database as they are written, see `Value::encode`,
+    /// and read back as strings.
+    Int(i64),
+    /// A value of a type registered by a module, see `module::ValueType`.
+    Module(Arc<dyn ModuleValue>),
+}
+
+/// Error for a string command run against a key holding a module value.
+#[derive(Debug)]
+pub struct WrongType;
+
+/// The slot of a key as handed to an update, noting whether it was written.
+///
+/// Reading goes through `Deref`. Anything borrowing the slot mutably counts
+/// as a write, even if it leaves the same value behind, so an update only
+/// reading the entry should not reach for `as_mut` or `take`.
+#[derive(Debug)]
+pub struct Slot<'a, T> {
+    value: &'a mut Option<T>,
+    written: &'a mut bool,
+}
+
+/// A single logical database, the keyspace selected with `SELECT`.
+///
+/// Keys past their expiration time are removed when next accessed, and in
+/// the background by `expire_cycle`, firing `expired` keyspace events either
+/// way. Changes are accounted for so the server can enforce `maxmemory`, and
+/// reported to clients tracking the keys changed.
+#[derive(Debug)]
+pub struct Db {
+    /// Entries are shared with the readers of `get_entry`, and copied by a
+    /// writer only while one still holds them.
+    entries: Arc<ShardedDB<Bytes, Arc<Entry>>>,
+    /// Keys with a time to live, by expiration time. A key's entry is
+    /// replaced when its deadline changes and dropped along with the key or
+    /// its time to live.
+    deadlines: Mutex<BTreeSet<(u64, Bytes)>>,
+    /// Approximate size of the keys and values, in bytes.
+    used_memory: AtomicUsize,
+    /// Index of the database, as reported in keyspace events. Changes with
+    /// `SWAPDB`.
+    index: AtomicUsize,
+    events: Arc<Notifier>,
+    tracking: Arc<Tracking>,
+}
+
+impl Entry {
+    pub fn new(value: Value) -> Entry {
+        Entry {
+            value,
+            expires_at: None,
+            flags: 0,
+            cas: 0,
+        }
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+/// An empty string, standing in for an entry moved out of its shard for the
+/// duration of `ShardedDB::update`.
+impl Default for Entry {
+    fn default() -> Entry {
+        Entry::new(Value::String(Bytes::new()))
+    }
+}
+
+impl Value {
+    /// Name of the type, as reported by `TYPE`.
+    pub fn type_name(&self) -> &str {
+        match self {
+            Value::String(_) | Value::Int(_) => "string",
+            Value::Module(value) => value.type_name(),
+        }
+    }
+
+    /// Name of the representation in memory, as reported by
+    /// `OBJECT ENCODING`.
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::String(_) | Value::Module(_) => "raw",
+        }
+    }
+
+    /// Approximate size in bytes. Integers are held in the entry itself.
+    pub fn size(&self) -> usize {
+        match self {
+            Value::String(value) => value.len(),
+            Value::Int(_) => 0,
+            Value::Module(value) => value.memory_usage(),
+        }
+    }
+
+    /// The value as a string, `None` for module values.
+    pub fn as_string(&self) -> Option<Bytes> {
+        match self {
+            Value::String(value) => Some(value.clone()),
+            Value::Int(value) => Some(value.to_string().into()),
+            Value::Module(_) => None,
+        }
+    }
+
+    /// Switches strings to the integer encoding when they read back the
+    /// same, as Redis does: `"12"` is stored as an integer, `"012"` and
+    /// `"+12"` are not.
+    fn encode(&mut self) {
+        if let Value::String(value) = self {
+            // The longest integer, `i64::MIN`, takes 20 bytes.
+            if value.len() > 20 {
+                return;
+            }
+            let int = std::str::from_utf8(value)
+                .ok()
+                .and_then(|text| text.parse::<i64>().ok())
+                .filter(|int| int.to_string().as_bytes() == &value[..]);
+            if let Some(int) = int {
+                *self = Value::Int(int);
+            }
+        }
+    }
+}
+
+/// Module values are only equal to themselves, they are not compared by
+/// content. Strings are equal whatever their encoding.
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Module(a), Value::Module(b)) => Arc::ptr_eq(a, b),
+            (Value::Module(_), _) | (_, Value::Module(_)) => false,
+            (a, b) => a.as_string() == b.as_string(),
+        }
+    }
+}
+
+impl<'a, T> Slot<'a, T> {
+    fn new(value: &'a mut Option<T>, written: &'a mut bool) -> Slot<'a, T> {
+        Slot { value, written }
+    }
+}
+
+impl<T> Deref for Slot<'_, T> {
+    type Target = Option<T>;
+
+    fn deref(&self) -> &Option<T> {
+        self.value
+    }
+}
+
+impl<T> DerefMut for Slot<'_, T> {
+    fn deref_mut(&mut self) -> &mut Option<T> {
+        *self.written = true;
+        self.value
+    }
+}
+
+impl fmt::Display for WrongType {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        "WRONGTYPE Operation against a key holding the wrong kind of value".fmt(fmt)
+    }
+}
+
+impl std::error::Error for WrongType {}
+
+impl Db {
+    pub fn new(
+        index: usize,
+        num_shards: usize,
+        shard_model: ShardModel,
+        events: Arc<Notifier>,
+        tracking: Arc<Tracking>,
+    ) -> Arc<Db> {
+        Arc::new(Db {
+            entries: ShardedDB::with_model(num_shards, shard_model),
+            deadlines: Mutex::new(BTreeSet::new()),
+            used_memory: AtomicUsize::new(0),
+            index: AtomicUsize::new(index),
+            events,
+            tracking,
+        })
+    }
+
+    pub fn index(&self) -> usize {
+        self.index.load(Ordering::Relaxed)
+    }
+
+    /// Publishes a keyspace event about `key` of this database.
+    pub fn notify(&self, class: EventClass, event: &str, key: &[u8]) {
+        self.events.notify(class, event, key, self.index());
+    }
+
+    /// Returns the string stored at `key`. Misses fire a `keymiss` event.
+    pub fn get(&self, key: &[u8]) -> Result<Option<Bytes>, WrongType> {
+        match self.with_entry(key, |entry| entry.map(|entry| entry.value.as_string())) {
+            Some(value) => value.map(Some).ok_or(WrongType),
+            None => {
+                self.notify(EventClass::KeyMiss, "keymiss", key);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Returns the value of `key` with its expiration time, the entry as
+    /// stored rather than a copy.
+    pub fn get_entry(&self, key: &[u8]) -> Option<Arc<Entry>> {
+        self.with_stored(key, |entry| entry.cloned())
+    }
+
+    /// Runs `f` against the entry of `key` where it is stored, rather than a
+    /// copy. An expired entry is passed as `None`, then removed.
+    ///
+    /// The shard of `key` stays locked while `f` runs, `f` must not use the
+    /// database.
+    pub fn with_entry<R>(&self, key: &[u8], f: impl FnOnce(Option<&Entry>) -> R) -> R {
+        self.with_stored(key, |entry| f(entry.map(|entry| &**entry)))
+    }
+
+    fn with_stored<R>(&self, key: &[u8], f: impl FnOnce(Option<&Arc<Entry>>) -> R) -> R {
+        let now = now_ms();
+        let (result, expired) = self.entries.with(key, |entry| match entry {
+            Some(entry) if entry.is_expired(now) => (f(None), true),
+            entry => (f(entry), false),
+        });
+        if expired {
+            self.update_entry(key, |_| ());
+        }
+        result
+    }
+
+    /// Stores the string `value` at `key`, discarding any previous value and
+    /// time to live.
+    pub fn insert(&self, key: &[u8], value: Bytes) {
+        self.update_entry(key, |mut slot| *slot = Some(Entry::new(Value::String(value))));
+    }
+
+    /// Removes `key`, returning whether it existed.
+    pub fn remove(&self, key: &[u8]) -> bool {
+        self.update_entry(key, |mut slot| slot.take()).is_some()
+    }
+
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.with_entry(key, |entry| entry.is_some())
+    }
+
+    /// Runs `f` against the string stored at `key`, as `ShardedDB::update`
+    /// does. A key that is kept keeps its time to live and flags.
+    ///
+    /// The entry is left as it was unless `f` writes the slot, so integers
+    /// keep their encoding and the key does not count as changed.
+    pub fn update<R>(
+        &self,
+        key: &[u8],
+        f: impl FnOnce(Slot<Bytes>) -> R,
+    ) -> Result<R, WrongType> {
+        self.update_entry(key, |mut slot| {
+            let mut value = match &*slot {
+                None => None,
+                Some(entry) => match entry.value.as_string() {
+                    Some(value) => Some(value),
+                    None => return Err(WrongType),
+                },
+            };
+
+            let mut written = false;
+            let result = f(Slot::new(&mut value, &mut written));
+            if !written {
+                return Ok(result);
+            }
+
+            let (expires_at, flags) = slot
+                .as_ref()
+                .map_or((None, 0), |entry| (entry.expires_at, entry.flags));
+            *slot = value.map(|value| Entry {
+                value: Value::String(value),
+                expires_at,
+                flags,
+                cas: 0,
+            });
+            Ok(result)
+        })
+    }
+
+    /// Runs `f` against the entry of `key` while holding the shard lock.
+    ///
+    /// An entry past its expiration time is removed before `f` sees the slot.
+    /// The key counts as changed when `f` writes the slot, see `Slot`.
+    pub fn update_entry<R>(&self, key: &[u8], f: impl FnOnce(Slot<Entry>) -> R) -> R {
+        let mut change = Change::default();
+        let result = self.entries.update(key, |slot| {
+            unshared(slot, |slot| {
+                change = self.before(key, slot);
+                let mut written = false;
+                let result = f(Slot::new(slot, &mut written));
+                self.after(key, slot, &mut change, written);
+                result
+            })
+        });
+
+        self.report(key, change);
+        result
+    }
+
+    /// Same as `update_entry` for two distinct keys, with both shards locked
+    /// for the duration of `f`.
+    pub fn update_pair<R>(
+        &self,
+        first: &[u8],
+        second: &[u8],
+        f: impl FnOnce(Slot<Entry>, Slot<Entry>) -> R,
+    ) -> R {
+        let mut first_change = Change::default();
+        let mut second_change = Change::default();
+        let result = self.entries.update_pair(first, second, |a, b| {
+            unshared(a, |a| {
+                unshared(b, |b| {
+                    first_change = self.before(first, a);
+                    second_change = self.before(second, b);
+                    let (mut a_written, mut b_written) = (false, false);
+                    let result = f(Slot::new(a, &mut a_written), Slot::new(b, &mut b_written));
+                    self.after(first, a, &mut first_change, a_written);
+                    self.after(second, b, &mut second_change, b_written);
+                    result
+                })
+            })
+        });
+
+        self.report(first, first_change);
+        self.report(second, second_change);
+        result
+    }
+
+    /// Number of keys, including expired keys not reclaimed yet.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&self) {
+        self.entries.clear();
+        self.deadlines.lock().unwrap().clear();
+        self.used_memory.store(0, Ordering::Relaxed);
+    }
+
+    /// Approximate size of the keys and values, in bytes.
+    pub fn used_memory(&self) -> usize {
+        self.used_memory.load(Ordering::Relaxed)
+    }
+
+    /// Approximate size of `key` and its value, as counted in `used_memory`.
+    pub fn memory_usage(&self, key: &[u8]) -> Option<usize> {
+        self.with_entry(key, |entry| entry.map(|entry| entry_size(key, Some(entry))))
+    }
+
+    /// Returns a random key, or `None` when the database is empty.
+    pub fn random_key(&self) -> Option<Bytes> {
+        // Every expired key picked is removed, so this ends.
+        while let Some(key) = self.entries.random_key() {
+            if self.contains_key(&key) {
+                return Some(key);
+            }
+        }
+
+        None
+    }
+
+    /// Every key, in no particular order.
+    pub fn keys(&self) -> Vec<Bytes> {
+        self.live(self.entries.keys())
+    }
+
+    /// Same as `ShardedDB::scan`, leaving out expired keys.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<Bytes>) {
+        let (cursor, keys) = self.entries.scan(cursor, count);
+        (cursor, self.live(keys))
+    }
+
+    /// Removes up to `limit` keys whose expiration time has passed, returning
+    /// how many deadlines were due.
+    pub fn expire_cycle(&self, limit: usize) -> usize {
+        let now = now_ms();
+        let mut due = vec![];
+        {
+            let mut deadlines = self.deadlines.lock().unwrap();
+            while due.len() < limit {
+                match deadlines.first() {
+                    Some((at, _)) if *at <= now => due.push(deadlines.pop_first().unwrap().1),
+                    _ => break,
+                }
+            }
+        }
+
+        // Keys changed since their deadline was taken out are left alone.
+        for key in &due {
+            self.update_entry(key, |_| ());
+        }
+
+        due.len()
+    }
+
+    /// Removes a key to free memory according to `policy`, firing an
+    /// `evicted` event. Returns `false` when there is no key to evict.
+    pub fn evict(&self, policy: EvictionPolicy) -> bool {
+        let key = match policy {
+            EvictionPolicy::NoEviction => None,
+            EvictionPolicy::AllKeysRandom => self.entries.random_key(),
+            EvictionPolicy::VolatileTtl => self.next_volatile(),
+        };
+
+        let key = match key {
+            Some(key) => key,
+            None => return false,
+        };
+
+        if self.update_entry(&key, |mut slot| slot.take()).is_some() {
+            self.notify(EventClass::Evicted, "evicted", &key);
+        }
+        true
+    }
+
+    /// Key with the closest expiration time, dropping stale deadlines.
+    fn next_volatile(&self) -> Option<Bytes> {
+        loop {
+            let (at, key) = self.deadlines.lock().unwrap().first().cloned()?;
+            let current = self
+                .entries
+                .with(&key, |entry| entry.is_some_and(|entry| entry.expires_at == Some(at)));
+            if current {
+                return Some(key);
+            }
+            self.deadlines.lock().unwrap().remove(&(at, key));
+        }
+    }
+
+    fn live(&self, keys: Vec<Bytes>) -> Vec<Bytes> {
+        keys.into_iter().filter(|key| self.contains_key(key)).collect()
+    }
+
+    /// Drops an expired entry from `slot` and records the state `f` starts
+    /// from.
+    fn before(&self, key: &[u8], slot: &mut Option<Entry>) -> Change {
+        let mut change = Change {
+            size: entry_size(key, slot.as_ref()),
+            expires_at: slot.as_ref().and_then(|entry| entry.expires_at),
+            ..Change::default()
+        };
+
+        if slot.as_ref().is_some_and(|entry| entry.is_expired(now_ms())) {
+            *slot = None;
+            change.expired = true;
+        }
+
+        change.existed = slot.is_some();
+        change
+    }
+
+    /// Accounts for what `f` did to `slot`, `written` telling whether it
+    /// wrote the slot.
+    fn after(&self, key: &[u8], slot: &mut Option<Entry>, change: &mut Change, written: bool) {
+        change.created = !change.existed && slot.is_some();
+        change.modified = change.expired || (written && (change.existed || slot.is_some()));
+
+        // Values are encoded once written, after telling whether they were.
+        if let Some(entry) = slot.as_mut().filter(|_| change.modified) {
+            entry.value.encode();
+            entry.cas = NEXT_CAS.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let size = entry_size(key, slot.as_ref());
+        if size > change.size {
+            self.used_memory.fetch_add(size - change.size, Ordering::Relaxed);
+        } else {
+            self.used_memory.fetch_sub(change.size - size, Ordering::Relaxed);
+        }
+
+        let expires_at = slot.as_ref().and_then(|entry| entry.expires_at);
+        if expires_at != change.expires_at {
+            let mut deadlines = self.deadlines.lock().unwrap();
+            if let Some(at) = change.expires_at {
+                deadlines.remove(&(at, Bytes::copy_from_slice(key)));
+            }
+            if let Some(at) = expires_at {
+                deadlines.insert((at, Bytes::copy_from_slice(key)));
+            }
+        }
+    }
+
+    /// Fires the events of a change, once the shard lock is released.
+    fn report(&self, key: &[u8], change: Change) {
+        if change.modified {
+            self.tracking.invalidate(key);
+        }
+        if change.expired {
+            self.notify(EventClass::Expired, "expired", key);
+        }
+        if change.created {
+            self.notify(EventClass::New, "new", key);
+        }
+    }
+}
+
+/// What happened to a slot during an update.
+#[derive(Debug, Default)]
+struct Change {
+    size: usize,
+    /// Expiration time of the entry `f` was called with, expired or not.
+    expires_at: Option<u64>,
+    existed: bool,
+    expired: bool,
+    created: bool,
+    modified: bool,
+}
+
+/// Runs `f` against the entry of `slot` taken out of its `Arc`, copied only
+/// when a reader still holds it, and stores what `f` leaves in a new one.
+fn unshared<R>(slot: &mut Option<Arc<Entry>>, f: impl FnOnce(&mut Option<Entry>) -> R) -> R {
+    let mut entry = slot.take().map(Arc::unwrap_or_clone);
+    let result = f(&mut entry);
+    *slot = entry.map(Arc::new);
+    result
+}
+
+fn entry_size(key: &[u8], entry: Option<&Entry>) -> usize {
+    entry.map_or(0, |entry| key.len() + entry.value.size() + ENTRY_OVERHEAD)
+}
+
+/// Current Unix time in milliseconds, the clock expiration times use.
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// The logical databases served by miniredis.
+///
+/// Databases are handed out as `Arc`s and looked up by index on every command,
+/// so `SWAPDB` and `FLUSHDB ASYNC` can replace a database wholesale while
+/// other connections keep running.
+#[derive(Debug)]
+pub struct Databases {
+    dbs: RwLock<Vec<Arc<Db>>>,
+    num_shards: usize,
+    shard_model: ShardModel,
+    events: Arc<Notifier>,
+    tracking: Arc<Tracking>,
+}
+
+impl Databases {
+    pub fn new(
+        count: usize,
+        num_shards: usize,
+        shard_model: ShardModel,
+        events: Arc<Notifier>,
+        tracking: Arc<Tracking>,
+    ) -> Databases {
+        let dbs = (0..count)
+            .map(|index| {
+                let events = Arc::clone(&events);
+                Db::new(index, num_shards, shard_model, events, Arc::clone(&tracking))
+            })
+            .collect();
+
+        Databases {
+            dbs: RwLock::new(dbs),
+            num_shards,
+            shard_model,
+            events,
+            tracking,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.dbs.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the database at `index`, which must be in range.
+    pub fn get(&self, index: usize) -> Arc<Db> {
+        Arc::clone(&self.dbs.read().unwrap()[index])
+    }
+
+    /// Exchanges two databases, connections using one see the other's data
+    /// from their next command on.
+    pub fn swap(&self, first: usize, second: usize) {
+        let mut dbs = self.dbs.write().unwrap();
+        dbs.swap(first, second);
+        dbs[first].index.store(first, Ordering::Relaxed);
+        dbs[second].index.store(second, Ordering::Relaxed);
+    }
+
+    /// Removes every key of the database at `index`.
+    ///
+    /// With `lazy` set the database is replaced by an empty one and the old
+    /// contents are dropped on a background thread, so large databases do
+    /// not stall the caller.
+    ///
+    /// Clients tracking keys are told to drop everything they cached.
+    pub fn flush(&self, index: usize, lazy: bool) {
+        self.clear(index, lazy);
+        self.tracking.invalidate_all();
+    }
+
+    pub fn flush_all(&self, lazy: bool) {
+        for index in 0..self.len() {
+            self.clear(index, lazy);
+        }
+        self.tracking.invalidate_all();
+    }
+
+    fn clear(&self, index: usize, lazy: bool) {
+        if lazy {
+            let empty = Db::new(
+                index,
+                self.num_shards,
+                self.shard_model,
+                Arc::clone(&self.events),
+                Arc::clone(&self.tracking),
+            );
+            let old = std::mem::replace(&mut self.dbs.write().unwrap()[index], empty);
+            std::thread::spawn(move || drop(old));
+        } else {
+            self.get(index).clear();
+        }
+    }
+
+    /// Approximate size of the keys and values of every database, in bytes.
+    pub fn used_memory(&self) -> usize {
+        self.dbs.read().unwrap().iter().map(|db| db.used_memory()).sum()
+    }
+
+    /// Runs `Db::expire_cycle` on every database.
+    pub fn expire_cycle(&self, limit: usize) {
+        for index in 0..self.len() {
+            self.get(index).expire_cycle(limit);
+        }
+    }
+
+    /// Evicts keys until the databases use at most `maxmemory` bytes,
+    /// returning `false` if that was not possible.
+    pub fn evict(&self, maxmemory: usize, policy: EvictionPolicy) -> bool {
+        while self.used_memory() > maxmemory {
+            let evicted = (0..self.len()).any(|index| {
+                let db = self.get(index);
+                db.used_memory() > 0 && db.evict(policy)
+            });
+
+            if !evicted {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Runs `f` with the entries of `first_key` in `first` and `second_key` in
+/// `second`, two different databases, holding both shard locks.
+///
+/// The databases are locked in address order, so two commands moving keys in
+/// opposite directions cannot deadlock.
+pub fn update_across<R>(
+    first: &Db,
+    first_key: &[u8],
+    second: &Db,
+    second_key: &[u8],
+    f: impl FnOnce(Slot<Entry>, Slot<Entry>) -> R,
+) -> R {
+    if (first as *const Db) < (second as *const Db) {
+        first.update_entry(first_key, |a| second.update_entry(second_key, |b| f(a, b)))
+    } else {
+        second.update_entry(second_key, |b| first.update_entry(first_key, |a| f(a, b)))
+    }
+}
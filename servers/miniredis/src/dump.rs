@@ -0,0 +1,133 @@
+//! Serialization of single keys for `DUMP`, `RESTORE` and `MIGRATE`.
+//!
+//! A payload holds the value, its expiration time and memcached flags,
+//! followed by a two byte format version and the CRC-64 of everything
+//! before it, as in Redis. The layout itself is specific to miniredis:
+//!
+//! ```text
+//! type name      u32 length + bytes, "string" or a module type
+//! value          u32 length + bytes, the string or `ModuleValue::save`
+//! expires at     u8 0, or u8 1 + u64 Unix time in milliseconds
+//! flags          u32
+//! version        u16
+//! checksum       u64, CRC-64/Jones as used by Redis
+//! ```
+//!
+//! Numbers are big endian apart from the trailer, little endian like Redis.
+
+use bytes::{Buf, BufMut, Bytes};
+
+use crate::db::{Entry, Value};
+use crate::encoding::{get_bytes, get_u32, get_u64, get_u8, put_bytes, Truncated};
+use crate::module::Modules;
+
+/// Version written in payloads. Payloads of later versions are refused.
+pub const DUMP_VERSION: u16 = 1;
+
+/// Error for payloads that are truncated, corrupted or too recent, worded
+/// as in Redis.
+pub const BAD_PAYLOAD: &str = "ERR DUMP payload version or checksum are wrong";
+
+/// Error for payloads whose checksum matches but whose fields do not.
+const BAD_FORMAT: &str = "ERR Bad data format";
+
+/// Table for CRC-64/Jones, reflected.
+const CRC64_TABLE: [u64; 256] = crc64_table();
+
+/// Serializes the value of `entry` with its expiration time and flags.
+pub fn serialize(entry: &Entry) -> Bytes {
+    let mut buf = vec![];
+
+    put_bytes(&mut buf, entry.value.type_name().as_bytes());
+    match &entry.value {
+        Value::String(value) => put_bytes(&mut buf, value),
+        Value::Int(value) => put_bytes(&mut buf, value.to_string().as_bytes()),
+        Value::Module(value) => put_bytes(&mut buf, &value.save()),
+    }
+    match entry.expires_at {
+        Some(at) => {
+            buf.put_u8(1);
+            buf.put_u64(at);
+        }
+        None => buf.put_u8(0),
+    }
+    buf.put_u32(entry.flags);
+
+    buf.put_u16_le(DUMP_VERSION);
+    let checksum = crc64(&buf);
+    buf.put_u64_le(checksum);
+    buf.into()
+}
+
+/// Reads back an entry serialized by `serialize`. Values of module types
+/// are loaded by the module registering the type.
+pub fn deserialize(data: &[u8], modules: &Modules) -> crate::Result<Entry> {
+    // Version and checksum are checked before anything is read.
+    if data.len() < 10 {
+        return Err(BAD_PAYLOAD.into());
+    }
+    let (body, mut checksum) = data.split_at(data.len() - 8);
+    if crc64(body) != checksum.get_u64_le() {
+        return Err(BAD_PAYLOAD.into());
+    }
+    let (mut buf, mut version) = body.split_at(body.len() - 2);
+    if version.get_u16_le() > DUMP_VERSION {
+        return Err(BAD_PAYLOAD.into());
+    }
+
+    read_entry(&mut buf, modules).map_err(|err| match err.downcast::<Truncated>() {
+        Ok(_) => BAD_FORMAT.into(),
+        Err(err) => err,
+    })
+}
+
+/// Reads the fields of a payload, version and checksum aside.
+fn read_entry(buf: &mut &[u8], modules: &Modules) -> crate::Result<Entry> {
+    let type_name = String::from_utf8(get_bytes(buf)?.to_vec())?;
+    let data = get_bytes(buf)?;
+    let value = match &type_name[..] {
+        "string" => Value::String(data),
+        type_name => modules.load_value(type_name, &data)?,
+    };
+
+    let mut entry = Entry::new(value);
+    entry.expires_at = match get_u8(buf)? {
+        0 => None,
+        _ => Some(get_u64(buf)?),
+    };
+    entry.flags = get_u32(buf)?;
+
+    if !buf.is_empty() {
+        return Err(BAD_FORMAT.into());
+    }
+    Ok(entry)
+}
+
+fn crc64(data: &[u8]) -> u64 {
+    data.iter().fold(0, |crc, &b| {
+        CRC64_TABLE[((crc ^ b as u64) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+const fn crc64_table() -> [u64; 256] {
+    // The Jones polynomial, bits reversed.
+    const POLY: u64 = 0x95ac9329ac4bc9b5;
+
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
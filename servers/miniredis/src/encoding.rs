@@ -0,0 +1,73 @@
+//! Fields of the binary formats miniredis writes: `DUMP` payloads, the Raft
+//! log and its snapshots, and the files of tools built on them.
+//!
+//! Numbers are big endian. Byte strings are a u32 length followed by as
+//! many bytes.
+
+use bytes::{Buf, BufMut, Bytes};
+use std::fmt;
+use std::io::{self, Read};
+
+/// Error for data ending in the middle of a field.
+#[derive(Debug)]
+pub struct Truncated;
+
+impl fmt::Display for Truncated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("truncated data")
+    }
+}
+
+impl std::error::Error for Truncated {}
+
+pub fn put_bytes(buf: &mut Vec<u8>, data: &[u8]) {
+    buf.put_u32(data.len() as u32);
+    buf.put_slice(data);
+}
+
+pub fn get_u8(buf: &mut &[u8]) -> Result<u8, Truncated> {
+    check_remaining(buf, 1)?;
+    Ok(buf.get_u8())
+}
+
+pub fn get_u32(buf: &mut &[u8]) -> Result<u32, Truncated> {
+    check_remaining(buf, 4)?;
+    Ok(buf.get_u32())
+}
+
+pub fn get_u64(buf: &mut &[u8]) -> Result<u64, Truncated> {
+    check_remaining(buf, 8)?;
+    Ok(buf.get_u64())
+}
+
+pub fn get_bytes(buf: &mut &[u8]) -> Result<Bytes, Truncated> {
+    let len = get_u32(buf)? as usize;
+    check_remaining(buf, len)?;
+    Ok(buf.copy_to_bytes(len))
+}
+
+fn check_remaining(buf: &[u8], len: usize) -> Result<(), Truncated> {
+    if buf.len() < len {
+        return Err(Truncated);
+    }
+    Ok(())
+}
+
+/// Reads a u32 from a stream.
+pub fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    input.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+/// Reads a byte string from a stream. Only the bytes actually there are
+/// allocated, whatever the length says.
+pub fn read_bytes(input: &mut impl Read) -> io::Result<Bytes> {
+    let len = read_u32(input)? as usize;
+    let mut buf = vec![];
+    input.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(buf.into())
+}
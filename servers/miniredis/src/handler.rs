@@ -0,0 +1,206 @@
+use std::future;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use socket2::{SockRef, TcpKeepalive};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time;
+
+use crate::cmd;
+use crate::config::ClientClass;
+use crate::outbox::Outbox;
+use crate::protocol::connection::Connection;
+use crate::protocol::frame::Frame;
+use crate::session::Session;
+use crate::state::ServerState;
+
+/// Serves a newly accepted connection until the client goes away or is
+/// disconnected.
+///
+/// Connections past `maxclients` are refused with an error.
+pub async fn serve(mut socket: TcpStream, addr: SocketAddr, state: Arc<ServerState>) {
+    let _guard = ClientGuard::new(&state);
+
+    if state.clients.load(Ordering::Relaxed) > state.config.maxclients() {
+        let _ = socket
+            .write_all(b"-ERR max number of clients reached\r\n")
+            .await;
+        return;
+    }
+
+    // Replies are small and written as soon as they are ready, waiting to
+    // coalesce them only adds latency.
+    let _ = socket.set_nodelay(true);
+
+    if let Some(interval) = state.config.tcp_keepalive() {
+        let keepalive = TcpKeepalive::new().with_time(interval);
+        let _ = SockRef::from(&socket).set_tcp_keepalive(&keepalive);
+    }
+
+    let session = Session::new(state.next_client_id(), addr);
+    state.tracking.connect(session.id, &session.outbox);
+    let mut handler = Handler {
+        connection: Connection::new(socket),
+        session,
+        state,
+    };
+
+    // Errors only end the connection, there is no one to report them to.
+    let _ = handler.run().await;
+    cmd::unsubscribe_all(&handler.state, &mut handler.session);
+    handler.state.tracking.disconnect(handler.session.id);
+}
+
+/// Per connection handler, reading commands and writing back their replies
+/// along with pub/sub messages and `MONITOR` output.
+#[derive(Debug)]
+struct Handler {
+    connection: Connection,
+    session: Session,
+    state: Arc<ServerState>,
+}
+
+impl Handler {
+    async fn run(&mut self) -> crate::Result<()> {
+        let outbox = Arc::clone(&self.session.outbox);
+
+        loop {
+            self.connection
+                .set_query_buffer_limit(self.state.config.client_query_buffer_limit());
+            let idle_deadline = self.idle_deadline();
+
+            let frame = tokio::select! {
+                frame = self.connection.read_frame() => frame?,
+                _ = outbox.ready() => {
+                    flush_outbox(&mut self.connection, &outbox).await?;
+                    continue;
+                }
+                line = recv_monitor(&mut self.session) => {
+                    match line {
+                        Ok(line) => self.connection.write_frame(&Frame::Simple(line)).await?,
+                        // A monitor that cannot keep up is disconnected rather
+                        // than silently missing commands.
+                        Err(_) => return Ok(()),
+                    }
+                    continue;
+                }
+                _ = sleep_until(idle_deadline) => return Ok(()),
+            };
+
+            let frame = match frame {
+                Some(frame) => frame,
+                None => return Ok(()),
+            };
+
+            self.session.last_interaction = Instant::now();
+            let response = self.state.dispatch(&mut self.session, frame).await;
+
+            // `HELLO` may have switched protocol, its own reply included.
+            self.connection.set_resp3(outbox.is_resp3());
+
+            // Confirmations queued by subscription commands go out before the
+            // reply, so the client sees them in order.
+            flush_outbox(&mut self.connection, &outbox).await?;
+
+            if let Some(response) = response {
+                self.write_reply(&response).await?;
+            }
+        }
+    }
+
+    /// Writes a command reply, enforcing the output buffer limit of normal
+    /// clients: a reply over the hard limit disconnects the client, one over
+    /// the soft limit must be read within the soft limit's seconds.
+    async fn write_reply(&mut self, response: &Frame) -> crate::Result<()> {
+        let limit = self
+            .state
+            .config
+            .client_output_buffer_limit(ClientClass::Normal);
+        let len = self.connection.encode(response) as u64;
+
+        if limit.hard > 0 && len > limit.hard {
+            return Err("client output buffer limit reached".into());
+        }
+
+        if limit.soft > 0 && len > limit.soft {
+            let seconds = Duration::from_secs(limit.soft_seconds);
+            return match time::timeout(seconds, self.connection.write_encoded()).await {
+                Ok(res) => Ok(res?),
+                Err(_) => Err("client output buffer limit reached".into()),
+            };
+        }
+
+        Ok(self.connection.write_encoded().await?)
+    }
+
+    /// When the client is disconnected for being idle, if ever. Subscribers
+    /// and monitors are expected to stay quiet and never time out.
+    fn idle_deadline(&self) -> Option<Instant> {
+        if self.session.is_subscribed() || self.session.monitor.is_some() {
+            return None;
+        }
+
+        self.state
+            .config
+            .timeout()
+            .map(|timeout| self.session.last_interaction + timeout)
+    }
+}
+
+/// Writes out the frames queued in `outbox`, failing if the client went past
+/// its output buffer limit, including while the write is in progress.
+async fn flush_outbox(connection: &mut Connection, outbox: &Outbox) -> crate::Result<()> {
+    let chunks = match outbox.take() {
+        Ok(chunks) => chunks,
+        Err(_) => return Err("client output buffer limit reached".into()),
+    };
+
+    if chunks.is_empty() {
+        return Ok(());
+    }
+
+    tokio::select! {
+        res = connection.write_bytes(&chunks) => res?,
+        _ = outbox.overflowed() => return Err("client output buffer limit reached".into()),
+    }
+
+    outbox.written(chunks.iter().map(|chunk| chunk.len()).sum());
+    Ok(())
+}
+
+async fn recv_monitor(session: &mut Session) -> Result<String, RecvError> {
+    match &mut session.monitor {
+        Some(monitor) => monitor.recv().await,
+        None => future::pending().await,
+    }
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => time::sleep_until(deadline.into()).await,
+        None => future::pending().await,
+    }
+}
+
+/// Keeps `ServerState::clients` up to date for the life of a connection.
+pub(crate) struct ClientGuard {
+    state: Arc<ServerState>,
+}
+
+impl ClientGuard {
+    pub(crate) fn new(state: &Arc<ServerState>) -> ClientGuard {
+        state.clients.fetch_add(1, Ordering::Relaxed);
+        ClientGuard {
+            state: Arc::clone(state),
+        }
+    }
+}
+
+impl Drop for ClientGuard {
+    fn drop(&mut self) {
+        self.state.clients.fetch_sub(1, Ordering::Relaxed);
+    }
+}
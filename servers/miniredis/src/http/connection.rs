@@ -0,0 +1,250 @@
+use bytes::{Buf, Bytes, BytesMut};
+use std::io;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
+use tokio::net::TcpStream;
+
+/// Largest request line and headers accepted.
+const MAX_HEADER_LEN: usize = 16 * 1024;
+
+/// An HTTP/1.1 request, with its body.
+#[derive(Debug)]
+pub(crate) struct Request {
+    pub(crate) method: String,
+    /// The path of the target, without the query string.
+    pub(crate) path: String,
+    headers: Vec<(String, String)>,
+    /// HTTP/1.0 closes the connection after each request by default.
+    http_1_0: bool,
+    pub(crate) body: Bytes,
+}
+
+/// A response, sent with a `Content-Length`.
+#[derive(Debug)]
+pub(crate) struct Response {
+    pub(crate) status: u16,
+    pub(crate) content_type: &'static str,
+    pub(crate) body: Vec<u8>,
+}
+
+/// A request that cannot be read, answered before closing the connection.
+#[derive(Debug)]
+pub(crate) struct Malformed(pub(crate) u16, pub(crate) &'static str);
+
+/// Reads requests and writes responses on an HTTP connection.
+#[derive(Debug)]
+pub(crate) struct Connection {
+    stream: BufWriter<TcpStream>,
+    buffer: BytesMut,
+    /// Sent as `Access-Control-Allow-Origin`, no CORS headers when `None`.
+    allow_origin: Option<String>,
+}
+
+impl Request {
+    /// Value of the header `name`, case-insensitive.
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| &value[..])
+    }
+
+    /// Whether the client expects the connection to stay open.
+    pub(crate) fn keep_alive(&self) -> bool {
+        match self.header("connection") {
+            Some(value) if value.eq_ignore_ascii_case("close") => false,
+            Some(value) if value.eq_ignore_ascii_case("keep-alive") => true,
+            _ => !self.http_1_0,
+        }
+    }
+}
+
+impl Response {
+    pub(crate) fn json(status: u16, body: &serde_json::Value) -> Response {
+        Response {
+            status,
+            content_type: "application/json",
+            body: body.to_string().into_bytes(),
+        }
+    }
+}
+
+impl Connection {
+    pub(crate) fn new(socket: TcpStream) -> Connection {
+        Connection {
+            stream: BufWriter::new(socket),
+            buffer: BytesMut::with_capacity(4 * 1024),
+            allow_origin: None,
+        }
+    }
+
+    /// Sets the origin allowed to use the gateway from a browser, for the
+    /// responses that follow.
+    pub(crate) fn set_allow_origin(&mut self, origin: Option<String>) {
+        self.allow_origin = origin;
+    }
+
+    pub(crate) fn allows_cors(&self) -> bool {
+        self.allow_origin.is_some()
+    }
+
+    /// Reads the next request, `None` once the client closed the connection.
+    /// Bodies are only read with a `Content-Length` of at most `max_body`.
+    pub(crate) async fn read_request(
+        &mut self,
+        max_body: usize,
+    ) -> crate::Result<Result<Option<Request>, Malformed>> {
+        let end = loop {
+            if let Some(end) = find(&self.buffer, b"\r\n\r\n") {
+                break end;
+            }
+            if self.buffer.len() > MAX_HEADER_LEN {
+                return Ok(Err(Malformed(431, "request headers too large")));
+            }
+            if 0 == self.stream.read_buf(&mut self.buffer).await? {
+                if self.buffer.is_empty() {
+                    return Ok(Ok(None));
+                }
+                return Err("connection reset by peer".into());
+            }
+        };
+
+        let head = self.buffer.split_to(end + 4);
+        let head = match std::str::from_utf8(&head[..end]) {
+            Ok(head) => head,
+            Err(_) => return Ok(Err(Malformed(400, "malformed request"))),
+        };
+        let mut request = match parse_head(head) {
+            Some(request) => request,
+            None => return Ok(Err(Malformed(400, "malformed request"))),
+        };
+
+        if request.header("transfer-encoding").is_some() {
+            return Ok(Err(Malformed(411, "chunked bodies are not supported")));
+        }
+        let len = match request.header("content-length").map(str::parse::<usize>) {
+            None => 0,
+            Some(Ok(len)) if len <= max_body => len,
+            Some(Ok(_)) => return Ok(Err(Malformed(413, "request body too large"))),
+            Some(Err(_)) => return Ok(Err(Malformed(400, "malformed request"))),
+        };
+
+        while self.buffer.len() < len {
+            if 0 == self.stream.read_buf(&mut self.buffer).await? {
+                return Err("connection reset by peer".into());
+            }
+        }
+        request.body = self.buffer.split_to(len).freeze();
+
+        Ok(Ok(Some(request)))
+    }
+
+    /// Writes a complete response, with CORS headers when an origin is
+    /// allowed.
+    pub(crate) async fn write_response(
+        &mut self,
+        response: &Response,
+        keep_alive: bool,
+    ) -> io::Result<()> {
+        let cors = match &self.allow_origin {
+            Some(origin) => format!(
+                "Access-Control-Allow-Origin: {}\r\nAccess-Control-Allow-Methods: GET, POST, OPTIONS\r\nAccess-Control-Allow-Headers: Content-Type\r\n",
+                origin
+            ),
+            None => String::new(),
+        };
+        let head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: {}\r\n\r\n",
+            response.status,
+            reason(response.status),
+            response.content_type,
+            response.body.len(),
+            cors,
+            if keep_alive { "keep-alive" } else { "close" },
+        );
+        self.stream.write_all(head.as_bytes()).await?;
+        self.stream.write_all(&response.body).await?;
+        self.stream.flush().await
+    }
+
+    /// Writes the headers of a response whose body is streamed, ending with
+    /// the connection.
+    pub(crate) async fn write_stream_head(&mut self, content_type: &str) -> io::Result<()> {
+        let cors = match &self.allow_origin {
+            Some(origin) => format!("Access-Control-Allow-Origin: {}\r\n", origin),
+            None => String::new(),
+        };
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nCache-Control: no-cache\r\n{}Connection: close\r\n\r\n",
+            content_type, cors,
+        );
+        self.stream.write_all(head.as_bytes()).await?;
+        self.stream.flush().await
+    }
+
+    /// Writes part of a streamed body.
+    pub(crate) async fn write_chunk(&mut self, chunk: &[u8]) -> io::Result<()> {
+        self.stream.write_all(chunk).await?;
+        self.stream.flush().await
+    }
+
+    /// Completes once the client closed the connection. Anything sent by
+    /// the client meanwhile is discarded.
+    pub(crate) async fn closed(&mut self) -> io::Result<()> {
+        loop {
+            if 0 == self.stream.read_buf(&mut self.buffer).await? {
+                return Ok(());
+            }
+            self.buffer.advance(self.buffer.len());
+        }
+    }
+}
+
+/// Parses the request line and headers.
+fn parse_head(head: &str) -> Option<Request> {
+    let mut lines = head.split("\r\n");
+    let mut parts = lines.next()?.split(' ');
+    let method = parts.next()?.to_string();
+    let target = parts.next()?;
+    let version = parts.next()?;
+    if !version.starts_with("HTTP/1.") || parts.next().is_some() {
+        return None;
+    }
+
+    let mut headers = vec![];
+    for line in lines {
+        let (name, value) = line.split_once(':')?;
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+
+    let path = target.split('?').next()?.to_string();
+    Some(Request {
+        method,
+        path,
+        headers,
+        http_1_0: version == "HTTP/1.0",
+        body: Bytes::new(),
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
@@ -0,0 +1,66 @@
+use bytes::Bytes;
+use serde_json::{json, Map, Value};
+
+use crate::protocol::frame::Frame;
+
+/// Converts a reply to JSON.
+///
+/// Strings that are not UTF-8 are converted lossily. Maps become objects
+/// when their keys are strings, arrays of `[key, value]` pairs otherwise.
+/// Errors nested in a reply, such as those of `EXEC`, become
+/// `{"error": message}`.
+pub(crate) fn from_frame(frame: Frame) -> Value {
+    match frame {
+        Frame::Simple(value) => Value::String(value),
+        Frame::Error(message) => json!({ "error": message }),
+        Frame::Integer(value) => Value::from(value),
+        Frame::Bulk(value) => Value::String(String::from_utf8_lossy(&value).into_owned()),
+        Frame::Null => Value::Null,
+        Frame::Array(frames) | Frame::Push(frames) => {
+            Value::Array(frames.into_iter().map(from_frame).collect())
+        }
+        Frame::Map(pairs) => {
+            if pairs.iter().all(|(key, _)| is_string(key)) {
+                let mut object = Map::new();
+                for (key, value) in pairs {
+                    if let Value::String(key) = from_frame(key) {
+                        object.insert(key, from_frame(value));
+                    }
+                }
+                Value::Object(object)
+            } else {
+                pairs
+                    .into_iter()
+                    .map(|(key, value)| json!([from_frame(key), from_frame(value)]))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Reads a command sent as a JSON array, such as `["SET", "key", 10]`.
+/// Numbers and booleans are taken as their text.
+pub(crate) fn to_command(body: &[u8]) -> Result<Frame, String> {
+    const EXPECTED: &str = "ERR expected a JSON array of strings";
+
+    let args: Vec<Value> = serde_json::from_slice(body).map_err(|_| EXPECTED)?;
+    if args.is_empty() {
+        return Err(EXPECTED.to_string());
+    }
+
+    let args = args
+        .into_iter()
+        .map(|arg| match arg {
+            Value::String(arg) => Ok(Frame::Bulk(Bytes::from(arg))),
+            Value::Number(arg) => Ok(Frame::Bulk(Bytes::from(arg.to_string()))),
+            Value::Bool(arg) => Ok(Frame::Bulk(Bytes::from(arg.to_string()))),
+            _ => Err(EXPECTED.to_string()),
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Frame::Array(args))
+}
+
+fn is_string(frame: &Frame) -> bool {
+    matches!(frame, Frame::Simple(_) | Frame::Bulk(_))
+}
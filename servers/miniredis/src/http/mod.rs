@@ -0,0 +1,291 @@
+//! HTTP/JSON gateway to the command dispatcher, for tools without a RESP
+//! client.
+//!
+//! - `GET /GET/key` runs the command and arguments given as path segments,
+//!   percent-decoded. Only commands that neither write nor administer the
+//!   server are run on `GET`, a link or an image can send one.
+//! - `POST /` runs the command sent as a JSON array, `["SET", "key", "value"]`,
+//!   with `Content-Type: application/json`.
+//! - `GET /SUBSCRIBE/channel...` and `GET /PSUBSCRIBE/pattern...` stream
+//!   messages as Server-Sent Events until the client goes away.
+//!
+//! Replies come back as `{"result": ...}`, error replies as
+//! `{"error": "..."}` with status 400. Each request runs on its own as if
+//! sent by a new client to database 0, so commands that keep state on the
+//! connection, such as `MULTI` or `SUBSCRIBE` outside of streaming, are
+//! refused.
+//!
+//! CORS is off unless `http-cors-allow-origin` is set: browsers then only
+//! let pages from that origin read replies or post commands. Requiring JSON
+//! on `POST` makes browsers check with the server first.
+
+mod connection;
+mod json;
+
+use bytes::Bytes;
+use serde_json::json;
+use std::io::Cursor;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::time;
+
+use crate::cmd::{self, Command};
+use crate::handler::ClientGuard;
+use crate::protocol::frame::Frame;
+use crate::session::Session;
+use crate::state::ServerState;
+use connection::{Connection, Malformed, Request, Response};
+
+/// How often a comment is sent on idle event streams, so proxies do not
+/// time them out.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Serves a connection accepted on the HTTP listener until the client goes
+/// away or asks to close it.
+///
+/// Connections count towards `maxclients` along with Redis ones.
+pub async fn serve(socket: TcpStream, addr: SocketAddr, state: Arc<ServerState>) {
+    let _guard = ClientGuard::new(&state);
+    let mut connection = Connection::new(socket);
+
+    if state.clients.load(Ordering::Relaxed) > state.config.maxclients() {
+        let response = error(503, "ERR max number of clients reached");
+        let _ = connection.write_response(&response, false).await;
+        return;
+    }
+
+    // Errors only end the connection, there is no one to report them to.
+    let _ = run(&mut connection, addr, &state).await;
+}
+
+async fn run(
+    connection: &mut Connection,
+    addr: SocketAddr,
+    state: &Arc<ServerState>,
+) -> crate::Result<()> {
+    let id = state.next_client_id();
+
+    loop {
+        let max_body = state.config.client_query_buffer_limit();
+        connection.set_allow_origin(state.config.http_cors_allow_origin());
+        let request = match connection.read_request(max_body).await? {
+            Ok(Some(request)) => request,
+            Ok(None) => return Ok(()),
+            Err(Malformed(status, message)) => {
+                connection
+                    .write_response(&error(status, message), false)
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        let keep_alive = request.keep_alive();
+        let response = match route(&request, connection.allows_cors()) {
+            Ok(Route::Command(frame, read_only)) => {
+                let mut session = Session::new(id, addr);
+                run_command(state, &mut session, frame, read_only).await
+            }
+            Ok(Route::Subscribe(frame)) => {
+                let mut session = Session::new(id, addr);
+                let res = stream_messages(connection, state, &mut session, frame).await;
+                cmd::unsubscribe_all(state, &mut session);
+                return res;
+            }
+            Ok(Route::Options) => Response {
+                status: 204,
+                content_type: "text/plain",
+                body: vec![],
+            },
+            Err(response) => response,
+        };
+
+        connection.write_response(&response, keep_alive).await?;
+        if !keep_alive {
+            return Ok(());
+        }
+    }
+}
+
+/// What a request asks for.
+enum Route {
+    /// A command, refused if it has effects when `read_only` is set.
+    Command(Frame, bool),
+    /// `SUBSCRIBE` or `PSUBSCRIBE`, streaming messages.
+    Subscribe(Frame),
+    /// A CORS preflight request.
+    Options,
+}
+
+fn route(request: &Request, cors: bool) -> Result<Route, Response> {
+    match &request.method[..] {
+        "GET" => {
+            let args = path_args(&request.path)
+                .ok_or_else(|| error(400, "ERR invalid percent-encoding in path"))?;
+            let name = match args.first() {
+                Some(name) => String::from_utf8_lossy(name).to_lowercase(),
+                None => return Err(error(404, "ERR no command given, try GET /PING")),
+            };
+
+            let frame = Frame::Array(args.into_iter().map(Frame::Bulk).collect());
+            match &name[..] {
+                "subscribe" | "psubscribe" => Ok(Route::Subscribe(frame)),
+                _ => Ok(Route::Command(frame, true)),
+            }
+        }
+        "POST" if request.path != "/" => Err(error(404, "ERR commands are posted to /")),
+        "POST" if !is_json(request) => Err(error(
+            415,
+            "ERR commands are posted as JSON, with Content-Type: application/json",
+        )),
+        "POST" => match json::to_command(&request.body) {
+            Ok(frame) => Ok(Route::Command(frame, false)),
+            Err(message) => Err(error(400, &message)),
+        },
+        "OPTIONS" if cors => Ok(Route::Options),
+        _ => Err(error(405, "ERR method not allowed")),
+    }
+}
+
+/// Runs a command on behalf of a client that only sends this one.
+async fn run_command(
+    state: &ServerState,
+    session: &mut Session,
+    frame: Frame,
+    read_only: bool,
+) -> Response {
+    let cmd = match Command::from_frame(frame.clone(), &state.modules) {
+        Ok(cmd) => cmd,
+        Err(err) => return reply(cmd::error_frame(&err)),
+    };
+
+    if !cmd.is_callable() {
+        return error(
+            403,
+            &format!("ERR '{}' is not available over HTTP", cmd.get_name()),
+        );
+    }
+    // Publishing is no write, but still more than a link should do.
+    let has_effects = cmd.is_write() || cmd.is_admin() || matches!(cmd, Command::Publish(_));
+    if read_only && has_effects {
+        return error(
+            405,
+            &format!("ERR '{}' has effects, send it with POST", cmd.get_name()),
+        );
+    }
+
+    reply(state.dispatch(session, frame).await.unwrap_or(Frame::Null))
+}
+
+/// Subscribes and writes each confirmation and message as an event, named
+/// after its kind, with the rest of it as JSON data:
+///
+/// ```text
+/// event: message
+/// data: ["channel","hello"]
+/// ```
+async fn stream_messages(
+    connection: &mut Connection,
+    state: &ServerState,
+    session: &mut Session,
+    frame: Frame,
+) -> crate::Result<()> {
+    // Messages are queued as RESP3 pushes, decoded again to be sent as JSON.
+    let outbox = Arc::clone(&session.outbox);
+    outbox.set_resp3(true);
+
+    if let Some(Frame::Error(message)) = state.execute(session, frame).await {
+        let response = reply(Frame::Error(message));
+        return Ok(connection.write_response(&response, false).await?);
+    }
+
+    connection.write_stream_head("text/event-stream").await?;
+    let mut keepalive = time::interval(KEEPALIVE_INTERVAL);
+    keepalive.tick().await;
+
+    loop {
+        let chunks = match outbox.take() {
+            Ok(chunks) => chunks,
+            Err(_) => return Err("client output buffer limit reached".into()),
+        };
+
+        for chunk in &chunks {
+            connection.write_chunk(&event(chunk)?).await?;
+        }
+        outbox.written(chunks.iter().map(|chunk| chunk.len()).sum());
+
+        tokio::select! {
+            _ = outbox.ready() => {}
+            _ = keepalive.tick() => connection.write_chunk(b": keepalive\n\n").await?,
+            res = connection.closed() => return Ok(res?),
+        }
+    }
+}
+
+/// Turns a push queued in the outbox into an event.
+fn event(chunk: &Bytes) -> crate::Result<Vec<u8>> {
+    let frame = match Frame::parse(&mut Cursor::new(&chunk[..])) {
+        Ok(frame) => frame,
+        Err(_) => return Err("protocol error; invalid pushed frame".into()),
+    };
+
+    let mut parts = match frame {
+        Frame::Push(parts) | Frame::Array(parts) if !parts.is_empty() => parts,
+        _ => return Err("protocol error; unexpected pushed frame".into()),
+    };
+    let kind = match parts.remove(0) {
+        Frame::Bulk(kind) => String::from_utf8_lossy(&kind).into_owned(),
+        Frame::Simple(kind) => kind,
+        _ => return Err("protocol error; unexpected pushed frame".into()),
+    };
+
+    let data = json::from_frame(Frame::Array(parts));
+    Ok(format!("event: {}\ndata: {}\n\n", kind, data).into_bytes())
+}
+
+/// Whether the body is declared as JSON, parameters such as the charset
+/// aside.
+fn is_json(request: &Request) -> bool {
+    request
+        .header("content-type")
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|media| media.trim().eq_ignore_ascii_case("application/json"))
+}
+
+/// Command arguments from the segments of a path, percent-decoded.
+fn path_args(path: &str) -> Option<Vec<Bytes>> {
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| percent_decode(segment).map(Bytes::from))
+        .collect()
+}
+
+fn percent_decode(segment: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(segment.len());
+    let mut bytes = segment.bytes();
+
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            out.push(b);
+        }
+    }
+
+    Some(out)
+}
+
+fn reply(frame: Frame) -> Response {
+    match frame {
+        Frame::Error(message) => error(400, &message),
+        frame => Response::json(200, &json!({ "result": json::from_frame(frame) })),
+    }
+}
+
+fn error(status: u16, message: &str) -> Response {
+    Response::json(status, &json!({ "error": message }))
+}
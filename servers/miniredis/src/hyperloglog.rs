@@ -0,0 +1,521 @@
+//! HyperLogLog cardinality estimation.
+//!
+//! The serialized form follows the layout Redis uses, so an HLL can be moved
+//! around with `GET`/`SET` like any other string:
+//!
+//! ```text
+//! +------+---+-----+----------+
+//! | HYLL | E | N/U | Cardin.  |
+//! +------+---+-----+----------+
+//! ```
+//!
+//! `E` is the encoding (dense or sparse), followed by three unused bytes and
+//! the cached cardinality as a little endian `u64`. The most significant bit
+//! of the last byte flags the cache as stale.
+//!
+//! The dense encoding packs 16384 6-bit registers. The sparse encoding is a
+//! run length encoding of the registers, which is far smaller while most of
+//! them are still zero:
+//!
+//! * `ZERO`:  `00xxxxxx` - a run of 1 to 64 zero registers.
+//! * `XZERO`: `01xxxxxx yyyyyyyy` - a run of 1 to 16384 zero registers.
+//! * `VAL`:   `1vvvvvxx` - a run of 1 to 4 registers set to 1 to 32.
+//!
+//! An HLL starts sparse and is promoted to dense once a register no longer
+//! fits a `VAL` opcode or the encoding outgrows `SPARSE_MAX_BYTES`.
+
+use bytes::Bytes;
+use std::fmt;
+
+/// Number of bits of the hash used to pick a register.
+const P: u32 = 14;
+/// Number of registers.
+pub const REGISTERS: usize = 1 << P;
+/// Number of bits of the hash used to count leading zeros.
+const Q: u32 = 64 - P;
+
+const REGISTER_BITS: usize = 6;
+const REGISTER_MAX: u8 = (1 << REGISTER_BITS) - 1;
+
+const HEADER_SIZE: usize = 16;
+const DENSE_SIZE: usize = HEADER_SIZE + (REGISTERS * REGISTER_BITS).div_ceil(8);
+const MAGIC: &[u8; 4] = b"HYLL";
+
+const DENSE: u8 = 0;
+const SPARSE: u8 = 1;
+
+/// Largest register value a sparse `VAL` opcode can represent.
+const SPARSE_VAL_MAX: u8 = 32;
+const SPARSE_VAL_MAX_LEN: usize = 4;
+const SPARSE_ZERO_MAX_LEN: usize = 64;
+const SPARSE_XZERO_MAX_LEN: usize = REGISTERS;
+
+/// Sparse HLLs larger than this are promoted to the dense encoding. This is
+/// the Redis default for `hll-sparse-max-bytes`.
+pub const SPARSE_MAX_BYTES: usize = 3000;
+
+const HASH_SEED: u64 = 0xadc8_3b19;
+
+/// In memory view of an HLL, registers are always kept unpacked.
+#[derive(Clone)]
+pub struct HyperLogLog {
+    registers: Box<[u8; REGISTERS]>,
+    dense: bool,
+    cached: Option<u64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The string does not hold an HLL at all.
+    WrongType,
+    /// The string looks like an HLL but its registers cannot be decoded.
+    Corrupted,
+}
+
+impl HyperLogLog {
+    /// Creates an empty HLL, sparse encoded.
+    pub fn new() -> HyperLogLog {
+        HyperLogLog {
+            registers: Box::new([0; REGISTERS]),
+            dense: false,
+            cached: Some(0),
+        }
+    }
+
+    /// Decodes the serialized form of an HLL.
+    pub fn decode(src: &[u8]) -> Result<HyperLogLog, Error> {
+        let header = Header::parse(src)?;
+        let body = &src[HEADER_SIZE..];
+        let mut registers = Box::new([0; REGISTERS]);
+
+        match header.encoding {
+            DENSE => {
+                for (i, register) in registers.iter_mut().enumerate() {
+                    *register = dense_get(body, i);
+                }
+            }
+            _ => sparse_decode(body, &mut registers)?,
+        }
+
+        Ok(HyperLogLog {
+            registers,
+            dense: header.encoding == DENSE,
+            cached: header.cached,
+        })
+    }
+
+    /// Returns the cardinality cached in the header of a serialized HLL,
+    /// without decoding its registers.
+    pub fn cached_count(src: &[u8]) -> Result<Option<u64>, Error> {
+        Ok(Header::parse(src)?.cached)
+    }
+
+    /// Serializes the HLL, using the sparse encoding for as long as it fits.
+    pub fn encode(&self) -> Bytes {
+        let body = if self.dense {
+            None
+        } else {
+            sparse_encode(&self.registers).filter(|body| HEADER_SIZE + body.len() <= SPARSE_MAX_BYTES)
+        };
+
+        let (encoding, body) = match body {
+            Some(body) => (SPARSE, body),
+            None => (DENSE, dense_encode(&self.registers)),
+        };
+
+        let mut out = Vec::with_capacity(HEADER_SIZE + body.len());
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&[encoding, 0, 0, 0]);
+        match self.cached {
+            Some(count) => out.extend_from_slice(&count.to_le_bytes()),
+            None => out.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1 << 7]),
+        }
+        out.extend_from_slice(&body);
+
+        Bytes::from(out)
+    }
+
+    /// Adds an element, returning `true` if a register changed and so the
+    /// estimate may have changed.
+    pub fn add(&mut self, element: &[u8]) -> bool {
+        let hash = murmurhash64a(element, HASH_SEED);
+        let index = (hash as usize) & (REGISTERS - 1);
+
+        // The sentinel bit bounds the count to Q + 1 for a hash of all zeros.
+        let count = ((hash >> P) | (1 << Q)).trailing_zeros() as u8 + 1;
+
+        if count > self.registers[index] {
+            self.set_register(index, count);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Folds `other` into this HLL, which then estimates the union of both.
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        for i in 0..REGISTERS {
+            if other.registers[i] > self.registers[i] {
+                self.set_register(i, other.registers[i]);
+            }
+        }
+
+        if other.dense {
+            self.dense = true;
+        }
+    }
+
+    /// Estimated cardinality, served from the cache when it is still valid.
+    pub fn count(&mut self) -> u64 {
+        if let Some(count) = self.cached {
+            return count;
+        }
+
+        let count = estimate(&self.registers);
+        self.cached = Some(count);
+        count
+    }
+
+    fn set_register(&mut self, index: usize, value: u8) {
+        self.registers[index] = value;
+        self.cached = None;
+
+        if value > SPARSE_VAL_MAX {
+            self.dense = true;
+        }
+    }
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        HyperLogLog::new()
+    }
+}
+
+struct Header {
+    encoding: u8,
+    cached: Option<u64>,
+}
+
+impl Header {
+    fn parse(src: &[u8]) -> Result<Header, Error> {
+        if src.len() < HEADER_SIZE || &src[..4] != MAGIC {
+            return Err(Error::WrongType);
+        }
+
+        let encoding = src[4];
+        match encoding {
+            DENSE if src.len() != DENSE_SIZE => return Err(Error::WrongType),
+            DENSE | SPARSE => {}
+            _ => return Err(Error::WrongType),
+        }
+
+        let cached = if src[15] & (1 << 7) == 0 {
+            Some(u64::from_le_bytes(src[8..16].try_into().unwrap()))
+        } else {
+            None
+        };
+
+        Ok(Header { encoding, cached })
+    }
+}
+
+/// Registers are packed least significant bit first, so a register may
+/// straddle two bytes.
+fn dense_get(body: &[u8], index: usize) -> u8 {
+    let byte = index * REGISTER_BITS / 8;
+    let shift = index * REGISTER_BITS % 8;
+
+    let lo = body[byte] as u16;
+    let hi = body.get(byte + 1).copied().unwrap_or(0) as u16;
+    (((hi << 8 | lo) >> shift) as u8) & REGISTER_MAX
+}
+
+fn dense_encode(registers: &[u8; REGISTERS]) -> Vec<u8> {
+    let mut body = vec![0; DENSE_SIZE - HEADER_SIZE];
+
+    for (index, &value) in registers.iter().enumerate() {
+        let byte = index * REGISTER_BITS / 8;
+        let shift = index * REGISTER_BITS % 8;
+        let bits = (value as u16) << shift;
+
+        body[byte] |= bits as u8;
+        if let Some(next) = body.get_mut(byte + 1) {
+            *next |= (bits >> 8) as u8;
+        }
+    }
+
+    body
+}
+
+fn sparse_decode(body: &[u8], registers: &mut [u8; REGISTERS]) -> Result<(), Error> {
+    let mut index = 0;
+    let mut ops = body.iter();
+
+    while let Some(&op) = ops.next() {
+        let (value, len) = match op >> 6 {
+            0b00 => (0, (op & 0x3f) as usize + 1),
+            0b01 => {
+                let low = *ops.next().ok_or(Error::Corrupted)? as usize;
+                (0, (((op & 0x3f) as usize) << 8 | low) + 1)
+            }
+            _ => (((op >> 2) & 0x1f) + 1, (op & 0x03) as usize + 1),
+        };
+
+        if index + len > REGISTERS {
+            return Err(Error::Corrupted);
+        }
+
+        registers[index..index + len].fill(value);
+        index += len;
+    }
+
+    // Every register has to be covered exactly once.
+    if index != REGISTERS {
+        return Err(Error::Corrupted);
+    }
+
+    Ok(())
+}
+
+/// Returns `None` when a register is too large for the sparse encoding.
+fn sparse_encode(registers: &[u8; REGISTERS]) -> Option<Vec<u8>> {
+    let mut body = vec![];
+    let mut index = 0;
+
+    while index < REGISTERS {
+        let value = registers[index];
+        let run = registers[index..].iter().take_while(|&&r| r == value).count();
+        index += run;
+
+        let mut left = run;
+        while left > 0 {
+            if value == 0 && left > SPARSE_ZERO_MAX_LEN {
+                let len = left.min(SPARSE_XZERO_MAX_LEN);
+                body.push(0x40 | ((len - 1) >> 8) as u8);
+                body.push(((len - 1) & 0xff) as u8);
+                left -= len;
+            } else if value == 0 {
+                body.push((left - 1) as u8);
+                left = 0;
+            } else if value <= SPARSE_VAL_MAX {
+                let len = left.min(SPARSE_VAL_MAX_LEN);
+                body.push(0x80 | (value - 1) << 2 | (len - 1) as u8);
+                left -= len;
+            } else {
+                return None;
+            }
+        }
+    }
+
+    Some(body)
+}
+
+/// Cardinality estimate using Otmar Ertl's improved estimator, which stays
+/// accurate for small cardinalities without the linear counting switch over.
+fn estimate(registers: &[u8; REGISTERS]) -> u64 {
+    let m = REGISTERS as f64;
+    let mut histogram = [0u32; Q as usize + 2];
+    for &register in registers.iter() {
+        histogram[register as usize] += 1;
+    }
+
+    let mut z = m * tau((m - histogram[Q as usize + 1] as f64) / m);
+    for j in (1..=Q as usize).rev() {
+        z += histogram[j] as f64;
+        z *= 0.5;
+    }
+    z += m * sigma(histogram[0] as f64 / m);
+
+    let alpha_inf = 0.5 / std::f64::consts::LN_2;
+    (alpha_inf * m * m / z).round() as u64
+}
+
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+
+    let mut y = 1.0;
+    let mut z = 1.0 - x;
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if previous == z {
+            return z / 3.0;
+        }
+    }
+}
+
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+
+    let mut y = 1.0;
+    let mut z = x;
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if previous == z {
+            return z;
+        }
+    }
+}
+
+/// MurmurHash2, 64 bit version, as used by Redis to hash HLL elements.
+fn murmurhash64a(key: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4_a793_5bd1_e995;
+    const R: u32 = 47;
+
+    let mut h = seed ^ (key.len() as u64).wrapping_mul(M);
+
+    let chunks = key.chunks_exact(8);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().unwrap());
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+
+    if !tail.is_empty() {
+        for (i, &byte) in tail.iter().enumerate() {
+            h ^= (byte as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::WrongType => "WRONGTYPE Key is not a valid HyperLogLog string value.".fmt(fmt),
+            Error::Corrupted => "INVALIDOBJ Corrupted HLL object detected".fmt(fmt),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(elements: impl IntoIterator<Item = u64>) -> HyperLogLog {
+        let mut hll = HyperLogLog::new();
+        for element in elements {
+            hll.add(element.to_string().as_bytes());
+        }
+        hll
+    }
+
+    fn encoding(src: &[u8]) -> u8 {
+        Header::parse(src).unwrap().encoding
+    }
+
+    #[test]
+    fn sparse_and_dense_round_trip() {
+        let mut hll = filled(0..100);
+        let sparse = hll.encode();
+        assert_eq!(encoding(&sparse), SPARSE);
+        assert_eq!(HyperLogLog::decode(&sparse).unwrap().registers, hll.registers);
+
+        hll.dense = true;
+        let dense = hll.encode();
+        assert_eq!(encoding(&dense), DENSE);
+        assert_eq!(dense.len(), DENSE_SIZE);
+        let decoded = HyperLogLog::decode(&dense).unwrap();
+        assert_eq!(decoded.registers, hll.registers);
+        assert!(decoded.dense);
+    }
+
+    #[test]
+    fn large_sparse_encodings_are_promoted() {
+        let mut hll = HyperLogLog::new();
+        let mut element = 0u64;
+        while encoding(&hll.encode()) == SPARSE {
+            assert!(hll.encode().len() <= SPARSE_MAX_BYTES);
+            hll.add(element.to_string().as_bytes());
+            element += 1;
+        }
+
+        // Promoted only once the sparse form would no longer fit.
+        assert!(HEADER_SIZE + sparse_encode(&hll.registers).unwrap().len() > SPARSE_MAX_BYTES);
+        assert_eq!(hll.encode().len(), DENSE_SIZE);
+    }
+
+    #[test]
+    fn registers_past_the_sparse_range_are_promoted() {
+        let mut hll = HyperLogLog::new();
+        hll.set_register(7, SPARSE_VAL_MAX);
+        assert_eq!(encoding(&hll.encode()), SPARSE);
+
+        hll.set_register(7, SPARSE_VAL_MAX + 1);
+        let encoded = hll.encode();
+        assert_eq!(encoding(&encoded), DENSE);
+        assert_eq!(HyperLogLog::decode(&encoded).unwrap().registers[7], SPARSE_VAL_MAX + 1);
+    }
+
+    #[test]
+    fn merging_sparse_and_dense() {
+        let mut sparse = filled(0..500);
+        let mut dense = filled(250..1000);
+        dense.dense = true;
+
+        let mut merged = HyperLogLog::decode(&sparse.encode()).unwrap();
+        merged.merge(&HyperLogLog::decode(&dense.encode()).unwrap());
+        assert_eq!(encoding(&merged.encode()), DENSE);
+        for i in 0..REGISTERS {
+            assert_eq!(merged.registers[i], sparse.registers[i].max(dense.registers[i]));
+        }
+        assert_eq!(merged.count(), filled(0..1000).count());
+
+        // The other way around gives the same registers.
+        dense.merge(&sparse);
+        assert_eq!(dense.registers, merged.registers);
+        sparse.merge(&dense);
+        assert_eq!(sparse.registers, merged.registers);
+    }
+
+    #[test]
+    fn estimates_are_within_two_percent() {
+        for cardinality in [10_000u64, 1_000_000] {
+            let count = filled(0..cardinality).count() as f64;
+            let error = (count - cardinality as f64).abs() / cardinality as f64;
+            assert!(error < 0.02, "{} estimated as {}", cardinality, count);
+        }
+    }
+
+    #[test]
+    fn layout_matches_redis() {
+        // "a" hashes to register 12711 with a count of 2. Redis stores that
+        // as an XZERO of 12711 registers, a VAL of 2 and an XZERO of the
+        // remaining 3672, behind a header flagging the cardinality as stale.
+        let mut hll = HyperLogLog::new();
+        assert!(hll.add(b"a"));
+        let expected: &[u8] = &[
+            b'H', b'Y', b'L', b'L', 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80, 0x71, 0xa6, 0x84, 0x4e,
+            0x57,
+        ];
+        assert_eq!(&hll.encode()[..], expected);
+
+        // An empty HLL is a single XZERO with a cardinality of 0.
+        let empty: &[u8] = &[
+            b'H', b'Y', b'L', b'L', 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x7f, 0xff,
+        ];
+        assert_eq!(&HyperLogLog::new().encode()[..], empty);
+    }
+}
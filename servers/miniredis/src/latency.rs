@@ -0,0 +1,114 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::protocol::frame::Frame;
+use crate::slowlog::unix_time;
+
+/// Number of samples kept per event.
+const HISTORY_LEN: usize = 160;
+
+/// Event recorded for commands exceeding `latency-monitor-threshold`.
+pub const COMMAND_EVENT: &str = "command";
+
+/// Samples latency spikes per event, such as slow commands, for
+/// `LATENCY LATEST` and `LATENCY HISTORY`.
+///
+/// Anything that may stall the server can record its own event through
+/// `LatencyMonitor::record`.
+#[derive(Debug, Default)]
+pub struct LatencyMonitor {
+    events: Mutex<HashMap<String, History>>,
+}
+
+#[derive(Debug, Default)]
+struct History {
+    /// `(unix time, latency in milliseconds)`, oldest first.
+    samples: VecDeque<(u64, u64)>,
+    max: u64,
+}
+
+impl LatencyMonitor {
+    pub fn new() -> LatencyMonitor {
+        LatencyMonitor::default()
+    }
+
+    /// Records a sample for `event` if it reaches `threshold_ms`, a threshold
+    /// of 0 meaning the monitor is disabled.
+    pub fn record(&self, event: &str, duration: Duration, threshold_ms: u64) {
+        let latency = duration.as_millis() as u64;
+        if threshold_ms == 0 || latency < threshold_ms {
+            return;
+        }
+
+        let now = unix_time();
+        let mut events = self.events.lock().unwrap();
+        let history = events.entry(event.to_string()).or_default();
+        history.max = history.max.max(latency);
+
+        // Samples are per second, keeping the worst one.
+        match history.samples.back_mut() {
+            Some((time, sample)) if *time == now => *sample = (*sample).max(latency),
+            _ => {
+                if history.samples.len() == HISTORY_LEN {
+                    history.samples.pop_front();
+                }
+                history.samples.push_back((now, latency));
+            }
+        }
+    }
+
+    /// `LATENCY LATEST` reply: the latest and all time worst sample of every
+    /// event.
+    pub fn latest(&self) -> Vec<Frame> {
+        let events = self.events.lock().unwrap();
+        let mut names: Vec<&String> = events.keys().collect();
+        names.sort();
+
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let history = &events[name];
+                let &(time, latency) = history.samples.back()?;
+                Some(Frame::Array(vec![
+                    Frame::Bulk(name.clone().into()),
+                    Frame::Integer(time as i64),
+                    Frame::Integer(latency as i64),
+                    Frame::Integer(history.max as i64),
+                ]))
+            })
+            .collect()
+    }
+
+    /// `LATENCY HISTORY` reply: every sample of `event`, oldest first.
+    pub fn history(&self, event: &str) -> Vec<Frame> {
+        let events = self.events.lock().unwrap();
+
+        events
+            .get(event)
+            .map(|history| {
+                history
+                    .samples
+                    .iter()
+                    .map(|&(time, latency)| {
+                        Frame::Array(vec![Frame::Integer(time as i64), Frame::Integer(latency as i64)])
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Drops the history of the given events, or of all events when `events`
+    /// is empty. Returns the number of events reset.
+    pub fn reset(&self, events: &[String]) -> usize {
+        let mut all = self.events.lock().unwrap();
+
+        if events.is_empty() {
+            let count = all.len();
+            all.clear();
+            return count;
+        }
+
+        events.iter().filter(|event| all.remove(*event).is_some()).count()
+    }
+}
@@ -0,0 +1,36 @@
+pub mod cmd;
+pub mod config;
+pub mod db;
+pub use db::{Databases, Db};
+
+pub mod dump;
+pub mod encoding;
+pub mod handler;
+pub mod http;
+pub mod hyperloglog;
+pub mod latency;
+pub mod locks;
+pub mod logging;
+pub mod memcached;
+pub mod module;
+pub mod monitor;
+pub mod notify;
+pub mod outbox;
+pub mod pattern;
+pub mod protocol;
+pub mod pubsub;
+pub mod raft;
+pub mod scripting;
+pub mod server;
+pub use server::Server;
+pub mod session;
+pub mod slowlog;
+pub mod state;
+pub mod tracking;
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// A specialized `Result` type for miniredis operations.
+///
+/// This is defined as a convenience.
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,107 @@
+//! Locks keeping transactions and scripts apart from the commands of other
+//! clients on the same keys.
+//!
+//! Keys map onto a fixed set of stripes, whatever their database. Commands
+//! hold the stripes of their keys shared, while `EXEC`, `EVAL` and `FCALL`
+//! hold the stripes of the keys they declare exclusively, so only commands
+//! on those keys wait for them. Commands over the whole keyspace, such as
+//! `KEYS` or `FLUSHALL`, hold every stripe, and commands about the server
+//! or the connection none.
+//!
+//! Stripes are taken in index order, so holders cannot deadlock each other,
+//! and waiting for them does not block the thread.
+
+use bytes::Bytes;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Number of stripes keys are spread over.
+const STRIPES: usize = 256;
+
+/// Keys a command needs to keep others off while it runs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scope {
+    /// The command touches no keys.
+    None,
+    Keys(Vec<Bytes>),
+    /// The command reads or changes any key, e.g. `SCAN` or `FLUSHDB`.
+    All,
+}
+
+#[derive(Debug)]
+pub struct KeyLocks {
+    stripes: Vec<RwLock<()>>,
+}
+
+/// Stripes held until dropped.
+#[derive(Debug)]
+pub struct KeyGuard<'a> {
+    _shared: Vec<RwLockReadGuard<'a, ()>>,
+    _exclusive: Vec<RwLockWriteGuard<'a, ()>>,
+}
+
+impl Scope {
+    /// Keys of both scopes, for a transaction made of several commands.
+    pub fn union(self, other: Scope) -> Scope {
+        match (self, other) {
+            (Scope::All, _) | (_, Scope::All) => Scope::All,
+            (Scope::None, scope) | (scope, Scope::None) => scope,
+            (Scope::Keys(mut keys), Scope::Keys(more)) => {
+                keys.extend(more);
+                Scope::Keys(keys)
+            }
+        }
+    }
+}
+
+impl KeyLocks {
+    pub fn new() -> KeyLocks {
+        KeyLocks {
+            stripes: (0..STRIPES).map(|_| RwLock::new(())).collect(),
+        }
+    }
+
+    /// Waits for the stripes of `scope`, shared or exclusively.
+    pub async fn lock(&self, scope: &Scope, exclusive: bool) -> KeyGuard<'_> {
+        let mut guard = KeyGuard {
+            _shared: vec![],
+            _exclusive: vec![],
+        };
+        for index in self.stripes_of(scope) {
+            let stripe = &self.stripes[index];
+            if exclusive {
+                guard._exclusive.push(stripe.write().await);
+            } else {
+                guard._shared.push(stripe.read().await);
+            }
+        }
+        guard
+    }
+
+    /// Indexes of the stripes of `scope`, in ascending order.
+    fn stripes_of(&self, scope: &Scope) -> Vec<usize> {
+        match scope {
+            Scope::None => vec![],
+            Scope::All => (0..self.stripes.len()).collect(),
+            Scope::Keys(keys) => {
+                let mut indexes: Vec<usize> = keys.iter().map(|key| stripe(key)).collect();
+                indexes.sort_unstable();
+                indexes.dedup();
+                indexes
+            }
+        }
+    }
+}
+
+impl Default for KeyLocks {
+    fn default() -> KeyLocks {
+        KeyLocks::new()
+    }
+}
+
+fn stripe(key: &[u8]) -> usize {
+    let mut s = DefaultHasher::new();
+    key.hash(&mut s);
+    (s.finish() as usize) % STRIPES
+}
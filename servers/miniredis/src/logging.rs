@@ -0,0 +1,79 @@
+//! The server log, written to stderr in the format of Redis.
+//!
+//! Messages below the configured `loglevel` are left out.
+
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+
+/// Importance of a log message, from the least to the most important, as
+/// numbered by `redis.log` levels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Verbose,
+    #[default]
+    Notice,
+    Warning,
+}
+
+impl LogLevel {
+    pub fn from_index(index: u8) -> Option<LogLevel> {
+        match index {
+            0 => Some(LogLevel::Debug),
+            1 => Some(LogLevel::Verbose),
+            2 => Some(LogLevel::Notice),
+            3 => Some(LogLevel::Warning),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Verbose => "verbose",
+            LogLevel::Notice => "notice",
+            LogLevel::Warning => "warning",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<LogLevel> {
+        match name {
+            "debug" => Some(LogLevel::Debug),
+            "verbose" => Some(LogLevel::Verbose),
+            "notice" => Some(LogLevel::Notice),
+            "warning" => Some(LogLevel::Warning),
+            _ => None,
+        }
+    }
+
+    /// Character marking the level in log lines.
+    fn mark(&self) -> char {
+        match self {
+            LogLevel::Debug => '.',
+            LogLevel::Verbose => '-',
+            LogLevel::Notice => '*',
+            LogLevel::Warning => '#',
+        }
+    }
+}
+
+/// Writes `message` to the log, unless `level` is below `loglevel`.
+pub fn log(config: &Config, level: LogLevel, message: &str) {
+    if level < config.loglevel() {
+        return;
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    eprintln!(
+        "{}:M {}.{:03} {} {}",
+        process::id(),
+        now.as_secs(),
+        now.subsec_millis(),
+        level.mark(),
+        message
+    );
+}
@@ -1,49 +1,108 @@
-use bytes::Bytes;
-use tokio::net::{TcpListener, TcpStream};
-use mini_redis::{Connection,Frame};
-use std::sync::Arc;
+use std::path::PathBuf;
 
-use shared_lib::sharded_db;
+use miniredis::config::Config;
+use miniredis::raft::RaftConfig;
+use miniredis::server::{Server, DEFAULT_ADDR};
+use shared_lib::sharded_db::ShardModel;
 
 #[tokio::main]
 async fn main() {
-    let listener = TcpListener::bind("127.0.0.1:6379").await.unwrap();
+    // `--memcached addr` also serves the memcached protocol, e.g. on
+    // 127.0.0.1:11211, and `--http addr` the HTTP/JSON gateway.
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let addr = take_arg(&mut args, "--bind").unwrap_or_else(|| DEFAULT_ADDR.to_string());
+    let memcached = take_arg(&mut args, "--memcached");
+    let http = take_arg(&mut args, "--http");
+    // `--shard-model rwlock` lets readers of a shard run side by side, and
+    // `--shard-model owner` hands shards to a pool of threads, one per core,
+    // in builds with the `bench` feature, see `ShardModel`.
+    let shard_model = take_arg(&mut args, "--shard-model").map(|model| {
+        model.parse::<ShardModel>().unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        })
+    });
+    let raft = take_raft_args(&mut args);
 
-    println!("Listening");
+    // Parameters can be set on the command line, e.g. `--slowlog-max-len 256`.
+    let config = Config::default();
+    if let Err(err) = config.apply_args(args) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
 
-    let db = sharded_db::ShardedDB::<Bytes>::new(10);
+    let mut builder = Server::builder().bind(addr).config(config);
+    if let Some(addr) = memcached {
+        builder = builder.memcached(addr);
+    }
+    if let Some(addr) = http {
+        builder = builder.http(addr);
+    }
+    if let Some(raft) = raft {
+        builder = builder.raft(raft);
+    }
+    if let Some(model) = shard_model {
+        builder = builder.shard_model(model);
+    }
+    let server = match builder.start().await {
+        Ok(server) => server,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
 
-    loop {
-        let (socket, _) = listener.accept().await.unwrap();
+    println!("Listening on {}", server.local_addr());
+    if let Some(addr) = server.memcached_addr() {
+        println!("Listening for memcached on {}", addr);
+    }
+    if let Some(addr) = server.http_addr() {
+        println!("Listening for HTTP on {}", addr);
+    }
 
-        println!("Accepted");
-        let db_clone = Arc::clone(&db);
-        tokio::spawn(async move {
-            process(socket, db_clone).await;
-        });
+    tokio::signal::ctrl_c().await.unwrap();
+    server.shutdown().await;
+}
+
+/// Removes `name` and the value following it from `args`, leaving the
+/// configuration parameters.
+fn take_arg(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|arg| arg == name)?;
+    if i + 1 == args.len() {
+        eprintln!("missing value for '{}'", name);
+        std::process::exit(1);
     }
+
+    args.remove(i);
+    Some(args.remove(i))
 }
 
-async fn process(socket: TcpStream, db: Arc<sharded_db::ShardedDB<Bytes>>){
-    use mini_redis::Command::{self, Get, Set};
-
-    let mut connection = Connection::new(socket);
-
-    while let Some(frame) = connection.read_frame().await.unwrap() {
-        let response = match Command::from_frame(frame).unwrap() {
-            Set(cmd) => {
-                db.insert(&cmd.key().to_string(), cmd.value().clone());
-                Frame::Simple("OK".to_string())
-            }
-            Get(cmd) => {
-                if let Some(value) = db.get(cmd.key()) {
-                    Frame::Bulk(value.clone())
-                } else {
-                    Frame::Null
-                }
-            }
-            cmd => panic!("unimplemented {:?}", cmd),
-        };
-        connection.write_frame(&response).await.unwrap();
-    }
-}
\ No newline at end of file
+/// Reads `--raft-id N`, which turns replication on, along with
+/// `--raft-peers 1=addr,2=addr,...` listing the initial members and
+/// `--raft-dir path`, `raft-N` by default.
+fn take_raft_args(args: &mut Vec<String>) -> Option<RaftConfig> {
+    let id = take_arg(args, "--raft-id");
+    let dir = take_arg(args, "--raft-dir");
+    let peers = take_arg(args, "--raft-peers");
+
+    let id = match id?.parse() {
+        Ok(id) => id,
+        Err(_) => {
+            eprintln!("invalid value for '--raft-id'");
+            std::process::exit(1);
+        }
+    };
+    let peers = match RaftConfig::parse_peers(peers.as_deref().unwrap_or("")) {
+        Ok(peers) => peers,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    Some(RaftConfig {
+        id,
+        dir: dir.map_or_else(|| PathBuf::from(format!("raft-{}", id)), PathBuf::from),
+        peers,
+    })
+}
@@ -0,0 +1,306 @@
+//! Memcached text protocol frontend, serving the same keyspace as the Redis
+//! protocol.
+//!
+//! Memcached clients work on database 0. Their items are plain strings to
+//! Redis clients: the flags stored along with them are kept in
+//! `Entry::flags`, and CAS tokens are `Entry::cas`, so a change made by a
+//! Redis client makes a pending `cas` fail like any other change.
+
+mod request;
+
+use bytes::{Buf, Bytes, BytesMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
+use tokio::net::TcpStream;
+
+use crate::handler::ClientGuard;
+use crate::state::ServerState;
+use request::{Error, Request};
+
+/// Largest value accepted, the default item size limit of memcached.
+const MAX_ITEM_SIZE: usize = 1024 * 1024;
+
+/// Longest command line accepted. Keys are at most 250 bytes, so only
+/// `get` with many keys comes close.
+const MAX_LINE_LEN: usize = 8 * 1024;
+
+/// Counters reported by `stats`, shared by the memcached connections.
+#[derive(Debug)]
+pub struct Stats {
+    started: Instant,
+    counters: Counters,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    curr_connections: AtomicU64,
+    total_connections: AtomicU64,
+    cmd_get: AtomicU64,
+    cmd_set: AtomicU64,
+    cmd_flush: AtomicU64,
+    cmd_touch: AtomicU64,
+    get_hits: AtomicU64,
+    get_misses: AtomicU64,
+    delete_misses: AtomicU64,
+    delete_hits: AtomicU64,
+    incr_misses: AtomicU64,
+    incr_hits: AtomicU64,
+    decr_misses: AtomicU64,
+    decr_hits: AtomicU64,
+    cas_misses: AtomicU64,
+    cas_hits: AtomicU64,
+    cas_badval: AtomicU64,
+    touch_hits: AtomicU64,
+    touch_misses: AtomicU64,
+    total_items: AtomicU64,
+}
+
+/// Reads requests and writes their replies on a memcached connection.
+#[derive(Debug)]
+struct Connection {
+    stream: BufWriter<TcpStream>,
+    buffer: BytesMut,
+}
+
+/// Serves a connection accepted on the memcached listener until the client
+/// goes away or sends `quit`.
+///
+/// Connections count towards `maxclients` along with Redis ones.
+pub async fn serve(mut socket: TcpStream, state: Arc<ServerState>, stats: Arc<Stats>) {
+    let _guard = ClientGuard::new(&state);
+
+    if state.clients.load(Ordering::Relaxed) > state.config.maxclients() {
+        let _ = socket
+            .write_all(b"SERVER_ERROR max number of clients reached\r\n")
+            .await;
+        return;
+    }
+
+    let _ = socket.set_nodelay(true);
+
+    stats
+        .counters
+        .curr_connections
+        .fetch_add(1, Ordering::Relaxed);
+    stats
+        .counters
+        .total_connections
+        .fetch_add(1, Ordering::Relaxed);
+
+    let mut connection = Connection::new(socket);
+    // Errors only end the connection, there is no one to report them to.
+    let _ = run(&mut connection, &state, &stats).await;
+
+    stats
+        .counters
+        .curr_connections
+        .fetch_sub(1, Ordering::Relaxed);
+}
+
+async fn run(
+    connection: &mut Connection,
+    state: &Arc<ServerState>,
+    stats: &Stats,
+) -> crate::Result<()> {
+    let mut out = BytesMut::new();
+
+    loop {
+        let line = match connection.read_line().await? {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+
+        let mut request = match Request::parse(&line) {
+            Ok(request) => request,
+            // The data block is swallowed to get back in sync, unless it
+            // could not even be sent, then there is nothing to sync with.
+            Err(Error::TooLarge(len)) => {
+                let block = len.checked_add(2);
+                if let Some(block) = block {
+                    connection.skip(block).await?;
+                }
+                connection
+                    .write(Error::TooLarge(len).reply().as_bytes())
+                    .await?;
+                match block {
+                    Some(_) => continue,
+                    None => return Ok(()),
+                }
+            }
+            Err(err) => {
+                connection.write(err.reply().as_bytes()).await?;
+                continue;
+            }
+        };
+
+        if let Request::Store(store) = &mut request {
+            match connection.read_data(store.len).await? {
+                Some(data) => store.data = data,
+                None => {
+                    connection.write(b"CLIENT_ERROR bad data chunk\r\n").await?;
+                    continue;
+                }
+            }
+        }
+
+        if let Request::Quit = request {
+            return Ok(());
+        }
+
+        let noreply = request.is_noreply();
+        let scope = request.lock_scope();
+        state
+            .run_shared(scope, || request.apply(state, stats, &mut out))
+            .await;
+        if !noreply {
+            connection.write(&out).await?;
+        }
+        out.clear();
+    }
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats {
+            started: Instant::now(),
+            counters: Counters::default(),
+        }
+    }
+
+    /// The counters that `stats reset` clears, in the order memcached
+    /// reports them.
+    fn counters(&self) -> [(&'static str, &AtomicU64); 18] {
+        let c = &self.counters;
+        [
+            ("cmd_get", &c.cmd_get),
+            ("cmd_set", &c.cmd_set),
+            ("cmd_flush", &c.cmd_flush),
+            ("cmd_touch", &c.cmd_touch),
+            ("get_hits", &c.get_hits),
+            ("get_misses", &c.get_misses),
+            ("delete_misses", &c.delete_misses),
+            ("delete_hits", &c.delete_hits),
+            ("incr_misses", &c.incr_misses),
+            ("incr_hits", &c.incr_hits),
+            ("decr_misses", &c.decr_misses),
+            ("decr_hits", &c.decr_hits),
+            ("cas_misses", &c.cas_misses),
+            ("cas_hits", &c.cas_hits),
+            ("cas_badval", &c.cas_badval),
+            ("touch_hits", &c.touch_hits),
+            ("touch_misses", &c.touch_misses),
+            ("total_items", &c.total_items),
+        ]
+    }
+
+    fn reset(&self) {
+        for (_, counter) in self.counters() {
+            counter.store(0, Ordering::Relaxed);
+        }
+        self.counters.total_connections.store(0, Ordering::Relaxed);
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Stats {
+        Stats::new()
+    }
+}
+
+fn incr(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+impl Connection {
+    fn new(socket: TcpStream) -> Connection {
+        Connection {
+            stream: BufWriter::new(socket),
+            buffer: BytesMut::with_capacity(4 * 1024),
+        }
+    }
+
+    /// Reads a command line, without its line ending. Memcached accepts
+    /// lines ending in `\n` alone.
+    async fn read_line(&mut self) -> crate::Result<Option<Bytes>> {
+        loop {
+            if let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+                let mut line = self.buffer.split_to(end + 1);
+                line.truncate(end);
+                if line.last() == Some(&b'\r') {
+                    line.truncate(end - 1);
+                }
+                return Ok(Some(line.freeze()));
+            }
+
+            // Memcached tells the client why before closing the connection.
+            if self.buffer.len() > MAX_LINE_LEN {
+                self.stream
+                    .write_all(b"CLIENT_ERROR line too long\r\n")
+                    .await?;
+                self.stream.flush().await?;
+                return Err("line too long".into());
+            }
+
+            if !self.fill().await? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Reads the data block of a storage command, `None` when it is not
+    /// followed by a line ending.
+    /// `len` was checked against `MAX_ITEM_SIZE` when parsing.
+    async fn read_data(&mut self, len: usize) -> crate::Result<Option<Bytes>> {
+        debug_assert!(len <= MAX_ITEM_SIZE);
+        while self.buffer.len() < len + 2 {
+            if !self.fill().await? {
+                return Err("connection reset by peer".into());
+            }
+        }
+
+        let data = self.buffer.split_to(len).freeze();
+        let end = self.buffer.split_to(2);
+        Ok(Some(data).filter(|_| &end[..] == b"\r\n"))
+    }
+
+    /// Discards `len` bytes of input, without holding on to them.
+    async fn skip(&mut self, mut len: usize) -> crate::Result<()> {
+        loop {
+            let n = len.min(self.buffer.len());
+            self.buffer.advance(n);
+            len -= n;
+
+            if len == 0 {
+                return Ok(());
+            }
+            if !self.fill().await? {
+                return Err("connection reset by peer".into());
+            }
+        }
+    }
+
+    /// Reads more input, returning `false` at the end of the stream.
+    async fn fill(&mut self) -> crate::Result<bool> {
+        if 0 == self.stream.read_buf(&mut self.buffer).await? {
+            if self.buffer.is_empty() {
+                return Ok(false);
+            }
+            return Err("connection reset by peer".into());
+        }
+
+        Ok(true)
+    }
+
+    /// Writes a reply, held back while more requests are already buffered
+    /// so pipelined replies go out together.
+    async fn write(&mut self, reply: &[u8]) -> crate::Result<()> {
+        self.stream.write_all(reply).await?;
+        if self.buffer.is_empty() {
+            self.stream.flush().await?;
+        }
+
+        Ok(())
+    }
+}
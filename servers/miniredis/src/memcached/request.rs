@@ -0,0 +1,558 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{incr, Stats, MAX_ITEM_SIZE};
+use crate::db::{self, Entry, Value};
+use crate::locks::Scope;
+use crate::notify::EventClass;
+use crate::state::ServerState;
+
+/// Version reported by `version` and `stats`, the memcached release whose
+/// protocol is followed.
+const VERSION: &str = "1.6.21";
+
+/// Longest key memcached accepts.
+const MAX_KEY_LEN: usize = 250;
+
+/// Expiration times up to 30 days are relative to now, later ones are Unix
+/// times.
+const MAX_RELATIVE_EXPTIME: i64 = 60 * 60 * 24 * 30;
+
+/// A request read from a memcached client.
+#[derive(Debug)]
+pub(crate) enum Request {
+    /// `get <key>*` or `gets <key>*`, the latter with CAS tokens.
+    Get {
+        keys: Vec<Bytes>,
+        cas: bool,
+    },
+    Store(Store),
+    /// `delete <key> [noreply]`
+    Delete {
+        key: Bytes,
+        noreply: bool,
+    },
+    /// `incr <key> <value> [noreply]` or `decr <key> <value> [noreply]`
+    Incr {
+        key: Bytes,
+        delta: u64,
+        decr: bool,
+        noreply: bool,
+    },
+    /// `touch <key> <exptime> [noreply]`
+    Touch {
+        key: Bytes,
+        exptime: i64,
+        noreply: bool,
+    },
+    /// `flush_all [delay] [noreply]`
+    FlushAll {
+        delay: u64,
+        noreply: bool,
+    },
+    /// `stats` or `stats reset`
+    Stats {
+        reset: bool,
+    },
+    /// `version`
+    Version,
+    /// `verbosity <level> [noreply]`, accepted and ignored.
+    Verbosity {
+        noreply: bool,
+    },
+    /// `quit`
+    Quit,
+}
+
+/// `<command> <key> <flags> <exptime> <bytes> [<cas unique>] [noreply]`,
+/// followed by a data block of `bytes` bytes.
+#[derive(Debug)]
+pub(crate) struct Store {
+    mode: Mode,
+    key: Bytes,
+    flags: u32,
+    exptime: i64,
+    pub(crate) len: usize,
+    noreply: bool,
+    /// Read after the command line.
+    pub(crate) data: Bytes,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Set,
+    Add,
+    Replace,
+    Append,
+    Prepend,
+    /// Store only if the item did not change since the client got this
+    /// token with `gets`.
+    Cas(u64),
+}
+
+/// What a storage command did.
+enum Outcome {
+    Stored,
+    NotStored,
+    Exists,
+    NotFound,
+}
+
+/// A request that cannot be served.
+#[derive(Debug)]
+pub(crate) enum Error {
+    /// An unknown command.
+    Unknown,
+    /// A malformed command.
+    Client(&'static str),
+    /// A storage command with more data than the item size limit, which
+    /// has to be skipped.
+    TooLarge(usize),
+}
+
+impl Request {
+    /// Parses a command line, without its line ending.
+    pub(crate) fn parse(line: &[u8]) -> Result<Request, Error> {
+        let tokens: Vec<&[u8]> = line
+            .split(|&b| b == b' ')
+            .filter(|token| !token.is_empty())
+            .collect();
+
+        let (command, args) = match tokens.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return Err(Error::Unknown),
+        };
+
+        let request = match command {
+            b"get" | b"gets" => {
+                if args.is_empty() {
+                    return Err(Error::Unknown);
+                }
+                Request::Get {
+                    keys: args.iter().map(|arg| key(arg)).collect::<Result<_, _>>()?,
+                    cas: command == b"gets",
+                }
+            }
+            b"set" => Request::Store(Store::parse(Mode::Set, args)?),
+            b"add" => Request::Store(Store::parse(Mode::Add, args)?),
+            b"replace" => Request::Store(Store::parse(Mode::Replace, args)?),
+            b"append" => Request::Store(Store::parse(Mode::Append, args)?),
+            b"prepend" => Request::Store(Store::parse(Mode::Prepend, args)?),
+            b"cas" => Request::Store(Store::parse(Mode::Cas(0), args)?),
+            b"delete" => {
+                // A time of `0` is accepted for older clients.
+                let (args, noreply) = noreply(args);
+                match args {
+                    [k] | [k, b"0"] => Request::Delete {
+                        key: key(k)?,
+                        noreply,
+                    },
+                    _ => {
+                        return Err(Error::Client(
+                            "bad command line format.  Usage: delete <key> [noreply]",
+                        ))
+                    }
+                }
+            }
+            b"incr" | b"decr" => match noreply(args) {
+                ([k, delta], noreply) => Request::Incr {
+                    key: key(k)?,
+                    delta: number(delta)
+                        .map_err(|_| Error::Client("invalid numeric delta argument"))?,
+                    decr: command == b"decr",
+                    noreply,
+                },
+                _ => return Err(Error::Unknown),
+            },
+            b"touch" => match noreply(args) {
+                ([k, exptime], noreply) => Request::Touch {
+                    key: key(k)?,
+                    exptime: number(exptime)
+                        .map_err(|_| Error::Client("invalid exptime argument"))?,
+                    noreply,
+                },
+                _ => return Err(Error::Unknown),
+            },
+            b"flush_all" => match noreply(args) {
+                ([], noreply) => Request::FlushAll { delay: 0, noreply },
+                ([delay], noreply) => Request::FlushAll {
+                    delay: number(delay)?,
+                    noreply,
+                },
+                _ => return Err(Error::Unknown),
+            },
+            b"stats" => match args {
+                [] => Request::Stats { reset: false },
+                [b"reset"] => Request::Stats { reset: true },
+                _ => return Err(Error::Unknown),
+            },
+            b"version" => Request::Version,
+            b"verbosity" => match noreply(args) {
+                ([_], noreply) => Request::Verbosity { noreply },
+                _ => return Err(Error::Unknown),
+            },
+            b"quit" => Request::Quit,
+            _ => return Err(Error::Unknown),
+        };
+
+        Ok(request)
+    }
+
+    /// Whether the client asked not to get a reply.
+    pub(crate) fn is_noreply(&self) -> bool {
+        match self {
+            Request::Store(store) => store.noreply,
+            Request::Delete { noreply, .. }
+            | Request::Incr { noreply, .. }
+            | Request::Touch { noreply, .. }
+            | Request::FlushAll { noreply, .. }
+            | Request::Verbosity { noreply } => *noreply,
+            _ => false,
+        }
+    }
+
+    /// Keys the request needs kept apart from transactions and scripts, see
+    /// `locks`.
+    pub(crate) fn lock_scope(&self) -> Scope {
+        match self {
+            Request::Get { keys, .. } => {
+                Scope::Keys(keys.clone())
+            }
+            Request::Store(Store { key, .. })
+            | Request::Delete { key, .. }
+            | Request::Incr { key, .. }
+            | Request::Touch { key, .. } => Scope::Keys(vec![key.clone()]),
+            Request::FlushAll { delay: 0, .. } => Scope::All,
+            _ => Scope::None,
+        }
+    }
+
+    /// Runs the request against database 0, writing the reply to `out`.
+    ///
+    /// Changes fire the same keyspace events as the matching Redis commands.
+    pub(crate) fn apply(self, state: &Arc<ServerState>, stats: &Stats, out: &mut BytesMut) {
+        let db = state.dbs.get(0);
+        let counters = &stats.counters;
+
+        match self {
+            Request::Get { keys, cas } => {
+                for key in keys {
+                    incr(&counters.cmd_get);
+                    let entry = db.get_entry(&key);
+                    match entry.map(|entry| (entry.value.as_string(), entry.flags, entry.cas)) {
+                        Some((Some(value), flags, token)) => {
+                            incr(&counters.get_hits);
+                            let header = if cas {
+                                format!(" {} {} {}\r\n", flags, value.len(), token)
+                            } else {
+                                format!(" {} {}\r\n", flags, value.len())
+                            };
+                            out.put_slice(b"VALUE ");
+                            out.put_slice(&key);
+                            out.put_slice(header.as_bytes());
+                            out.put_slice(&value);
+                            out.put_slice(b"\r\n");
+                        }
+                        // Values of other types do not exist for memcached.
+                        Some((None, ..)) => incr(&counters.get_misses),
+                        None => {
+                            incr(&counters.get_misses);
+                            db.notify(EventClass::KeyMiss, "keymiss", &key);
+                        }
+                    }
+                }
+                out.put_slice(b"END\r\n");
+            }
+            Request::Store(store) => store.apply(state, stats, out),
+            Request::Delete { key, .. } => {
+                if db.remove(&key) {
+                    incr(&counters.delete_hits);
+                    db.notify(EventClass::Generic, "del", &key);
+                    out.put_slice(b"DELETED\r\n");
+                } else {
+                    incr(&counters.delete_misses);
+                    out.put_slice(b"NOT_FOUND\r\n");
+                }
+            }
+            Request::Incr {
+                key, delta, decr, ..
+            } => {
+                let (hits, misses, event) = if decr {
+                    (&counters.decr_hits, &counters.decr_misses, "decrby")
+                } else {
+                    (&counters.incr_hits, &counters.incr_misses, "incrby")
+                };
+
+                let result = db.update(&key, |mut slot| {
+                    let current = slot.as_ref()?;
+                    let current = std::str::from_utf8(current)
+                        .ok()
+                        .and_then(|current| current.parse::<u64>().ok());
+                    // Incrementing wraps around, decrementing stops at 0.
+                    let value = current.map(|current| match decr {
+                        false => current.wrapping_add(delta),
+                        true => current.saturating_sub(delta),
+                    });
+                    if let Some(value) = value {
+                        *slot = Some(Bytes::from(value.to_string()));
+                    }
+                    Some(value)
+                });
+
+                match result {
+                    Ok(None) => {
+                        incr(misses);
+                        out.put_slice(b"NOT_FOUND\r\n");
+                    }
+                    Ok(Some(Some(value))) => {
+                        incr(hits);
+                        db.notify(EventClass::String, event, &key);
+                        out.put_slice(format!("{}\r\n", value).as_bytes());
+                    }
+                    Ok(Some(None)) | Err(_) => out.put_slice(
+                        b"CLIENT_ERROR cannot increment or decrement non-numeric value\r\n",
+                    ),
+                }
+            }
+            Request::Touch { key, exptime, .. } => {
+                incr(&counters.cmd_touch);
+                let touched = db.update_entry(&key, |mut slot| match slot.as_mut() {
+                    Some(entry) => {
+                        entry.expires_at = expires_at(exptime);
+                        true
+                    }
+                    None => false,
+                });
+
+                if touched {
+                    incr(&counters.touch_hits);
+                    db.notify(EventClass::Generic, "expire", &key);
+                    out.put_slice(b"TOUCHED\r\n");
+                } else {
+                    incr(&counters.touch_misses);
+                    out.put_slice(b"NOT_FOUND\r\n");
+                }
+            }
+            Request::FlushAll { delay, .. } => {
+                incr(&counters.cmd_flush);
+                if delay == 0 {
+                    state.dbs.flush(0, false);
+                } else {
+                    let state = Arc::clone(state);
+                    tokio::spawn(async move {
+                        tokio::time::sleep(Duration::from_secs(delay)).await;
+                        state
+                            .run_shared(Scope::All, || state.dbs.flush(0, false))
+                            .await;
+                    });
+                }
+                out.put_slice(b"OK\r\n");
+            }
+            Request::Stats { reset: true } => {
+                stats.reset();
+                out.put_slice(b"RESET\r\n");
+            }
+            Request::Stats { reset: false } => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_secs());
+                let mut lines = vec![
+                    ("pid", std::process::id().to_string()),
+                    ("uptime", stats.started.elapsed().as_secs().to_string()),
+                    ("time", now.to_string()),
+                    ("version", VERSION.to_string()),
+                    ("pointer_size", (usize::BITS).to_string()),
+                    (
+                        "curr_connections",
+                        counters
+                            .curr_connections
+                            .load(Ordering::Relaxed)
+                            .to_string(),
+                    ),
+                    (
+                        "total_connections",
+                        counters
+                            .total_connections
+                            .load(Ordering::Relaxed)
+                            .to_string(),
+                    ),
+                ];
+                for (name, counter) in stats.counters() {
+                    lines.push((name, counter.load(Ordering::Relaxed).to_string()));
+                }
+                lines.push(("curr_items", db.len().to_string()));
+                lines.push(("bytes", db.used_memory().to_string()));
+                lines.push(("limit_maxbytes", state.config.maxmemory().to_string()));
+
+                for (name, value) in lines {
+                    out.put_slice(format!("STAT {} {}\r\n", name, value).as_bytes());
+                }
+                out.put_slice(b"END\r\n");
+            }
+            Request::Version => out.put_slice(format!("VERSION {}\r\n", VERSION).as_bytes()),
+            Request::Verbosity { .. } => out.put_slice(b"OK\r\n"),
+            Request::Quit => {}
+        }
+    }
+}
+
+impl Store {
+    fn parse(mode: Mode, args: &[&[u8]]) -> Result<Store, Error> {
+        let (args, noreply) = noreply(args);
+        let (name, flags, exptime, len, cas) = match (mode, args) {
+            (Mode::Cas(_), [name, flags, exptime, len, cas]) => {
+                (name, flags, exptime, len, Some(cas))
+            }
+            (Mode::Cas(_), _) => return Err(Error::Unknown),
+            (_, [name, flags, exptime, len]) => (name, flags, exptime, len, None),
+            _ => return Err(Error::Unknown),
+        };
+
+        let len: usize = number(len)?;
+        if len > MAX_ITEM_SIZE {
+            return Err(Error::TooLarge(len));
+        }
+
+        let mode = match cas {
+            Some(cas) => Mode::Cas(number(cas)?),
+            None => mode,
+        };
+
+        Ok(Store {
+            mode,
+            key: key(name)?,
+            flags: number(flags)?,
+            exptime: number(exptime)?,
+            len,
+            noreply,
+            data: Bytes::new(),
+        })
+    }
+
+    fn apply(self, state: &ServerState, stats: &Stats, out: &mut BytesMut) {
+        let counters = &stats.counters;
+        incr(&counters.cmd_set);
+
+        if !state.free_memory() {
+            out.put_slice(b"SERVER_ERROR out of memory storing object\r\n");
+            return;
+        }
+
+        let db = state.dbs.get(0);
+        let expires_at = expires_at(self.exptime);
+        let result = db.update_entry(&self.key, |mut slot| {
+            match (self.mode, slot.as_ref()) {
+                (Mode::Add, Some(_)) => return Outcome::NotStored,
+                (Mode::Replace | Mode::Append | Mode::Prepend, None) => return Outcome::NotStored,
+                (Mode::Cas(_), None) => return Outcome::NotFound,
+                (Mode::Cas(cas), Some(entry)) if entry.cas != cas => return Outcome::Exists,
+                // Appending keeps the flags and expiration time of the item.
+                (Mode::Append | Mode::Prepend, Some(entry)) => {
+                    let current = match entry.value.as_string() {
+                        Some(current) => current,
+                        None => return Outcome::NotStored,
+                    };
+                    let mut value = BytesMut::with_capacity(current.len() + self.data.len());
+                    if self.mode == Mode::Append {
+                        value.put_slice(&current);
+                        value.put_slice(&self.data);
+                    } else {
+                        value.put_slice(&self.data);
+                        value.put_slice(&current);
+                    }
+                    if let Some(entry) = slot.as_mut() {
+                        entry.value = Value::String(value.freeze());
+                    }
+                    return Outcome::Stored;
+                }
+                _ => {}
+            }
+
+            let mut entry = Entry::new(Value::String(self.data));
+            entry.expires_at = expires_at;
+            entry.flags = self.flags;
+            *slot = Some(entry);
+            Outcome::Stored
+        });
+
+        if let Mode::Cas(_) = self.mode {
+            match result {
+                Outcome::Stored => incr(&counters.cas_hits),
+                Outcome::Exists => incr(&counters.cas_badval),
+                _ => incr(&counters.cas_misses),
+            }
+        }
+
+        let reply: &[u8] = match result {
+            Outcome::Stored => {
+                incr(&counters.total_items);
+                match self.mode {
+                    Mode::Append | Mode::Prepend => {
+                        db.notify(EventClass::String, "append", &self.key);
+                    }
+                    _ => {
+                        db.notify(EventClass::String, "set", &self.key);
+                        if expires_at.is_some() {
+                            db.notify(EventClass::Generic, "expire", &self.key);
+                        }
+                    }
+                }
+                b"STORED\r\n"
+            }
+            Outcome::NotStored => b"NOT_STORED\r\n",
+            Outcome::Exists => b"EXISTS\r\n",
+            Outcome::NotFound => b"NOT_FOUND\r\n",
+        };
+        out.put_slice(reply);
+    }
+}
+
+impl Error {
+    /// The line sent back to the client.
+    pub(crate) fn reply(&self) -> String {
+        match self {
+            Error::Unknown => "ERROR\r\n".to_string(),
+            Error::Client(message) => format!("CLIENT_ERROR {}\r\n", message),
+            Error::TooLarge(_) => "SERVER_ERROR object too large for cache\r\n".to_string(),
+        }
+    }
+}
+
+/// Splits off a trailing `noreply`.
+fn noreply<'a, 'b>(args: &'a [&'b [u8]]) -> (&'a [&'b [u8]], bool) {
+    match args.split_last() {
+        Some((last, rest)) if *last == b"noreply" => (rest, true),
+        _ => (args, false),
+    }
+}
+
+/// Keys are binary in the keyspace, only the memcached rules apply: no
+/// longer than 250 bytes and no control characters.
+fn key(arg: &[u8]) -> Result<Bytes, Error> {
+    if arg.len() > MAX_KEY_LEN || arg.iter().any(|b| b.is_ascii_control()) {
+        return Err(Error::Client("bad command line format"));
+    }
+
+    Ok(Bytes::copy_from_slice(arg))
+}
+
+fn number<T: FromStr>(arg: &[u8]) -> Result<T, Error> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|arg| arg.parse().ok())
+        .ok_or(Error::Client("bad command line format"))
+}
+
+/// Converts a memcached expiration time: 0 never expires, negative times
+/// expire right away.
+fn expires_at(exptime: i64) -> Option<u64> {
+    match exptime {
+        0 => None,
+        ..=-1 => Some(0),
+        1..=MAX_RELATIVE_EXPTIME => Some(db::now_ms().saturating_add(exptime as u64 * 1000)),
+        _ => Some((exptime as u64).saturating_mul(1000)),
+    }
+}
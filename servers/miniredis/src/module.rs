@@ -0,0 +1,421 @@
+//! Extension point for custom commands and value types, implemented in Rust
+//! and registered when the server starts.
+//!
+//! ```no_run
+//! # async fn run() -> miniredis::Result<()> {
+//! use bytes::Bytes;
+//! use miniredis::module::{CommandFlags, Context, Module, Registry, ValueType};
+//! use miniredis::protocol::frame::Frame;
+//! use miniredis::server::Server;
+//!
+//! #[derive(Debug, Clone, Default)]
+//! struct Counter(i64);
+//!
+//! impl ValueType for Counter {
+//!     const NAME: &'static str = "counter";
+//!
+//!     fn memory_usage(&self) -> usize {
+//!         8
+//!     }
+//!
+//!     fn save(&self) -> Vec<u8> {
+//!         self.0.to_le_bytes().to_vec()
+//!     }
+//!
+//!     fn load(data: &[u8]) -> miniredis::Result<Counter> {
+//!         Ok(Counter(i64::from_le_bytes(data.try_into()?)))
+//!     }
+//! }
+//!
+//! struct Counters;
+//!
+//! impl Module for Counters {
+//!     fn name(&self) -> &str {
+//!         "counters"
+//!     }
+//!
+//!     fn register(&self, registry: &mut Registry) -> miniredis::Result<()> {
+//!         registry.value_type::<Counter>()?;
+//!         registry.command(
+//!             "counter.incr",
+//!             CommandFlags::WRITE,
+//!             |ctx: &mut Context, args: Vec<Bytes>| {
+//!                 let key = ctx.key(args.first())?;
+//!                 let value = ctx.update(&key, |slot: &mut Option<Counter>| {
+//!                     let counter = slot.get_or_insert_with(Counter::default);
+//!                     counter.0 += 1;
+//!                     counter.0
+//!                 })?;
+//!                 Ok(Frame::Integer(value))
+//!             },
+//!         )
+//!     }
+//! }
+//!
+//! let server = Server::builder().module(Counters).start().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use bytes::Bytes;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::cmd::{self, Command};
+use crate::db::{Db, Entry, Value, WrongType};
+use crate::notify::EventClass;
+use crate::protocol::frame::Frame;
+use crate::pubsub::ClientId;
+use crate::session::Session;
+use crate::state::ServerState;
+
+/// A set of commands and value types plugged into the server.
+pub trait Module: Send + Sync + 'static {
+    /// Name the module is listed under by `MODULE LIST`.
+    fn name(&self) -> &str;
+
+    fn version(&self) -> i64 {
+        1
+    }
+
+    /// Registers the commands and value types of the module, once, when the
+    /// server starts. An error stops the server from starting.
+    fn register(&self, registry: &mut Registry) -> crate::Result<()>;
+}
+
+/// Implementation of a command registered by a module.
+///
+/// Implemented for closures taking the same arguments as `call`.
+pub trait CommandHandler: Send + Sync + 'static {
+    /// Runs the command, `args` being the arguments after the command name.
+    /// Errors are sent to the client, with a Redis error code when the
+    /// message starts with one.
+    fn call(&self, ctx: &mut Context, args: Vec<Bytes>) -> crate::Result<Frame>;
+}
+
+/// How a module command behaves, for the server to treat it like built-in
+/// commands.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CommandFlags {
+    /// The command changes the dataset, it is refused in read-only scripts.
+    pub write: bool,
+    /// The command may grow the dataset, it is refused while over
+    /// `maxmemory` with nothing left to evict.
+    pub deny_oom: bool,
+}
+
+/// A type of value stored at keys, next to strings.
+///
+/// Values are shared between readers, writes work on a copy when the value
+/// is still being read.
+pub trait ValueType: Clone + fmt::Debug + Send + Sync + 'static {
+    /// Name reported by `TYPE`, unique among the registered types.
+    const NAME: &'static str;
+
+    /// Approximate size in bytes, counted towards `maxmemory`.
+    fn memory_usage(&self) -> usize;
+
+    /// Serializes the value, for snapshots of the keyspace.
+    fn save(&self) -> Vec<u8>;
+
+    /// Reads back a value serialized by `save`.
+    fn load(data: &[u8]) -> crate::Result<Self>;
+}
+
+/// A value of any type registered by a module, as stored in the keyspace.
+pub trait ModuleValue: fmt::Debug + Send + Sync + 'static {
+    fn type_name(&self) -> &'static str;
+    fn memory_usage(&self) -> usize;
+    fn save(&self) -> Vec<u8>;
+    fn as_any(&self) -> &dyn Any;
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+}
+
+/// Registers the commands and types of one module, see `Module::register`.
+pub struct Registry<'a> {
+    modules: &'a mut Modules,
+    module: String,
+}
+
+/// Everything registered by the modules the server was started with.
+#[derive(Debug, Default)]
+pub struct Modules {
+    /// Names and versions of the modules, in registration order.
+    modules: Vec<(String, i64)>,
+    commands: HashMap<String, Arc<ModuleCommandSpec>>,
+    types: HashMap<&'static str, Loader>,
+}
+
+/// A command registered by a module.
+pub struct ModuleCommandSpec {
+    pub name: String,
+    pub module: String,
+    pub flags: CommandFlags,
+    handler: Box<dyn CommandHandler>,
+}
+
+type Loader = fn(&[u8]) -> crate::Result<Arc<dyn ModuleValue>>;
+
+/// What a module command sees of the server, on behalf of the client that
+/// sent it.
+pub struct Context<'a> {
+    state: &'a ServerState,
+    session: &'a mut Session,
+}
+
+impl<F> CommandHandler for F
+where
+    F: Fn(&mut Context, Vec<Bytes>) -> crate::Result<Frame> + Send + Sync + 'static,
+{
+    fn call(&self, ctx: &mut Context, args: Vec<Bytes>) -> crate::Result<Frame> {
+        self(ctx, args)
+    }
+}
+
+impl CommandFlags {
+    pub const READ_ONLY: CommandFlags = CommandFlags {
+        write: false,
+        deny_oom: false,
+    };
+    pub const WRITE: CommandFlags = CommandFlags {
+        write: true,
+        deny_oom: true,
+    };
+}
+
+impl<T: ValueType> ModuleValue for T {
+    fn type_name(&self) -> &'static str {
+        T::NAME
+    }
+
+    fn memory_usage(&self) -> usize {
+        ValueType::memory_usage(self)
+    }
+
+    fn save(&self) -> Vec<u8> {
+        ValueType::save(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+impl Registry<'_> {
+    /// Registers command `name`, case-insensitive like every command. Names
+    /// of built-in commands and of commands of other modules are refused.
+    pub fn command(
+        &mut self,
+        name: &str,
+        flags: CommandFlags,
+        handler: impl CommandHandler,
+    ) -> crate::Result<()> {
+        let name = name.to_lowercase();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("invalid command name '{}'", name).into());
+        }
+        if cmd::is_builtin(&name) || self.modules.commands.contains_key(&name) {
+            return Err(format!("command '{}' already exists", name).into());
+        }
+
+        let spec = ModuleCommandSpec {
+            name: name.clone(),
+            module: self.module.clone(),
+            flags,
+            handler: Box::new(handler),
+        };
+        self.modules.commands.insert(name, Arc::new(spec));
+        Ok(())
+    }
+
+    /// Registers value type `T`, so values of the type can be read back
+    /// from snapshots.
+    pub fn value_type<T: ValueType>(&mut self) -> crate::Result<()> {
+        if T::NAME == "string" || self.modules.types.contains_key(T::NAME) {
+            return Err(format!("value type '{}' already exists", T::NAME).into());
+        }
+
+        self.modules.types.insert(T::NAME, |data| {
+            let value: Arc<dyn ModuleValue> = Arc::new(T::load(data)?);
+            Ok(value)
+        });
+        Ok(())
+    }
+}
+
+impl Modules {
+    /// Registers `modules`, in order.
+    pub fn new(modules: &[Box<dyn Module>]) -> crate::Result<Modules> {
+        let mut registered = Modules::default();
+
+        for module in modules {
+            let name = module.name().to_string();
+            if registered.modules.iter().any(|(other, _)| *other == name) {
+                return Err(format!("module '{}' is already loaded", name).into());
+            }
+
+            let mut registry = Registry {
+                modules: &mut registered,
+                module: name.clone(),
+            };
+            module
+                .register(&mut registry)
+                .map_err(|err| format!("module '{}' failed to register: {}", name, err))?;
+            registered.modules.push((name, module.version()));
+        }
+
+        Ok(registered)
+    }
+
+    /// Names and versions of the modules.
+    pub fn list(&self) -> &[(String, i64)] {
+        &self.modules
+    }
+
+    /// The command registered as `name`, in lower case.
+    pub fn command(&self, name: &str) -> Option<Arc<ModuleCommandSpec>> {
+        self.commands.get(name).cloned()
+    }
+
+    /// Reads back a value of type `type_name` serialized with
+    /// `ModuleValue::save`.
+    pub fn load_value(&self, type_name: &str, data: &[u8]) -> crate::Result<Value> {
+        let load = self
+            .types
+            .get(type_name)
+            .ok_or_else(|| format!("unknown value type '{}'", type_name))?;
+        Ok(Value::Module(load(data)?))
+    }
+}
+
+impl ModuleCommandSpec {
+    pub(crate) fn call(&self, ctx: &mut Context, args: Vec<Bytes>) -> crate::Result<Frame> {
+        self.handler.call(ctx, args)
+    }
+}
+
+impl fmt::Debug for ModuleCommandSpec {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("ModuleCommandSpec")
+            .field("name", &self.name)
+            .field("module", &self.module)
+            .field("flags", &self.flags)
+            .finish()
+    }
+}
+
+impl<'a> Context<'a> {
+    pub(crate) fn new(state: &'a ServerState, session: &'a mut Session) -> Context<'a> {
+        Context { state, session }
+    }
+
+    /// Id of the client that sent the command.
+    pub fn client_id(&self) -> ClientId {
+        self.session.id
+    }
+
+    /// The database selected by the client, for string values.
+    pub fn db(&self) -> Arc<Db> {
+        self.state.dbs.get(self.session.db)
+    }
+
+    /// Reads a key argument, failing with the arity error of Redis when it
+    /// is missing.
+    pub fn key(&self, arg: Option<&Bytes>) -> crate::Result<Bytes> {
+        let arg = arg.ok_or("ERR wrong number of arguments")?;
+        Ok(arg.clone())
+    }
+
+    /// Returns the value of type `T` stored at `key`. Keys holding any other
+    /// type fail with a `WRONGTYPE` error.
+    pub fn get<T: ValueType>(&self, key: &[u8]) -> crate::Result<Option<Arc<T>>> {
+        match self.db().get_entry(key).as_deref() {
+            None => Ok(None),
+            Some(Entry {
+                value: Value::Module(value),
+                ..
+            }) if value.as_any().is::<T>() => Ok(Arc::clone(value).into_any().downcast::<T>().ok()),
+            Some(_) => Err(WrongType.into()),
+        }
+    }
+
+    /// Runs `f` against the value of type `T` stored at `key`, which `f` may
+    /// create, change or remove. A key that is kept keeps its time to live
+    /// and flags.
+    ///
+    /// The key counts as changed for clients tracking it, unless it holds
+    /// another type, which fails with a `WRONGTYPE` error.
+    pub fn update<T: ValueType, R>(
+        &self,
+        key: &[u8],
+        f: impl FnOnce(&mut Option<T>) -> R,
+    ) -> crate::Result<R> {
+        let result = self.db().update_entry(key, |mut slot| {
+            if slot.as_ref().is_some_and(|entry| !is_type::<T>(&entry.value)) {
+                return None;
+            }
+
+            let (expires_at, flags) = slot
+                .as_ref()
+                .map_or((None, 0), |entry| (entry.expires_at, entry.flags));
+            let mut value = match slot.take() {
+                Some(Entry {
+                    value: Value::Module(value),
+                    ..
+                }) => Some(Arc::unwrap_or_clone(value.into_any().downcast::<T>().ok()?)),
+                _ => None,
+            };
+
+            let result = f(&mut value);
+            *slot = value.map(|value| Entry {
+                value: Value::Module(Arc::new(value)),
+                expires_at,
+                flags,
+                cas: 0,
+            });
+            Some(result)
+        });
+
+        result.ok_or_else(|| WrongType.into())
+    }
+
+    /// Publishes a keyspace event of the module class, `d` in
+    /// `notify-keyspace-events`.
+    pub fn notify(&self, event: &str, key: &[u8]) {
+        self.db().notify(EventClass::Module, event, key);
+    }
+
+    /// Runs a command, built-in or registered by a module, as part of this
+    /// one. Errors are returned as error replies.
+    pub fn call(&mut self, args: &[&[u8]]) -> Frame {
+        let frame = Frame::Array(
+            args.iter()
+                .map(|arg| Frame::Bulk(Bytes::copy_from_slice(arg)))
+                .collect(),
+        );
+
+        let cmd = match Command::from_frame(frame, &self.state.modules) {
+            Ok(cmd) => cmd,
+            Err(err) => return cmd::error_frame(&err),
+        };
+
+        if !cmd.is_callable() {
+            return Frame::Error(format!(
+                "ERR '{}' can not be called by other commands",
+                cmd.get_name()
+            ));
+        }
+
+        cmd.apply(self.state, self.session).unwrap_or(Frame::Null)
+    }
+}
+
+fn is_type<T: ValueType>(value: &Value) -> bool {
+    matches!(value, Value::Module(value) if value.as_any().is::<T>())
+}
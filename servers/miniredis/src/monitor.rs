@@ -0,0 +1,88 @@
+use bytes::Bytes;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+/// Lines a monitor may fall behind by before it is disconnected.
+const CAPACITY: usize = 1024;
+
+/// Fans out every command processed by the server to the connections that
+/// issued `MONITOR`.
+///
+/// Lines are only formatted while at least one monitor is attached, so the
+/// cost without monitors is a single check per command.
+#[derive(Debug)]
+pub struct Monitors {
+    sender: broadcast::Sender<String>,
+}
+
+impl Monitors {
+    pub fn new() -> Monitors {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        Monitors { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.sender.subscribe()
+    }
+
+    /// Returns `true` when no connection is monitoring.
+    pub fn is_empty(&self) -> bool {
+        self.sender.receiver_count() == 0
+    }
+
+    /// Sends a command to every monitor, formatted as Redis does:
+    ///
+    /// ```text
+    /// 1339518083.107412 [0 127.0.0.1:60866] "set" "key" "value"
+    /// ```
+    pub fn feed(&self, db: usize, client: SocketAddr, args: &[Bytes]) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        let mut line = format!(
+            "{}.{:06} [{} {}]",
+            now.as_secs(),
+            now.subsec_micros(),
+            db,
+            client
+        );
+        for arg in args {
+            line.push(' ');
+            push_quoted(&mut line, arg);
+        }
+
+        // Sending only fails when every monitor went away meanwhile.
+        let _ = self.sender.send(line);
+    }
+}
+
+impl Default for Monitors {
+    fn default() -> Self {
+        Monitors::new()
+    }
+}
+
+/// Appends `arg` as a double quoted string, escaping anything that is not
+/// printable ASCII.
+fn push_quoted(line: &mut String, arg: &[u8]) {
+    line.push('"');
+    for &byte in arg {
+        match byte {
+            b'\\' => line.push_str("\\\\"),
+            b'"' => line.push_str("\\\""),
+            b'\n' => line.push_str("\\n"),
+            b'\r' => line.push_str("\\r"),
+            b'\t' => line.push_str("\\t"),
+            0x07 => line.push_str("\\a"),
+            0x08 => line.push_str("\\b"),
+            b' '..=b'~' => line.push(byte as char),
+            _ => {
+                let _ = write!(line, "\\x{:02x}", byte);
+            }
+        }
+    }
+    line.push('"');
+}
@@ -0,0 +1,148 @@
+use bytes::Bytes;
+use std::sync::Arc;
+
+use crate::config::{ClientClass, Config};
+use crate::pubsub::PubSub;
+
+/// Publish events to `__keyspace@<db>__:<key>` channels.
+const KEYSPACE: u32 = 1 << 0;
+/// Publish events to `__keyevent@<db>__:<event>` channels.
+const KEYEVENT: u32 = 1 << 1;
+
+/// Classes of keyspace events, in the order `notify-keyspace-events` letters
+/// are listed by `CONFIG GET`.
+///
+/// miniredis only stores strings and values of module types, the list, set,
+/// hash, sorted set and stream classes are accepted for compatibility but
+/// never fire.
+const CLASSES: &[(char, u32)] = &[
+    ('g', 1 << 2),
+    ('$', 1 << 3),
+    ('l', 1 << 4),
+    ('s', 1 << 5),
+    ('h', 1 << 6),
+    ('z', 1 << 7),
+    ('x', 1 << 8),
+    ('e', 1 << 9),
+    ('t', 1 << 10),
+    ('d', 1 << 11),
+];
+
+/// Classes left out of the `A` alias.
+const KEY_MISS: u32 = 1 << 12;
+const NEW_KEY: u32 = 1 << 13;
+
+/// Every class `A` stands for.
+const ALL: u32 = (1 << 12) - (1 << 2);
+
+/// Kind of change a keyspace event reports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventClass {
+    /// Type independent commands, such as `DEL`, `EXPIRE` or `RENAME`.
+    Generic,
+    /// Commands writing strings.
+    String,
+    /// Keys removed on reaching their time to live.
+    Expired,
+    /// Keys removed to get back under `maxmemory`.
+    Evicted,
+    /// Reads of keys that do not exist.
+    KeyMiss,
+    /// Keys added to a database.
+    New,
+    /// Commands registered by modules.
+    Module,
+}
+
+impl EventClass {
+    fn flag(self) -> u32 {
+        match self {
+            EventClass::Generic => 1 << 2,
+            EventClass::String => 1 << 3,
+            EventClass::Expired => 1 << 8,
+            EventClass::Evicted => 1 << 9,
+            EventClass::Module => 1 << 11,
+            EventClass::KeyMiss => KEY_MISS,
+            EventClass::New => NEW_KEY,
+        }
+    }
+}
+
+/// Publishes keyspace events to the clients subscribed to them, as selected
+/// by `notify-keyspace-events`.
+#[derive(Debug)]
+pub struct Notifier {
+    config: Arc<Config>,
+    pubsub: Arc<PubSub>,
+}
+
+impl Notifier {
+    pub fn new(config: Arc<Config>, pubsub: Arc<PubSub>) -> Notifier {
+        Notifier { config, pubsub }
+    }
+
+    /// Reports `event` on `key` of database `db`.
+    pub fn notify(&self, class: EventClass, event: &str, key: &[u8], db: usize) {
+        let flags = self.config.notify_keyspace_events();
+        if flags & class.flag() == 0 {
+            return;
+        }
+
+        let limit = self.config.client_output_buffer_limit(ClientClass::PubSub);
+
+        if flags & KEYSPACE != 0 {
+            let mut channel = format!("__keyspace@{}__:", db).into_bytes();
+            channel.extend_from_slice(key);
+            let channel = Bytes::from(channel);
+            let message = Bytes::copy_from_slice(event.as_bytes());
+            self.pubsub.publish(&channel, &message, limit);
+        }
+
+        if flags & KEYEVENT != 0 {
+            let channel = Bytes::from(format!("__keyevent@{}__:{}", db, event));
+            let message = Bytes::copy_from_slice(key);
+            self.pubsub.publish(&channel, &message, limit);
+        }
+    }
+}
+
+/// Parses the letters of `notify-keyspace-events`, e.g. `KEA` or `Ex`.
+///
+/// As in Redis, classes without `K` or `E` are kept but nothing is published.
+pub fn parse_flags(value: &str) -> Option<u32> {
+    value.chars().try_fold(0, |flags, c| {
+        let flag = match c {
+            'K' => KEYSPACE,
+            'E' => KEYEVENT,
+            'A' => ALL,
+            'm' => KEY_MISS,
+            'n' => NEW_KEY,
+            _ => CLASSES.iter().find(|&&(letter, _)| letter == c)?.1,
+        };
+        Some(flags | flag)
+    })
+}
+
+/// The inverse of `parse_flags`, with `A` standing in for every class it
+/// covers.
+pub fn format_flags(flags: u32) -> String {
+    let mut value = String::new();
+
+    if flags & ALL == ALL {
+        value.push('A');
+    } else {
+        for &(letter, flag) in CLASSES {
+            if flags & flag != 0 {
+                value.push(letter);
+            }
+        }
+    }
+
+    for (letter, flag) in [('K', KEYSPACE), ('E', KEYEVENT), ('m', KEY_MISS), ('n', NEW_KEY)] {
+        if flags & flag != 0 {
+            value.push(letter);
+        }
+    }
+
+    value
+}
@@ -0,0 +1,141 @@
+use bytes::{Bytes, BytesMut};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+use crate::config::BufferLimit;
+use crate::protocol::frame::Frame;
+
+/// Frames queued for a client by other connections, such as pub/sub
+/// messages, waiting for the client's connection to write them out.
+///
+/// Queued bytes are accounted against an output buffer limit, so a client that
+/// does not read fast enough is disconnected instead of making the server
+/// buffer without bound.
+#[derive(Debug, Default)]
+pub struct Outbox {
+    state: Mutex<State>,
+    /// Signalled when frames are queued or the outbox overflows.
+    notify: Notify,
+    /// Signalled only when the outbox overflows.
+    overflow: Notify,
+    /// Whether the client switched to RESP3 with `HELLO`, which decides how
+    /// frames are encoded.
+    resp3: AtomicBool,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    queue: VecDeque<Bytes>,
+    /// Bytes queued or taken but not yet written to the socket.
+    pending: usize,
+    /// When `pending` went over the soft limit.
+    soft_since: Option<Instant>,
+    overflowed: bool,
+}
+
+/// The client went past its output buffer limit and must be disconnected.
+#[derive(Debug)]
+pub struct Overflow;
+
+impl Outbox {
+    pub fn new() -> Outbox {
+        Outbox::default()
+    }
+
+    pub fn is_resp3(&self) -> bool {
+        self.resp3.load(Ordering::Relaxed)
+    }
+
+    pub fn set_resp3(&self, resp3: bool) {
+        self.resp3.store(resp3, Ordering::Relaxed);
+    }
+
+    /// Queues `frame`, returning `false` if the client is past `limit` and
+    /// the frame was dropped.
+    pub fn push(&self, frame: &Frame, limit: BufferLimit) -> bool {
+        let mut buf = BytesMut::new();
+        if self.is_resp3() {
+            frame.encode_resp3(&mut buf);
+        } else {
+            frame.encode(&mut buf);
+        }
+        self.push_encoded(buf.freeze(), limit)
+    }
+
+    /// Same as `push` for a frame that is already encoded, so a frame sent to
+    /// many clients is only encoded once. The encoding must match
+    /// `is_resp3`.
+    pub fn push_encoded(&self, bytes: Bytes, limit: BufferLimit) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.overflowed {
+            return false;
+        }
+
+        state.pending += bytes.len();
+        if state.over(limit) {
+            state.overflowed = true;
+            state.queue.clear();
+            drop(state);
+
+            self.notify.notify_one();
+            self.overflow.notify_one();
+            return false;
+        }
+
+        state.queue.push_back(bytes);
+        drop(state);
+
+        self.notify.notify_one();
+        true
+    }
+
+    /// Takes every queued frame. They still count against the limit until
+    /// `written` is called.
+    pub fn take(&self) -> Result<Vec<Bytes>, Overflow> {
+        let mut state = self.state.lock().unwrap();
+        if state.overflowed {
+            return Err(Overflow);
+        }
+
+        Ok(state.queue.drain(..).collect())
+    }
+
+    /// Records that `len` bytes taken from the outbox reached the socket.
+    pub fn written(&self, len: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.pending = state.pending.saturating_sub(len);
+    }
+
+    /// Completes once frames have been queued since the last call, or the
+    /// outbox overflowed.
+    pub async fn ready(&self) {
+        self.notify.notified().await
+    }
+
+    /// Completes once the outbox overflowed, so a write stuck on a client
+    /// that stopped reading can be abandoned.
+    pub async fn overflowed(&self) {
+        self.overflow.notified().await
+    }
+}
+
+impl State {
+    fn over(&mut self, limit: BufferLimit) -> bool {
+        let pending = self.pending as u64;
+
+        if limit.hard > 0 && pending > limit.hard {
+            return true;
+        }
+
+        if limit.soft > 0 && pending > limit.soft {
+            let since = *self.soft_since.get_or_insert_with(Instant::now);
+            return since.elapsed() > Duration::from_secs(limit.soft_seconds);
+        }
+
+        self.soft_since = None;
+        false
+    }
+}
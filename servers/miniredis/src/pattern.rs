@@ -0,0 +1,87 @@
+//! Glob-style pattern matching, as used by `CONFIG GET` and friends.
+//!
+//! Supports the same syntax as Redis:
+//!
+//! * `?` matches any single byte.
+//! * `*` matches any run of bytes, including none.
+//! * `[abc]`, `[^abc]` and `[a-z]` match a byte in, or not in, the set.
+//! * `\x` matches `x` literally.
+
+/// Returns `true` when the whole of `string` matches `pattern`.
+pub fn matches(pattern: &[u8], string: &[u8]) -> bool {
+    let mut p = 0;
+    let mut s = 0;
+
+    // Where to resume after the last `*` if the rest of the pattern fails.
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while s < string.len() {
+        let step = match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, s));
+                p += 1;
+                continue;
+            }
+            Some(b'?') => Some(p + 1),
+            Some(b'[') => match_class(pattern, p, string[s]),
+            Some(b'\\') if p + 1 < pattern.len() => (pattern[p + 1] == string[s]).then_some(p + 2),
+            Some(&c) => (c == string[s]).then_some(p + 1),
+            None => None,
+        };
+
+        match (step, backtrack) {
+            (Some(next), _) => {
+                p = next;
+                s += 1;
+            }
+            // Let the last `*` swallow one more byte and try again.
+            (None, Some((star, consumed))) => {
+                p = star + 1;
+                s = consumed + 1;
+                backtrack = Some((star, consumed + 1));
+            }
+            (None, None) => return false,
+        }
+    }
+
+    // Trailing stars match the empty remainder.
+    pattern[p.min(pattern.len())..].iter().all(|&c| c == b'*')
+}
+
+/// Matches `c` against the class starting at `pattern[start] == b'['`,
+/// returning the index just past the class on success.
+fn match_class(pattern: &[u8], start: usize, c: u8) -> Option<usize> {
+    let mut p = start + 1;
+    let negate = pattern.get(p) == Some(&b'^');
+    if negate {
+        p += 1;
+    }
+
+    let mut found = false;
+    loop {
+        match pattern.get(p) {
+            // An unterminated class ends with the pattern.
+            None => break,
+            Some(b']') => {
+                p += 1;
+                break;
+            }
+            Some(b'\\') if p + 1 < pattern.len() => {
+                found |= pattern[p + 1] == c;
+                p += 2;
+            }
+            Some(&low) if pattern.get(p + 1) == Some(&b'-') && p + 2 < pattern.len() => {
+                let high = pattern[p + 2];
+                let (low, high) = if low <= high { (low, high) } else { (high, low) };
+                found |= (low..=high).contains(&c);
+                p += 3;
+            }
+            Some(&member) => {
+                found |= member == c;
+                p += 1;
+            }
+        }
+    }
+
+    (found != negate).then_some(p)
+}
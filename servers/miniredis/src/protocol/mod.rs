@@ -0,0 +1,2 @@
+pub use miniredis_protocol::{connection, frame};
+pub mod parse;
@@ -0,0 +1,188 @@
+use bytes::{Bytes, BytesMut};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::config::BufferLimit;
+use crate::outbox::Outbox;
+use crate::pattern;
+use crate::protocol::frame::Frame;
+
+/// Identifies a connection to the server.
+pub type ClientId = u64;
+
+type Subscribers = HashMap<ClientId, Arc<Outbox>>;
+
+/// Channel and pattern subscriptions of every connection.
+///
+/// Messages are queued on each subscriber's `Outbox`, so publishing never
+/// waits on a slow subscriber.
+#[derive(Debug, Default)]
+pub struct PubSub {
+    channels: Mutex<HashMap<Bytes, Subscribers>>,
+    patterns: Mutex<HashMap<Bytes, Subscribers>>,
+}
+
+impl PubSub {
+    pub fn new() -> PubSub {
+        PubSub::default()
+    }
+
+    /// Subscribes a client to `channel`, returning `false` if it already was.
+    pub fn subscribe(&self, channel: Bytes, client: ClientId, outbox: &Arc<Outbox>) -> bool {
+        let mut channels = self.channels.lock().unwrap();
+        channels
+            .entry(channel)
+            .or_default()
+            .insert(client, Arc::clone(outbox))
+            .is_none()
+    }
+
+    pub fn unsubscribe(&self, channel: &[u8], client: ClientId) {
+        remove(&mut self.channels.lock().unwrap(), channel, client);
+    }
+
+    /// Subscribes a client to channels matching `pattern`, returning `false`
+    /// if it already was.
+    pub fn psubscribe(&self, pattern: Bytes, client: ClientId, outbox: &Arc<Outbox>) -> bool {
+        let mut patterns = self.patterns.lock().unwrap();
+        patterns
+            .entry(pattern)
+            .or_default()
+            .insert(client, Arc::clone(outbox))
+            .is_none()
+    }
+
+    pub fn punsubscribe(&self, pattern: &[u8], client: ClientId) {
+        remove(&mut self.patterns.lock().unwrap(), pattern, client);
+    }
+
+    /// Delivers `message` to the subscribers of `channel` and of every
+    /// matching pattern, returning how many subscriptions received it.
+    ///
+    /// Subscribers over `limit` are dropped from delivery, their connection
+    /// closes itself on noticing the overflow.
+    pub fn publish(&self, channel: &Bytes, message: &Bytes, limit: BufferLimit) -> usize {
+        let mut receivers = 0;
+
+        if let Some(subscribers) = self.channels.lock().unwrap().get(&channel[..]) {
+            let mut frame = Encoded::new(Frame::Push(vec![
+                Frame::Bulk("message".into()),
+                Frame::Bulk(channel.clone()),
+                Frame::Bulk(message.clone()),
+            ]));
+
+            for outbox in subscribers.values() {
+                outbox.push_encoded(frame.get(outbox), limit);
+                receivers += 1;
+            }
+        }
+
+        for (pattern, subscribers) in self.patterns.lock().unwrap().iter() {
+            if !pattern::matches(pattern, channel) {
+                continue;
+            }
+
+            let mut frame = Encoded::new(Frame::Push(vec![
+                Frame::Bulk("pmessage".into()),
+                Frame::Bulk(pattern.clone()),
+                Frame::Bulk(channel.clone()),
+                Frame::Bulk(message.clone()),
+            ]));
+
+            for outbox in subscribers.values() {
+                outbox.push_encoded(frame.get(outbox), limit);
+                receivers += 1;
+            }
+        }
+
+        receivers
+    }
+
+    /// Delivers `message` to `client` alone, if it is subscribed to `channel`.
+    pub fn send_to(&self, channel: &Bytes, message: Frame, client: ClientId, limit: BufferLimit) -> bool {
+        let channels = self.channels.lock().unwrap();
+        let outbox = match channels.get(&channel[..]).and_then(|subscribers| subscribers.get(&client)) {
+            Some(outbox) => outbox,
+            None => return false,
+        };
+
+        outbox.push(
+            &Frame::Push(vec![
+                Frame::Bulk("message".into()),
+                Frame::Bulk(channel.clone()),
+                message,
+            ]),
+            limit,
+        )
+    }
+
+    /// Channels with at least one subscriber, optionally filtered by a glob
+    /// style pattern.
+    pub fn channels(&self, filter: Option<&[u8]>) -> Vec<Bytes> {
+        self.channels
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|channel| filter.is_none_or(|filter| pattern::matches(filter, channel)))
+            .cloned()
+            .collect()
+    }
+
+    pub fn num_subscribers(&self, channel: &[u8]) -> usize {
+        self.channels
+            .lock()
+            .unwrap()
+            .get(channel)
+            .map_or(0, |subscribers| subscribers.len())
+    }
+
+    /// Number of distinct patterns subscribed to.
+    pub fn num_patterns(&self) -> usize {
+        self.patterns.lock().unwrap().len()
+    }
+}
+
+fn remove(map: &mut HashMap<Bytes, Subscribers>, name: &[u8], client: ClientId) {
+    if let Some(subscribers) = map.get_mut(name) {
+        subscribers.remove(&client);
+        if subscribers.is_empty() {
+            map.remove(name);
+        }
+    }
+}
+
+/// A frame sent to many subscribers, encoded at most once per protocol.
+struct Encoded {
+    frame: Frame,
+    resp2: Option<Bytes>,
+    resp3: Option<Bytes>,
+}
+
+impl Encoded {
+    fn new(frame: Frame) -> Encoded {
+        Encoded {
+            frame,
+            resp2: None,
+            resp3: None,
+        }
+    }
+
+    /// The encoding `outbox` expects.
+    fn get(&mut self, outbox: &Outbox) -> Bytes {
+        let frame = &self.frame;
+        let encoded = if outbox.is_resp3() {
+            self.resp3.get_or_insert_with(|| {
+                let mut buf = BytesMut::new();
+                frame.encode_resp3(&mut buf);
+                buf.freeze()
+            })
+        } else {
+            self.resp2.get_or_insert_with(|| {
+                let mut buf = BytesMut::new();
+                frame.encode(&mut buf);
+                buf.freeze()
+            })
+        };
+        encoded.clone()
+    }
+}
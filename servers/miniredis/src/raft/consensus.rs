@@ -0,0 +1,937 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, Notify};
+use tokio::{task, time};
+
+use super::log::{EntryKind, HardState, Log, LogEntry};
+use super::rpc::{AppendRequest, Peer, Reply, SnapshotRequest, VoteRequest};
+use super::snapshot::{self, Snapshot};
+use super::{election_timeout, Member, NodeId, Raft, Waiter, ELECTION_TIMEOUT, SNAPSHOT_THRESHOLD};
+use crate::locks::Scope;
+use crate::protocol::frame::Frame;
+use crate::state::ServerState;
+
+/// How often the leader reaches each follower, with entries or without.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often election timeouts are checked.
+const TICK_INTERVAL: Duration = Duration::from_millis(20);
+
+const RPC_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Snapshots may be large, they get longer to make it across.
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Most entries sent in a single `RAFT.APPEND`, or applied in one go.
+const MAX_BATCH: usize = 512;
+
+/// Times the copy of a compacted log catches up with new entries before
+/// giving up until the next snapshot, see `Raft::rewrite_log`.
+const MAX_REWRITE_ROUNDS: usize = 8;
+
+/// The state of the node that changes with elections and replication.
+#[derive(Debug)]
+pub(super) struct Core {
+    pub(super) hard: HardState,
+    pub(super) role: Role,
+    pub(super) leader: Option<NodeId>,
+    pub(super) log: Log,
+    /// Index of the last entry known to be stored by a majority.
+    pub(super) commit_index: u64,
+    /// Members as of the snapshot, for when the log holds no configuration.
+    pub(super) snapshot_config: Vec<Member>,
+    /// Members as of the latest configuration in the log, committed or not.
+    pub(super) members: Vec<Member>,
+    pub(super) peers: HashMap<NodeId, Arc<Peer>>,
+    pub(super) election_deadline: Instant,
+    /// When a leader was last heard from.
+    pub(super) last_heard: Option<Instant>,
+    /// Votes received as a candidate.
+    pub(super) votes: HashSet<NodeId>,
+    /// Replication to each follower, while leader.
+    pub(super) progress: HashMap<NodeId, Progress>,
+    /// Heartbeat rounds started by reads, to tell which acknowledgements
+    /// came after a read arrived.
+    pub(super) round: u64,
+    /// A snapshot to load into the keyspace before applying more entries.
+    pub(super) pending_snapshot: Option<Snapshot>,
+    /// Whether the leader is syncing its log, see `Raft::sync_loop`.
+    pub(super) syncing: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Role {
+    Follower,
+    Candidate,
+    Leader,
+}
+
+/// What the leader knows of a follower's log.
+#[derive(Debug)]
+pub(super) struct Progress {
+    next_index: u64,
+    match_index: u64,
+    /// Latest heartbeat round the follower acknowledged.
+    round: u64,
+    last_ack: Instant,
+    /// Wakes the replication task when there is something to send.
+    wake: Arc<Notify>,
+}
+
+/// Work picked up by the applier.
+enum Apply {
+    Install(Snapshot),
+    Entries(Vec<LogEntry>),
+}
+
+impl Core {
+    pub(super) fn is_member(&self, id: NodeId) -> bool {
+        self.members.iter().any(|member| member.id == id)
+    }
+
+    /// Whether the members in `ids` make a majority of the group.
+    fn is_quorum(&self, ids: impl Fn(NodeId) -> bool) -> bool {
+        let count = self.members.iter().filter(|member| ids(member.id)).count();
+        count * 2 > self.members.len()
+    }
+
+    /// Opens connections to new members, and forgets removed ones.
+    pub(super) fn sync_peers(&mut self, id: NodeId) {
+        let mut peers = HashMap::new();
+        for member in self.members.iter().filter(|member| member.id != id) {
+            let peer = match self.peers.remove(&member.id) {
+                Some(peer) if peer.addr == member.addr => peer,
+                _ => Arc::new(Peer::new(member.addr.clone())),
+            };
+            peers.insert(member.id, peer);
+        }
+        self.peers = peers;
+    }
+
+    fn reset_election_deadline(&mut self) {
+        self.election_deadline = Instant::now() + election_timeout();
+    }
+}
+
+impl Raft {
+    pub(super) async fn tick_loop(self: Arc<Self>) {
+        let mut interval = time::interval(TICK_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.tick();
+        }
+    }
+
+    /// Stands for election once the election timeout passed without
+    /// hearing from a leader. A leader that has not heard from a majority
+    /// for as long steps down, it may have been replaced.
+    fn tick(self: &Arc<Self>) {
+        let mut core = self.core.lock().unwrap();
+
+        if core.role == Role::Leader {
+            let recent = |id| {
+                id == self.id
+                    || core
+                        .progress
+                        .get(&id)
+                        .is_some_and(|progress| progress.last_ack.elapsed() < ELECTION_TIMEOUT)
+            };
+            if !core.is_quorum(recent) {
+                let term = core.hard.term;
+                self.step_down(&mut core, term);
+            }
+            return;
+        }
+
+        if Instant::now() < core.election_deadline {
+            return;
+        }
+        core.reset_election_deadline();
+
+        // Nodes waiting to join, or removed, never stand.
+        if !core.is_member(self.id) {
+            return;
+        }
+
+        core.hard = HardState {
+            term: core.hard.term + 1,
+            voted_for: Some(self.id),
+        };
+        self.persist(&core);
+        core.role = Role::Candidate;
+        core.leader = None;
+        core.votes = HashSet::from([self.id]);
+
+        if core.is_quorum(|id| id == self.id) {
+            self.become_leader(&mut core);
+            return;
+        }
+
+        let term = core.hard.term;
+        let request = VoteRequest {
+            term,
+            candidate: self.id,
+            last_index: core.log.last_index(),
+            last_term: core.log.last_term(),
+        }
+        .into_frame();
+
+        for (&id, peer) in &core.peers {
+            let raft = Arc::clone(self);
+            let peer = Arc::clone(peer);
+            let request = request.clone();
+            self.spawn(async move {
+                if let Ok(reply) = peer.call(&request, RPC_TIMEOUT).await {
+                    raft.handle_vote_reply(id, term, reply);
+                }
+            });
+        }
+    }
+
+    fn handle_vote_reply(self: &Arc<Self>, id: NodeId, term: u64, reply: Reply) {
+        let mut core = self.core.lock().unwrap();
+        if reply.term > core.hard.term {
+            self.step_down(&mut core, reply.term);
+            return;
+        }
+        if core.role != Role::Candidate || core.hard.term != term || !reply.success {
+            return;
+        }
+
+        core.votes.insert(id);
+        if core.is_quorum(|id| core.votes.contains(&id)) {
+            self.become_leader(&mut core);
+        }
+    }
+
+    fn become_leader(self: &Arc<Self>, core: &mut Core) {
+        core.role = Role::Leader;
+        core.leader = Some(self.id);
+        core.progress.clear();
+        let next_index = core.log.last_index() + 1;
+        let ids: Vec<NodeId> = core.peers.keys().copied().collect();
+        for id in ids {
+            self.add_progress(core, id, next_index);
+        }
+
+        // Entries of earlier terms are only known to be committed once an
+        // entry of this term is.
+        self.append(core, EntryKind::Noop);
+        self.changed();
+    }
+
+    fn add_progress(self: &Arc<Self>, core: &mut Core, id: NodeId, next_index: u64) {
+        let progress = Progress {
+            next_index,
+            match_index: 0,
+            round: 0,
+            last_ack: Instant::now(),
+            wake: Arc::new(Notify::new()),
+        };
+        core.progress.insert(id, progress);
+        self.spawn(Arc::clone(self).replicate(id, core.hard.term));
+    }
+
+    /// Becomes a follower, of `term` if it is a newer one. Clients waiting
+    /// for their writes are told the outcome is unknown.
+    fn step_down(&self, core: &mut Core, term: u64) {
+        if term > core.hard.term {
+            core.hard = HardState {
+                term,
+                voted_for: None,
+            };
+            self.persist(core);
+        }
+
+        if core.role == Role::Leader {
+            core.leader = None;
+            for (_, waiter) in self.waiters.lock().unwrap().drain() {
+                let _ = waiter.reply.send(Frame::Error(
+                    "TRYAGAIN leadership changed, the write may or may not be applied".to_string(),
+                ));
+            }
+        }
+
+        core.role = Role::Follower;
+        core.progress.clear();
+        core.reset_election_deadline();
+        self.changed();
+    }
+
+    /// Appends an entry to the leader's log, returning its index.
+    fn append(self: &Arc<Self>, core: &mut Core, kind: EntryKind) -> u64 {
+        let index = core.log.last_index() + 1;
+        let entry = LogEntry {
+            term: core.hard.term,
+            index,
+            kind,
+        };
+
+        let config = match &entry.kind {
+            EntryKind::Config(members) => Some(members.clone()),
+            _ => None,
+        };
+        if let Err(err) = core.log.append(vec![entry]) {
+            fatal(err);
+        }
+
+        // A configuration takes effect as soon as it is in the log.
+        if let Some(members) = config {
+            core.members = members;
+            core.sync_peers(self.id);
+            core.progress.retain(|id, _| core.peers.contains_key(id));
+            let new: Vec<NodeId> = core
+                .peers
+                .keys()
+                .filter(|id| !core.progress.contains_key(id))
+                .copied()
+                .collect();
+            for id in new {
+                self.add_progress(core, id, index);
+            }
+        }
+
+        for progress in core.progress.values() {
+            progress.wake.notify_one();
+        }
+        // The entry counts for this node once on disk.
+        if !core.syncing {
+            core.syncing = true;
+            self.spawn(Arc::clone(self).sync_loop());
+        }
+        index
+    }
+
+    /// Syncs the leader's log for as long as entries are appended to it,
+    /// committing them as they reach the disk.
+    async fn sync_loop(self: Arc<Self>) {
+        loop {
+            let sync = {
+                let mut core = self.core.lock().unwrap();
+                match core.log.pending_sync() {
+                    Ok(Some(sync)) => sync,
+                    Ok(None) => {
+                        core.syncing = false;
+                        return;
+                    }
+                    Err(err) => fatal(err),
+                }
+            };
+            let sync = disk(move || sync.run()).await;
+
+            let mut core = self.core.lock().unwrap();
+            core.log.synced(sync);
+            self.advance_commit(&mut core);
+        }
+    }
+
+    /// Waits until the entries up to `index` are on disk, or gone from the
+    /// log.
+    async fn sync_log(&self, index: u64) {
+        loop {
+            let sync = {
+                let core = self.core.lock().unwrap();
+                if core.log.synced_index() >= index.min(core.log.last_index()) {
+                    return;
+                }
+                match core.log.pending_sync() {
+                    Ok(Some(sync)) => sync,
+                    Ok(None) => return,
+                    Err(err) => fatal(err),
+                }
+            };
+            let sync = disk(move || sync.run()).await;
+            self.core.lock().unwrap().log.synced(sync);
+        }
+    }
+
+    /// Submits an entry as leader, replying once it is applied.
+    pub(super) async fn submit(self: &Arc<Self>, kind: EntryKind) -> Frame {
+        let reply = {
+            let mut core = self.core.lock().unwrap();
+            if core.role != Role::Leader {
+                return self.not_leader(&core);
+            }
+            if matches!(kind, EntryKind::Config(_)) && core.log.config_index() > core.commit_index {
+                return Frame::Error(
+                    "TRYAGAIN a membership change is already in progress".to_string(),
+                );
+            }
+
+            let (reply, rx) = oneshot::channel();
+            let waiter = Waiter {
+                term: core.hard.term,
+                reply,
+            };
+            let index = core.log.last_index() + 1;
+            self.waiters.lock().unwrap().insert(index, waiter);
+            self.append(&mut core, kind);
+            rx
+        };
+
+        reply.await.unwrap_or_else(|_| {
+            Frame::Error("TRYAGAIN leadership changed, the write may or may not be applied".into())
+        })
+    }
+
+    /// Waits until reads can be served: this node is still the leader, as
+    /// confirmed by a majority after the read arrived, and it applied every
+    /// entry committed by then.
+    pub(super) async fn read_barrier(self: &Arc<Self>) -> Result<(), Frame> {
+        let deadline = time::Instant::now() + ELECTION_TIMEOUT;
+        let mut changes = self.changes.subscribe();
+
+        let (term, round) = {
+            let mut core = self.core.lock().unwrap();
+            if core.role != Role::Leader {
+                return Err(self.not_leader(&core));
+            }
+            core.round += 1;
+            for progress in core.progress.values() {
+                progress.wake.notify_one();
+            }
+            (core.hard.term, core.round)
+        };
+
+        let read_index = loop {
+            {
+                let core = self.core.lock().unwrap();
+                if core.role != Role::Leader || core.hard.term != term {
+                    return Err(self.not_leader(&core));
+                }
+
+                let acked = |id| {
+                    id == self.id
+                        || core
+                            .progress
+                            .get(&id)
+                            .is_some_and(|progress| progress.round >= round)
+                };
+                // Until an entry of its term is committed, the leader may
+                // not know of every committed entry.
+                if core.log.term_at(core.commit_index) == Some(term) && core.is_quorum(acked) {
+                    break core.commit_index;
+                }
+            }
+
+            if time::timeout_at(deadline, changes.changed()).await.is_err() {
+                return Err(timed_out());
+            }
+        };
+
+        let mut applied = self.applied.subscribe();
+        let res = time::timeout_at(deadline, applied.wait_for(|&index| index >= read_index)).await;
+        match res {
+            Ok(Ok(_)) => Ok(()),
+            _ => Err(timed_out()),
+        }
+    }
+
+    /// Sends entries, or the snapshot, to a follower for as long as this
+    /// node leads in `term`.
+    async fn replicate(self: Arc<Self>, id: NodeId, term: u64) {
+        loop {
+            let (peer, wake, request) = {
+                let core = self.core.lock().unwrap();
+                if core.role != Role::Leader || core.hard.term != term {
+                    return;
+                }
+                let (progress, peer) = match (core.progress.get(&id), core.peers.get(&id)) {
+                    (Some(progress), Some(peer)) => (progress, Arc::clone(peer)),
+                    _ => return,
+                };
+
+                let request = if progress.next_index <= core.log.snapshot_index() {
+                    None
+                } else {
+                    let prev_index = progress.next_index - 1;
+                    let request = AppendRequest {
+                        term,
+                        leader: self.id,
+                        prev_index,
+                        prev_term: core.log.term_at(prev_index).unwrap_or(0),
+                        commit: core.commit_index,
+                        entries: core.log.entries_from(progress.next_index, MAX_BATCH),
+                    };
+                    Some((request, core.round))
+                };
+                (peer, Arc::clone(&progress.wake), request)
+            };
+
+            let res = match request {
+                Some((request, round)) => {
+                    let prev_index = request.prev_index;
+                    let sent = request.entries.len() as u64;
+                    let reply = peer.call(&request.into_frame(), RPC_TIMEOUT).await;
+                    reply.map(|reply| {
+                        self.handle_append_reply(id, term, prev_index + sent, round, reply)
+                    })
+                }
+                None => self.send_snapshot(id, term, &peer).await,
+            };
+
+            match res {
+                Ok(true) => {}
+                Ok(false) => {
+                    let _ = time::timeout(HEARTBEAT_INTERVAL, wake.notified()).await;
+                }
+                Err(_) => time::sleep(HEARTBEAT_INTERVAL).await,
+            }
+        }
+    }
+
+    async fn send_snapshot(&self, id: NodeId, term: u64, peer: &Peer) -> crate::Result<bool> {
+        let snapshot = Snapshot::load(&self.dir)?.ok_or("snapshot missing")?;
+        let index = snapshot.index;
+        let request = SnapshotRequest {
+            term,
+            leader: self.id,
+            snapshot,
+        };
+        let reply = peer.call(&request.into_frame(), SNAPSHOT_TIMEOUT).await?;
+
+        let mut core = self.core.lock().unwrap();
+        if reply.term > core.hard.term {
+            self.step_down(&mut core, reply.term);
+            return Ok(false);
+        }
+        if let Some(progress) = core.progress.get_mut(&id) {
+            progress.match_index = progress.match_index.max(index);
+            progress.next_index = progress.match_index + 1;
+            progress.last_ack = Instant::now();
+        }
+        Ok(true)
+    }
+
+    /// Takes in the reply to `RAFT.APPEND` with entries up to `last_sent`,
+    /// returning whether there is more to send right away.
+    fn handle_append_reply(
+        &self,
+        id: NodeId,
+        term: u64,
+        last_sent: u64,
+        round: u64,
+        reply: Reply,
+    ) -> bool {
+        let mut core = self.core.lock().unwrap();
+        if reply.term > core.hard.term {
+            self.step_down(&mut core, reply.term);
+            return false;
+        }
+        if core.role != Role::Leader || core.hard.term != term {
+            return false;
+        }
+
+        let last_index = core.log.last_index();
+        let progress = match core.progress.get_mut(&id) {
+            Some(progress) => progress,
+            None => return false,
+        };
+        progress.last_ack = Instant::now();
+        progress.round = progress.round.max(round);
+
+        if !reply.success {
+            // Back up to where the follower's log may agree, at most to
+            // the end of it.
+            progress.next_index = (progress.next_index - 1).min(reply.match_index + 1).max(1);
+            self.changed();
+            return true;
+        }
+
+        progress.match_index = progress.match_index.max(last_sent);
+        progress.next_index = progress.match_index + 1;
+        let more = progress.next_index <= last_index;
+        self.advance_commit(&mut core);
+        self.changed();
+        more
+    }
+
+    /// Commits the entries of the current term stored by a majority, and
+    /// those before them.
+    fn advance_commit(&self, core: &mut Core) {
+        if core.role != Role::Leader || core.members.is_empty() {
+            return;
+        }
+
+        let mut matched: Vec<u64> = core
+            .members
+            .iter()
+            .map(|member| match core.progress.get(&member.id) {
+                _ if member.id == self.id => core.log.synced_index(),
+                Some(progress) => progress.match_index,
+                None => 0,
+            })
+            .collect();
+        matched.sort_unstable_by(|a, b| b.cmp(a));
+        let index = matched[matched.len() / 2];
+
+        if index <= core.commit_index || core.log.term_at(index) != Some(core.hard.term) {
+            return;
+        }
+        core.commit_index = index;
+        self.committed.notify_one();
+        self.changed();
+
+        // A leader removed from the group leads until the change commits.
+        if !core.is_member(self.id) && core.log.config_index() <= index {
+            let term = core.hard.term;
+            self.step_down(core, term);
+        }
+    }
+
+    pub(super) fn handle_vote(self: &Arc<Self>, request: VoteRequest) -> Reply {
+        let mut core = self.core.lock().unwrap();
+
+        // Members that heard from a leader lately ignore candidates, so a
+        // node removed from the group cannot disrupt it.
+        let leader_alive = core.role == Role::Leader
+            || core
+                .last_heard
+                .is_some_and(|at| at.elapsed() < ELECTION_TIMEOUT);
+        if request.term < core.hard.term || (leader_alive && request.term > core.hard.term) {
+            return self.reply(&core, false, 0);
+        }
+        if request.term > core.hard.term {
+            self.step_down(&mut core, request.term);
+        }
+
+        let up_to_date = (request.last_term, request.last_index)
+            >= (core.log.last_term(), core.log.last_index());
+        let free = core.hard.voted_for.is_none_or(|id| id == request.candidate);
+        if !up_to_date || !free {
+            return self.reply(&core, false, 0);
+        }
+
+        core.hard.voted_for = Some(request.candidate);
+        self.persist(&core);
+        core.reset_election_deadline();
+        self.reply(&core, true, 0)
+    }
+
+    /// Appends the leader's entries, replying once they are on disk.
+    pub(super) async fn handle_append(self: &Arc<Self>, request: AppendRequest) -> Reply {
+        let reply = self.append_entries(request);
+        if !reply.success {
+            return reply;
+        }
+        self.sync_log(reply.match_index).await;
+
+        // A newer leader may have replaced the entries meanwhile.
+        let core = self.core.lock().unwrap();
+        if core.hard.term != reply.term {
+            return self.reply(&core, false, 0);
+        }
+        reply
+    }
+
+    fn append_entries(self: &Arc<Self>, request: AppendRequest) -> Reply {
+        let mut core = self.core.lock().unwrap();
+        if request.term < core.hard.term {
+            let last_index = core.log.last_index();
+            return self.reply(&core, false, last_index);
+        }
+        self.follow(&mut core, request.term, request.leader);
+
+        let last_index = core.log.last_index();
+        if request.prev_index > last_index {
+            return self.reply(&core, false, last_index);
+        }
+        // Entries covered by the snapshot are committed, they agree with
+        // the leader's.
+        if request.prev_index >= core.log.snapshot_index()
+            && core.log.term_at(request.prev_index) != Some(request.prev_term)
+        {
+            return self.reply(&core, false, request.prev_index - 1);
+        }
+
+        let last_new = request.prev_index + request.entries.len() as u64;
+        let mut new = vec![];
+        let mut reconfigured = false;
+        for entry in request.entries {
+            if entry.index <= core.log.snapshot_index() {
+                continue;
+            }
+            match core.log.term_at(entry.index) {
+                Some(term) if term == entry.term => continue,
+                Some(_) => {
+                    if let Err(err) = core.log.truncate_from(entry.index) {
+                        fatal(err);
+                    }
+                    reconfigured = true;
+                }
+                None => {}
+            }
+            reconfigured |= matches!(entry.kind, EntryKind::Config(_));
+            new.push(entry);
+        }
+
+        if !new.is_empty() {
+            if let Err(err) = core.log.append(new) {
+                fatal(err);
+            }
+        }
+        if reconfigured {
+            core.members = core
+                .log
+                .config_at(core.log.last_index(), &core.snapshot_config);
+            core.sync_peers(self.id);
+        }
+
+        let commit_index = request.commit.min(last_new);
+        if commit_index > core.commit_index {
+            core.commit_index = commit_index;
+            self.committed.notify_one();
+        }
+
+        self.reply(&core, true, last_new)
+    }
+
+    /// Saves the leader's snapshot, to be loaded by the applier.
+    pub(super) async fn handle_snapshot(self: &Arc<Self>, request: SnapshotRequest) -> Reply {
+        {
+            let mut core = self.core.lock().unwrap();
+            if request.term < core.hard.term {
+                return self.reply(&core, false, 0);
+            }
+            self.follow(&mut core, request.term, request.leader);
+            if request.snapshot.index <= core.commit_index {
+                let commit_index = core.commit_index;
+                return self.reply(&core, true, commit_index);
+            }
+        }
+
+        let _saving = self.snapshots.lock().await;
+        {
+            let core = self.core.lock().unwrap();
+            if request.snapshot.index <= core.log.snapshot_index() {
+                let commit_index = core.commit_index;
+                return self.reply(&core, true, commit_index);
+            }
+        }
+        let snapshot = request.snapshot;
+        let dir = self.dir.clone();
+        let snapshot = disk(move || snapshot.save(&dir).map(|()| snapshot)).await;
+
+        // The log follows the snapshot on disk. The keyspace only does if
+        // it is behind, entries may have been committed meanwhile.
+        let reply = {
+            let mut core = self.core.lock().unwrap();
+            if let Err(err) = core.log.compact(snapshot.index, snapshot.term) {
+                fatal(err);
+            }
+            core.snapshot_config = snapshot.config.clone();
+            core.members = core
+                .log
+                .config_at(core.log.last_index(), &core.snapshot_config);
+            core.sync_peers(self.id);
+
+            if snapshot.index > core.commit_index {
+                core.commit_index = snapshot.index;
+                core.pending_snapshot = Some(snapshot);
+                self.committed.notify_one();
+            }
+            let commit_index = core.commit_index;
+            self.reply(&core, request.term == core.hard.term, commit_index)
+        };
+
+        self.rewrite_log().await;
+        reply
+    }
+
+    /// Takes `leader` as the leader of `term`, which is at least the
+    /// current one.
+    fn follow(&self, core: &mut Core, term: u64, leader: NodeId) {
+        if term > core.hard.term || core.role != Role::Follower {
+            self.step_down(core, term);
+        }
+        core.leader = Some(leader);
+        core.last_heard = Some(Instant::now());
+        core.reset_election_deadline();
+    }
+
+    fn reply(&self, core: &Core, success: bool, match_index: u64) -> Reply {
+        Reply {
+            term: core.hard.term,
+            success,
+            match_index,
+        }
+    }
+
+    /// Runs committed entries against the keyspace, in log order, and hands
+    /// the replies to the clients waiting for them.
+    pub(super) async fn apply_loop(self: Arc<Self>, state: Arc<ServerState>) {
+        let mut applied = 0;
+
+        loop {
+            let work = {
+                let mut core = self.core.lock().unwrap();
+                if let Some(snapshot) = core.pending_snapshot.take() {
+                    Some(Apply::Install(snapshot))
+                } else if applied < core.commit_index {
+                    let mut entries = core.log.entries_from(applied + 1, MAX_BATCH);
+                    entries.retain(|entry| entry.index <= core.commit_index);
+                    Some(Apply::Entries(entries))
+                } else {
+                    None
+                }
+            };
+
+            match work {
+                None => {
+                    self.committed.notified().await;
+                    continue;
+                }
+                Some(Apply::Install(snapshot)) => {
+                    let res = state
+                        .run_exclusive(|| snapshot::load_keyspace(&state, &snapshot.data))
+                        .await;
+                    if let Err(err) = res {
+                        fatal(err);
+                    }
+                    applied = snapshot.index;
+                }
+                Some(Apply::Entries(entries)) => {
+                    for entry in entries {
+                        let reply = match entry.kind {
+                            EntryKind::Command { db, args } => state.apply_logged(db, args).await,
+                            _ => Frame::ok(),
+                        };
+                        applied = entry.index;
+                        self.resolve(entry.index, entry.term, reply);
+                    }
+                }
+            }
+
+            self.applied.send_replace(applied);
+            self.changed();
+
+            let snapshot_index = self.core.lock().unwrap().log.snapshot_index();
+            if applied >= snapshot_index + SNAPSHOT_THRESHOLD {
+                self.take_snapshot(&state, applied).await;
+            }
+        }
+    }
+
+    fn resolve(&self, index: u64, term: u64, reply: Frame) {
+        let waiter = match self.waiters.lock().unwrap().remove(&index) {
+            Some(waiter) => waiter,
+            None => return,
+        };
+
+        let reply = if waiter.term == term {
+            reply
+        } else {
+            Frame::Error("TRYAGAIN leadership changed, the write was not applied".to_string())
+        };
+        let _ = waiter.reply.send(reply);
+    }
+
+    /// Saves the keyspace as of entry `applied` and drops the entries it
+    /// covers from the log.
+    ///
+    /// The keyspace is serialized and saved on threads where blocking is
+    /// fine, without holding up the node.
+    async fn take_snapshot(&self, state: &Arc<ServerState>, applied: u64) {
+        let _saving = self.snapshots.lock().await;
+        let (term, config) = {
+            let core = self.core.lock().unwrap();
+            // A snapshot from the leader may have been installed meanwhile.
+            if core.log.snapshot_index() >= applied {
+                return;
+            }
+            match core.log.term_at(applied) {
+                Some(term) => (term, core.log.config_at(applied, &core.snapshot_config)),
+                None => return,
+            }
+        };
+
+        // Only the applier writes to the keyspace, apart from expiration.
+        let keyspace = Arc::clone(state);
+        let data = state
+            .run_shared_blocking(Scope::All, move || snapshot::save_keyspace(&keyspace))
+            .await;
+        let snapshot = Snapshot {
+            index: applied,
+            term,
+            config: config.clone(),
+            data,
+        };
+        let dir = self.dir.clone();
+        disk(move || snapshot.save(&dir)).await;
+
+        {
+            let mut core = self.core.lock().unwrap();
+            if let Err(err) = core.log.compact(applied, term) {
+                fatal(err);
+            }
+            core.snapshot_config = config;
+        }
+        self.rewrite_log().await;
+    }
+
+    /// Drops the entries covered by the snapshot from the log file, by
+    /// writing a copy of the rest. The copy catches up with the entries
+    /// appended meanwhile, up to `MAX_REWRITE_ROUNDS` times. The caller
+    /// holds `snapshots`.
+    async fn rewrite_log(&self) {
+        let mut rewrite = match self.core.lock().unwrap().log.start_rewrite() {
+            Some(rewrite) => rewrite,
+            None => return,
+        };
+
+        for _ in 0..MAX_REWRITE_ROUNDS {
+            let written = disk(move || rewrite.write()).await;
+            match self.core.lock().unwrap().log.finish_rewrite(written) {
+                Ok(Some(rest)) => rewrite = rest,
+                Ok(None) => return,
+                Err(err) => fatal(err),
+            }
+        }
+        disk(move || rewrite.discard()).await;
+    }
+
+    fn not_leader(&self, core: &Core) -> Frame {
+        let leader = core
+            .leader
+            .filter(|&id| id != self.id)
+            .and_then(|id| core.members.iter().find(|member| member.id == id));
+
+        match leader {
+            Some(leader) => Frame::Error(format!("NOTLEADER {}", leader.addr)),
+            None => Frame::Error("TRYAGAIN no leader elected yet".to_string()),
+        }
+    }
+
+    fn persist(&self, core: &Core) {
+        if let Err(err) = core.hard.save(&self.dir) {
+            fatal(err);
+        }
+    }
+
+    fn changed(&self) {
+        self.changes.send_modify(|changes| *changes += 1);
+    }
+}
+
+/// Runs disk I/O of the node on a thread where blocking is fine.
+async fn disk<R: Send + 'static>(f: impl FnOnce() -> io::Result<R> + Send + 'static) -> R {
+    match task::spawn_blocking(f).await {
+        Ok(Ok(res)) => res,
+        Ok(Err(err)) => fatal(err),
+        Err(err) => std::panic::resume_unwind(err.into_panic()),
+    }
+}
+
+fn timed_out() -> Frame {
+    Frame::Error("TRYAGAIN could not confirm leadership in time".to_string())
+}
+
+/// Raft cannot go on without its state on disk, nor can the server without
+/// Raft.
+fn fatal(err: impl Display) -> ! {
+    eprintln!("raft: cannot write state: {}", err);
+    std::process::exit(1);
+}
@@ -0,0 +1,523 @@
+use bytes::{Buf, BufMut, Bytes};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use super::{Member, NodeId};
+use crate::encoding::{get_bytes, get_u32, get_u64, get_u8, put_bytes};
+
+/// Where `Rewrite` writes the copy of the log, apart from the temporary
+/// files of `write_atomically`.
+const REWRITE_FILE: &str = "log.rewrite";
+
+/// A record of the replicated log.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LogEntry {
+    pub(crate) term: u64,
+    pub(crate) index: u64,
+    pub(crate) kind: EntryKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum EntryKind {
+    /// Appended by a new leader, committing the entries of earlier terms
+    /// along with it.
+    Noop,
+    /// A command, with the database it runs against.
+    Command { db: usize, args: Vec<Bytes> },
+    /// The members of the group from this entry on.
+    Config(Vec<Member>),
+}
+
+/// Vote and term, which must survive a restart for elections to be safe.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct HardState {
+    pub(crate) term: u64,
+    pub(crate) voted_for: Option<NodeId>,
+}
+
+/// The entries following the latest snapshot, in memory and in a file of
+/// length-prefixed records.
+///
+/// The log is kept under the node's lock, so the slow parts of writing it
+/// are done apart: appended entries are synced with `PendingSync`, and the
+/// entries compacted away are dropped from the file with `Rewrite`.
+#[derive(Debug)]
+pub(crate) struct Log {
+    /// Index and term of the last entry covered by the snapshot, 0 without
+    /// one.
+    snapshot_index: u64,
+    snapshot_term: u64,
+    entries: Vec<LogEntry>,
+    path: PathBuf,
+    file: File,
+    /// Index of the last entry known to be on disk.
+    synced: u64,
+    /// Bumped each time the file is replaced, syncs of the previous one no
+    /// longer count then.
+    generation: u64,
+    /// Whether the file was renamed into place since the directory was last
+    /// synced, its entries are not safe until it is.
+    dir_dirty: bool,
+    /// Whether the file still holds entries covered by the snapshot.
+    stale: bool,
+}
+
+/// A sync of the log file, run without holding the log.
+#[derive(Debug)]
+pub(crate) struct PendingSync {
+    file: File,
+    /// The directory, when the file was renamed into place since it was
+    /// last synced.
+    dir: Option<PathBuf>,
+    /// Last entry written to the file when the sync started.
+    index: u64,
+    generation: u64,
+}
+
+/// A copy of the log file without the entries covered by the snapshot,
+/// written without holding the log, then renamed into place by
+/// `Log::finish_rewrite`.
+#[derive(Debug)]
+pub(crate) struct Rewrite {
+    data: Vec<u8>,
+    dir: PathBuf,
+    /// Last entry in `data`, and last one covered by the snapshot then.
+    index: u64,
+    snapshot_index: u64,
+    generation: u64,
+    /// Whether `data` follows what was written already.
+    started: bool,
+}
+
+impl LogEntry {
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.put_u64(self.term);
+        buf.put_u64(self.index);
+        match &self.kind {
+            EntryKind::Noop => buf.put_u8(0),
+            EntryKind::Command { db, args } => {
+                buf.put_u8(1);
+                buf.put_u32(*db as u32);
+                buf.put_u32(args.len() as u32);
+                for arg in args {
+                    put_bytes(buf, arg);
+                }
+            }
+            EntryKind::Config(members) => {
+                buf.put_u8(2);
+                put_members(buf, members);
+            }
+        }
+    }
+
+    pub(crate) fn decode(buf: &mut &[u8]) -> crate::Result<LogEntry> {
+        let term = get_u64(buf)?;
+        let index = get_u64(buf)?;
+        let kind = match get_u8(buf)? {
+            0 => EntryKind::Noop,
+            1 => {
+                let db = get_u32(buf)? as usize;
+                let len = get_u32(buf)?;
+                let args = (0..len).map(|_| get_bytes(buf)).collect::<Result<_, _>>()?;
+                EntryKind::Command { db, args }
+            }
+            2 => EntryKind::Config(get_members(buf)?),
+            kind => return Err(format!("unknown log entry kind {}", kind).into()),
+        };
+
+        Ok(LogEntry { term, index, kind })
+    }
+}
+
+impl HardState {
+    /// Reads the state saved in `dir`, the default one if there is none.
+    pub(crate) fn load(dir: &Path) -> crate::Result<HardState> {
+        let data = match fs::read(dir.join("state")) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HardState::default()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut buf = &data[..];
+        let term = get_u64(&mut buf)?;
+        let voted_for = get_u64(&mut buf)?;
+        Ok(HardState {
+            term,
+            voted_for: Some(voted_for).filter(|&id| id != 0),
+        })
+    }
+
+    pub(crate) fn save(&self, dir: &Path) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(16);
+        buf.put_u64(self.term);
+        buf.put_u64(self.voted_for.unwrap_or(0));
+        write_atomically(dir, "state", &buf)
+    }
+}
+
+impl Log {
+    /// Opens the log kept in `dir`, following a snapshot that ends at
+    /// `snapshot_index`. A record cut short by a crash is dropped.
+    pub(crate) fn open(dir: &Path, snapshot_index: u64, snapshot_term: u64) -> crate::Result<Log> {
+        let path = dir.join("log");
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)?;
+        let mut data = vec![];
+        file.read_to_end(&mut data)?;
+
+        let mut entries = vec![];
+        let mut stale = false;
+        let mut buf = &data[..];
+        while buf.len() >= 4 {
+            let len = (&buf[..4]).get_u32() as usize;
+            if buf.len() < 4 + len {
+                break;
+            }
+            let mut record = &buf[4..4 + len];
+            let entry = LogEntry::decode(&mut record)?;
+            buf.advance(4 + len);
+
+            // Entries may be left over from before the snapshot, when the
+            // process stopped before compacting the log.
+            if entry.index > snapshot_index {
+                entries.push(entry);
+            } else {
+                stale = true;
+            }
+        }
+
+        let valid = data.len() - buf.len();
+        if valid < data.len() {
+            file.set_len(valid as u64)?;
+        }
+
+        let synced = entries.last().map_or(snapshot_index, |entry| entry.index);
+        let log = Log {
+            snapshot_index,
+            snapshot_term,
+            entries,
+            path,
+            file,
+            synced,
+            generation: 0,
+            dir_dirty: false,
+            stale,
+        };
+
+        for (i, entry) in log.entries.iter().enumerate() {
+            if entry.index != snapshot_index + 1 + i as u64 {
+                return Err(format!("log entry {} out of place", entry.index).into());
+            }
+        }
+
+        Ok(log)
+    }
+
+    pub(crate) fn snapshot_index(&self) -> u64 {
+        self.snapshot_index
+    }
+
+    pub(crate) fn last_index(&self) -> u64 {
+        self.entries
+            .last()
+            .map_or(self.snapshot_index, |entry| entry.index)
+    }
+
+    pub(crate) fn last_term(&self) -> u64 {
+        self.entries
+            .last()
+            .map_or(self.snapshot_term, |entry| entry.term)
+    }
+
+    /// Term of the entry at `index`, `None` when it is past the end of the
+    /// log or compacted away, save for the last entry of the snapshot.
+    pub(crate) fn term_at(&self, index: u64) -> Option<u64> {
+        if index == self.snapshot_index {
+            return Some(self.snapshot_term);
+        }
+
+        self.get(index).map(|entry| entry.term)
+    }
+
+    pub(crate) fn get(&self, index: u64) -> Option<&LogEntry> {
+        if index <= self.snapshot_index {
+            return None;
+        }
+
+        self.entries.get((index - self.snapshot_index - 1) as usize)
+    }
+
+    /// Up to `max` entries from `index` on, which must not be compacted.
+    pub(crate) fn entries_from(&self, index: u64, max: usize) -> Vec<LogEntry> {
+        let start = (index - self.snapshot_index - 1) as usize;
+        self.entries.iter().skip(start).take(max).cloned().collect()
+    }
+
+    /// The latest configuration at or before `index`, `fallback` being the
+    /// one of the snapshot.
+    pub(crate) fn config_at(&self, index: u64, fallback: &[Member]) -> Vec<Member> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.index <= index)
+            .find_map(|entry| match &entry.kind {
+                EntryKind::Config(members) => Some(members.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(|| fallback.to_vec())
+    }
+
+    /// Index of the latest configuration entry, 0 if the configuration
+    /// comes from the snapshot.
+    pub(crate) fn config_index(&self) -> u64 {
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| matches!(entry.kind, EntryKind::Config(_)))
+            .map_or(0, |entry| entry.index)
+    }
+
+    /// Index of the last entry known to be on disk.
+    pub(crate) fn synced_index(&self) -> u64 {
+        self.synced
+    }
+
+    /// Appends entries following the last one. They are written to the
+    /// file, but only on disk once synced, see `pending_sync`.
+    pub(crate) fn append(&mut self, entries: Vec<LogEntry>) -> io::Result<()> {
+        let mut buf = vec![];
+        for entry in &entries {
+            put_record(&mut buf, entry);
+        }
+
+        self.file.write_all(&buf)?;
+        self.entries.extend(entries);
+        Ok(())
+    }
+
+    /// A sync of the entries written so far, `None` if they are on disk.
+    pub(crate) fn pending_sync(&self) -> io::Result<Option<PendingSync>> {
+        if self.synced >= self.last_index() && !self.dir_dirty {
+            return Ok(None);
+        }
+
+        Ok(Some(PendingSync {
+            file: self.file.try_clone()?,
+            dir: self.dir_dirty.then(|| self.dir().to_path_buf()),
+            index: self.last_index(),
+            generation: self.generation,
+        }))
+    }
+
+    /// Takes note of a sync that completed.
+    pub(crate) fn synced(&mut self, sync: PendingSync) {
+        if sync.generation != self.generation {
+            return;
+        }
+        self.synced = self.synced.max(sync.index);
+        if sync.dir.is_some() {
+            self.dir_dirty = false;
+        }
+    }
+
+    /// Writes and syncs the entries appended so far, blocking.
+    pub(crate) fn sync(&mut self) -> io::Result<()> {
+        if let Some(sync) = self.pending_sync()? {
+            let sync = sync.run()?;
+            self.synced(sync);
+        }
+
+        Ok(())
+    }
+
+    /// Removes the entry at `index` and those after it, which conflict with
+    /// the leader's.
+    pub(crate) fn truncate_from(&mut self, index: u64) -> io::Result<()> {
+        self.entries
+            .truncate((index - self.snapshot_index - 1) as usize);
+        self.rewrite()
+    }
+
+    /// Drops the entries covered by a snapshot taken up to `index`, which
+    /// has `term`. The whole log goes when it does not reach `index` or
+    /// disagrees with the snapshot.
+    ///
+    /// Entries covered by the snapshot are skipped when opening the log, so
+    /// they only leave the file with `start_rewrite`. Conflicting entries
+    /// are removed right away.
+    pub(crate) fn compact(&mut self, index: u64, term: u64) -> io::Result<()> {
+        let agrees = self.term_at(index) == Some(term);
+        if agrees {
+            let covered = (index - self.snapshot_index) as usize;
+            self.entries.drain(..covered);
+        } else {
+            self.entries.clear();
+        }
+
+        self.snapshot_index = index;
+        self.snapshot_term = term;
+        self.synced = self.synced.max(index);
+        if agrees {
+            self.stale = true;
+            Ok(())
+        } else {
+            self.rewrite()
+        }
+    }
+
+    /// A copy of the file without the entries covered by the snapshot,
+    /// `None` if there are none.
+    pub(crate) fn start_rewrite(&self) -> Option<Rewrite> {
+        if !self.stale {
+            return None;
+        }
+
+        let mut data = vec![];
+        for entry in &self.entries {
+            put_record(&mut data, entry);
+        }
+        Some(Rewrite {
+            data,
+            dir: self.dir().to_path_buf(),
+            index: self.last_index(),
+            snapshot_index: self.snapshot_index,
+            generation: self.generation,
+            started: false,
+        })
+    }
+
+    /// Puts a copy written by `Rewrite::write` in place of the file, once
+    /// it holds every entry. Otherwise the copy is returned with the entries
+    /// appended since, to be written in turn. It is dropped if the file was
+    /// replaced meanwhile.
+    pub(crate) fn finish_rewrite(&mut self, mut rewrite: Rewrite) -> io::Result<Option<Rewrite>> {
+        if rewrite.generation != self.generation {
+            rewrite.discard()?;
+            return Ok(None);
+        }
+
+        if rewrite.index < self.last_index() {
+            rewrite.data.clear();
+            for entry in self
+                .entries
+                .iter()
+                .filter(|entry| entry.index > rewrite.index)
+            {
+                put_record(&mut rewrite.data, entry);
+            }
+            rewrite.index = self.last_index();
+            rewrite.started = true;
+            return Ok(Some(rewrite));
+        }
+
+        // Every entry is on disk in both files, whichever one a crash
+        // leaves. Entries appended from now on are not safe until the
+        // directory is synced.
+        fs::rename(rewrite.dir.join(REWRITE_FILE), &self.path)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.generation += 1;
+        self.dir_dirty = true;
+        // The log may have been compacted again meanwhile.
+        self.stale = self.snapshot_index > rewrite.snapshot_index;
+        Ok(None)
+    }
+
+    fn dir(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new("."))
+    }
+
+    /// Replaces the file with the entries in memory.
+    fn rewrite(&mut self) -> io::Result<()> {
+        let mut buf = vec![];
+        for entry in &self.entries {
+            put_record(&mut buf, entry);
+        }
+
+        write_atomically(self.dir(), "log", &buf)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.generation += 1;
+        self.synced = self.last_index();
+        self.dir_dirty = false;
+        self.stale = false;
+        Ok(())
+    }
+}
+
+impl PendingSync {
+    /// Syncs the file, blocking.
+    pub(crate) fn run(self) -> io::Result<PendingSync> {
+        self.file.sync_data()?;
+        if let Some(dir) = &self.dir {
+            File::open(dir)?.sync_all()?;
+        }
+
+        Ok(self)
+    }
+}
+
+impl Rewrite {
+    /// Writes the copy next to the file, or the entries added to it, and
+    /// syncs it, blocking.
+    pub(crate) fn write(self) -> io::Result<Rewrite> {
+        let path = self.dir.join(REWRITE_FILE);
+        let mut file = if self.started {
+            OpenOptions::new().append(true).open(path)?
+        } else {
+            File::create(path)?
+        };
+        file.write_all(&self.data)?;
+        file.sync_all()?;
+
+        Ok(self)
+    }
+
+    /// Gives up on the copy, blocking.
+    pub(crate) fn discard(self) -> io::Result<()> {
+        match fs::remove_file(self.dir.join(REWRITE_FILE)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn put_record(buf: &mut Vec<u8>, entry: &LogEntry) {
+    let start = buf.len();
+    buf.put_u32(0);
+    entry.encode(buf);
+    let len = (buf.len() - start - 4) as u32;
+    buf[start..start + 4].copy_from_slice(&len.to_be_bytes());
+}
+
+/// Replaces `dir/name` with `data`, so that a crash leaves either the old or
+/// the new contents.
+pub(crate) fn write_atomically(dir: &Path, name: &str, data: &[u8]) -> io::Result<()> {
+    let tmp = dir.join(format!("{}.tmp", name));
+    let mut file = File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&tmp, dir.join(name))?;
+    File::open(dir)?.sync_all()
+}
+
+pub(crate) fn put_members(buf: &mut Vec<u8>, members: &[Member]) {
+    buf.put_u32(members.len() as u32);
+    for member in members {
+        buf.put_u64(member.id);
+        put_bytes(buf, member.addr.as_bytes());
+    }
+}
+
+pub(crate) fn get_members(buf: &mut &[u8]) -> crate::Result<Vec<Member>> {
+    let len = get_u32(buf)?;
+    (0..len)
+        .map(|_| {
+            let id = get_u64(buf)?;
+            let addr = String::from_utf8(get_bytes(buf)?.to_vec())?;
+            Ok(Member { id, addr })
+        })
+        .collect()
+}
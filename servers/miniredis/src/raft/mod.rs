@@ -0,0 +1,540 @@
+//! Replication of writes among a group of servers with Raft, so that an
+//! acknowledged write survives the loss of a minority of them.
+//!
+//! Commands that write are appended to a log by the leader and run by every
+//! member, in log order, once a majority stored them. The client gets its
+//! reply when the leader ran the command. Reads are served by the leader
+//! after it confirmed with a majority that it still leads, so they see every
+//! write acknowledged before them. Writes and reads sent to other members
+//! are answered with `-NOTLEADER <addr>`, naming the leader.
+//!
+//! Members talk to each other on their Redis port, with `RAFT.VOTE`,
+//! `RAFT.APPEND` and `RAFT.SNAPSHOT`. Administrators use `RAFT INFO`,
+//! `RAFT NODES`, and `RAFT ADDNODE id addr` and `RAFT REMOVENODE id` to
+//! change the members one at a time. A node joining the group starts with
+//! no peers and waits for the leader to reach it. `RAFT PROMOTE` has a
+//! member stand for election right away, for failover tools.
+//!
+//! Term, vote, log and snapshots are kept on disk, the log being synced
+//! before entries are acknowledged. The log is compacted into a snapshot of
+//! the keyspace once it grows past `SNAPSHOT_THRESHOLD` entries.
+//!
+//! `MULTI` is refused, scripts run atomically and are replicated instead.
+//! Keys expire on each member by its own clock. Relative times to live given
+//! to `SET`, `EXPIRE`, `PEXPIRE` and `RESTORE` are turned into Unix times by
+//! the leader before the command goes into the log, so members and replays
+//! of the log agree on when keys expire. Those set by scripts are still
+//! counted from when each member runs the script. Memcached clients bypass
+//! the log, the two cannot be combined.
+
+mod consensus;
+mod log;
+mod rpc;
+mod snapshot;
+
+use bytes::Bytes;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, watch, Notify};
+use tokio::task::JoinSet;
+
+use crate::cmd::{self, unknown_subcommand, Command};
+use crate::db;
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+use crate::session::Session;
+use crate::state::{self, ServerState};
+use consensus::{Core, Role};
+use log::{EntryKind, HardState, Log, LogEntry};
+use rpc::{AppendRequest, SnapshotRequest, VoteRequest};
+use snapshot::Snapshot;
+
+/// Identifies a member of the group, from 1 up.
+pub type NodeId = u64;
+
+/// Entries applied past the latest snapshot before a new one is taken.
+const SNAPSHOT_THRESHOLD: u64 = 10_000;
+
+/// How long followers wait without hearing from a leader before they stand
+/// for election, at least. A random delay of up to as much again is added
+/// so that candidates rarely split the votes.
+const ELECTION_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// A member of the group and the address of its Redis listener.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub id: NodeId,
+    pub addr: String,
+}
+
+/// Identity and storage of a server replicating with Raft.
+#[derive(Debug, Clone)]
+pub struct RaftConfig {
+    pub id: NodeId,
+    /// Directory holding the state, log and snapshot of the node.
+    pub dir: PathBuf,
+    /// Initial members of the group, this node included, used when `dir`
+    /// holds no state yet. Empty for a node to be added to a running group.
+    pub peers: Vec<Member>,
+}
+
+/// The Raft node of a server, see the module documentation.
+#[derive(Debug)]
+pub struct Raft {
+    id: NodeId,
+    dir: PathBuf,
+    core: Mutex<Core>,
+    /// Woken when entries are committed or a snapshot is to be installed.
+    committed: Notify,
+    /// Index of the last entry run against the keyspace.
+    applied: watch::Sender<u64>,
+    /// Bumped on changes to acknowledgements, commit index and role, for
+    /// reads waiting on them.
+    changes: watch::Sender<u64>,
+    /// Clients waiting for their write, by log index.
+    waiters: Mutex<HashMap<u64, Waiter>>,
+    /// Held while saving a snapshot and compacting the log.
+    snapshots: tokio::sync::Mutex<()>,
+    tasks: Mutex<JoinSet<()>>,
+}
+
+/// A client waiting for the entry it submitted to be applied.
+#[derive(Debug)]
+struct Waiter {
+    /// Term the entry was appended in. Another entry ending up at the same
+    /// index means the write was lost.
+    term: u64,
+    reply: oneshot::Sender<Frame>,
+}
+
+/// Requests members send each other.
+enum Rpc {
+    Vote(VoteRequest),
+    Append(AppendRequest),
+    Snapshot(SnapshotRequest),
+}
+
+/// `RAFT` sub-commands, for administrators.
+#[derive(Debug)]
+enum RaftCmd {
+    /// `RAFT INFO`
+    Info,
+    /// `RAFT NODES`
+    Nodes,
+    /// `RAFT ADDNODE id addr`
+    AddNode(Member),
+    /// `RAFT REMOVENODE id`
+    RemoveNode(NodeId),
+    /// `RAFT PROMOTE`
+    Promote,
+}
+
+impl RaftConfig {
+    /// Parses a list of members such as `1=127.0.0.1:7001,2=127.0.0.1:7002`.
+    pub fn parse_peers(list: &str) -> crate::Result<Vec<Member>> {
+        list.split(',')
+            .filter(|member| !member.is_empty())
+            .map(|member| {
+                let (id, addr) = member
+                    .split_once('=')
+                    .ok_or_else(|| format!("invalid member '{}', expected id=addr", member))?;
+                Ok(Member {
+                    id: parse_id(id)?,
+                    addr: addr.to_string(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Raft {
+    /// Reads the state kept in `config.dir`, creating it for a new node.
+    pub fn open(config: RaftConfig) -> crate::Result<Arc<Raft>> {
+        if config.id == 0 {
+            return Err("raft node ids start at 1".into());
+        }
+        fs::create_dir_all(&config.dir)?;
+
+        let hard = HardState::load(&config.dir)?;
+        let snapshot = Snapshot::load(&config.dir)?;
+        let (snapshot_index, snapshot_term, snapshot_config) = match &snapshot {
+            Some(snapshot) => (snapshot.index, snapshot.term, snapshot.config.clone()),
+            None => (0, 0, vec![]),
+        };
+        let mut log = Log::open(&config.dir, snapshot_index, snapshot_term)?;
+
+        // Every founding member starts with the same first entry, so their
+        // logs agree on it from the start.
+        if log.last_index() == 0 && !config.peers.is_empty() {
+            if !config.peers.iter().any(|member| member.id == config.id) {
+                return Err("the initial members must include this node".into());
+            }
+            log.append(vec![LogEntry {
+                term: 0,
+                index: 1,
+                kind: EntryKind::Config(config.peers.clone()),
+            }])?;
+            log.sync()?;
+        }
+
+        let members = log.config_at(log.last_index(), &snapshot_config);
+        let mut core = Core {
+            hard,
+            role: Role::Follower,
+            leader: None,
+            log,
+            commit_index: snapshot_index,
+            snapshot_config,
+            members,
+            peers: HashMap::new(),
+            election_deadline: Instant::now() + election_timeout(),
+            last_heard: None,
+            votes: HashSet::new(),
+            progress: HashMap::new(),
+            round: 0,
+            // Loaded into the keyspace before any entry is applied.
+            pending_snapshot: snapshot,
+            syncing: false,
+        };
+        core.sync_peers(config.id);
+
+        Ok(Arc::new(Raft {
+            id: config.id,
+            dir: config.dir,
+            core: Mutex::new(core),
+            committed: Notify::new(),
+            applied: watch::Sender::new(0),
+            changes: watch::Sender::new(0),
+            waiters: Mutex::new(HashMap::new()),
+            snapshots: tokio::sync::Mutex::new(()),
+            tasks: Mutex::new(JoinSet::new()),
+        }))
+    }
+
+    /// Starts taking part in elections and applying committed entries to
+    /// the keyspace of `state`.
+    pub fn start(self: &Arc<Self>, state: Arc<ServerState>) {
+        self.spawn(Arc::clone(self).tick_loop());
+        self.spawn(Arc::clone(self).apply_loop(state));
+    }
+
+    /// Stops every background task of the node.
+    pub fn stop(&self) {
+        self.tasks.lock().unwrap().abort_all();
+    }
+
+    /// Runs a command received from the client of `session`: through the
+    /// log if it writes, after confirming leadership if it reads, right
+    /// away if it only concerns this server or the connection.
+    pub(crate) async fn execute(
+        self: &Arc<Self>,
+        state: &ServerState,
+        session: &mut Session,
+        frame: Frame,
+    ) -> Option<Frame> {
+        let mut args = state::command_args(&frame);
+        let name = args
+            .first()
+            .map(|name| String::from_utf8_lossy(name).to_lowercase())
+            .unwrap_or_default();
+
+        match &name[..] {
+            "raft" => return Some(self.admin(frame).await),
+            "raft.vote" | "raft.append" | "raft.snapshot" => {
+                return Some(self.rpc(&name, frame).await)
+            }
+            _ => {}
+        }
+
+        // Commands refused to subscribed clients are refused as usual.
+        if session.is_subscribed() && !session.outbox.is_resp3() {
+            return state.execute(session, frame).await;
+        }
+
+        // Syntax errors are reported as usual too.
+        let cmd = match Command::from_frame(frame.clone(), &state.modules) {
+            Ok(cmd) => cmd,
+            Err(_) => return state.execute(session, frame).await,
+        };
+
+        if cmd.is_transaction_control() {
+            return Some(Frame::Error(
+                "ERR MULTI is not supported when replicating with Raft, use EVAL".to_string(),
+            ));
+        }
+
+        // Every member would move the keys on applying it.
+        if let Command::Migrate(_) = cmd {
+            return Some(Frame::Error(
+                "ERR MIGRATE is not supported when replicating with Raft, use DUMP and RESTORE"
+                    .to_string(),
+            ));
+        }
+
+        if cmd.is_node_local() {
+            return state.execute(session, frame).await;
+        }
+
+        if !cmd.is_replicated() {
+            return match self.read_barrier().await {
+                Ok(()) => state.execute(session, frame).await,
+                Err(err) => Some(err),
+            };
+        }
+
+        if cmd.is_denied_when_oom() && !state.free_memory() {
+            return Some(Frame::Error(
+                "OOM command not allowed when used memory > 'maxmemory'.".to_string(),
+            ));
+        }
+
+        // Members may not have the script cached, the log gets its body.
+        if name == "evalsha" && args.len() > 1 {
+            match state.scripts.get(&String::from_utf8_lossy(&args[1])) {
+                Some(body) => {
+                    args[0] = Bytes::from_static(b"EVAL");
+                    args[1] = body;
+                }
+                None => {
+                    return Some(Frame::Error(
+                        "NOSCRIPT No matching script. Please use EVAL.".to_string(),
+                    ))
+                }
+            }
+        }
+
+        absolute_ttls(&name, &mut args, db::now_ms());
+
+        Some(
+            self.submit(EntryKind::Command {
+                db: session.db,
+                args,
+            })
+            .await,
+        )
+    }
+
+    async fn admin(self: &Arc<Self>, frame: Frame) -> Frame {
+        let cmd = match RaftCmd::from_frame(frame) {
+            Ok(cmd) => cmd,
+            Err(err) => return cmd::error_frame(&err),
+        };
+
+        match cmd {
+            RaftCmd::Info => Frame::Bulk(self.info().into()),
+            RaftCmd::Nodes => {
+                let core = self.core.lock().unwrap();
+                Frame::Array(
+                    core.members
+                        .iter()
+                        .map(|member| {
+                            let role = if core.leader == Some(member.id) {
+                                "leader"
+                            } else {
+                                "follower"
+                            };
+                            Frame::Array(vec![
+                                Frame::Integer(member.id as i64),
+                                Frame::Bulk(member.addr.clone().into()),
+                                Frame::Bulk(role.into()),
+                            ])
+                        })
+                        .collect(),
+                )
+            }
+            RaftCmd::AddNode(member) => {
+                let mut members = self.core.lock().unwrap().members.clone();
+                if members.iter().any(|m| m.id == member.id) {
+                    return Frame::Error(format!("ERR node {} is already a member", member.id));
+                }
+                members.push(member);
+                self.submit(EntryKind::Config(members)).await
+            }
+            RaftCmd::RemoveNode(id) => {
+                let mut members = self.core.lock().unwrap().members.clone();
+                if !members.iter().any(|m| m.id == id) {
+                    return Frame::Error(format!("ERR node {} is not a member", id));
+                }
+                if members.len() == 1 {
+                    return Frame::Error("ERR cannot remove the last member".to_string());
+                }
+                members.retain(|m| m.id != id);
+                self.submit(EntryKind::Config(members)).await
+            }
+            RaftCmd::Promote => {
+                let mut core = self.core.lock().unwrap();
+                if !core.is_member(self.id) {
+                    return Frame::Error("ERR this node is not a member".to_string());
+                }
+                // Members that still hear from a leader turn the election
+                // down, the node stays a follower then.
+                if core.role != Role::Leader {
+                    core.election_deadline = Instant::now();
+                }
+                Frame::ok()
+            }
+        }
+    }
+
+    /// Handles a request from another member.
+    async fn rpc(self: &Arc<Self>, name: &str, frame: Frame) -> Frame {
+        let res = (|| -> crate::Result<Rpc> {
+            let mut parse = Parse::new(frame)?;
+            parse.next_string()?;
+
+            let rpc = match name {
+                "raft.vote" => Rpc::Vote(VoteRequest::parse_frames(&mut parse)?),
+                "raft.append" => Rpc::Append(AppendRequest::parse_frames(&mut parse)?),
+                _ => Rpc::Snapshot(SnapshotRequest::parse_frames(&mut parse)?),
+            };
+            parse.finish()?;
+            Ok(rpc)
+        })();
+
+        let reply = match res {
+            Ok(Rpc::Vote(request)) => self.handle_vote(request),
+            Ok(Rpc::Append(request)) => self.handle_append(request).await,
+            Ok(Rpc::Snapshot(request)) => self.handle_snapshot(request).await,
+            Err(err) => return cmd::error_frame(&err),
+        };
+        reply.into_frame()
+    }
+
+    fn info(&self) -> String {
+        let core = self.core.lock().unwrap();
+        let role = match core.role {
+            Role::Follower => "follower",
+            Role::Candidate => "candidate",
+            Role::Leader => "leader",
+        };
+        let members: Vec<String> = core
+            .members
+            .iter()
+            .map(|member| format!("{}={}", member.id, member.addr))
+            .collect();
+
+        let mut info = String::new();
+        let _ = write!(
+            info,
+            "node_id:{}\r\nrole:{}\r\nterm:{}\r\nleader_id:{}\r\nmembers:{}\r\ncommit_index:{}\r\nlast_applied:{}\r\nlast_log_index:{}\r\nlast_log_term:{}\r\nsnapshot_index:{}\r\n",
+            self.id,
+            role,
+            core.hard.term,
+            core.leader.unwrap_or(0),
+            members.join(","),
+            core.commit_index,
+            *self.applied.borrow(),
+            core.log.last_index(),
+            core.log.last_term(),
+            core.log.snapshot_index(),
+        );
+        info
+    }
+
+    fn spawn(&self, task: impl std::future::Future<Output = ()> + Send + 'static) {
+        let mut tasks = self.tasks.lock().unwrap();
+        // Reap finished tasks so the set does not grow forever.
+        while tasks.try_join_next().is_some() {}
+        tasks.spawn(task);
+    }
+}
+
+impl RaftCmd {
+    /// Parses a `RAFT` command, name included.
+    fn from_frame(frame: Frame) -> crate::Result<RaftCmd> {
+        let mut parse = Parse::new(frame)?;
+        parse.next_string()?;
+        let sub_command = parse.next_string()?.to_lowercase();
+
+        let cmd = match &sub_command[..] {
+            "info" => RaftCmd::Info,
+            "nodes" => RaftCmd::Nodes,
+            "addnode" => RaftCmd::AddNode(Member {
+                id: parse_id(&parse.next_string()?)?,
+                addr: parse.next_string()?,
+            }),
+            "removenode" => RaftCmd::RemoveNode(parse_id(&parse.next_string()?)?),
+            "promote" => RaftCmd::Promote,
+            _ => return Err(unknown_subcommand(&sub_command, "RAFT").into()),
+        };
+        parse.finish()?;
+
+        Ok(cmd)
+    }
+}
+
+fn parse_id(id: &str) -> crate::Result<NodeId> {
+    match id.parse::<NodeId>() {
+        Ok(id) if id > 0 => Ok(id),
+        _ => Err(format!("ERR invalid node id '{}'", id).into()),
+    }
+}
+
+/// Rewrites the relative times to live of command `name` into Unix times
+/// in milliseconds, as of `now`: `SET ... EX|PX` into `PXAT`, `EXPIRE` and
+/// `PEXPIRE` into `PEXPIREAT`, and `RESTORE` gets `ABSTTL`.
+///
+/// The command was parsed already, arguments that do not parse are left
+/// alone all the same.
+fn absolute_ttls(name: &str, args: &mut Vec<Bytes>, now: u64) {
+    let at = |arg: &Bytes, unit: i64| -> Option<Bytes> {
+        let ttl: i64 = std::str::from_utf8(arg).ok()?.parse().ok()?;
+        let at = now.saturating_add_signed(ttl.saturating_mul(unit));
+        Some(at.to_string().into())
+    };
+
+    match name {
+        "set" => {
+            let mut i = 3;
+            while i + 1 < args.len() {
+                let option = args[i].to_ascii_uppercase();
+                match &option[..] {
+                    b"EX" | b"PX" => {
+                        let unit = if &option[..] == b"EX" { 1000 } else { 1 };
+                        if let Some(at) = at(&args[i + 1], unit) {
+                            args[i] = Bytes::from_static(b"PXAT");
+                            args[i + 1] = at;
+                        }
+                        i += 2;
+                    }
+                    b"EXAT" | b"PXAT" => i += 2,
+                    _ => i += 1,
+                }
+            }
+        }
+        "expire" | "pexpire" if args.len() > 2 => {
+            let unit = if name == "expire" { 1000 } else { 1 };
+            if let Some(at) = at(&args[2], unit) {
+                args[0] = Bytes::from_static(b"PEXPIREAT");
+                args[2] = at;
+            }
+        }
+        "restore" if args.len() > 3 => {
+            let absolute = args[4..]
+                .iter()
+                .any(|arg| arg.eq_ignore_ascii_case(b"ABSTTL"));
+            // 0 stands for no time to live.
+            if !absolute && &args[2][..] != b"0" {
+                if let Some(at) = at(&args[2], 1) {
+                    args[2] = at;
+                    args.push(Bytes::from_static(b"ABSTTL"));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A randomized election timeout, see `ELECTION_TIMEOUT`.
+fn election_timeout() -> Duration {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u8(0);
+    let jitter = hasher.finish() % ELECTION_TIMEOUT.as_millis() as u64;
+    ELECTION_TIMEOUT + Duration::from_millis(jitter)
+}
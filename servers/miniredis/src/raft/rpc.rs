@@ -0,0 +1,227 @@
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time;
+
+use super::log::LogEntry;
+use super::snapshot::Snapshot;
+use super::NodeId;
+use crate::encoding::get_u32;
+use crate::protocol::connection::Connection;
+use crate::protocol::frame::Frame;
+use crate::protocol::parse::Parse;
+
+/// Another member of the group, reached on its Redis port.
+#[derive(Debug)]
+pub(crate) struct Peer {
+    pub(crate) addr: String,
+    /// Kept open between calls, reopened after a failed one.
+    connection: Mutex<Option<Connection>>,
+}
+
+/// `RAFT.VOTE term candidate last-index last-term`
+#[derive(Debug)]
+pub(crate) struct VoteRequest {
+    pub(crate) term: u64,
+    pub(crate) candidate: NodeId,
+    pub(crate) last_index: u64,
+    pub(crate) last_term: u64,
+}
+
+/// `RAFT.APPEND term leader prev-index prev-term commit entries`, with the
+/// entries encoded in a single argument. Sent without entries as a
+/// heartbeat.
+#[derive(Debug)]
+pub(crate) struct AppendRequest {
+    pub(crate) term: u64,
+    pub(crate) leader: NodeId,
+    pub(crate) prev_index: u64,
+    pub(crate) prev_term: u64,
+    pub(crate) commit: u64,
+    pub(crate) entries: Vec<LogEntry>,
+}
+
+/// `RAFT.SNAPSHOT term leader snapshot`, for a follower missing entries
+/// the leader compacted.
+#[derive(Debug)]
+pub(crate) struct SnapshotRequest {
+    pub(crate) term: u64,
+    pub(crate) leader: NodeId,
+    pub(crate) snapshot: Snapshot,
+}
+
+/// Replies are arrays of integers: the term of the peer first, then
+/// whether the vote was granted, or whether the entries were appended
+/// along with the last index known to match the leader's log.
+#[derive(Debug)]
+pub(crate) struct Reply {
+    pub(crate) term: u64,
+    pub(crate) success: bool,
+    pub(crate) match_index: u64,
+}
+
+impl Peer {
+    pub(crate) fn new(addr: String) -> Peer {
+        Peer {
+            addr,
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Sends a request and waits for the reply, giving up after `timeout`.
+    pub(crate) async fn call(&self, request: &Frame, timeout: Duration) -> crate::Result<Reply> {
+        let mut connection = self.connection.lock().await;
+
+        let res = time::timeout(timeout, async {
+            if connection.is_none() {
+                let socket = TcpStream::connect(&self.addr).await?;
+                socket.set_nodelay(true)?;
+                *connection = Some(Connection::new(socket));
+            }
+
+            let conn = connection.as_mut().unwrap();
+            conn.write_frame(request).await?;
+            match conn.read_frame().await? {
+                Some(frame) => Reply::from_frame(frame),
+                None => Err("connection closed by peer".into()),
+            }
+        })
+        .await;
+
+        match res {
+            Ok(Ok(reply)) => Ok(reply),
+            Ok(Err(err)) => {
+                *connection = None;
+                Err(err)
+            }
+            // A late reply would be taken for the next one.
+            Err(_) => {
+                *connection = None;
+                Err("request timed out".into())
+            }
+        }
+    }
+}
+
+impl VoteRequest {
+    /// The `RAFT.VOTE` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<VoteRequest> {
+        Ok(VoteRequest {
+            term: next_u64(parse)?,
+            candidate: next_u64(parse)?,
+            last_index: next_u64(parse)?,
+            last_term: next_u64(parse)?,
+        })
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        command(
+            "RAFT.VOTE",
+            [self.term, self.candidate, self.last_index, self.last_term],
+            None,
+        )
+    }
+}
+
+impl AppendRequest {
+    /// The `RAFT.APPEND` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<AppendRequest> {
+        let term = next_u64(parse)?;
+        let leader = next_u64(parse)?;
+        let prev_index = next_u64(parse)?;
+        let prev_term = next_u64(parse)?;
+        let commit = next_u64(parse)?;
+
+        let data = parse.next_bytes()?;
+        let mut buf = &data[..];
+        let entries = (0..get_u32(&mut buf)?)
+            .map(|_| LogEntry::decode(&mut buf))
+            .collect::<Result<_, _>>()?;
+
+        Ok(AppendRequest {
+            term,
+            leader,
+            prev_index,
+            prev_term,
+            commit,
+            entries,
+        })
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut data = (self.entries.len() as u32).to_be_bytes().to_vec();
+        for entry in &self.entries {
+            entry.encode(&mut data);
+        }
+
+        command(
+            "RAFT.APPEND",
+            [
+                self.term,
+                self.leader,
+                self.prev_index,
+                self.prev_term,
+                self.commit,
+            ],
+            Some(data.into()),
+        )
+    }
+}
+
+impl SnapshotRequest {
+    /// The `RAFT.SNAPSHOT` string has already been consumed.
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SnapshotRequest> {
+        Ok(SnapshotRequest {
+            term: next_u64(parse)?,
+            leader: next_u64(parse)?,
+            snapshot: Snapshot::decode(&parse.next_bytes()?)?,
+        })
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        command(
+            "RAFT.SNAPSHOT",
+            [self.term, self.leader],
+            Some(self.snapshot.encode().into()),
+        )
+    }
+}
+
+impl Reply {
+    pub(crate) fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Integer(self.term as i64),
+            Frame::Integer(self.success as i64),
+            Frame::Integer(self.match_index as i64),
+        ])
+    }
+
+    fn from_frame(frame: Frame) -> crate::Result<Reply> {
+        if let Frame::Error(message) = frame {
+            return Err(message.into());
+        }
+
+        let mut parse = Parse::new(frame)?;
+        Ok(Reply {
+            term: next_u64(&mut parse)?,
+            success: next_u64(&mut parse)? != 0,
+            match_index: next_u64(&mut parse)?,
+        })
+    }
+}
+
+fn command<const N: usize>(name: &str, numbers: [u64; N], data: Option<Bytes>) -> Frame {
+    let mut parts = vec![Frame::Bulk(Bytes::from(name.to_string()))];
+    parts.extend(
+        numbers
+            .iter()
+            .map(|n| Frame::Bulk(Bytes::from(n.to_string()))),
+    );
+    parts.extend(data.map(Frame::Bulk));
+    Frame::Array(parts)
+}
+
+fn next_u64(parse: &mut Parse) -> crate::Result<u64> {
+    Ok(u64::try_from(parse.next_int()?)?)
+}
@@ -0,0 +1,112 @@
+use bytes::{BufMut, Bytes};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::log::{get_members, put_members, write_atomically};
+use super::Member;
+use crate::dump;
+use crate::encoding::{get_bytes, get_u32, get_u64, put_bytes};
+use crate::state::ServerState;
+
+/// The state machine as of a log entry, replacing the entries up to it.
+#[derive(Debug, Clone)]
+pub(crate) struct Snapshot {
+    /// Index and term of the last entry applied.
+    pub(crate) index: u64,
+    pub(crate) term: u64,
+    /// Members of the group as of that entry.
+    pub(crate) config: Vec<Member>,
+    /// The keyspace and function libraries, see `save_keyspace`.
+    pub(crate) data: Bytes,
+}
+
+impl Snapshot {
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.data.len() + 64);
+        buf.put_u64(self.index);
+        buf.put_u64(self.term);
+        put_members(&mut buf, &self.config);
+        put_bytes(&mut buf, &self.data);
+        buf
+    }
+
+    pub(crate) fn decode(mut buf: &[u8]) -> crate::Result<Snapshot> {
+        let buf = &mut buf;
+        Ok(Snapshot {
+            index: get_u64(buf)?,
+            term: get_u64(buf)?,
+            config: get_members(buf)?,
+            data: get_bytes(buf)?,
+        })
+    }
+
+    /// Reads the snapshot kept in `dir`, if one was taken.
+    pub(crate) fn load(dir: &Path) -> crate::Result<Option<Snapshot>> {
+        match fs::read(dir.join("snapshot")) {
+            Ok(data) => Ok(Some(Snapshot::decode(&data)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub(crate) fn save(&self, dir: &Path) -> io::Result<()> {
+        write_atomically(dir, "snapshot", &self.encode())
+    }
+}
+
+/// Serializes every database and function library, each key as its
+/// `DUMP` payload.
+pub(crate) fn save_keyspace(state: &ServerState) -> Bytes {
+    let mut buf = vec![];
+
+    let libraries = state.scripts.libraries(None);
+    buf.put_u32(libraries.len() as u32);
+    for library in libraries {
+        put_bytes(&mut buf, &library.code);
+    }
+
+    for index in 0..state.dbs.len() {
+        let db = state.dbs.get(index);
+        for key in db.keys() {
+            let entry = match db.get_entry(&key) {
+                Some(entry) => entry,
+                None => continue,
+            };
+
+            buf.put_u32(index as u32);
+            put_bytes(&mut buf, &key);
+            put_bytes(&mut buf, &dump::serialize(&entry));
+        }
+    }
+
+    buf.into()
+}
+
+/// Replaces every database and function library with those serialized by
+/// `save_keyspace`. The caller holds the server's exclusive lock.
+pub(crate) fn load_keyspace(state: &ServerState, mut data: &[u8]) -> crate::Result<()> {
+    let buf = &mut data;
+    state.dbs.flush_all(false);
+    state.scripts.flush_libraries();
+
+    for _ in 0..get_u32(buf)? {
+        state.scripts.load_library(get_bytes(buf)?, true)?;
+    }
+
+    while !buf.is_empty() {
+        let index = get_u32(buf)? as usize;
+        let key = get_bytes(buf)?;
+        let entry = dump::deserialize(&get_bytes(buf)?, &state.modules)?;
+        if index >= state.dbs.len() {
+            return Err(format!("snapshot refers to database {}", index).into());
+        }
+
+        state
+            .dbs
+            .get(index)
+            .update_entry(&key, |mut slot| *slot = Some(entry));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,889 @@
+//! Server-side scripting in Lua, with the `redis.call` API of Redis.
+//!
+//! Scripts are either run with `EVAL`, or grouped in libraries loaded with
+//! `FUNCTION LOAD` and called with `FCALL`. Commands called by a script run
+//! one after the other without commands of other clients on its keys in
+//! between.
+//!
+//! A script running for longer than `busy-reply-threshold` makes the server
+//! busy: other clients get `BUSY` replies until it ends or is stopped with
+//! `SCRIPT KILL` or `FUNCTION KILL`, which only works on scripts that did
+//! not write yet.
+
+use bytes::Bytes;
+use mlua::{
+    Function, HookTriggers, Lua, LuaOptions, RegistryKey, StdLib, Table, Value, Variadic,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::cmd::{self, Command};
+use crate::config::Config;
+use crate::logging::{self, LogLevel};
+use crate::pattern;
+use crate::protocol::frame::Frame;
+use crate::pubsub::ClientId;
+use crate::session::Session;
+use crate::state::ServerState;
+
+thread_local! {
+    /// Lua state of the thread, shared by the scripts it runs. Each run gets
+    /// its own global environment, so scripts do not see each other's
+    /// globals.
+    static LUA: Lua = new_lua().expect("failed to set up Lua");
+}
+
+/// Functions building the per-run parts of the `redis` API, in Lua as they
+/// raise Lua values as errors.
+const HELPERS: &str = r#"
+local helpers = {}
+
+-- `redis.call` is `redis.pcall` raising error replies.
+function helpers.make_call(pcall)
+    return function(...)
+        local reply = pcall(...)
+        if type(reply) == 'table' and reply.err then
+            error(reply, 0)
+        end
+        return reply
+    end
+end
+
+function helpers.make_register(registered)
+    return function(name, callback)
+        local flags = {}
+        if type(name) == 'table' then
+            callback = name.callback
+            flags = name.flags or {}
+            name = name.function_name
+        end
+        if type(name) ~= 'string' or type(callback) ~= 'function' then
+            error('wrong arguments given to redis.register_function', 2)
+        end
+        if registered[name] then
+            error('Function ' .. name .. ' already exists', 2)
+        end
+        registered[name] = { callback = callback, flags = flags }
+    end
+end
+
+return helpers
+"#;
+
+/// Lua instructions between two checks of a running script, for being busy
+/// or killed.
+const HOOK_INSTRUCTIONS: u32 = 10_000;
+
+/// Handed out to libraries as they are loaded, so a library replaced under
+/// the same name is not mistaken for the one compiled before.
+static NEXT_LIBRARY_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Flags a function may be registered with.
+const FUNCTION_FLAGS: [&str; 5] = [
+    "no-writes",
+    "allow-oom",
+    "allow-stale",
+    "no-cluster",
+    "allow-cross-slot-keys",
+];
+
+/// Scripts and function libraries known to the server, and the scripts
+/// running.
+#[derive(Debug)]
+pub struct Scripts {
+    /// Bodies of the scripts run with `EVAL` or loaded, by SHA1.
+    scripts: Mutex<HashMap<String, Bytes>>,
+    libraries: Mutex<Libraries>,
+    /// Scripts and functions running, by client.
+    running: Mutex<HashMap<ClientId, Arc<Running>>>,
+    /// Number of running scripts past `busy-reply-threshold`.
+    busy: Arc<AtomicUsize>,
+    config: Arc<Config>,
+}
+
+#[derive(Debug, Default)]
+struct Libraries {
+    libraries: BTreeMap<String, Library>,
+    /// Library registering each function.
+    functions: HashMap<String, String>,
+}
+
+/// Functions loaded together with `FUNCTION LOAD`.
+#[derive(Debug, Clone)]
+pub struct Library {
+    pub name: String,
+    pub code: Bytes,
+    pub functions: Vec<FunctionInfo>,
+    /// Tells this load of the library apart from others, see
+    /// `NEXT_LIBRARY_ID`.
+    id: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionInfo {
+    pub name: String,
+    pub flags: Vec<String>,
+}
+
+/// What a run of the Lua interpreter calls.
+enum Entry<'a> {
+    Script(&'a [u8]),
+    Function(&'a Library, &'a str),
+}
+
+/// A script or function being run, as seen by the Lua hook checking on it
+/// and by `SCRIPT KILL` and `FUNCTION KILL`.
+#[derive(Debug)]
+struct Running {
+    function: bool,
+    started: Instant,
+    /// Set once past `busy-reply-threshold`.
+    busy: AtomicBool,
+    killed: AtomicBool,
+    /// Scripts that wrote cannot be killed, their writes would be half done.
+    wrote: AtomicBool,
+}
+
+/// Libraries compiled by the Lua state of a thread, by name, kept in its
+/// app data.
+#[derive(Default)]
+struct Compiled {
+    libraries: HashMap<String, CompiledLibrary>,
+}
+
+struct CompiledLibrary {
+    id: u64,
+    /// The functions the library registered.
+    registered: RegistryKey,
+    /// The `redis` table of the library's environment, where `redis.call`
+    /// is bound for each call.
+    redis: RegistryKey,
+}
+
+impl Scripts {
+    pub fn new(config: Arc<Config>) -> Scripts {
+        Scripts {
+            scripts: Mutex::default(),
+            libraries: Mutex::default(),
+            running: Mutex::default(),
+            busy: Arc::new(AtomicUsize::new(0)),
+            config,
+        }
+    }
+
+    /// Whether a script has been running for longer than
+    /// `busy-reply-threshold`.
+    pub fn is_busy(&self) -> bool {
+        self.busy.load(Ordering::Relaxed) > 0
+    }
+
+    /// Stops the running scripts, or functions with `function`, replying as
+    /// `SCRIPT KILL` and `FUNCTION KILL` do.
+    pub fn kill(&self, function: bool) -> Frame {
+        let running = self.running.lock().unwrap();
+        let mut targets = running
+            .values()
+            .filter(|running| running.function == function)
+            .peekable();
+
+        if targets.peek().is_none() {
+            return Frame::Error("NOTBUSY No scripts in execution right now.".to_string());
+        }
+
+        let mut killed = false;
+        for running in targets.filter(|running| !running.wrote.load(Ordering::Relaxed)) {
+            running.killed.store(true, Ordering::Relaxed);
+            killed = true;
+        }
+
+        if killed {
+            Frame::ok()
+        } else {
+            Frame::Error("UNKILLABLE Sorry the script already executed write commands against the dataset. You can either wait the script termination or restart the server.".to_string())
+        }
+    }
+
+    fn start(&self, client: ClientId, function: bool) -> Arc<Running> {
+        let running = Arc::new(Running {
+            function,
+            started: Instant::now(),
+            busy: AtomicBool::new(false),
+            killed: AtomicBool::new(false),
+            wrote: AtomicBool::new(false),
+        });
+        self.running
+            .lock()
+            .unwrap()
+            .insert(client, Arc::clone(&running));
+        running
+    }
+
+    fn finish(&self, client: ClientId) {
+        if let Some(running) = self.running.lock().unwrap().remove(&client) {
+            if running.busy.load(Ordering::Relaxed) {
+                self.busy.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Caches `body`, returning its SHA1.
+    pub fn load(&self, body: Bytes) -> String {
+        let sha = sha1_hex(&body);
+        self.scripts.lock().unwrap().insert(sha.clone(), body);
+        sha
+    }
+
+    pub fn get(&self, sha: &str) -> Option<Bytes> {
+        self.scripts
+            .lock()
+            .unwrap()
+            .get(&sha.to_ascii_lowercase())
+            .cloned()
+    }
+
+    pub fn contains(&self, sha: &str) -> bool {
+        self.get(sha).is_some()
+    }
+
+    pub fn flush(&self) {
+        self.scripts.lock().unwrap().clear();
+    }
+
+    /// Loads a library of functions, replying with its name. The code starts
+    /// with a `#!lua name=<library>` line and registers functions with
+    /// `redis.register_function`.
+    pub fn load_library(&self, code: Bytes, replace: bool) -> crate::Result<String> {
+        let (shebang, _) = Shebang::parse(&code)?.ok_or("ERR Missing library metadata")?;
+        let name = shebang.name.ok_or("ERR Library name was not given")?;
+        if !is_valid_name(&name) {
+            return Err("ERR Library names can only contain letters, numbers, or underscores(_) and must be at least one character long".into());
+        }
+
+        let functions = LUA
+            .with(|lua| register_functions(lua, &self.config, &code))
+            .map_err(|err| format!("ERR Error registering functions: {}", lua_message(&err)))?;
+        let functions = functions?;
+
+        let mut libraries = self.libraries.lock().unwrap();
+        if libraries.libraries.contains_key(&name) && !replace {
+            return Err(format!("ERR Library '{}' already exists", name).into());
+        }
+
+        for function in &functions {
+            match libraries.functions.get(&function.name) {
+                Some(library) if *library != name => {
+                    return Err(format!("ERR Function {} already exists", function.name).into());
+                }
+                _ => {}
+            }
+        }
+
+        libraries.remove(&name);
+        for function in &functions {
+            libraries
+                .functions
+                .insert(function.name.clone(), name.clone());
+        }
+        libraries.libraries.insert(
+            name.clone(),
+            Library {
+                name: name.clone(),
+                code,
+                functions,
+                id: NEXT_LIBRARY_ID.fetch_add(1, Ordering::Relaxed),
+            },
+        );
+
+        Ok(name)
+    }
+
+    /// Deletes a library and its functions, returning `false` if there is no
+    /// such library.
+    pub fn delete_library(&self, name: &str) -> bool {
+        self.libraries.lock().unwrap().remove(name)
+    }
+
+    pub fn flush_libraries(&self) {
+        *self.libraries.lock().unwrap() = Libraries::default();
+    }
+
+    /// Libraries whose name matches `pattern`, by name.
+    pub fn libraries(&self, pattern: Option<&str>) -> Vec<Library> {
+        self.libraries
+            .lock()
+            .unwrap()
+            .libraries
+            .values()
+            .filter(|library| {
+                pattern.is_none_or(|pattern| {
+                    pattern::matches(pattern.as_bytes(), library.name.as_bytes())
+                })
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// The library registering function `name`.
+    pub fn function(&self, name: &str) -> Option<Library> {
+        let libraries = self.libraries.lock().unwrap();
+        let library = libraries.functions.get(name)?;
+        libraries.libraries.get(library).cloned()
+    }
+}
+
+impl Libraries {
+    fn remove(&mut self, name: &str) -> bool {
+        match self.libraries.remove(name) {
+            Some(library) => {
+                for function in &library.functions {
+                    self.functions.remove(&function.name);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Library {
+    pub fn function(&self, name: &str) -> Option<&FunctionInfo> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+}
+
+impl Running {
+    /// Called from the Lua hook: flags the script busy once it ran for
+    /// `limit`, and stops it with an error once killed.
+    fn check(&self, busy: &AtomicUsize, limit: Duration) -> mlua::Result<()> {
+        if !self.busy.load(Ordering::Relaxed) && self.started.elapsed() >= limit {
+            self.busy.store(true, Ordering::Relaxed);
+            busy.fetch_add(1, Ordering::Relaxed);
+        }
+
+        if self.killed.load(Ordering::Relaxed) {
+            let by = if self.function { "FUNCTION KILL" } else { "SCRIPT KILL" };
+            return Err(mlua::Error::RuntimeError(format!(
+                "Script killed by user with {}...",
+                by
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl FunctionInfo {
+    /// Functions flagged `no-writes` may be called with `FCALL_RO`.
+    pub fn is_read_only(&self) -> bool {
+        self.flags.iter().any(|flag| flag == "no-writes")
+    }
+}
+
+/// The `#!<engine> name=<name> flags=<flag>,...` first line of a script or
+/// library.
+struct Shebang {
+    name: Option<String>,
+    flags: Vec<String>,
+}
+
+impl Shebang {
+    /// Parses the shebang line of `code`, if it has one, returning it with
+    /// the offset of the code after it.
+    fn parse(code: &[u8]) -> crate::Result<Option<(Shebang, usize)>> {
+        if !code.starts_with(b"#!") {
+            return Ok(None);
+        }
+
+        let end = code.iter().position(|&c| c == b'\n').unwrap_or(code.len());
+        let line = String::from_utf8_lossy(&code[2..end]);
+        let mut parts = line.split_whitespace();
+
+        let engine = parts.next().unwrap_or_default();
+        if !engine.eq_ignore_ascii_case("lua") {
+            return Err(format!("ERR Engine '{}' not found", engine).into());
+        }
+
+        let mut shebang = Shebang {
+            name: None,
+            flags: vec![],
+        };
+        for part in parts {
+            match part.split_once('=') {
+                Some(("name", name)) => shebang.name = Some(name.to_string()),
+                Some(("flags", flags)) => {
+                    for flag in flags.split(',').filter(|flag| !flag.is_empty()) {
+                        if !FUNCTION_FLAGS.contains(&flag) {
+                            return Err(
+                                format!("ERR Unexpected flag in script shebang: {}", flag).into()
+                            );
+                        }
+                        shebang.flags.push(flag.to_string());
+                    }
+                }
+                _ => return Err(format!("ERR Invalid metadata value given: {}", part).into()),
+            }
+        }
+
+        Ok(Some((shebang, end)))
+    }
+}
+
+/// Runs the script `body` with `EVAL`, `sha` being its SHA1.
+pub fn eval(
+    state: &ServerState,
+    session: &mut Session,
+    body: &[u8],
+    sha: &str,
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+    read_only: bool,
+) -> Frame {
+    // The shebang line is kept as an empty line, so line numbers in errors
+    // match the script.
+    let (read_only, body) = match Shebang::parse(body) {
+        Ok(None) => (read_only, body),
+        Ok(Some((shebang, end))) => {
+            let no_writes = shebang.flags.iter().any(|flag| flag == "no-writes");
+            (read_only || no_writes, &body[end..])
+        }
+        Err(err) => return cmd::error_frame(&err),
+    };
+
+    let context = format!("script: {}", sha);
+    run(
+        state,
+        session,
+        Entry::Script(body),
+        keys,
+        args,
+        read_only,
+        &context,
+    )
+}
+
+/// Calls `function` of `library` with `FCALL`.
+pub fn fcall(
+    state: &ServerState,
+    session: &mut Session,
+    library: &Library,
+    function: &FunctionInfo,
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+    read_only: bool,
+) -> Frame {
+    let context = format!("function: {}", function.name);
+    let read_only = read_only || function.is_read_only();
+    let entry = Entry::Function(library, &function.name);
+    run(state, session, entry, keys, args, read_only, &context)
+}
+
+fn run(
+    state: &ServerState,
+    session: &mut Session,
+    entry: Entry,
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+    read_only: bool,
+    context: &str,
+) -> Frame {
+    // `SELECT` in a script only applies to the rest of the script.
+    let db = session.db;
+    let client = session.id;
+    let running = state
+        .scripts
+        .start(client, matches!(entry, Entry::Function(..)));
+
+    let result = LUA.with(|lua| {
+        let hook = Arc::clone(&running);
+        let busy = Arc::clone(&state.scripts.busy);
+        let limit = state.config.busy_reply_threshold();
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(HOOK_INSTRUCTIONS),
+            move |_, _| hook.check(&busy, limit),
+        );
+
+        let result = lua.scope(|scope| {
+            let pcall = scope.create_function_mut(|lua, args: Variadic<Value>| {
+                to_lua(lua, call(state, session, &running, &args, read_only))
+            })?;
+
+            let keys = strings(lua, &keys)?;
+            let args = strings(lua, &args)?;
+
+            let (function, params) = match entry {
+                Entry::Script(body) => {
+                    let env = environment(lua, &state.config)?;
+                    bind_calls(lua, &env.get("redis")?, pcall)?;
+                    env.raw_set("KEYS", keys)?;
+                    env.raw_set("ARGV", args)?;
+                    let function = lua
+                        .load(body)
+                        .set_name("=user_script")
+                        .set_environment(env)
+                        .into_function()?;
+                    (function, vec![])
+                }
+                Entry::Function(library, name) => {
+                    let (registered, redis) = compiled_library(lua, &state.config, library)?;
+                    bind_calls(lua, &redis, pcall)?;
+                    let function: Table = registered.get(name)?;
+                    let callback: Function = function.get("callback")?;
+                    (callback, vec![Value::Table(keys), Value::Table(args)])
+                }
+            };
+
+            let pcall: Function = lua.globals().get("pcall")?;
+            let (ok, value): (bool, Value) = pcall.call((function, Variadic::from_iter(params)))?;
+            Ok(match (ok, value) {
+                (true, value) => from_lua(value),
+                (false, Value::Table(reply)) if reply.contains_key("err")? => {
+                    from_lua(Value::Table(reply))
+                }
+                (false, err) => {
+                    Frame::Error(format!("ERR {} {}", lua_value_message(&err), context))
+                }
+            })
+        });
+
+        lua.remove_hook();
+        result
+    });
+
+    state.scripts.finish(client);
+    session.db = db;
+    result.unwrap_or_else(|err| Frame::Error(format!("ERR {} {}", lua_message(&err), context)))
+}
+
+fn strings<'lua>(lua: &'lua Lua, items: &[Bytes]) -> mlua::Result<Table<'lua>> {
+    let table = lua.create_table_with_capacity(items.len(), 0)?;
+    for item in items {
+        table.raw_push(lua.create_string(item)?)?;
+    }
+    Ok(table)
+}
+
+/// Runs a command on behalf of a script, replying with an error frame when it
+/// cannot run.
+fn call(
+    state: &ServerState,
+    session: &mut Session,
+    running: &Running,
+    args: &[Value],
+    read_only: bool,
+) -> Frame {
+    if args.is_empty() {
+        return Frame::Error(
+            "ERR Please specify at least one argument for this redis lib call".to_string(),
+        );
+    }
+
+    let mut parts = Vec::with_capacity(args.len());
+    for arg in args {
+        match arg {
+            Value::String(s) => parts.push(Frame::Bulk(Bytes::copy_from_slice(s.as_bytes()))),
+            Value::Integer(n) => parts.push(Frame::Bulk(n.to_string().into())),
+            Value::Number(n) => parts.push(Frame::Bulk(format_number(*n).into())),
+            _ => {
+                return Frame::Error(
+                    "ERR Lua redis lib command arguments must be strings or integers".to_string(),
+                )
+            }
+        }
+    }
+
+    let cmd = match Command::from_frame(Frame::Array(parts), &state.modules) {
+        Ok(cmd) => cmd,
+        Err(err) => return cmd::error_frame(&err),
+    };
+
+    if !cmd.is_callable() {
+        return Frame::Error("ERR This Redis command is not allowed from script".to_string());
+    }
+
+    if read_only && cmd.is_write() {
+        return Frame::Error(
+            "ERR Write commands are not allowed from read-only scripts.".to_string(),
+        );
+    }
+
+    if cmd.is_denied_when_oom() && !state.free_memory() {
+        return Frame::Error("OOM command not allowed when used memory > 'maxmemory'.".to_string());
+    }
+
+    if cmd.is_write() {
+        running.wrote.store(true, Ordering::Relaxed);
+    }
+    cmd.apply(state, session).unwrap_or(Frame::Null)
+}
+
+fn new_lua() -> mlua::Result<Lua> {
+    let lua = Lua::new_with(
+        StdLib::TABLE | StdLib::STRING | StdLib::MATH,
+        LuaOptions::default(),
+    )?;
+
+    // Scripts have no business with the file system.
+    for name in ["dofile", "loadfile"] {
+        lua.globals().raw_set(name, Value::Nil)?;
+    }
+
+    let redis = lua.create_table()?;
+    redis.set(
+        "status_reply",
+        lua.create_function(|lua, status: mlua::String| lua.create_table_from([("ok", status)]))?,
+    )?;
+    redis.set(
+        "error_reply",
+        lua.create_function(|lua, err: mlua::String| lua.create_table_from([("err", err)]))?,
+    )?;
+    redis.set(
+        "sha1hex",
+        lua.create_function(|_, s: mlua::String| Ok(sha1_hex(s.as_bytes())))?,
+    )?;
+    for (level, name) in ["LOG_DEBUG", "LOG_VERBOSE", "LOG_NOTICE", "LOG_WARNING"]
+        .into_iter()
+        .enumerate()
+    {
+        redis.set(name, level)?;
+    }
+    // Effects of scripts are always what gets replicated, as in Redis 7.
+    redis.set("replicate_commands", lua.create_function(|_, ()| Ok(true))?)?;
+
+    let helpers: Table = lua.load(HELPERS).set_name("=helpers").eval()?;
+    lua.set_named_registry_value("redis", redis)?;
+    lua.set_named_registry_value("helpers", helpers)?;
+    lua.set_app_data(Compiled::default());
+
+    Ok(lua)
+}
+
+/// Global environment of a run, with the `redis` API but for `redis.call`
+/// and `redis.pcall`, see `bind_calls`.
+fn environment<'lua>(lua: &'lua Lua, config: &Arc<Config>) -> mlua::Result<Table<'lua>> {
+    let api: Table = lua.named_registry_value("redis")?;
+
+    let redis = lua.create_table()?;
+    for pair in api.pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        redis.raw_set(key, value)?;
+    }
+
+    let config = Arc::clone(config);
+    redis.raw_set(
+        "log",
+        lua.create_function(move |_, (level, message): (i64, mlua::String)| {
+            let level = u8::try_from(level)
+                .ok()
+                .and_then(LogLevel::from_index)
+                .ok_or_else(|| mlua::Error::RuntimeError("Invalid debug level.".to_string()))?;
+            logging::log(&config, level, &message.to_string_lossy());
+            Ok(())
+        })?,
+    )?;
+
+    let env = lua.create_table()?;
+    env.raw_set("redis", redis)?;
+    env.set_metatable(Some(lua.create_table_from([("__index", lua.globals())])?));
+    Ok(env)
+}
+
+/// Sets `redis.pcall` to `pcall`, and `redis.call` to the same raising
+/// error replies, for the run about to start.
+fn bind_calls<'lua>(lua: &'lua Lua, redis: &Table<'lua>, pcall: Function<'lua>) -> mlua::Result<()> {
+    let helpers: Table = lua.named_registry_value("helpers")?;
+    let make_call: Function = helpers.get("make_call")?;
+    redis.raw_set("call", make_call.call::<_, Function>(pcall.clone())?)?;
+    redis.raw_set("pcall", pcall)
+}
+
+/// The functions registered by `library` with the `redis` table they see,
+/// running the library's code the first time it is called on this thread.
+fn compiled_library<'lua>(
+    lua: &'lua Lua,
+    config: &Arc<Config>,
+    library: &Library,
+) -> mlua::Result<(Table<'lua>, Table<'lua>)> {
+    if let Some(compiled) = lua.app_data_ref::<Compiled>() {
+        if let Some(cached) = compiled.libraries.get(&library.name) {
+            if cached.id == library.id {
+                return Ok((
+                    lua.registry_value(&cached.registered)?,
+                    lua.registry_value(&cached.redis)?,
+                ));
+            }
+        }
+    }
+
+    let env = environment(lua, config)?;
+    let registered = load_library(lua, &env, &library.code)?;
+    let redis: Table = env.get("redis")?;
+    let cached = CompiledLibrary {
+        id: library.id,
+        registered: lua.create_registry_value(registered.clone())?,
+        redis: lua.create_registry_value(redis.clone())?,
+    };
+
+    // The library this one replaces, if any, is dropped.
+    if let Some(mut compiled) = lua.app_data_mut::<Compiled>() {
+        compiled.libraries.insert(library.name.clone(), cached);
+    }
+    lua.expire_registry_values();
+    Ok((registered, redis))
+}
+
+/// Runs the code of a library in `env`, returning the functions it
+/// registered, by name.
+fn load_library<'lua>(lua: &'lua Lua, env: &Table<'lua>, code: &[u8]) -> mlua::Result<Table<'lua>> {
+    let helpers: Table = lua.named_registry_value("helpers")?;
+    let make_register: Function = helpers.get("make_register")?;
+
+    let registered = lua.create_table()?;
+    let redis: Table = env.get("redis")?;
+    redis.raw_set(
+        "register_function",
+        make_register.call::<_, Function>(registered.clone())?,
+    )?;
+
+    let end = code.iter().position(|&c| c == b'\n').unwrap_or(code.len());
+    lua.load(&code[end..])
+        .set_name("=user_function")
+        .set_environment(env.clone())
+        .exec()?;
+
+    Ok(registered)
+}
+
+/// Loads library `code` to find out the functions it registers.
+fn register_functions(
+    lua: &Lua,
+    config: &Arc<Config>,
+    code: &[u8],
+) -> mlua::Result<crate::Result<Vec<FunctionInfo>>> {
+    let env = environment(lua, config)?;
+    let registered = load_library(lua, &env, code)?;
+
+    let mut functions = vec![];
+    for pair in registered.pairs::<String, Table>() {
+        let (name, function) = pair?;
+        if !is_valid_name(&name) {
+            return Ok(Err("ERR Function names can only contain letters, numbers, or underscores(_) and must be at least one character long".into()));
+        }
+
+        let mut flags = vec![];
+        for flag in function
+            .get::<_, Table>("flags")?
+            .sequence_values::<String>()
+        {
+            let flag = flag?;
+            if !FUNCTION_FLAGS.contains(&&flag[..]) {
+                return Ok(Err("ERR unknown flag given".into()));
+            }
+            flags.push(flag);
+        }
+
+        functions.push(FunctionInfo { name, flags });
+    }
+
+    if functions.is_empty() {
+        return Ok(Err("ERR No functions registered".into()));
+    }
+
+    functions.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(Ok(functions))
+}
+
+/// Converts a reply to the value `redis.call` returns, following the rules
+/// of Redis: error and status replies become tables with an `err` or `ok`
+/// field, and nil replies `false`.
+fn to_lua(lua: &Lua, frame: Frame) -> mlua::Result<Value<'_>> {
+    Ok(match frame {
+        Frame::Simple(status) => Value::Table(lua.create_table_from([("ok", status)])?),
+        Frame::Error(err) => Value::Table(lua.create_table_from([("err", err)])?),
+        Frame::Integer(n) => Value::Integer(n),
+        Frame::Bulk(bytes) => Value::String(lua.create_string(&bytes)?),
+        Frame::Null => Value::Boolean(false),
+        Frame::Array(items) | Frame::Push(items) => {
+            let table = lua.create_table_with_capacity(items.len(), 0)?;
+            for item in items {
+                table.raw_push(to_lua(lua, item)?)?;
+            }
+            Value::Table(table)
+        }
+        // Scripts speak RESP2, where maps are flat arrays.
+        Frame::Map(pairs) => {
+            let table = lua.create_table_with_capacity(pairs.len() * 2, 0)?;
+            for (key, value) in pairs {
+                table.raw_push(to_lua(lua, key)?)?;
+                table.raw_push(to_lua(lua, value)?)?;
+            }
+            Value::Table(table)
+        }
+    })
+}
+
+/// Converts the value returned by a script to its reply. Numbers are
+/// truncated to integers, and arrays end at their first nil.
+fn from_lua(value: Value) -> Frame {
+    match value {
+        Value::Boolean(true) => Frame::Integer(1),
+        Value::Integer(n) => Frame::Integer(n),
+        Value::Number(n) => Frame::Integer(n as i64),
+        Value::String(s) => Frame::Bulk(Bytes::copy_from_slice(s.as_bytes())),
+        Value::Table(table) => {
+            if let Ok(err) = table.raw_get::<_, mlua::String>("err") {
+                return Frame::Error(err.to_string_lossy().into_owned());
+            }
+            if let Ok(status) = table.raw_get::<_, mlua::String>("ok") {
+                return Frame::Simple(status.to_string_lossy().into_owned());
+            }
+
+            let mut items = vec![];
+            for value in table.sequence_values::<Value>() {
+                match value {
+                    Ok(Value::Nil) | Err(_) => break,
+                    Ok(value) => items.push(from_lua(value)),
+                }
+            }
+            Frame::Array(items)
+        }
+        _ => Frame::Null,
+    }
+}
+
+/// Formats a number argument the way Lua turns numbers into strings.
+fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        (n as i64).to_string()
+    } else {
+        n.to_string()
+    }
+}
+
+fn lua_value_message(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_string_lossy().into_owned(),
+        Value::Error(err) => lua_message(err),
+        value => format!("{:?}", value),
+    }
+}
+
+/// The message of a Lua error, without the traceback mlua adds to errors
+/// raised by callbacks.
+fn lua_message(err: &mlua::Error) -> String {
+    match err {
+        mlua::Error::SyntaxError { message, .. } | mlua::Error::RuntimeError(message) => {
+            message.clone()
+        }
+        mlua::Error::CallbackError { cause, .. } => lua_message(cause),
+        err => err.to_string(),
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub fn sha1_hex(data: &[u8]) -> String {
+    sha1_smol::Sha1::from(data).digest().to_string()
+}
@@ -0,0 +1,312 @@
+use std::fmt;
+use std::future;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time;
+
+use shared_lib::sharded_db::ShardModel;
+
+use crate::config::Config;
+use crate::handler;
+use crate::http;
+use crate::logging::{self, LogLevel};
+use crate::memcached;
+use crate::module::{Module, Modules};
+use crate::raft::{Raft, RaftConfig};
+use crate::state::{ServerState, NUM_SHARDS};
+
+/// Address the server binds to unless told otherwise.
+pub const DEFAULT_ADDR: &str = "127.0.0.1:6379";
+
+/// How often keys past their expiration time are looked for, the same as
+/// the default `hz` of Redis.
+const ACTIVE_EXPIRE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Entry point for running miniredis in process.
+///
+/// ```no_run
+/// # async fn run() -> miniredis::Result<()> {
+/// use miniredis::server::Server;
+///
+/// let server = Server::builder().bind("127.0.0.1:0").start().await?;
+/// println!("listening on {}", server.local_addr());
+/// server.shutdown().await;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Server;
+
+/// Configures a server before starting it.
+pub struct Builder {
+    addr: String,
+    memcached_addr: Option<String>,
+    http_addr: Option<String>,
+    config: Config,
+    num_shards: usize,
+    shard_model: ShardModel,
+    modules: Vec<Box<dyn Module>>,
+    raft: Option<RaftConfig>,
+}
+
+/// A running server. Dropping the handle leaves the server running, call
+/// `shutdown` to stop it.
+#[derive(Debug)]
+pub struct ServerHandle {
+    local_addr: SocketAddr,
+    memcached_addr: Option<SocketAddr>,
+    http_addr: Option<SocketAddr>,
+    state: Arc<ServerState>,
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl Server {
+    pub fn builder() -> Builder {
+        Builder {
+            addr: DEFAULT_ADDR.to_string(),
+            memcached_addr: None,
+            http_addr: None,
+            config: Config::default(),
+            num_shards: NUM_SHARDS,
+            shard_model: ShardModel::default(),
+            modules: vec![],
+            raft: None,
+        }
+    }
+}
+
+impl Builder {
+    /// Sets the address to listen on. Port `0` picks a free port, see
+    /// `ServerHandle::local_addr` for the one picked.
+    pub fn bind(mut self, addr: impl Into<String>) -> Builder {
+        self.addr = addr.into();
+        self
+    }
+
+    /// Also serves the memcached text protocol on `addr`, on the same
+    /// keyspace, see `memcached`.
+    pub fn memcached(mut self, addr: impl Into<String>) -> Builder {
+        self.memcached_addr = Some(addr.into());
+        self
+    }
+
+    /// Also serves the HTTP/JSON gateway on `addr`, see `http`.
+    pub fn http(mut self, addr: impl Into<String>) -> Builder {
+        self.http_addr = Some(addr.into());
+        self
+    }
+
+    pub fn config(mut self, config: Config) -> Builder {
+        self.config = config;
+        self
+    }
+
+    /// Number of shards each database is split into, to trade memory for
+    /// less lock contention. `start` fails on `0`.
+    pub fn shards(mut self, num_shards: usize) -> Builder {
+        self.num_shards = num_shards;
+        self
+    }
+
+    /// Whether shards are locked by the connections using them, with a
+    /// mutex by default or a reader-writer lock, or owned by a pool of
+    /// threads that connections send their commands to. The latter blocks
+    /// the runtime's workers while they wait, so `start` fails on it unless
+    /// the `bench` feature is enabled.
+    pub fn shard_model(mut self, model: ShardModel) -> Builder {
+        self.shard_model = model;
+        self
+    }
+
+    /// Adds the commands and value types of `module`, registered in the
+    /// order modules are added.
+    pub fn module(mut self, module: impl Module) -> Builder {
+        self.modules.push(Box::new(module));
+        self
+    }
+
+    /// Replicates writes with the other members of a Raft group, see
+    /// `raft`.
+    pub fn raft(mut self, config: RaftConfig) -> Builder {
+        self.raft = Some(config);
+        self
+    }
+
+    /// Binds the listener and starts accepting connections in the background.
+    pub async fn start(self) -> crate::Result<ServerHandle> {
+        if self.num_shards == 0 {
+            return Err("the number of shards must be positive".into());
+        }
+        if self.shard_model == ShardModel::Owner && !cfg!(feature = "bench") {
+            return Err("the owner shard model is for benchmarks, enable the `bench` feature".into());
+        }
+        if self.raft.is_some() && self.memcached_addr.is_some() {
+            return Err("memcached clients are not replicated, it cannot be used with Raft".into());
+        }
+
+        let modules = Modules::new(&self.modules)?;
+        let raft = self.raft.map(Raft::open).transpose()?;
+        let listener = TcpListener::bind(&self.addr).await?;
+        let local_addr = listener.local_addr()?;
+        let memcached = bind(self.memcached_addr.as_deref()).await?;
+        let memcached_addr = memcached.as_ref().map(TcpListener::local_addr).transpose()?;
+        let http = bind(self.http_addr.as_deref()).await?;
+        let http_addr = http.as_ref().map(TcpListener::local_addr).transpose()?;
+        let state = ServerState::new(self.config, self.num_shards, self.shard_model, modules, raft);
+        if let Some(raft) = &state.raft {
+            raft.start(Arc::clone(&state));
+        }
+        let (shutdown, shutdown_rx) = oneshot::channel();
+
+        let listeners = Listeners {
+            redis: listener,
+            memcached,
+            http,
+        };
+        let task = tokio::spawn(run(listeners, Arc::clone(&state), shutdown_rx));
+
+        Ok(ServerHandle {
+            local_addr,
+            memcached_addr,
+            http_addr,
+            state,
+            shutdown,
+            task,
+        })
+    }
+}
+
+impl fmt::Debug for Builder {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let modules: Vec<&str> = self.modules.iter().map(|module| module.name()).collect();
+        fmt.debug_struct("Builder")
+            .field("addr", &self.addr)
+            .field("memcached_addr", &self.memcached_addr)
+            .field("http_addr", &self.http_addr)
+            .field("config", &self.config)
+            .field("num_shards", &self.num_shards)
+            .field("shard_model", &self.shard_model)
+            .field("modules", &modules)
+            .field("raft", &self.raft)
+            .finish()
+    }
+}
+
+impl ServerHandle {
+    /// Address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Address of the memcached listener, if the server was started with
+    /// one.
+    pub fn memcached_addr(&self) -> Option<SocketAddr> {
+        self.memcached_addr
+    }
+
+    /// Address of the HTTP listener, if the server was started with one.
+    pub fn http_addr(&self) -> Option<SocketAddr> {
+        self.http_addr
+    }
+
+    /// State shared by the server's connections, to inspect or seed the
+    /// keyspace directly.
+    pub fn state(&self) -> &Arc<ServerState> {
+        &self.state
+    }
+
+    /// Stops accepting connections, closes the open ones and waits for the
+    /// server to wind down.
+    pub async fn shutdown(self) {
+        let _ = self.shutdown.send(());
+        let _ = self.task.await;
+    }
+}
+
+/// The listeners of a server, the memcached and HTTP ones being optional.
+struct Listeners {
+    redis: TcpListener,
+    memcached: Option<TcpListener>,
+    http: Option<TcpListener>,
+}
+
+async fn run(
+    listeners: Listeners,
+    state: Arc<ServerState>,
+    shutdown: oneshot::Receiver<()>,
+) {
+    let shutdown = shutdown_requested(shutdown);
+    tokio::pin!(shutdown);
+    let mut connections = JoinSet::new();
+    let mut expire = time::interval(ACTIVE_EXPIRE_INTERVAL);
+    let stats = Arc::new(memcached::Stats::new());
+
+    loop {
+        tokio::select! {
+            res = listeners.redis.accept() => match res {
+                Ok((socket, addr)) => {
+                    connections.spawn(handler::serve(socket, addr, Arc::clone(&state)));
+                }
+                // Accept errors, such as running out of file descriptors,
+                // only affect the connection being accepted.
+                Err(err) => log_accept_error(&state, err),
+            },
+            res = accept(listeners.memcached.as_ref()) => match res {
+                Ok((socket, _)) => {
+                    let stats = Arc::clone(&stats);
+                    connections.spawn(memcached::serve(socket, Arc::clone(&state), stats));
+                }
+                Err(err) => log_accept_error(&state, err),
+            },
+            res = accept(listeners.http.as_ref()) => match res {
+                Ok((socket, addr)) => {
+                    connections.spawn(http::serve(socket, addr, Arc::clone(&state)));
+                }
+                Err(err) => log_accept_error(&state, err),
+            },
+            // Reap finished connections so the set does not grow forever.
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            _ = expire.tick() => state.active_expire().await,
+            _ = &mut shutdown => break,
+        }
+    }
+
+    connections.shutdown().await;
+    if let Some(raft) = &state.raft {
+        raft.stop();
+    }
+}
+
+/// Completes once `ServerHandle::shutdown` is called. A handle dropped
+/// without it closes the channel instead, which leaves the server running.
+async fn shutdown_requested(shutdown: oneshot::Receiver<()>) {
+    if shutdown.await.is_err() {
+        future::pending::<()>().await;
+    }
+}
+
+fn log_accept_error(state: &ServerState, err: io::Error) {
+    logging::log(&state.config, LogLevel::Warning, &format!("accept error: {}", err));
+}
+
+async fn bind(addr: Option<&str>) -> io::Result<Option<TcpListener>> {
+    match addr {
+        Some(addr) => Ok(Some(TcpListener::bind(addr).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Accepts a connection on an optional listener, never completing without
+/// one.
+async fn accept(listener: Option<&TcpListener>) -> io::Result<(TcpStream, SocketAddr)> {
+    match listener {
+        Some(listener) => listener.accept().await,
+        None => future::pending().await,
+    }
+}
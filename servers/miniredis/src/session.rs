@@ -0,0 +1,78 @@
+use bytes::Bytes;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::broadcast;
+
+use crate::cmd::Command;
+use crate::outbox::Outbox;
+use crate::pubsub::ClientId;
+use crate::tracking::TrackingOptions;
+
+/// Per connection state kept between commands.
+#[derive(Debug)]
+pub struct Session {
+    /// Unique id of the connection.
+    pub id: ClientId,
+    /// Address of the client.
+    pub addr: SocketAddr,
+    /// Name set with `CLIENT SETNAME`.
+    pub name: Option<Bytes>,
+    /// Index of the database selected with `SELECT`.
+    pub db: usize,
+    /// Commands processed by the server, once the client issued `MONITOR`.
+    pub monitor: Option<broadcast::Receiver<String>>,
+    /// Frames queued for the client by other connections.
+    pub outbox: Arc<Outbox>,
+    /// Channels subscribed to with `SUBSCRIBE`.
+    pub channels: HashSet<Bytes>,
+    /// Patterns subscribed to with `PSUBSCRIBE`.
+    pub patterns: HashSet<Bytes>,
+    /// When the client last sent a command.
+    pub last_interaction: Instant,
+    /// Commands queued since `MULTI`.
+    pub transaction: Option<Transaction>,
+    /// Options of `CLIENT TRACKING`, while it is on.
+    pub tracking: Option<TrackingOptions>,
+    /// Answer of the latest `CLIENT CACHING`, for the next command only.
+    pub caching: Option<bool>,
+}
+
+/// A transaction being queued, run by `EXEC`.
+#[derive(Debug, Default)]
+pub struct Transaction {
+    pub commands: Vec<Command>,
+    /// Set when a command could not be queued, `EXEC` then aborts.
+    pub failed: bool,
+}
+
+impl Session {
+    pub fn new(id: ClientId, addr: SocketAddr) -> Session {
+        Session {
+            id,
+            addr,
+            name: None,
+            db: 0,
+            monitor: None,
+            outbox: Arc::new(Outbox::new()),
+            channels: HashSet::new(),
+            patterns: HashSet::new(),
+            last_interaction: Instant::now(),
+            transaction: None,
+            tracking: None,
+            caching: None,
+        }
+    }
+
+    /// A subscribed client can only manage its subscriptions until it
+    /// unsubscribes from everything.
+    pub fn is_subscribed(&self) -> bool {
+        !self.channels.is_empty() || !self.patterns.is_empty()
+    }
+
+    /// Total number of channel and pattern subscriptions.
+    pub fn subscriptions(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+}
@@ -0,0 +1,116 @@
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::protocol::frame::Frame;
+
+/// Only this many arguments of a command are kept in an entry.
+const MAX_ARGS: usize = 32;
+/// Arguments longer than this are truncated.
+const MAX_ARG_LEN: usize = 128;
+
+/// Bounded log of the commands that took longer than
+/// `slowlog-log-slower-than` to run.
+#[derive(Debug, Default)]
+pub struct SlowLog {
+    entries: Mutex<VecDeque<Entry>>,
+    next_id: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    id: u64,
+    timestamp: u64,
+    duration: Duration,
+    args: Vec<Bytes>,
+    client: SocketAddr,
+}
+
+impl SlowLog {
+    pub fn new() -> SlowLog {
+        SlowLog::default()
+    }
+
+    /// Records a slow command, evicting the oldest entries past `max_len`.
+    pub fn push(&self, args: &[Bytes], duration: Duration, client: SocketAddr, max_len: usize) {
+        let entry = Entry {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            timestamp: unix_time(),
+            duration,
+            args: truncate_args(args),
+            client,
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.push_front(entry);
+        entries.truncate(max_len);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn reset(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Returns up to `count` entries, newest first, in the `SLOWLOG GET`
+    /// reply format.
+    pub fn frames(&self, count: usize) -> Vec<Frame> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .take(count)
+            .map(|entry| {
+                Frame::Array(vec![
+                    Frame::Integer(entry.id as i64),
+                    Frame::Integer(entry.timestamp as i64),
+                    Frame::Integer(entry.duration.as_micros() as i64),
+                    Frame::Array(entry.args.iter().cloned().map(Frame::Bulk).collect()),
+                    Frame::Bulk(entry.client.to_string().into()),
+                    // Client name, connections cannot be named yet.
+                    Frame::Bulk(Bytes::new()),
+                ])
+            })
+            .collect()
+    }
+}
+
+/// Keeps entries small no matter how large the logged command was.
+fn truncate_args(args: &[Bytes]) -> Vec<Bytes> {
+    let kept = if args.len() > MAX_ARGS { MAX_ARGS - 1 } else { args.len() };
+
+    let mut out: Vec<Bytes> = args[..kept]
+        .iter()
+        .map(|arg| {
+            if arg.len() > MAX_ARG_LEN {
+                let mut truncated = arg[..MAX_ARG_LEN].to_vec();
+                truncated.extend_from_slice(format!("... ({} more bytes)", arg.len() - MAX_ARG_LEN).as_bytes());
+                Bytes::from(truncated)
+            } else {
+                arg.clone()
+            }
+        })
+        .collect();
+
+    if kept < args.len() {
+        out.push(format!("... ({} more arguments)", args.len() - kept).into());
+    }
+
+    out
+}
+
+pub(crate) fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,325 @@
+use bytes::Bytes;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tokio::task;
+
+use shared_lib::sharded_db::ShardModel;
+
+use crate::cmd::{self, Command};
+use crate::config::Config;
+use crate::db::{Databases, DEFAULT_DATABASES};
+use crate::latency::{self, LatencyMonitor};
+use crate::locks::{KeyLocks, Scope};
+use crate::module::Modules;
+use crate::monitor::Monitors;
+use crate::notify::Notifier;
+use crate::protocol::frame::Frame;
+use crate::pubsub::{ClientId, PubSub};
+use crate::raft::Raft;
+use crate::scripting::Scripts;
+use crate::session::Session;
+use crate::slowlog::SlowLog;
+use crate::tracking::{self, Tracking};
+
+/// Default number of shards of each database.
+pub const NUM_SHARDS: usize = 10;
+
+/// Most keys each database expires per run of `ServerState::active_expire`,
+/// so a burst of expirations does not hold up commands for long.
+const ACTIVE_EXPIRE_LIMIT: usize = 1000;
+
+/// State shared by every connection to the server.
+#[derive(Debug)]
+pub struct ServerState {
+    pub dbs: Databases,
+    pub config: Arc<Config>,
+    pub slowlog: SlowLog,
+    pub latency: LatencyMonitor,
+    pub monitors: Monitors,
+    pub pubsub: Arc<PubSub>,
+    pub tracking: Arc<Tracking>,
+    pub scripts: Scripts,
+    pub modules: Modules,
+    /// Number of connected clients, checked against `maxclients`.
+    pub clients: AtomicUsize,
+    /// The Raft node writes go through, when replicating.
+    pub raft: Option<Arc<Raft>>,
+    next_client_id: AtomicU64,
+    /// Held by commands on their keys, exclusively by `EXEC` and scripts so
+    /// a transaction or script runs without other commands on its keys
+    /// interleaving.
+    locks: KeyLocks,
+}
+
+impl ServerState {
+    pub fn new(
+        config: Config,
+        num_shards: usize,
+        shard_model: ShardModel,
+        modules: Modules,
+        raft: Option<Arc<Raft>>,
+    ) -> Arc<ServerState> {
+        let config = Arc::new(config);
+        let pubsub = Arc::new(PubSub::new());
+        let events = Arc::new(Notifier::new(Arc::clone(&config), Arc::clone(&pubsub)));
+        let tracking = Arc::new(Tracking::new(Arc::clone(&config), Arc::clone(&pubsub)));
+        let scripts = Scripts::new(Arc::clone(&config));
+
+        Arc::new(ServerState {
+            dbs: Databases::new(
+                DEFAULT_DATABASES,
+                num_shards,
+                shard_model,
+                events,
+                Arc::clone(&tracking),
+            ),
+            config,
+            slowlog: SlowLog::new(),
+            latency: LatencyMonitor::new(),
+            monitors: Monitors::new(),
+            pubsub,
+            tracking,
+            scripts,
+            modules,
+            clients: AtomicUsize::new(0),
+            raft,
+            next_client_id: AtomicU64::new(1),
+            locks: KeyLocks::new(),
+        })
+    }
+
+    /// Hands out ids to new connections, ids are never reused.
+    pub fn next_client_id(&self) -> ClientId {
+        self.next_client_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Runs a command received from the client of `session`, through the
+    /// Raft log when replicating, see `execute` otherwise.
+    pub async fn dispatch(&self, session: &mut Session, frame: Frame) -> Option<Frame> {
+        match &self.raft {
+            Some(raft) => raft.execute(self, session, frame).await,
+            None => self.execute(session, frame).await,
+        }
+    }
+
+    /// Parses and runs a command received from the client of `session`,
+    /// returning the reply, if any.
+    ///
+    /// Commands are fed to connections running `MONITOR`. Execution is timed,
+    /// slow commands end up in the slowlog and the latency monitor.
+    pub async fn execute(&self, session: &mut Session, frame: Frame) -> Option<Frame> {
+        // The frame is consumed by parsing, keep the arguments around for
+        // monitors or in case the command turns out to be slow.
+        let monitored = !self.monitors.is_empty();
+        let args = if monitored || self.is_timing() {
+            command_args(&frame)
+        } else {
+            vec![]
+        };
+
+        let cmd = match Command::from_frame(frame, &self.modules) {
+            Ok(cmd) => cmd,
+            Err(err) => {
+                // A command that cannot be queued aborts the transaction.
+                if let Some(transaction) = &mut session.transaction {
+                    transaction.failed = true;
+                }
+                return Some(cmd::error_frame(&err));
+            }
+        };
+
+        if self.scripts.is_busy() && !cmd.is_allowed_when_busy() {
+            return Some(Frame::Error(
+                "BUSY Redis is busy running a script. You can only call SCRIPT KILL or FUNCTION KILL."
+                    .to_string(),
+            ));
+        }
+
+        // RESP3 clients get pub/sub messages as pushes, apart from replies, so
+        // they may keep sending any command.
+        if session.is_subscribed() && !session.outbox.is_resp3() && !cmd.is_allowed_when_subscribed() {
+            return Some(Frame::Error(format!(
+                "ERR Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+                cmd.get_name()
+            )));
+        }
+
+        if monitored && cmd.is_monitored() {
+            self.monitors.feed(session.db, session.addr, &args);
+        }
+
+        if cmd.is_denied_when_oom() && !self.free_memory() {
+            if let Some(transaction) = &mut session.transaction {
+                transaction.failed = true;
+            }
+            return Some(Frame::Error(
+                "OOM command not allowed when used memory > 'maxmemory'.".to_string(),
+            ));
+        }
+
+        if let Some(transaction) = &mut session.transaction {
+            if !cmd.is_transaction_control() {
+                if let Command::Unknown(unknown) = cmd {
+                    transaction.failed = true;
+                    return Some(unknown.apply());
+                }
+                if let Command::Migrate(_) = cmd {
+                    transaction.failed = true;
+                    return Some(Frame::Error(cmd::MIGRATE_IN_TRANSACTION.to_string()));
+                }
+
+                transaction.commands.push(cmd);
+                return Some(Frame::Simple("QUEUED".to_string()));
+            }
+        }
+
+        // `CLIENT CACHING` applies to the command that follows it, or to
+        // the whole transaction.
+        let sets_caching = cmd.is_client_caching();
+
+        // Takes the locks of its keys on its own, see `Migrate`.
+        if let Command::Migrate(migrate) = cmd {
+            let start = Instant::now();
+            let response = migrate.apply(self, session).await;
+            self.record(&args, start.elapsed(), session);
+            session.caching = None;
+            return Some(response);
+        }
+
+        let scope = match (&cmd, &session.transaction) {
+            (Command::Exec(_), Some(transaction)) => transaction
+                .commands
+                .iter()
+                .fold(Scope::None, |scope, cmd| scope.union(cmd.lock_scope())),
+            _ => cmd.lock_scope(),
+        };
+        let _guard = self.locks.lock(&scope, cmd.is_atomic()).await;
+
+        let start = Instant::now();
+        let response = if cmd.is_atomic() {
+            run_blocking(|| tracking::on_behalf_of(session.id, || cmd.apply(self, session)))
+        } else {
+            tracking::on_behalf_of(session.id, || cmd.apply(self, session))
+        };
+        self.record(&args, start.elapsed(), session);
+
+        if !sets_caching && session.transaction.is_none() {
+            session.caching = None;
+        }
+
+        response
+    }
+
+    /// Runs a command taken from the Raft log against database `db`, on
+    /// behalf of no client in particular.
+    ///
+    /// The command was checked when submitted, it is only fed to monitors.
+    pub(crate) async fn apply_logged(&self, db: usize, args: Vec<Bytes>) -> Frame {
+        let mut session = Session::new(0, SocketAddr::from(([0, 0, 0, 0], 0)));
+        session.db = db;
+
+        if !self.monitors.is_empty() {
+            self.monitors.feed(db, session.addr, &args);
+        }
+
+        let frame = Frame::Array(args.into_iter().map(Frame::Bulk).collect());
+        let cmd = match Command::from_frame(frame, &self.modules) {
+            Ok(cmd) => cmd,
+            Err(err) => return cmd::error_frame(&err),
+        };
+
+        let _guard = self.locks.lock(&cmd.lock_scope(), cmd.is_atomic()).await;
+        cmd.apply(self, &mut session).unwrap_or(Frame::Null)
+    }
+
+    /// Removes keys past their expiration time, meant to run periodically on
+    /// top of keys expiring when accessed.
+    ///
+    /// Keys do not expire in the middle of a transaction.
+    pub async fn active_expire(&self) {
+        self.run_shared(Scope::All, || self.dbs.expire_cycle(ACTIVE_EXPIRE_LIMIT))
+            .await
+    }
+
+    /// Runs `f` as a single command on the keys of `scope`, between
+    /// transactions and scripts, for requests that do not go through
+    /// `execute`.
+    pub(crate) async fn run_shared<R>(&self, scope: Scope, f: impl FnOnce() -> R) -> R {
+        let _shared = self.locks.lock(&scope, false).await;
+        f()
+    }
+
+    /// Like `run_shared`, with `f` running on a thread where blocking is
+    /// fine, for work too long to hold up a worker thread.
+    pub(crate) async fn run_shared_blocking<R: Send + 'static>(
+        &self,
+        scope: Scope,
+        f: impl FnOnce() -> R + Send + 'static,
+    ) -> R {
+        let _shared = self.locks.lock(&scope, false).await;
+        match task::spawn_blocking(f).await {
+            Ok(res) => res,
+            Err(err) => std::panic::resume_unwind(err.into_panic()),
+        }
+    }
+
+    /// Runs `f` with no command running alongside it.
+    pub(crate) async fn run_exclusive<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _exclusive = self.locks.lock(&Scope::All, true).await;
+        f()
+    }
+
+    /// Evicts keys as needed to get under `maxmemory`, returning `false` if
+    /// the databases are still over it.
+    pub(crate) fn free_memory(&self) -> bool {
+        let maxmemory = self.config.maxmemory();
+        maxmemory == 0 || self.dbs.evict(maxmemory, self.config.maxmemory_policy())
+    }
+
+    fn is_timing(&self) -> bool {
+        self.config.slowlog_log_slower_than() >= 0 || self.config.latency_monitor_threshold() > 0
+    }
+
+    fn record(&self, args: &[Bytes], duration: Duration, session: &Session) {
+        let slower_than = self.config.slowlog_log_slower_than();
+        if slower_than >= 0 && duration.as_micros() >= slower_than as u128 {
+            self.slowlog
+                .push(args, duration, session.addr, self.config.slowlog_max_len());
+        }
+
+        self.latency.record(
+            latency::COMMAND_EVENT,
+            duration,
+            self.config.latency_monitor_threshold(),
+        );
+    }
+}
+
+pub(crate) fn command_args(frame: &Frame) -> Vec<Bytes> {
+    match frame {
+        Frame::Array(parts) => parts
+            .iter()
+            .filter_map(|part| match part {
+                Frame::Bulk(arg) => Some(arg.clone()),
+                Frame::Simple(arg) => Some(arg.clone().into()),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Runs `f`, a transaction or script that may take long, letting the
+/// runtime hand the other tasks of this worker thread to another one
+/// meanwhile. They would otherwise wait for `f`, `SCRIPT KILL` included.
+fn run_blocking<R>(f: impl FnOnce() -> R) -> R {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
@@ -0,0 +1,245 @@
+use bytes::Bytes;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::config::{ClientClass, Config};
+use crate::outbox::Outbox;
+use crate::protocol::frame::Frame;
+use crate::pubsub::{ClientId, PubSub};
+
+/// Channel RESP2 clients subscribe to when they are the target of a
+/// `REDIRECT`.
+const INVALIDATE_CHANNEL: &str = "__redis__:invalidate";
+
+thread_local! {
+    /// Client whose command is running on this thread, so `NOLOOP` clients
+    /// are not told about their own writes.
+    static WRITER: Cell<Option<ClientId>> = const { Cell::new(None) };
+}
+
+/// How a client tracks keys, set with `CLIENT TRACKING on`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackingOptions {
+    /// Client invalidations are sent to instead of this one.
+    pub redirect: Option<ClientId>,
+    /// Track every key starting with one of `prefixes` rather than the keys
+    /// read by the client.
+    pub bcast: bool,
+    pub prefixes: Vec<Bytes>,
+    /// Only track keys read right after `CLIENT CACHING yes`.
+    pub optin: bool,
+    /// Track keys read unless right after `CLIENT CACHING no`.
+    pub optout: bool,
+    /// Skip invalidations of keys the client changed itself.
+    pub noloop: bool,
+}
+
+/// Server side of client-side caching: remembers which clients may hold
+/// which keys, and tells them when those keys change.
+///
+/// Invalidations are pushed to RESP3 clients, or to a client subscribed to
+/// `__redis__:invalidate` when redirected.
+#[derive(Debug)]
+pub struct Tracking {
+    inner: Mutex<Inner>,
+    /// Number of clients tracking keys, so writes skip the lock when there
+    /// are none.
+    trackers: AtomicUsize,
+    config: Arc<Config>,
+    pubsub: Arc<PubSub>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// Every connection, as any of them may be the target of a redirect.
+    connections: HashMap<ClientId, Arc<Outbox>>,
+    trackers: HashMap<ClientId, TrackingOptions>,
+    /// Keys read by clients in the default mode, with those clients.
+    keys: HashMap<Bytes, HashSet<ClientId>>,
+    /// The other way around, so a client's keys go when it stops tracking.
+    reads: HashMap<ClientId, HashSet<Bytes>>,
+    /// Prefixes of clients in broadcast mode, with those clients.
+    prefixes: HashMap<Bytes, HashSet<ClientId>>,
+}
+
+impl TrackingOptions {
+    /// Whether keys read by the next command are remembered, given the
+    /// latest `CLIENT CACHING` answer.
+    pub fn tracks_reads(&self, caching: Option<bool>) -> bool {
+        !self.bcast
+            && (!self.optin || caching == Some(true))
+            && (!self.optout || caching != Some(false))
+    }
+}
+
+impl Tracking {
+    pub fn new(config: Arc<Config>, pubsub: Arc<PubSub>) -> Tracking {
+        Tracking {
+            inner: Mutex::new(Inner::default()),
+            trackers: AtomicUsize::new(0),
+            config,
+            pubsub,
+        }
+    }
+
+    pub fn connect(&self, client: ClientId, outbox: &Arc<Outbox>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.connections.insert(client, Arc::clone(outbox));
+    }
+
+    pub fn disconnect(&self, client: ClientId) {
+        self.disable(client);
+        self.inner.lock().unwrap().connections.remove(&client);
+    }
+
+    /// Whether `client` is still connected.
+    pub fn is_connected(&self, client: ClientId) -> bool {
+        self.inner.lock().unwrap().connections.contains_key(&client)
+    }
+
+    /// Starts tracking for `client`, or updates its options. Prefixes add up
+    /// with the ones given before.
+    pub fn enable(&self, client: ClientId, options: &TrackingOptions) {
+        let mut inner = self.inner.lock().unwrap();
+
+        for prefix in &options.prefixes {
+            inner
+                .prefixes
+                .entry(prefix.clone())
+                .or_default()
+                .insert(client);
+        }
+
+        if inner.trackers.insert(client, options.clone()).is_none() {
+            self.trackers.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Stops tracking for `client`, forgetting the keys it read.
+    pub fn disable(&self, client: ClientId) {
+        let mut inner = self.inner.lock().unwrap();
+
+        let options = match inner.trackers.remove(&client) {
+            Some(options) => options,
+            None => return,
+        };
+        self.trackers.fetch_sub(1, Ordering::Relaxed);
+
+        for key in inner.reads.remove(&client).unwrap_or_default() {
+            if let Some(clients) = inner.keys.get_mut(&key) {
+                clients.remove(&client);
+                if clients.is_empty() {
+                    inner.keys.remove(&key);
+                }
+            }
+        }
+
+        for prefix in &options.prefixes {
+            if let Some(clients) = inner.prefixes.get_mut(prefix) {
+                clients.remove(&client);
+                if clients.is_empty() {
+                    inner.prefixes.remove(prefix);
+                }
+            }
+        }
+    }
+
+    /// Remembers that `client` read `keys`, it is told when they change.
+    pub fn remember(&self, client: ClientId, keys: &[Bytes]) {
+        let mut inner = self.inner.lock().unwrap();
+        // Tracking may have been turned off by another connection meanwhile.
+        if !inner.trackers.contains_key(&client) {
+            return;
+        }
+        for key in keys {
+            inner.keys.entry(key.clone()).or_default().insert(client);
+            inner.reads.entry(client).or_default().insert(key.clone());
+        }
+    }
+
+    /// Tells the clients that may hold `key` that it changed.
+    pub fn invalidate(&self, key: &[u8]) {
+        if self.trackers.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+
+        let writer = WRITER.with(Cell::get);
+        let mut inner = self.inner.lock().unwrap();
+
+        let mut clients = inner.keys.remove(key).unwrap_or_default();
+        for client in &clients {
+            if let Some(keys) = inner.reads.get_mut(client) {
+                keys.remove(key);
+            }
+        }
+        for (prefix, subscribers) in &inner.prefixes {
+            if key.starts_with(&prefix[..]) {
+                clients.extend(subscribers);
+            }
+        }
+
+        let keys = Frame::Array(vec![Frame::Bulk(Bytes::copy_from_slice(key))]);
+        for client in clients {
+            match inner.trackers.get(&client) {
+                Some(options) if options.noloop && writer == Some(client) => {}
+                Some(options) => self.send(&inner, client, options, keys.clone()),
+                None => {}
+            }
+        }
+    }
+
+    /// Tells every tracking client that all keys changed, after a flush.
+    pub fn invalidate_all(&self) {
+        if self.trackers.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.keys.clear();
+        inner.reads.clear();
+
+        for (&client, options) in &inner.trackers {
+            self.send(&inner, client, options, Frame::Null);
+        }
+    }
+
+    fn send(&self, inner: &Inner, client: ClientId, options: &TrackingOptions, keys: Frame) {
+        let limit = self.config.client_output_buffer_limit(ClientClass::PubSub);
+        let target = options.redirect.unwrap_or(client);
+
+        match inner.connections.get(&target) {
+            Some(outbox) if outbox.is_resp3() => {
+                outbox.push(
+                    &Frame::Push(vec![Frame::Bulk("invalidate".into()), keys]),
+                    limit,
+                );
+            }
+            // A RESP2 client can only receive invalidations as pub/sub
+            // messages, on behalf of another client.
+            Some(_) if options.redirect.is_some() => {
+                self.pubsub
+                    .send_to(&Bytes::from(INVALIDATE_CHANNEL), keys, target, limit);
+            }
+            Some(_) => {}
+            None => {
+                if let Some(outbox) = inner.connections.get(&client).filter(|o| o.is_resp3()) {
+                    let broken = Frame::Push(vec![
+                        Frame::Bulk("tracking-redir-broken".into()),
+                        Frame::Integer(target as i64),
+                    ]);
+                    outbox.push(&broken, limit);
+                }
+            }
+        }
+    }
+}
+
+/// Runs `f` as a command of `client`, see `TrackingOptions::noloop`.
+pub fn on_behalf_of<R>(client: ClientId, f: impl FnOnce() -> R) -> R {
+    let previous = WRITER.with(|writer| writer.replace(Some(client)));
+    let result = f();
+    WRITER.with(|writer| writer.set(previous));
+    result
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use common::{array, int, is_error, Client};
+use miniredis::protocol::frame::Frame;
+
+#[tokio::test]
+async fn widest_signed_fields_sign_extend() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    let reply = client
+        .query(&["BITFIELD", "k", "SET", "i63", "0", "-1", "GET", "i63", "0"])
+        .await;
+    assert_eq!(reply, array(vec![int(0), int(-1)]));
+
+    let min = i64::MIN.to_string();
+    let reply = client
+        .query(&["BITFIELD", "k2", "SET", "i64", "0", &min, "GET", "i64", "0"])
+        .await;
+    assert_eq!(reply, array(vec![int(0), int(i64::MIN)]));
+
+    let reply = client
+        .query(&[
+            "BITFIELD", "k2", "GET", "i63", "0", "GET", "u63", "0", "GET", "i1", "0",
+        ])
+        .await;
+    assert_eq!(reply, array(vec![int(-(1 << 62)), int(1 << 62), int(-1)]));
+}
+
+#[tokio::test]
+async fn unsigned_fields_read_their_bit_pattern() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    let reply = client
+        .query(&[
+            "BITFIELD", "k", "SET", "u63", "0", "-1", "GET", "u63", "0", "GET", "u1", "62",
+        ])
+        .await;
+    assert_eq!(reply, array(vec![int(0), int(i64::MAX), int(1)]));
+
+    let reply = client
+        .query(&["BITFIELD", "k", "SET", "u8", "100", "256"])
+        .await;
+    assert_eq!(reply, array(vec![int(0)]));
+    let reply = client.query(&["BITFIELD", "k", "GET", "u8", "100"]).await;
+    assert_eq!(reply, array(vec![int(0)]));
+}
+
+#[tokio::test]
+async fn overflow_policies() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    // WRAP is the default.
+    let reply = client
+        .query(&[
+            "BITFIELD", "k", "SET", "i8", "0", "127", "INCRBY", "i8", "0", "1",
+        ])
+        .await;
+    assert_eq!(reply, array(vec![int(0), int(-128)]));
+
+    let reply = client
+        .query(&[
+            "BITFIELD", "k", "OVERFLOW", "SAT", "INCRBY", "i8", "0", "-200",
+        ])
+        .await;
+    assert_eq!(reply, array(vec![int(-128)]));
+
+    let reply = client
+        .query(&[
+            "BITFIELD", "k", "OVERFLOW", "FAIL", "INCRBY", "i8", "0", "-1", "GET", "i8", "0",
+        ])
+        .await;
+    assert_eq!(reply, array(vec![Frame::Null, int(-128)]));
+
+    let reply = client
+        .query(&[
+            "BITFIELD", "k", "INCRBY", "u2", "100", "1", "OVERFLOW", "SAT", "INCRBY", "u2", "102",
+            "1",
+        ])
+        .await;
+    assert_eq!(reply, array(vec![int(1), int(1)]));
+    for expected in [(2, 2), (3, 3), (0, 3)] {
+        let reply = client
+            .query(&[
+                "BITFIELD", "k", "INCRBY", "u2", "100", "1", "OVERFLOW", "SAT", "INCRBY", "u2",
+                "102", "1",
+            ])
+            .await;
+        assert_eq!(reply, array(vec![int(expected.0), int(expected.1)]));
+    }
+}
+
+#[tokio::test]
+async fn widest_fields_wrap_and_saturate() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+    let max = i64::MAX.to_string();
+
+    let reply = client
+        .query(&[
+            "BITFIELD", "k", "SET", "i64", "0", &max, "INCRBY", "i64", "0", "1",
+        ])
+        .await;
+    assert_eq!(reply, array(vec![int(0), int(i64::MIN)]));
+
+    let reply = client
+        .query(&[
+            "BITFIELD", "k", "OVERFLOW", "SAT", "INCRBY", "i64", "0", "-1",
+        ])
+        .await;
+    assert_eq!(reply, array(vec![int(i64::MIN)]));
+
+    let reply = client
+        .query(&[
+            "BITFIELD", "k", "SET", "u63", "0", &max, "INCRBY", "u63", "0", "1",
+        ])
+        .await;
+    assert_eq!(reply, array(vec![int(1 << 62), int(0)]));
+}
+
+#[tokio::test]
+async fn invalid_types_are_refused() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    for encoding in ["u64", "i65", "i0", "x8"] {
+        let reply = client.query(&["BITFIELD", "k", "GET", encoding, "0"]).await;
+        assert!(is_error(&reply, "ERR"), "{}: {:?}", encoding, reply);
+    }
+}
@@ -0,0 +1,126 @@
+//! Helpers shared by the integration tests: an embedded server and a bare
+//! RESP client built on the server's own `Connection`.
+
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use tokio::net::TcpStream;
+
+use miniredis::config::Config;
+use miniredis::notify::Notifier;
+use miniredis::protocol::connection::Connection;
+use miniredis::protocol::frame::Frame;
+use miniredis::pubsub::PubSub;
+use miniredis::server::{Builder, Server, ServerHandle};
+use miniredis::tracking::Tracking;
+use miniredis::Db;
+use shared_lib::sharded_db::ShardModel;
+
+/// Starts a server on a free port.
+pub async fn start() -> ServerHandle {
+    start_with(Server::builder()).await
+}
+
+/// Starts the server configured by `builder` on a free port.
+pub async fn start_with(builder: Builder) -> ServerHandle {
+    builder.bind("127.0.0.1:0").start().await.unwrap()
+}
+
+/// A database on its own, without a server expiring keys in the background.
+pub fn db() -> Arc<Db> {
+    db_with(ShardModel::Mutex)
+}
+
+pub fn db_with(model: ShardModel) -> Arc<Db> {
+    let config = Arc::new(Config::default());
+    let pubsub = Arc::new(PubSub::new());
+    let events = Arc::new(Notifier::new(Arc::clone(&config), Arc::clone(&pubsub)));
+    let tracking = Arc::new(Tracking::new(config, pubsub));
+    Db::new(0, 4, model, events, tracking)
+}
+
+pub struct Client {
+    connection: Connection,
+}
+
+impl Client {
+    pub async fn connect(server: &ServerHandle) -> Client {
+        let socket = TcpStream::connect(server.local_addr()).await.unwrap();
+        Client {
+            connection: Connection::new(socket),
+        }
+    }
+
+    /// Sends a command and waits for its reply.
+    pub async fn query(&mut self, args: &[&str]) -> Frame {
+        let args: Vec<&[u8]> = args.iter().map(|arg| arg.as_bytes()).collect();
+        self.query_bytes(&args).await
+    }
+
+    pub async fn query_bytes(&mut self, args: &[&[u8]]) -> Frame {
+        self.send_bytes(args).await;
+        self.read().await
+    }
+
+    /// Sends a command without waiting for its reply.
+    pub async fn send(&mut self, args: &[&str]) {
+        let args: Vec<&[u8]> = args.iter().map(|arg| arg.as_bytes()).collect();
+        self.send_bytes(&args).await
+    }
+
+    pub async fn send_bytes(&mut self, args: &[&[u8]]) {
+        let frame = Frame::Array(
+            args.iter()
+                .map(|arg| Frame::Bulk(Bytes::copy_from_slice(arg)))
+                .collect(),
+        );
+        self.connection.write_frame(&frame).await.unwrap();
+    }
+
+    /// Reads the next frame, a reply or a push.
+    pub async fn read(&mut self) -> Frame {
+        self.connection
+            .read_frame()
+            .await
+            .unwrap()
+            .expect("connection closed")
+    }
+
+    /// Reads until the server closes the connection, returning the frames
+    /// read meanwhile.
+    pub async fn closed(&mut self) -> Vec<Frame> {
+        let mut frames = vec![];
+        while let Ok(Some(frame)) = self.connection.read_frame().await {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    /// Switches the connection to RESP3, for pushes to arrive as such.
+    pub async fn resp3(&mut self) {
+        self.query(&["HELLO", "3"]).await;
+    }
+}
+
+pub fn ok() -> Frame {
+    Frame::ok()
+}
+
+pub fn int(value: i64) -> Frame {
+    Frame::Integer(value)
+}
+
+pub fn bulk(value: &str) -> Frame {
+    Frame::Bulk(Bytes::copy_from_slice(value.as_bytes()))
+}
+
+pub fn array(frames: Vec<Frame>) -> Frame {
+    Frame::Array(frames)
+}
+
+/// Whether `frame` is an error starting with `prefix`, e.g. `BUSY`.
+pub fn is_error(frame: &Frame, prefix: &str) -> bool {
+    matches!(frame, Frame::Error(msg) if msg.starts_with(prefix))
+}
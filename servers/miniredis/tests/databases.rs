@@ -0,0 +1,133 @@
+mod common;
+
+use common::{bulk, int, is_error, ok, Client};
+use miniredis::protocol::frame::Frame;
+
+#[tokio::test]
+async fn databases_are_isolated() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+    let mut other = Client::connect(&server).await;
+
+    client.query(&["SET", "key", "zero"]).await;
+    assert_eq!(other.query(&["SELECT", "1"]).await, ok());
+    assert_eq!(other.query(&["GET", "key"]).await, Frame::Null);
+    other.query(&["SET", "key", "one"]).await;
+    other.query(&["SET", "more", "one"]).await;
+
+    assert_eq!(client.query(&["GET", "key"]).await, bulk("zero"));
+    assert_eq!(client.query(&["DBSIZE"]).await, int(1));
+    assert_eq!(other.query(&["DBSIZE"]).await, int(2));
+
+    let reply = client.query(&["SELECT", "16"]).await;
+    assert!(
+        is_error(&reply, "ERR DB index is out of range"),
+        "{:?}",
+        reply
+    );
+}
+
+#[tokio::test]
+async fn flushdb_and_flushall() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    client.query(&["SET", "key", "zero"]).await;
+    client.query(&["SELECT", "1"]).await;
+    client.query(&["SET", "key", "one"]).await;
+
+    assert_eq!(client.query(&["FLUSHDB", "ASYNC"]).await, ok());
+    assert_eq!(client.query(&["DBSIZE"]).await, int(0));
+    client.query(&["SELECT", "0"]).await;
+    assert_eq!(client.query(&["DBSIZE"]).await, int(1));
+
+    assert_eq!(client.query(&["FLUSHALL"]).await, ok());
+    assert_eq!(client.query(&["DBSIZE"]).await, int(0));
+    let reply = client.query(&["FLUSHDB", "LATER"]).await;
+    assert!(is_error(&reply, "ERR syntax error"), "{:?}", reply);
+}
+
+#[tokio::test]
+async fn swapdb_swaps_what_connections_see() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+    let mut other = Client::connect(&server).await;
+
+    client.query(&["SET", "key", "zero"]).await;
+    other.query(&["SELECT", "1"]).await;
+    other.query(&["SET", "key", "one"]).await;
+
+    assert_eq!(client.query(&["SWAPDB", "0", "1"]).await, ok());
+    assert_eq!(client.query(&["GET", "key"]).await, bulk("one"));
+    assert_eq!(other.query(&["GET", "key"]).await, bulk("zero"));
+}
+
+#[tokio::test]
+async fn move_and_copy_across_databases() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    client.query(&["SET", "moved", "value"]).await;
+    client.query(&["SET", "copied", "value", "EX", "100"]).await;
+    assert_eq!(client.query(&["MOVE", "moved", "1"]).await, int(1));
+    assert_eq!(client.query(&["MOVE", "moved", "1"]).await, int(0));
+    assert_eq!(
+        client.query(&["COPY", "copied", "copy", "DB", "1"]).await,
+        int(1)
+    );
+    assert_eq!(
+        client.query(&["COPY", "copied", "copy", "DB", "1"]).await,
+        int(0)
+    );
+    let reply = client.query(&["MOVE", "copied", "0"]).await;
+    assert!(
+        is_error(&reply, "ERR source and destination"),
+        "{:?}",
+        reply
+    );
+
+    assert_eq!(client.query(&["GET", "moved"]).await, Frame::Null);
+    assert_eq!(client.query(&["GET", "copied"]).await, bulk("value"));
+    client.query(&["SELECT", "1"]).await;
+    assert_eq!(client.query(&["GET", "moved"]).await, bulk("value"));
+    assert_eq!(client.query(&["GET", "copy"]).await, bulk("value"));
+    match client.query(&["TTL", "copy"]).await {
+        Frame::Integer(ttl) => assert!((90..=100).contains(&ttl)),
+        reply => panic!("unexpected reply {:?}", reply),
+    }
+}
+
+#[tokio::test]
+async fn rename_and_renamenx() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    client.query(&["SET", "a", "1"]).await;
+    client.query(&["SET", "b", "2"]).await;
+    assert_eq!(client.query(&["RENAMENX", "a", "b"]).await, int(0));
+    assert_eq!(client.query(&["RENAME", "a", "c"]).await, ok());
+    assert_eq!(client.query(&["GET", "a"]).await, Frame::Null);
+    assert_eq!(client.query(&["GET", "c"]).await, bulk("1"));
+    assert_eq!(client.query(&["RENAMENX", "c", "d"]).await, int(1));
+
+    let reply = client.query(&["RENAME", "missing", "e"]).await;
+    assert!(is_error(&reply, "ERR no such key"), "{:?}", reply);
+}
+
+#[tokio::test]
+async fn randomkey_touch_and_unlink() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    assert_eq!(client.query(&["RANDOMKEY"]).await, Frame::Null);
+    client.query(&["SET", "a", "1"]).await;
+    client.query(&["SET", "b", "2"]).await;
+    match client.query(&["RANDOMKEY"]).await {
+        Frame::Bulk(key) => assert!(&key[..] == b"a" || &key[..] == b"b"),
+        reply => panic!("unexpected reply {:?}", reply),
+    }
+
+    assert_eq!(client.query(&["TOUCH", "a", "b", "missing"]).await, int(2));
+    assert_eq!(client.query(&["UNLINK", "a", "missing"]).await, int(1));
+    assert_eq!(client.query(&["RANDOMKEY"]).await, bulk("b"));
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use common::db;
+use miniredis::db::{Entry, Value};
+use miniredis::Db;
+
+fn set(db: &Db, key: &[u8], value: &'static str) {
+    db.update_entry(key, |mut slot| *slot = Some(Entry::new(Value::String(value.into()))));
+}
+
+#[test]
+fn entries_are_handed_out_as_stored() {
+    let db = db();
+    set(&db, b"key", "v");
+
+    let first = db.get_entry(b"key").unwrap();
+    let second = db.get_entry(b"key").unwrap();
+    assert!(Arc::ptr_eq(&first, &second));
+}
+
+#[test]
+fn writes_leave_handed_out_entries_alone() {
+    let db = db();
+    set(&db, b"key", "old");
+    let before = db.get_entry(b"key").unwrap();
+
+    set(&db, b"key", "new");
+    let after = db.get_entry(b"key").unwrap();
+    assert!(!Arc::ptr_eq(&before, &after));
+    assert!(matches!(&before.value, Value::String(value) if value == "old"));
+    assert!(matches!(&after.value, Value::String(value) if value == "new"));
+}
+
+fn cas(db: &Db, key: &[u8]) -> u64 {
+    db.get_entry(key).unwrap().cas
+}
+
+#[test]
+fn writing_the_same_value_counts_as_a_change() {
+    let db = db();
+    db.update(b"key", |mut slot| *slot = Some(Bytes::new())).unwrap();
+    let before = cas(&db, b"key");
+
+    db.update(b"key", |mut slot| *slot = Some(Bytes::new())).unwrap();
+    assert_ne!(cas(&db, b"key"), before);
+}
+
+#[test]
+fn reading_through_an_update_is_not_a_change() {
+    let db = db();
+    set(&db, b"key", "v");
+    let before = cas(&db, b"key");
+
+    assert!(db.update(b"key", |slot| slot.is_some()).unwrap());
+    assert!(db.update_entry(b"key", |slot| slot.is_some()));
+    assert_eq!(cas(&db, b"key"), before);
+}
@@ -0,0 +1,247 @@
+mod common;
+
+use common::{bulk, int, is_error, ok, Client};
+use miniredis::protocol::frame::Frame;
+use std::time::Duration;
+
+async fn dump(client: &mut Client, key: &str) -> Vec<u8> {
+    match client.query(&["DUMP", key]).await {
+        Frame::Bulk(payload) => payload.to_vec(),
+        reply => panic!("unexpected reply {:?}", reply),
+    }
+}
+
+async fn pttl(client: &mut Client, key: &str) -> i64 {
+    match client.query(&["PTTL", key]).await {
+        Frame::Integer(ttl) => ttl,
+        reply => panic!("unexpected reply {:?}", reply),
+    }
+}
+
+/// `MIGRATE` of `keys` from the current database of `client` to database
+/// `db` of the server on `port`, with `options` such as `COPY`.
+async fn migrate(
+    client: &mut Client,
+    port: u16,
+    db: &str,
+    options: &[&str],
+    keys: &[&str],
+) -> Frame {
+    let port = port.to_string();
+    let mut args = vec!["MIGRATE", "127.0.0.1", &port, "", db, "5000"];
+    args.extend_from_slice(options);
+    args.push("KEYS");
+    args.extend_from_slice(keys);
+    client.query(&args).await
+}
+
+#[tokio::test]
+async fn dump_and_restore_round_trip() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    assert_eq!(
+        client.query(&["SET", "string", "value", "EX", "100"]).await,
+        ok()
+    );
+    assert_eq!(client.query(&["SET", "int", "12345"]).await, ok());
+    let string = dump(&mut client, "string").await;
+    let number = dump(&mut client, "int").await;
+
+    assert_eq!(
+        client
+            .query_bytes(&[b"RESTORE", b"string:copy", b"0", &string])
+            .await,
+        ok()
+    );
+    assert_eq!(
+        client
+            .query_bytes(&[b"RESTORE", b"int:copy", b"0", &number])
+            .await,
+        ok()
+    );
+    assert_eq!(client.query(&["GET", "string:copy"]).await, bulk("value"));
+    assert!((90_000..=100_000).contains(&pttl(&mut client, "string:copy").await));
+    assert_eq!(client.query(&["GET", "int:copy"]).await, bulk("12345"));
+    assert_eq!(client.query(&["PTTL", "int:copy"]).await, int(-1));
+    assert_eq!(
+        client.query(&["OBJECT", "ENCODING", "int:copy"]).await,
+        bulk("int")
+    );
+
+    // Existing keys are only replaced when asked to.
+    let reply = client
+        .query_bytes(&[b"RESTORE", b"int:copy", b"0", &number])
+        .await;
+    assert!(is_error(&reply, "BUSYKEY"), "{:?}", reply);
+    assert_eq!(
+        client
+            .query_bytes(&[b"RESTORE", b"int:copy", b"0", &number, b"REPLACE"])
+            .await,
+        ok()
+    );
+    assert_eq!(client.query(&["GET", "int:copy"]).await, bulk("12345"));
+
+    // A given time to live replaces the one in the payload.
+    assert_eq!(
+        client
+            .query_bytes(&[b"RESTORE", b"string:ttl", b"5000", &string])
+            .await,
+        ok()
+    );
+    assert!((4_000..=5_000).contains(&pttl(&mut client, "string:ttl").await));
+}
+
+#[tokio::test]
+async fn restore_refuses_corrupted_payloads() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    client.query(&["SET", "key", "value"]).await;
+    let mut payload = dump(&mut client, "key").await;
+    payload[2] ^= 0xff;
+
+    let reply = client
+        .query_bytes(&[b"RESTORE", b"copy", b"0", &payload])
+        .await;
+    assert!(is_error(&reply, "ERR DUMP payload"), "{:?}", reply);
+    let reply = client.query(&["RESTORE", "copy", "0", "short"]).await;
+    assert!(is_error(&reply, "ERR DUMP payload"), "{:?}", reply);
+    assert_eq!(client.query(&["GET", "copy"]).await, Frame::Null);
+}
+
+#[tokio::test]
+async fn migrate_moves_keys() {
+    let source = common::start().await;
+    let target = common::start().await;
+    let mut client = Client::connect(&source).await;
+    let mut other = Client::connect(&target).await;
+
+    client.query(&["SET", "a", "1"]).await;
+    client.query(&["SET", "b", "2", "EX", "100"]).await;
+    assert_eq!(
+        migrate(
+            &mut client,
+            target.local_addr().port(),
+            "2",
+            &[],
+            &["a", "b", "missing"]
+        )
+        .await,
+        ok()
+    );
+
+    assert_eq!(client.query(&["DEL", "a", "b"]).await, int(0));
+    other.query(&["SELECT", "2"]).await;
+    assert_eq!(other.query(&["GET", "a"]).await, bulk("1"));
+    assert_eq!(other.query(&["GET", "b"]).await, bulk("2"));
+    assert!((90_000..=100_000).contains(&pttl(&mut other, "b").await));
+
+    // Nothing left to move.
+    assert_eq!(
+        migrate(&mut client, target.local_addr().port(), "2", &[], &["a"]).await,
+        Frame::Simple("NOKEY".into())
+    );
+}
+
+#[tokio::test]
+async fn migrate_copy_and_replace() {
+    let source = common::start().await;
+    let target = common::start().await;
+    let mut client = Client::connect(&source).await;
+    let mut other = Client::connect(&target).await;
+
+    client.query(&["SET", "key", "new"]).await;
+    other.query(&["SET", "key", "old"]).await;
+
+    // The key is kept here when the target refuses it.
+    let reply = migrate(
+        &mut client,
+        target.local_addr().port(),
+        "0",
+        &["COPY"],
+        &["key"],
+    )
+    .await;
+    assert!(
+        is_error(&reply, "ERR Target instance replied with error: BUSYKEY"),
+        "{:?}",
+        reply
+    );
+    assert_eq!(other.query(&["GET", "key"]).await, bulk("old"));
+
+    assert_eq!(
+        migrate(
+            &mut client,
+            target.local_addr().port(),
+            "0",
+            &["COPY", "REPLACE"],
+            &["key"]
+        )
+        .await,
+        ok()
+    );
+    assert_eq!(other.query(&["GET", "key"]).await, bulk("new"));
+    assert_eq!(client.query(&["GET", "key"]).await, bulk("new"));
+}
+
+#[tokio::test]
+async fn migrate_to_the_same_server() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    client.query(&["SET", "key", "value"]).await;
+    let reply = tokio::time::timeout(
+        Duration::from_secs(2),
+        migrate(&mut client, server.local_addr().port(), "1", &[], &["key"]),
+    )
+    .await
+    .expect("MIGRATE to the same server hung");
+    assert_eq!(reply, ok());
+    assert_eq!(client.query(&["GET", "key"]).await, Frame::Null);
+    client.query(&["SELECT", "1"]).await;
+    assert_eq!(client.query(&["GET", "key"]).await, bulk("value"));
+
+    // Restored over itself, the key is left rather than deleted.
+    assert_eq!(
+        migrate(
+            &mut client,
+            server.local_addr().port(),
+            "1",
+            &["REPLACE"],
+            &["key"]
+        )
+        .await,
+        ok()
+    );
+    assert_eq!(client.query(&["GET", "key"]).await, bulk("value"));
+}
+
+#[tokio::test]
+async fn migrate_reports_unreachable_targets() {
+    let server = common::start().await;
+    let target = common::start().await;
+    let mut client = Client::connect(&server).await;
+    client.query(&["SET", "key", "value"]).await;
+    let port = target.local_addr().port();
+    target.shutdown().await;
+
+    let reply = migrate(&mut client, port, "0", &[], &["key"]).await;
+    assert!(is_error(&reply, "IOERR"), "{:?}", reply);
+    assert_eq!(client.query(&["GET", "key"]).await, bulk("value"));
+}
+
+#[tokio::test]
+async fn migrate_is_refused_in_transactions() {
+    let source = common::start().await;
+    let target = common::start().await;
+    let mut client = Client::connect(&source).await;
+
+    client.query(&["SET", "key", "value"]).await;
+    client.query(&["MULTI"]).await;
+    let reply = migrate(&mut client, target.local_addr().port(), "0", &[], &["key"]).await;
+    assert!(is_error(&reply, "ERR MIGRATE"), "{:?}", reply);
+    let reply = client.query(&["EXEC"]).await;
+    assert!(is_error(&reply, "EXECABORT"), "{:?}", reply);
+    assert_eq!(client.query(&["GET", "key"]).await, bulk("value"));
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{array, bulk, db, db_with, int, ok, Client};
+use miniredis::db::{now_ms, Entry, Value};
+use miniredis::Db;
+use shared_lib::sharded_db::ShardModel;
+
+fn set_ttl(db: &Db, key: &[u8], ttl: Option<u64>) {
+    db.update_entry(key, |mut slot| {
+        let entry = slot.get_or_insert_with(|| Entry::new(Value::String("v".into())));
+        entry.expires_at = ttl.map(|ttl| now_ms() + ttl);
+    });
+}
+
+#[test]
+fn deleted_keys_leave_no_deadline_behind() {
+    let db = db();
+    set_ttl(&db, b"key", Some(20));
+    db.remove(b"key");
+
+    std::thread::sleep(Duration::from_millis(30));
+    assert_eq!(db.expire_cycle(100), 0);
+}
+
+#[test]
+fn changed_and_cleared_ttls_leave_no_deadline_behind() {
+    let db = db();
+    set_ttl(&db, b"moved", Some(20));
+    set_ttl(&db, b"moved", Some(60_000));
+    set_ttl(&db, b"persisted", Some(20));
+    set_ttl(&db, b"persisted", None);
+    set_ttl(&db, b"due", Some(20));
+
+    std::thread::sleep(Duration::from_millis(30));
+    assert_eq!(db.expire_cycle(100), 1);
+    assert!(db.contains_key(b"moved"));
+    assert!(db.contains_key(b"persisted"));
+    assert!(!db.contains_key(b"due"));
+}
+
+#[test]
+fn expired_keys_read_in_place_are_removed() {
+    for model in [ShardModel::Mutex, ShardModel::RwLock, ShardModel::Owner] {
+        let db = db_with(model);
+        set_ttl(&db, b"due", Some(20));
+        set_ttl(&db, b"kept", None);
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(db.with_entry(b"due", |entry| entry.is_none()));
+        assert!(db.get_entry(b"kept").is_some());
+        assert_eq!(db.expire_cycle(100), 0);
+        assert_eq!(db.get(b"due").unwrap(), None);
+        assert_eq!(db.get(b"kept").unwrap(), Some("v".into()));
+    }
+}
+
+#[tokio::test]
+async fn expired_keys_fire_one_event() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+    let mut subscriber = Client::connect(&server).await;
+    assert_eq!(
+        client
+            .query(&["CONFIG", "SET", "notify-keyspace-events", "Ex"])
+            .await,
+        ok()
+    );
+    subscriber
+        .query(&["SUBSCRIBE", "__keyevent@0__:expired"])
+        .await;
+
+    client.query(&["SET", "gone", "v", "PX", "20"]).await;
+    client.query(&["SET", "kept", "v", "PX", "20"]).await;
+    assert_eq!(client.query(&["PERSIST", "kept"]).await, int(1));
+
+    let message = tokio::time::timeout(Duration::from_secs(1), subscriber.read())
+        .await
+        .unwrap();
+    assert_eq!(
+        message,
+        array(vec![
+            bulk("message"),
+            bulk("__keyevent@0__:expired"),
+            bulk("gone")
+        ])
+    );
+
+    // Neither a second event for `gone` nor one for `kept` follow.
+    let more = tokio::time::timeout(Duration::from_millis(250), subscriber.read()).await;
+    assert!(more.is_err());
+    assert_eq!(client.query(&["GET", "kept"]).await, bulk("v"));
+}
+
+#[tokio::test]
+async fn keyspace_events_follow_the_configured_classes() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+    let mut subscriber = Client::connect(&server).await;
+    client
+        .query(&["CONFIG", "SET", "notify-keyspace-events", "Kg"])
+        .await;
+    subscriber.query(&["PSUBSCRIBE", "__keyspace@0__:*"]).await;
+
+    // `set` is a string event, not enabled, while `del` is generic.
+    client.query(&["SET", "key", "v"]).await;
+    client.query(&["DEL", "key"]).await;
+
+    assert_eq!(
+        subscriber.read().await,
+        array(vec![
+            bulk("pmessage"),
+            bulk("__keyspace@0__:*"),
+            bulk("__keyspace@0__:key"),
+            bulk("del"),
+        ])
+    );
+}
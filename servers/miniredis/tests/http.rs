@@ -0,0 +1,214 @@
+mod common;
+
+use common::Client;
+use miniredis::protocol::frame::Frame;
+use miniredis::server::{Server, ServerHandle};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+async fn start() -> ServerHandle {
+    common::start_with(Server::builder().http("127.0.0.1:0")).await
+}
+
+/// A response, with its status, headers and body.
+struct Response {
+    status: u16,
+    head: String,
+    body: String,
+}
+
+impl Response {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.head.lines().find_map(|line| {
+            let (header, value) = line.split_once(':')?;
+            header.eq_ignore_ascii_case(name).then_some(value.trim())
+        })
+    }
+}
+
+/// Sends a request on a new connection, closed after the response.
+async fn request(
+    server: &ServerHandle,
+    method: &str,
+    path: &str,
+    headers: &[&str],
+    body: &str,
+) -> Response {
+    let mut socket = TcpStream::connect(server.http_addr().unwrap())
+        .await
+        .unwrap();
+    let mut request = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n",
+        method, path
+    );
+    for header in headers {
+        request.push_str(header);
+        request.push_str("\r\n");
+    }
+    request.push_str(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+    socket.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    socket.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    Response {
+        status: head.split(' ').nth(1).unwrap().parse().unwrap(),
+        head: head.to_string(),
+        body: body.to_string(),
+    }
+}
+
+async fn get(server: &ServerHandle, path: &str) -> Response {
+    request(server, "GET", path, &[], "").await
+}
+
+async fn post(server: &ServerHandle, body: &str) -> Response {
+    request(
+        server,
+        "POST",
+        "/",
+        &["Content-Type: application/json"],
+        body,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn get_runs_reads() {
+    let server = start().await;
+    let mut client = Client::connect(&server).await;
+    client.query(&["SET", "a key", "value"]).await;
+
+    let response = get(&server, "/GET/a%20key").await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, r#"{"result":"value"}"#);
+}
+
+#[tokio::test]
+async fn get_refuses_writes() {
+    let server = start().await;
+
+    let response = get(&server, "/SET/key/value").await;
+    assert_eq!(response.status, 405);
+    let response = get(&server, "/DEL/key").await;
+    assert_eq!(response.status, 405);
+}
+
+#[tokio::test]
+async fn get_refuses_admin_commands() {
+    let server = start().await;
+
+    for path in [
+        "/CONFIG/SET/maxmemory-policy/allkeys-random",
+        "/CONFIG/GET/maxmemory-policy",
+        "/SLOWLOG/RESET",
+        "/FLUSHALL",
+        "/FLUSHDB",
+        "/LATENCY/RESET",
+        "/PUBLISH/channel/message",
+    ] {
+        assert_eq!(get(&server, path).await.status, 405, "{}", path);
+    }
+    // Not available over HTTP at all.
+    assert_eq!(get(&server, "/SCRIPT/FLUSH").await.status, 403);
+
+    let mut client = Client::connect(&server).await;
+    assert_eq!(
+        client.query(&["CONFIG", "GET", "maxmemory-policy"]).await,
+        Frame::Array(vec![
+            Frame::Bulk("maxmemory-policy".into()),
+            Frame::Bulk("noeviction".into()),
+        ])
+    );
+}
+
+#[tokio::test]
+async fn post_runs_writes() {
+    let server = start().await;
+
+    let response = post(&server, r#"["SET", "key", "value"]"#).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, r#"{"result":"OK"}"#);
+    let response = post(&server, r#"["CONFIG", "SET", "slowlog-max-len", "10"]"#).await;
+    assert_eq!(response.status, 200);
+
+    let response = post(&server, r#"["GET", "key"]"#).await;
+    assert_eq!(response.body, r#"{"result":"value"}"#);
+}
+
+#[tokio::test]
+async fn post_requires_json() {
+    let server = start().await;
+
+    // What a form or a plain `fetch` from any page may send without asking.
+    for headers in [&["Content-Type: text/plain"][..], &[]] {
+        let response = request(&server, "POST", "/", headers, r#"["SET", "key", "value"]"#).await;
+        assert_eq!(response.status, 415);
+    }
+    let response = request(
+        &server,
+        "POST",
+        "/",
+        &["Content-Type: application/json; charset=utf-8"],
+        r#"["SET", "key", "value"]"#,
+    )
+    .await;
+    assert_eq!(response.status, 200);
+
+    let mut client = Client::connect(&server).await;
+    assert_eq!(client.query(&["DBSIZE"]).await, Frame::Integer(1));
+}
+
+#[tokio::test]
+async fn connection_commands_are_refused() {
+    let server = start().await;
+
+    assert_eq!(post(&server, r#"["MULTI"]"#).await.status, 403);
+    assert_eq!(post(&server, r#"["MONITOR"]"#).await.status, 403);
+}
+
+#[tokio::test]
+async fn cors_is_off_by_default() {
+    let server = start().await;
+
+    let response = get(&server, "/PING").await;
+    assert_eq!(response.header("access-control-allow-origin"), None);
+    let response = request(&server, "OPTIONS", "/", &["Origin: http://example.com"], "").await;
+    assert_eq!(response.status, 405);
+    assert_eq!(response.header("access-control-allow-origin"), None);
+}
+
+#[tokio::test]
+async fn cors_allows_the_configured_origin() {
+    let server = start().await;
+    let response = post(
+        &server,
+        r#"["CONFIG", "SET", "http-cors-allow-origin", "http://example.com"]"#,
+    )
+    .await;
+    assert_eq!(response.status, 200);
+
+    let response = request(&server, "OPTIONS", "/", &["Origin: http://example.com"], "").await;
+    assert_eq!(response.status, 204);
+    assert_eq!(
+        response.header("access-control-allow-origin"),
+        Some("http://example.com")
+    );
+    let response = get(&server, "/PING").await;
+    assert_eq!(
+        response.header("access-control-allow-origin"),
+        Some("http://example.com")
+    );
+}
+
+#[tokio::test]
+async fn origins_cannot_inject_headers() {
+    let server = start().await;
+
+    let response = post(
+        &server,
+        r#"["CONFIG", "SET", "http-cors-allow-origin", "*\r\nX-Evil: 1"]"#,
+    )
+    .await;
+    assert_eq!(response.status, 400);
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use common::{bulk, is_error, ok, Client};
+use miniredis::protocol::frame::Frame;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time;
+
+/// Fails the test if the server keeps the connection open for `secs`.
+async fn assert_closed_within(client: &mut Client, secs: u64) -> Vec<Frame> {
+    time::timeout(Duration::from_secs(secs), client.closed())
+        .await
+        .expect("the connection stayed open")
+}
+
+async fn config_set(client: &mut Client, name: &str, value: &str) {
+    assert_eq!(client.query(&["CONFIG", "SET", name, value]).await, ok());
+}
+
+#[tokio::test]
+async fn clients_past_maxclients_are_refused() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+    config_set(&mut client, "maxclients", "1").await;
+
+    let mut refused = Client::connect(&server).await;
+    let frames = assert_closed_within(&mut refused, 1).await;
+    assert_eq!(frames.len(), 1);
+    assert!(is_error(&frames[0], "ERR max number of clients reached"));
+
+    // The slot is free again once the first client leaves.
+    drop(client);
+    time::sleep(Duration::from_millis(100)).await;
+    let mut client = Client::connect(&server).await;
+    assert_eq!(client.query(&["PING"]).await, Frame::Simple("PONG".into()));
+}
+
+#[tokio::test]
+async fn idle_clients_are_disconnected() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+    let mut subscriber = Client::connect(&server).await;
+    subscriber.query(&["SUBSCRIBE", "channel"]).await;
+    config_set(&mut client, "timeout", "1").await;
+
+    assert!(assert_closed_within(&mut client, 3).await.is_empty());
+
+    // Subscribers stay connected however long they wait for messages.
+    let mut publisher = Client::connect(&server).await;
+    publisher.query(&["PUBLISH", "channel", "hello"]).await;
+    assert_eq!(
+        subscriber.read().await,
+        Frame::Array(vec![bulk("message"), bulk("channel"), bulk("hello")])
+    );
+}
+
+#[tokio::test]
+async fn clients_past_the_query_buffer_limit_are_disconnected() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+    config_set(&mut client, "client-query-buffer-limit", "1kb").await;
+
+    // A bulk string that never ends, its bytes piling up unparsed.
+    let mut socket = TcpStream::connect(server.local_addr()).await.unwrap();
+    socket
+        .write_all(b"*2\r\n$3\r\nGET\r\n$100000\r\n")
+        .await
+        .unwrap();
+    let _ = socket.write_all(&[b'x'; 4096]).await;
+
+    let mut buf = vec![];
+    let read = time::timeout(Duration::from_secs(1), socket.read_to_end(&mut buf))
+        .await
+        .expect("the connection stayed open");
+    assert!(read.is_err() || buf.is_empty());
+
+    // Commands under the limit still go through.
+    assert_eq!(client.query(&["PING"]).await, Frame::Simple("PONG".into()));
+}
+
+#[tokio::test]
+async fn replies_past_the_hard_limit_disconnect() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+    client.query(&["SET", "small", "value"]).await;
+    client.query(&["SET", "large", &"x".repeat(1000)]).await;
+    config_set(&mut client, "client-output-buffer-limit", "normal 100 0 0").await;
+
+    assert_eq!(client.query(&["GET", "small"]).await, bulk("value"));
+    client.send(&["GET", "large"]).await;
+    assert!(assert_closed_within(&mut client, 1).await.is_empty());
+}
+
+#[tokio::test]
+async fn replies_left_unread_past_the_soft_limit_disconnect() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+    let value = "x".repeat(32 * 1024 * 1024);
+    client.query(&["SET", "large", &value]).await;
+    config_set(&mut client, "client-output-buffer-limit", "normal 0 1mb 1").await;
+
+    // More than the socket buffers hold, so the write waits on the reader.
+    let mut socket = TcpStream::connect(server.local_addr()).await.unwrap();
+    socket
+        .write_all(b"*2\r\n$3\r\nGET\r\n$5\r\nlarge\r\n")
+        .await
+        .unwrap();
+    time::sleep(Duration::from_secs(2)).await;
+
+    let mut buf = vec![];
+    let _ = time::timeout(Duration::from_secs(5), socket.read_to_end(&mut buf))
+        .await
+        .expect("the connection stayed open");
+    assert!(buf.len() < value.len());
+}
+
+#[tokio::test]
+async fn subscribers_past_the_pubsub_limit_are_disconnected() {
+    let server = common::start().await;
+    let mut publisher = Client::connect(&server).await;
+    let mut subscriber = Client::connect(&server).await;
+    config_set(
+        &mut publisher,
+        "client-output-buffer-limit",
+        "pubsub 1mb 0 0",
+    )
+    .await;
+    subscriber.query(&["SUBSCRIBE", "channel"]).await;
+
+    // The subscriber reads nothing while far more than the limit is sent.
+    let message = "x".repeat(1024 * 1024);
+    for _ in 0..64 {
+        publisher.query(&["PUBLISH", "channel", &message]).await;
+    }
+
+    let frames = assert_closed_within(&mut subscriber, 5).await;
+    assert!(frames.len() < 64, "{} messages delivered", frames.len());
+    assert_eq!(
+        publisher.query(&["PUBLISH", "channel", "late"]).await,
+        Frame::Integer(0)
+    );
+}
+
+#[tokio::test]
+async fn replica_limits_are_refused() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    let reply = client
+        .query(&[
+            "CONFIG",
+            "SET",
+            "client-output-buffer-limit",
+            "replica 256mb 64mb 60",
+        ])
+        .await;
+    assert!(is_error(&reply, "ERR Invalid argument"), "{:?}", reply);
+    assert_eq!(
+        client
+            .query(&["CONFIG", "GET", "client-output-buffer-limit"])
+            .await,
+        Frame::Array(vec![
+            bulk("client-output-buffer-limit"),
+            bulk("normal 0 0 0 pubsub 33554432 8388608 60"),
+        ])
+    );
+}
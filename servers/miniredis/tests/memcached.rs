@@ -0,0 +1,224 @@
+mod common;
+
+use common::Client;
+use miniredis::protocol::frame::Frame;
+use miniredis::server::{Server, ServerHandle};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+async fn start() -> ServerHandle {
+    common::start_with(Server::builder().memcached("127.0.0.1:0")).await
+}
+
+/// A memcached text protocol client, replies are read raw.
+struct Memcached {
+    socket: TcpStream,
+}
+
+impl Memcached {
+    async fn connect(server: &ServerHandle) -> Memcached {
+        let socket = TcpStream::connect(server.memcached_addr().unwrap())
+            .await
+            .unwrap();
+        Memcached { socket }
+    }
+
+    /// Sends `request` and reads until the reply ends with `end`.
+    async fn query(&mut self, request: &[u8], end: &[u8]) -> Vec<u8> {
+        self.socket.write_all(request).await.unwrap();
+
+        let mut reply = Vec::new();
+        while !reply.ends_with(end) {
+            let mut buf = [0; 4096];
+            let n = self.socket.read(&mut buf).await.unwrap();
+            assert!(n > 0, "connection closed after {:?}", reply);
+            reply.extend_from_slice(&buf[..n]);
+        }
+        reply
+    }
+
+    /// Whether the server closed the connection.
+    async fn is_closed(&mut self) -> bool {
+        let mut buf = [0; 64];
+        matches!(self.socket.read(&mut buf).await, Ok(0) | Err(_))
+    }
+}
+
+#[tokio::test]
+async fn set_then_get() {
+    let server = start().await;
+    let mut client = Memcached::connect(&server).await;
+
+    assert_eq!(
+        client
+            .query(b"set greeting 5 0 5\r\nhello\r\n", b"\r\n")
+            .await,
+        b"STORED\r\n"
+    );
+    assert_eq!(
+        client.query(b"get greeting missing\r\n", b"END\r\n").await,
+        b"VALUE greeting 5 5\r\nhello\r\nEND\r\n"
+    );
+}
+
+#[tokio::test]
+async fn keys_are_not_required_to_be_utf8() {
+    let server = start().await;
+    let mut client = Memcached::connect(&server).await;
+
+    assert_eq!(
+        client.query(b"set caf\xe9 0 0 2\r\nok\r\n", b"\r\n").await,
+        b"STORED\r\n"
+    );
+    assert_eq!(
+        client.query(b"get caf\xe9\r\n", b"END\r\n").await,
+        b"VALUE caf\xe9 0 2\r\nok\r\nEND\r\n"
+    );
+
+    // The same key, seen from the Redis side.
+    let mut redis = Client::connect(&server).await;
+    assert_eq!(
+        redis.query_bytes(&[b"GET", b"caf\xe9"]).await,
+        Frame::Bulk("ok".into())
+    );
+}
+
+#[tokio::test]
+async fn keys_with_control_characters_are_refused() {
+    let server = start().await;
+    let mut client = Memcached::connect(&server).await;
+
+    assert_eq!(
+        client.query(b"get a\x01b\r\n", b"\r\n").await,
+        b"CLIENT_ERROR bad command line format\r\n"
+    );
+    let long = format!("get {}\r\n", "k".repeat(251));
+    assert_eq!(
+        client.query(long.as_bytes(), b"\r\n").await,
+        b"CLIENT_ERROR bad command line format\r\n"
+    );
+}
+
+#[tokio::test]
+async fn too_large_items_are_swallowed() {
+    let server = start().await;
+    let mut client = Memcached::connect(&server).await;
+
+    let len = 1024 * 1024 + 1;
+    let mut request = format!("set big 0 0 {}\r\n", len).into_bytes();
+    request.extend(std::iter::repeat_n(b'x', len));
+    request.extend_from_slice(b"\r\n");
+    assert_eq!(
+        client.query(&request, b"\r\n").await,
+        b"SERVER_ERROR object too large for cache\r\n"
+    );
+
+    // The data block was skipped, the connection is still in sync.
+    assert_eq!(
+        client.query(b"version\r\n", b"\r\n").await,
+        b"VERSION 1.6.21\r\n"
+    );
+}
+
+#[tokio::test]
+async fn lengths_past_usize_close_the_connection() {
+    let server = start().await;
+    let mut client = Memcached::connect(&server).await;
+
+    let request = format!("set big 0 0 {}\r\n", usize::MAX);
+    assert_eq!(
+        client.query(request.as_bytes(), b"\r\n").await,
+        b"SERVER_ERROR object too large for cache\r\n"
+    );
+    assert!(client.is_closed().await);
+}
+
+#[tokio::test]
+async fn bad_data_chunks_are_refused() {
+    let server = start().await;
+    let mut client = Memcached::connect(&server).await;
+
+    // The rest of the block is then read as a command, as memcached does.
+    let reply = client
+        .query(b"set key 0 0 2\r\nabcd\r\n", b"ERROR\r\n")
+        .await;
+    assert!(reply.starts_with(b"CLIENT_ERROR bad data chunk\r\n"));
+}
+
+#[tokio::test]
+async fn incr_and_decr() {
+    let server = start().await;
+    let mut client = Memcached::connect(&server).await;
+
+    client.query(b"set counter 0 0 2\r\n10\r\n", b"\r\n").await;
+    assert_eq!(
+        client.query(b"incr counter 5\r\n", b"\r\n").await,
+        b"15\r\n"
+    );
+    // Decrementing stops at zero.
+    assert_eq!(
+        client.query(b"decr counter 100\r\n", b"\r\n").await,
+        b"0\r\n"
+    );
+    assert_eq!(
+        client.query(b"incr missing 1\r\n", b"\r\n").await,
+        b"NOT_FOUND\r\n"
+    );
+}
+
+#[tokio::test]
+async fn cas_fails_after_a_change() {
+    let server = start().await;
+    let mut client = Memcached::connect(&server).await;
+
+    client.query(b"set key 0 0 1\r\na\r\n", b"\r\n").await;
+    let reply = client.query(b"gets key\r\n", b"END\r\n").await;
+    let header = String::from_utf8(reply).unwrap();
+    let token = header.split_whitespace().nth(4).unwrap().to_string();
+
+    let request = format!("cas key 0 0 1 {}\r\nb\r\n", token);
+    assert_eq!(
+        client.query(request.as_bytes(), b"\r\n").await,
+        b"STORED\r\n"
+    );
+    // The token is stale now.
+    let request = format!("cas key 0 0 1 {}\r\nc\r\n", token);
+    assert_eq!(
+        client.query(request.as_bytes(), b"\r\n").await,
+        b"EXISTS\r\n"
+    );
+}
+
+/// The `cas` token of `key`, from `gets`.
+async fn cas_token(client: &mut Memcached, key: &str) -> String {
+    let request = format!("gets {}\r\n", key);
+    let reply = client.query(request.as_bytes(), b"END\r\n").await;
+    let header = String::from_utf8(reply).unwrap();
+    header.split_whitespace().nth(4).unwrap().to_string()
+}
+
+#[tokio::test]
+async fn failed_updates_leave_integers_alone() {
+    let server = start().await;
+    let mut client = Memcached::connect(&server).await;
+    let mut redis = Client::connect(&server).await;
+
+    redis.query(&["SET", "key", "-5"]).await;
+    let token = cas_token(&mut client, "key").await;
+
+    // Neither a negative number nor an integer is a counter or an HLL.
+    assert_eq!(
+        client.query(b"incr key 1\r\n", b"\r\n").await,
+        b"CLIENT_ERROR cannot increment or decrement non-numeric value\r\n"
+    );
+    assert!(matches!(
+        redis.query(&["PFCOUNT", "key"]).await,
+        Frame::Error(_)
+    ));
+
+    assert_eq!(cas_token(&mut client, "key").await, token);
+    assert_eq!(
+        redis.query(&["OBJECT", "ENCODING", "key"]).await,
+        Frame::Bulk("int".into())
+    );
+}
@@ -0,0 +1,184 @@
+mod common;
+
+use bytes::Bytes;
+use common::{bulk, int, is_error, ok, Client};
+use miniredis::module::{CommandFlags, Context, Module, Registry, ValueType};
+use miniredis::protocol::frame::Frame;
+use miniredis::server::Server;
+
+#[derive(Debug, Clone, Default)]
+struct Counter(i64);
+
+impl ValueType for Counter {
+    const NAME: &'static str = "counter";
+
+    fn memory_usage(&self) -> usize {
+        8
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.0.to_le_bytes().to_vec()
+    }
+
+    fn load(data: &[u8]) -> miniredis::Result<Counter> {
+        Ok(Counter(i64::from_le_bytes(data.try_into()?)))
+    }
+}
+
+struct Counters;
+
+impl Module for Counters {
+    fn name(&self) -> &str {
+        "counters"
+    }
+
+    fn register(&self, registry: &mut Registry) -> miniredis::Result<()> {
+        registry.value_type::<Counter>()?;
+        registry.command(
+            "counter.incr",
+            CommandFlags::WRITE,
+            |ctx: &mut Context, args: Vec<Bytes>| {
+                let key = ctx.key(args.first())?;
+                let value = ctx.update(&key, |slot: &mut Option<Counter>| {
+                    let counter = slot.get_or_insert_with(Counter::default);
+                    counter.0 += 1;
+                    counter.0
+                })?;
+                Ok(Frame::Integer(value))
+            },
+        )?;
+        registry.command(
+            "counter.get",
+            CommandFlags::READ_ONLY,
+            |ctx: &mut Context, args: Vec<Bytes>| {
+                let key = ctx.key(args.first())?;
+                let value = ctx.get::<Counter>(&key)?;
+                Ok(Frame::Integer(value.map_or(0, |counter| counter.0)))
+            },
+        )?;
+        // Copies a counter to a string through a built-in command.
+        registry.command(
+            "counter.save",
+            CommandFlags::WRITE,
+            |ctx: &mut Context, args: Vec<Bytes>| {
+                let key = ctx.key(args.first())?;
+                let target = ctx.key(args.get(1))?;
+                let value = ctx.get::<Counter>(&key)?.map_or(0, |counter| counter.0);
+                Ok(ctx.call(&[b"SET", &target, value.to_string().as_bytes()]))
+            },
+        )
+    }
+}
+
+/// Registers a command under the name of a built-in one.
+struct Clashing;
+
+impl Module for Clashing {
+    fn name(&self) -> &str {
+        "clashing"
+    }
+
+    fn register(&self, registry: &mut Registry) -> miniredis::Result<()> {
+        registry.command("get", CommandFlags::READ_ONLY, |_: &mut Context, _| {
+            Ok(Frame::Null)
+        })
+    }
+}
+
+async fn start() -> miniredis::server::ServerHandle {
+    common::start_with(Server::builder().module(Counters)).await
+}
+
+#[tokio::test]
+async fn module_commands_run_against_module_values() {
+    let server = start().await;
+    let mut client = Client::connect(&server).await;
+
+    assert_eq!(client.query(&["COUNTER.INCR", "hits"]).await, int(1));
+    assert_eq!(client.query(&["counter.incr", "hits"]).await, int(2));
+    assert_eq!(client.query(&["COUNTER.GET", "hits"]).await, int(2));
+    assert_eq!(
+        client.query(&["TYPE", "hits"]).await,
+        Frame::Simple("counter".into())
+    );
+
+    // Strings and counters do not mix.
+    let reply = client.query(&["GET", "hits"]).await;
+    assert!(is_error(&reply, "WRONGTYPE"), "{:?}", reply);
+    client.query(&["SET", "name", "value"]).await;
+    let reply = client.query(&["COUNTER.INCR", "name"]).await;
+    assert!(is_error(&reply, "WRONGTYPE"), "{:?}", reply);
+    assert_eq!(client.query(&["GET", "name"]).await, bulk("value"));
+
+    let reply = client.query(&["COUNTER.INCR"]).await;
+    assert!(
+        is_error(&reply, "ERR wrong number of arguments"),
+        "{:?}",
+        reply
+    );
+}
+
+#[tokio::test]
+async fn module_commands_call_built_in_ones() {
+    let server = start().await;
+    let mut client = Client::connect(&server).await;
+
+    client.query(&["COUNTER.INCR", "hits"]).await;
+    assert_eq!(client.query(&["COUNTER.SAVE", "hits", "copy"]).await, ok());
+    assert_eq!(client.query(&["GET", "copy"]).await, bulk("1"));
+}
+
+#[tokio::test]
+async fn module_values_survive_dump_and_restore() {
+    let server = start().await;
+    let mut client = Client::connect(&server).await;
+
+    for _ in 0..3 {
+        client.query(&["COUNTER.INCR", "hits"]).await;
+    }
+    let payload = match client.query(&["DUMP", "hits"]).await {
+        Frame::Bulk(payload) => payload,
+        reply => panic!("unexpected reply {:?}", reply),
+    };
+    assert_eq!(
+        client
+            .query_bytes(&[b"RESTORE", b"copy", b"0", &payload])
+            .await,
+        ok()
+    );
+    assert_eq!(client.query(&["COUNTER.GET", "copy"]).await, int(3));
+    assert_eq!(
+        client.query(&["TYPE", "copy"]).await,
+        Frame::Simple("counter".into())
+    );
+}
+
+#[tokio::test]
+async fn modules_are_listed() {
+    let server = start().await;
+    let mut client = Client::connect(&server).await;
+
+    assert_eq!(
+        client.query(&["MODULE", "LIST"]).await,
+        Frame::Array(vec![Frame::Array(vec![
+            bulk("name"),
+            bulk("counters"),
+            bulk("ver"),
+            int(1)
+        ])])
+    );
+}
+
+#[tokio::test]
+async fn clashing_commands_stop_the_server_from_starting() {
+    let result = Server::builder()
+        .bind("127.0.0.1:0")
+        .module(Clashing)
+        .start()
+        .await;
+    let err = match result {
+        Ok(_) => panic!("the server started"),
+        Err(err) => err,
+    };
+    assert!(err.to_string().contains("already exists"), "{}", err);
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::{ok, Client};
+use miniredis::protocol::frame::Frame;
+use std::time::Duration;
+
+async fn next_line(monitor: &mut Client) -> String {
+    match tokio::time::timeout(Duration::from_secs(1), monitor.read())
+        .await
+        .expect("no command was fed to the monitor")
+    {
+        Frame::Simple(line) => line,
+        frame => panic!("unexpected frame {:?}", frame),
+    }
+}
+
+#[tokio::test]
+async fn monitors_see_every_command() {
+    let server = common::start().await;
+    let mut monitor = Client::connect(&server).await;
+    let mut client = Client::connect(&server).await;
+
+    assert_eq!(monitor.query(&["MONITOR"]).await, ok());
+    client.query(&["SET", "key", "a \"quoted\"\nvalue"]).await;
+    client.query(&["SELECT", "3"]).await;
+    client.query(&["GET", "key"]).await;
+
+    let line = next_line(&mut monitor).await;
+    let (timestamp, rest) = line.split_once(' ').unwrap();
+    assert!(timestamp.parse::<f64>().is_ok(), "{}", line);
+    assert!(rest.starts_with("[0 127.0.0.1:"), "{}", line);
+    assert!(
+        rest.ends_with(r#"] "SET" "key" "a \"quoted\"\nvalue""#),
+        "{}",
+        line
+    );
+
+    assert!(next_line(&mut monitor).await.ends_with(r#"] "SELECT" "3""#));
+    let line = next_line(&mut monitor).await;
+    assert!(line.contains("[3 127.0.0.1:"), "{}", line);
+    assert!(line.ends_with(r#"] "GET" "key""#), "{}", line);
+}
+
+#[tokio::test]
+async fn admin_commands_are_not_fed() {
+    let server = common::start().await;
+    let mut monitor = Client::connect(&server).await;
+    let mut client = Client::connect(&server).await;
+
+    monitor.query(&["MONITOR"]).await;
+    client.query(&["CONFIG", "GET", "maxclients"]).await;
+    client.query(&["SLOWLOG", "LEN"]).await;
+    client.query(&["PING"]).await;
+
+    assert!(next_line(&mut monitor).await.ends_with(r#"] "PING""#));
+}
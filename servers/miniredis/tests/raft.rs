@@ -0,0 +1,112 @@
+mod common;
+
+use common::{bulk, int, is_error, ok, Client};
+use miniredis::protocol::frame::Frame;
+use miniredis::raft::{Member, RaftConfig};
+use miniredis::server::{Server, ServerHandle};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// An empty directory for the state of a node, unique to the test.
+fn node_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("miniredis-raft-{}-{}", test, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Starts a group of one node keeping its state in `dir`, and waits until
+/// it leads.
+async fn start(dir: &Path) -> (ServerHandle, Client) {
+    let config = RaftConfig {
+        id: 1,
+        dir: dir.to_path_buf(),
+        peers: vec![Member {
+            id: 1,
+            addr: "127.0.0.1:0".to_string(),
+        }],
+    };
+    let server = common::start_with(Server::builder().raft(config)).await;
+    let mut client = Client::connect(&server).await;
+
+    for _ in 0..100 {
+        let reply = client.query(&["GET", "probe"]).await;
+        if !is_error(&reply, "TRYAGAIN") {
+            return (server, client);
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("no leader elected");
+}
+
+#[tokio::test]
+async fn writes_are_replayed_after_a_restart() {
+    let dir = node_dir("replay");
+
+    let (server, mut client) = start(&dir).await;
+    assert_eq!(client.query(&["SET", "key", "value"]).await, ok());
+    assert_eq!(client.query(&["SET", "other", "value"]).await, ok());
+    assert_eq!(client.query(&["DEL", "other"]).await, int(1));
+    server.shutdown().await;
+
+    let (server, mut client) = start(&dir).await;
+    assert_eq!(client.query(&["GET", "key"]).await, bulk("value"));
+    assert_eq!(client.query(&["GET", "other"]).await, Frame::Null);
+    server.shutdown().await;
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn relative_ttls_do_not_restart_on_replay() {
+    let dir = node_dir("ttl");
+
+    let (server, mut client) = start(&dir).await;
+    assert_eq!(
+        client.query(&["SET", "set", "value", "PX", "300"]).await,
+        ok()
+    );
+    assert_eq!(client.query(&["SET", "expire", "value"]).await, ok());
+    assert_eq!(client.query(&["PEXPIRE", "expire", "300"]).await, int(1));
+    assert_eq!(
+        client.query(&["SET", "long", "value", "EX", "100"]).await,
+        ok()
+    );
+    server.shutdown().await;
+
+    // Replaying the commands as sent would give the keys another 300ms.
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    let (server, mut client) = start(&dir).await;
+    assert_eq!(client.query(&["GET", "set"]).await, Frame::Null);
+    assert_eq!(client.query(&["GET", "expire"]).await, Frame::Null);
+    match client.query(&["TTL", "long"]).await {
+        Frame::Integer(ttl) => assert!((90..=100).contains(&ttl), "{}", ttl),
+        reply => panic!("unexpected reply {:?}", reply),
+    }
+    server.shutdown().await;
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn snapshots_survive_a_restart() {
+    let dir = node_dir("snapshot");
+
+    // Enough writes for a snapshot to be taken and the log compacted.
+    let (server, mut client) = start(&dir).await;
+    let writes = 10_500;
+    for i in 0..writes {
+        client.send(&["SET", &format!("key:{}", i), "value"]).await;
+    }
+    for _ in 0..writes {
+        assert_eq!(client.read().await, ok());
+    }
+    server.shutdown().await;
+    assert!(dir.join("snapshot").exists());
+
+    let (server, mut client) = start(&dir).await;
+    assert_eq!(client.query(&["DBSIZE"]).await, int(writes));
+    assert_eq!(client.query(&["GET", "key:0"]).await, bulk("value"));
+    server.shutdown().await;
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
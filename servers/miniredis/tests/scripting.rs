@@ -0,0 +1,183 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{array, bulk, int, is_error, ok, Client};
+use miniredis::protocol::frame::Frame;
+
+const LIBRARY: &str = "#!lua name=counters
+local calls = 0
+redis.register_function('calls', function(keys, args)
+    calls = calls + 1
+    return calls
+end)
+redis.register_function('set', function(keys, args)
+    return redis.call('SET', keys[1], args[1])
+end)";
+
+/// Starts `args` on a connection of its own, returning its reply once done.
+fn spawn_query(
+    mut client: Client,
+    args: &'static [&'static str],
+) -> tokio::task::JoinHandle<Frame> {
+    tokio::spawn(async move { client.query(args).await })
+}
+
+/// Waits for a script started by another client to turn the server busy.
+async fn wait_busy(client: &mut Client) {
+    for _ in 0..100 {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        if is_error(&client.query(&["PING"]).await, "BUSY") {
+            return;
+        }
+    }
+    panic!("the server never got busy");
+}
+
+#[tokio::test]
+async fn eval_sees_keys_and_args_and_calls_commands() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    let script = "redis.call('SET', KEYS[1], ARGV[1]) return {redis.call('GET', KEYS[1]), 7}";
+    assert_eq!(
+        client.query(&["EVAL", script, "1", "key", "value"]).await,
+        array(vec![bulk("value"), int(7)])
+    );
+
+    let failing = "return redis.call('GET')";
+    assert!(is_error(
+        &client.query(&["EVAL", failing, "0"]).await,
+        "ERR"
+    ));
+    let caught = "return redis.pcall('NOSUCHCOMMAND')";
+    assert!(is_error(
+        &client.query(&["EVAL", caught, "0"]).await,
+        "ERR unknown command"
+    ));
+}
+
+#[tokio::test]
+async fn redis_log_takes_known_levels_only() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    let log = "redis.log(redis.LOG_DEBUG, 'hidden at the default loglevel') return 1";
+    assert_eq!(client.query(&["EVAL", log, "0"]).await, int(1));
+    let invalid = "redis.log(42, 'nope')";
+    assert!(is_error(
+        &client.query(&["EVAL", invalid, "0"]).await,
+        "ERR Invalid debug level."
+    ));
+}
+
+#[tokio::test]
+async fn libraries_are_compiled_once_until_replaced() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    assert_eq!(
+        client.query(&["FUNCTION", "LOAD", LIBRARY]).await,
+        bulk("counters")
+    );
+    assert_eq!(client.query(&["FCALL", "calls", "0"]).await, int(1));
+    assert_eq!(client.query(&["FCALL", "calls", "0"]).await, int(2));
+    assert_eq!(client.query(&["FCALL", "set", "1", "key", "v"]).await, ok());
+    assert_eq!(client.query(&["GET", "key"]).await, bulk("v"));
+
+    client
+        .query(&["FUNCTION", "LOAD", "REPLACE", LIBRARY])
+        .await;
+    assert_eq!(client.query(&["FCALL", "calls", "0"]).await, int(1));
+}
+
+#[tokio::test]
+async fn kill_without_a_script_running() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    assert!(is_error(
+        &client.query(&["SCRIPT", "KILL"]).await,
+        "NOTBUSY"
+    ));
+    assert!(is_error(
+        &client.query(&["FUNCTION", "KILL"]).await,
+        "NOTBUSY"
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn script_kill_stops_a_busy_script() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+    client
+        .query(&["CONFIG", "SET", "busy-reply-threshold", "50"])
+        .await;
+
+    let script = spawn_query(
+        Client::connect(&server).await,
+        &["EVAL", "while true do end", "0"],
+    );
+    wait_busy(&mut client).await;
+
+    assert!(is_error(&client.query(&["GET", "key"]).await, "BUSY"));
+    assert!(is_error(
+        &client.query(&["FUNCTION", "KILL"]).await,
+        "NOTBUSY"
+    ));
+    assert_eq!(client.query(&["SCRIPT", "KILL"]).await, ok());
+
+    let reply = script.await.unwrap();
+    assert!(
+        matches!(&reply, Frame::Error(msg) if msg.contains("Script killed by user with SCRIPT KILL"))
+    );
+    assert_eq!(client.query(&["PING"]).await, Frame::Simple("PONG".into()));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn function_kill_stops_a_busy_function() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+    client
+        .query(&["CONFIG", "SET", "lua-time-limit", "50"])
+        .await;
+    let library = "#!lua name=spin
+redis.register_function('spin', function() while true do end end)";
+    client.query(&["FUNCTION", "LOAD", library]).await;
+
+    let function = spawn_query(Client::connect(&server).await, &["FCALL", "spin", "0"]);
+    wait_busy(&mut client).await;
+
+    assert_eq!(client.query(&["FUNCTION", "KILL"]).await, ok());
+    assert!(is_error(
+        &function.await.unwrap(),
+        "ERR Script killed by user with FUNCTION KILL"
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn scripts_that_wrote_cannot_be_killed() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+    client
+        .query(&["CONFIG", "SET", "busy-reply-threshold", "50"])
+        .await;
+
+    let script = spawn_query(
+        Client::connect(&server).await,
+        &[
+            "EVAL",
+            "redis.call('SET', KEYS[1], 'x') local i = 0 while i < 100000000 do i = i + 1 end return 1",
+            "1",
+            "key",
+        ],
+    );
+    wait_busy(&mut client).await;
+
+    assert!(is_error(
+        &client.query(&["SCRIPT", "KILL"]).await,
+        "UNKILLABLE"
+    ));
+    assert_eq!(script.await.unwrap(), int(1));
+    assert_eq!(client.query(&["GET", "key"]).await, bulk("x"));
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use common::Client;
+use miniredis::protocol::frame::Frame;
+use miniredis::server::Server;
+use shared_lib::sharded_db::ShardModel;
+
+#[tokio::test]
+async fn dropping_the_handle_leaves_the_server_running() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+    let addr = server.local_addr();
+    drop(server);
+
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert_eq!(client.query(&["PING"]).await, Frame::Simple("PONG".into()));
+    assert!(tokio::net::TcpStream::connect(addr).await.is_ok());
+}
+
+#[tokio::test]
+async fn shutdown_stops_the_server() {
+    let server = common::start().await;
+    let addr = server.local_addr();
+    server.shutdown().await;
+
+    assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn zero_shards_are_refused() {
+    let builder = Server::builder().bind("127.0.0.1:0").shards(0);
+    assert!(builder.start().await.is_err());
+}
+
+#[tokio::test]
+async fn owner_shards_are_only_for_benchmarks() {
+    let builder = Server::builder()
+        .bind("127.0.0.1:0")
+        .shard_model(ShardModel::Owner);
+    assert_eq!(builder.start().await.is_ok(), cfg!(feature = "bench"));
+}
+
+#[tokio::test]
+async fn config_reports_the_number_of_databases() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    assert_eq!(
+        client.query(&["CONFIG", "GET", "databases"]).await,
+        Frame::Array(vec![
+            Frame::Bulk("databases".into()),
+            Frame::Bulk("16".into()),
+        ])
+    );
+    assert!(matches!(
+        client.query(&["CONFIG", "SET", "databases", "4"]).await,
+        Frame::Error(_)
+    ));
+    assert_eq!(client.query(&["SELECT", "15"]).await, Frame::ok());
+    assert!(matches!(client.query(&["SELECT", "16"]).await, Frame::Error(_)));
+}
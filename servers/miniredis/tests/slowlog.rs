@@ -0,0 +1,121 @@
+mod common;
+
+use common::{bulk, int, ok, Client};
+use miniredis::protocol::frame::Frame;
+
+/// A script keeping the server busy for a few milliseconds.
+const BUSY_SCRIPT: &str = "local i = 0 while i < 5000000 do i = i + 1 end return i";
+
+#[tokio::test]
+async fn slow_commands_are_logged() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    assert_eq!(
+        client
+            .query(&["CONFIG", "SET", "slowlog-log-slower-than", "0"])
+            .await,
+        ok()
+    );
+    client.query(&["SET", "key", "value"]).await;
+
+    let entries = match client.query(&["SLOWLOG", "GET", "1"]).await {
+        Frame::Array(entries) => entries,
+        reply => panic!("unexpected reply {:?}", reply),
+    };
+    assert_eq!(entries.len(), 1);
+    match &entries[0] {
+        Frame::Array(fields) => {
+            assert_eq!(
+                fields[3],
+                Frame::Array(vec![bulk("SET"), bulk("key"), bulk("value")])
+            );
+            assert!(
+                matches!(&fields[4], Frame::Bulk(addr) if addr.starts_with(b"127.0.0.1:")),
+                "{:?}",
+                fields[4]
+            );
+        }
+        entry => panic!("unexpected entry {:?}", entry),
+    }
+
+    // Nothing is logged once disabled again.
+    client
+        .query(&["CONFIG", "SET", "slowlog-log-slower-than", "-1"])
+        .await;
+    assert_eq!(client.query(&["SLOWLOG", "RESET"]).await, ok());
+    client.query(&["GET", "key"]).await;
+    assert_eq!(client.query(&["SLOWLOG", "LEN"]).await, int(0));
+}
+
+#[tokio::test]
+async fn slowlog_keeps_the_newest_entries() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    client
+        .query(&["CONFIG", "SET", "slowlog-max-len", "2"])
+        .await;
+    client
+        .query(&["CONFIG", "SET", "slowlog-log-slower-than", "0"])
+        .await;
+    for key in ["a", "b", "c"] {
+        client.query(&["GET", key]).await;
+    }
+
+    assert_eq!(client.query(&["SLOWLOG", "LEN"]).await, int(2));
+    let entries = match client.query(&["SLOWLOG", "GET"]).await {
+        Frame::Array(entries) => entries,
+        reply => panic!("unexpected reply {:?}", reply),
+    };
+    let args: Vec<Frame> = entries
+        .into_iter()
+        .map(|entry| match entry {
+            Frame::Array(mut fields) => fields.remove(3),
+            entry => panic!("unexpected entry {:?}", entry),
+        })
+        .collect();
+    assert_eq!(
+        args,
+        vec![
+            Frame::Array(vec![bulk("SLOWLOG"), bulk("LEN")]),
+            Frame::Array(vec![bulk("GET"), bulk("c")]),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn latency_spikes_are_sampled() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    assert_eq!(
+        client.query(&["LATENCY", "LATEST"]).await,
+        Frame::Array(vec![])
+    );
+    client
+        .query(&["CONFIG", "SET", "latency-monitor-threshold", "1"])
+        .await;
+    client.query(&["EVAL", BUSY_SCRIPT, "0"]).await;
+
+    match client.query(&["LATENCY", "LATEST"]).await {
+        Frame::Array(events) => {
+            assert_eq!(events.len(), 1);
+            match &events[0] {
+                Frame::Array(fields) => assert_eq!(fields[0], bulk("command")),
+                event => panic!("unexpected event {:?}", event),
+            }
+        }
+        reply => panic!("unexpected reply {:?}", reply),
+    }
+    match client.query(&["LATENCY", "HISTORY", "command"]).await {
+        Frame::Array(samples) => assert_eq!(samples.len(), 1),
+        reply => panic!("unexpected reply {:?}", reply),
+    }
+
+    assert_eq!(client.query(&["LATENCY", "RESET"]).await, int(1));
+    assert_eq!(
+        client.query(&["LATENCY", "HISTORY", "command"]).await,
+        Frame::Array(vec![])
+    );
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{array, bulk, ok, Client};
+use miniredis::protocol::frame::Frame;
+
+fn invalidate(key: &str) -> Frame {
+    Frame::Push(vec![bulk("invalidate"), array(vec![bulk(key)])])
+}
+
+/// Asserts no push arrives within a short wait.
+async fn assert_quiet(client: &mut Client) {
+    let next = tokio::time::timeout(Duration::from_millis(100), client.read()).await;
+    assert!(next.is_err(), "unexpected {:?}", next);
+}
+
+#[tokio::test]
+async fn keys_read_are_invalidated_once_when_changed() {
+    let server = common::start().await;
+    let mut tracker = Client::connect(&server).await;
+    let mut writer = Client::connect(&server).await;
+    tracker.resp3().await;
+
+    assert_eq!(tracker.query(&["CLIENT", "TRACKING", "on"]).await, ok());
+    tracker.query(&["GET", "key"]).await;
+    writer.query(&["SET", "key", "1"]).await;
+    assert_eq!(tracker.read().await, invalidate("key"));
+
+    // The key is only tracked again once read again.
+    writer.query(&["SET", "key", "2"]).await;
+    assert_quiet(&mut tracker).await;
+}
+
+#[tokio::test]
+async fn rewriting_the_same_value_invalidates_the_key() {
+    let server = common::start().await;
+    let mut tracker = Client::connect(&server).await;
+    let mut writer = Client::connect(&server).await;
+    tracker.resp3().await;
+
+    writer.query(&["SET", "key", ""]).await;
+    tracker.query(&["CLIENT", "TRACKING", "on"]).await;
+    tracker.query(&["GET", "key"]).await;
+    writer.query(&["SET", "key", ""]).await;
+    assert_eq!(tracker.read().await, invalidate("key"));
+}
+
+#[tokio::test]
+async fn turning_tracking_off_forgets_the_keys_read() {
+    let server = common::start().await;
+    let mut tracker = Client::connect(&server).await;
+    let mut writer = Client::connect(&server).await;
+    tracker.resp3().await;
+
+    tracker.query(&["CLIENT", "TRACKING", "on"]).await;
+    tracker.query(&["GET", "key"]).await;
+    assert_eq!(tracker.query(&["CLIENT", "TRACKING", "off"]).await, ok());
+    tracker.query(&["CLIENT", "TRACKING", "on"]).await;
+
+    writer.query(&["SET", "key", "1"]).await;
+    assert_quiet(&mut tracker).await;
+}
+
+#[tokio::test]
+async fn noloop_skips_the_clients_own_writes() {
+    let server = common::start().await;
+    let mut tracker = Client::connect(&server).await;
+    let mut writer = Client::connect(&server).await;
+    tracker.resp3().await;
+
+    tracker.query(&["CLIENT", "TRACKING", "on", "NOLOOP"]).await;
+    tracker.query(&["GET", "a"]).await;
+    tracker.query(&["GET", "b"]).await;
+    tracker.query(&["SET", "a", "1"]).await;
+    assert_quiet(&mut tracker).await;
+
+    writer.query(&["SET", "b", "1"]).await;
+    assert_eq!(tracker.read().await, invalidate("b"));
+}
+
+#[tokio::test]
+async fn bcast_tracks_keys_by_prefix_without_reads() {
+    let server = common::start().await;
+    let mut tracker = Client::connect(&server).await;
+    let mut writer = Client::connect(&server).await;
+    tracker.resp3().await;
+
+    tracker
+        .query(&["CLIENT", "TRACKING", "on", "BCAST", "PREFIX", "user:"])
+        .await;
+    writer.query(&["SET", "post:1", "x"]).await;
+    writer.query(&["SET", "user:1", "x"]).await;
+    assert_eq!(tracker.read().await, invalidate("user:1"));
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use common::{array, bulk, ok, Client};
+
+/// Keeps a worker busy long enough for other clients to get a word in.
+const SLOW_SCRIPT: &str =
+    "local i = 0 while i < 50000000 do i = i + 1 end redis.call('SET', KEYS[1], 'done')";
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn scripts_only_hold_up_commands_on_their_keys() {
+    let server = common::start().await;
+    let mut other = Client::connect(&server).await;
+    assert_eq!(other.query(&["SET", "b", "1"]).await, ok());
+
+    let mut scripted = Client::connect(&server).await;
+    let script =
+        tokio::spawn(async move { scripted.query(&["EVAL", SLOW_SCRIPT, "1", "a"]).await });
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+    assert_eq!(other.query(&["GET", "b"]).await, bulk("1"));
+    assert!(!script.is_finished());
+    assert_eq!(other.query(&["GET", "a"]).await, bulk("done"));
+    script.await.unwrap();
+}
+
+#[tokio::test]
+async fn exec_runs_the_queued_commands() {
+    let server = common::start().await;
+    let mut client = Client::connect(&server).await;
+
+    assert_eq!(client.query(&["MULTI"]).await, ok());
+    client.query(&["SET", "a", "1"]).await;
+    client.query(&["GET", "a"]).await;
+    client.query(&["FLUSHDB"]).await;
+    client.query(&["SET", "b", "2"]).await;
+    assert_eq!(
+        client.query(&["EXEC"]).await,
+        array(vec![ok(), bulk("1"), ok(), ok()])
+    );
+
+    assert_eq!(client.query(&["GET", "b"]).await, bulk("2"));
+}
@@ -0,0 +1,135 @@
+//! Failover supervisor for a group of miniredis nodes replicating with Raft,
+//! modelled on Redis Sentinel.
+//!
+//! ```text
+//! minisentinel --nodes 127.0.0.1:7001,127.0.0.1:7002,127.0.0.1:7003 \
+//!     --sentinels 127.0.0.1:26380,127.0.0.1:26381 --quorum 2
+//! ```
+//!
+//! Each supervisor polls the nodes with `RAFT INFO`, learning of members
+//! added later. A node that has not answered for `--down-after` is down as
+//! far as this supervisor knows. Once `--quorum` supervisors agree a member
+//! is down, one of them, elected by the others for the attempt, fails it
+//! over: if it was the leader and the group did not elect another one, the
+//! most up to date member is promoted with `RAFT PROMOTE`. With
+//! `--remove-after`, a node silent for that long is then removed from the
+//! group so the majority is counted without it, and added back once it
+//! answers again.
+//!
+//! Clients ask any supervisor for the leader with
+//! `SENTINEL GET-MASTER-ADDR-BY-NAME <name>`, as they would Redis Sentinel.
+
+mod sentinel;
+mod server;
+
+use std::process;
+use std::time::Duration;
+
+use sentinel::{Options, Sentinel};
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+const USAGE: &str = "\
+Usage: minisentinel --nodes <addr,...> [OPTIONS]
+
+Options:
+  --bind <host:port>            Address to answer queries on (default: 127.0.0.1:26379).
+  --name <name>                 Name clients know the group by (default: mygroup).
+  --nodes <addr,...>            Members of the group, more are found from them.
+  --sentinels <addr,...>        The other supervisors of the group.
+  --quorum <n>                  Supervisors that must agree a node is down
+                                (default: a majority of them).
+  --down-after <ms>             Silence after which a node is down (default: 5000).
+  --failover-timeout <ms>       Time given to a failover before another is
+                                attempted (default: 30000).
+  --remove-after <ms>           Silence after which a failed over node is
+                                removed from the group (default: never).";
+
+#[tokio::main]
+async fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            process::exit(1);
+        }
+    };
+
+    let bind = options.bind.clone();
+    let sentinel = Sentinel::new(options);
+    sentinel.start();
+
+    if let Err(err) = server::run(&bind, sentinel).await {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> std::result::Result<Options, String> {
+    let mut options = Options {
+        bind: "127.0.0.1:26379".to_string(),
+        name: "mygroup".to_string(),
+        nodes: vec![],
+        sentinels: vec![],
+        quorum: 0,
+        down_after: Duration::from_millis(5000),
+        failover_timeout: Duration::from_millis(30000),
+        remove_after: None,
+    };
+
+    while let Some(name) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for '{}'", name))?;
+
+        match &name[..] {
+            "--bind" => options.bind = value,
+            "--name" => options.name = value,
+            "--nodes" => options.nodes = parse_list(&value),
+            "--sentinels" => options.sentinels = parse_list(&value),
+            "--quorum" => options.quorum = parse_number(&name, &value)?,
+            "--down-after" => {
+                options.down_after = Duration::from_millis(parse_number(&name, &value)?)
+            }
+            "--failover-timeout" => {
+                options.failover_timeout = Duration::from_millis(parse_number(&name, &value)?)
+            }
+            "--remove-after" => {
+                options.remove_after = Some(Duration::from_millis(parse_number(&name, &value)?))
+            }
+            _ => return Err(format!("unknown option '{}'", name)),
+        }
+    }
+
+    if options.nodes.is_empty() {
+        return Err("no nodes to monitor, see --nodes".to_string());
+    }
+    let supervisors = options.sentinels.len() + 1;
+    if options.quorum == 0 {
+        options.quorum = supervisors as u64 / 2 + 1;
+    }
+    if options.quorum as usize > supervisors {
+        return Err(format!(
+            "a quorum of {} cannot be reached by {} supervisors",
+            options.quorum, supervisors
+        ));
+    }
+
+    Ok(options)
+}
+
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .filter(|addr| !addr.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> std::result::Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for '{}'", value, name))
+}
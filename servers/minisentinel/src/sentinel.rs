@@ -0,0 +1,766 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+use tokio::time;
+
+use miniredis_client::{Client, Cmd, Frame};
+
+/// How often nodes are polled and failures looked for.
+const POLL_INTERVAL: Duration = Duration::from_millis(1000);
+
+/// How long a node or another supervisor gets to answer.
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Upper bound of the random delay before asking for votes, so supervisors
+/// that noticed a failure together rarely split the votes.
+const MAX_ELECTION_DELAY: Duration = Duration::from_millis(1000);
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub bind: String,
+    /// Name clients know the group by.
+    pub name: String,
+    /// Members to start from.
+    pub nodes: Vec<String>,
+    /// Addresses of the other supervisors.
+    pub sentinels: Vec<String>,
+    /// Supervisors, this one included, that must agree a node is down.
+    pub quorum: u64,
+    pub down_after: Duration,
+    pub failover_timeout: Duration,
+    /// Silence after which a failed over node is removed from the group,
+    /// never when `None`.
+    pub remove_after: Option<Duration>,
+}
+
+/// A supervisor: what it knows of the nodes, and of the elections held
+/// among supervisors to pick the one running a failover.
+#[derive(Debug)]
+pub struct Sentinel {
+    pub options: Options,
+    /// Identifies this supervisor in failover elections.
+    pub run_id: String,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Every node heard of, by address.
+    nodes: HashMap<String, Node>,
+    /// Address of the latest leader seen, and its term.
+    leader: Option<(String, u64)>,
+    /// Latest failover election heard of.
+    current_epoch: u64,
+    /// Supervisor voted for in `current_epoch`.
+    voted_for: Option<String>,
+    /// Nodes this supervisor removed from the group, by address, to add
+    /// back once they answer again.
+    removed: HashMap<String, u64>,
+}
+
+/// What a node last reported of itself with `RAFT INFO`.
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub id: Option<u64>,
+    pub role: String,
+    pub term: u64,
+    pub last_log_index: u64,
+    /// Members as the node knows them, `(id, addr)`.
+    pub members: Vec<(u64, String)>,
+    /// When the node last answered, if ever.
+    last_ok: Option<Instant>,
+    /// When the node was first heard of.
+    added: Instant,
+    /// Set while enough supervisors agree the node is down.
+    pub odown: bool,
+    /// When a failover of the node was last attempted.
+    failover_started: Option<Instant>,
+}
+
+impl Sentinel {
+    pub fn new(options: Options) -> Arc<Sentinel> {
+        let mut state = State::default();
+        for addr in &options.nodes {
+            state.nodes.insert(addr.clone(), Node::new());
+        }
+
+        Arc::new(Sentinel {
+            options,
+            run_id: run_id(),
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Starts polling the nodes given on the command line, and looking for
+    /// failed ones.
+    pub fn start(self: &Arc<Self>) {
+        for addr in &self.options.nodes {
+            tokio::spawn(Arc::clone(self).monitor(addr.clone()));
+        }
+        tokio::spawn(Arc::clone(self).supervise());
+    }
+
+    /// Address of the leader: the one reachable with the latest term, or
+    /// the last one seen when none is.
+    pub fn leader(&self) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        state.leader.as_ref().map(|(addr, _)| addr.clone())
+    }
+
+    /// Every node heard of, with whether this supervisor considers it down.
+    pub fn nodes(&self) -> Vec<(String, Node, bool)> {
+        let state = self.state.lock().unwrap();
+        let mut nodes: Vec<_> = state
+            .nodes
+            .iter()
+            .map(|(addr, node)| (addr.clone(), node.clone(), self.is_down(node)))
+            .collect();
+        nodes.sort_by(|a, b| a.0.cmp(&b.0));
+        nodes
+    }
+
+    /// Answers `SENTINEL IS-MASTER-DOWN-BY-ADDR`: whether the node at `addr`
+    /// is down as far as this supervisor knows, and the supervisor it votes
+    /// for in failover election `epoch`, asked for by `run_id` unless `*`.
+    pub fn is_down_by_addr(&self, addr: &str, epoch: u64, run_id: &str) -> (bool, String, u64) {
+        let mut state = self.state.lock().unwrap();
+        let down = state.nodes.get(addr).is_some_and(|node| self.is_down(node));
+
+        if run_id != "*" && epoch > state.current_epoch {
+            state.current_epoch = epoch;
+            state.voted_for = Some(run_id.to_string());
+            println!("+vote-for-leader {} {}", run_id, epoch);
+        }
+
+        let vote = match &state.voted_for {
+            Some(vote) if run_id != "*" && epoch == state.current_epoch => vote.clone(),
+            _ => "*".to_string(),
+        };
+        (down, vote, state.current_epoch)
+    }
+
+    pub fn current_epoch(&self) -> u64 {
+        self.state.lock().unwrap().current_epoch
+    }
+
+    /// Whether the node has not answered for `down_after`.
+    fn is_down(&self, node: &Node) -> bool {
+        node.last_ok.unwrap_or(node.added).elapsed() > self.options.down_after
+    }
+
+    /// Polls a node until the process ends.
+    async fn monitor(self: Arc<Self>, addr: String) {
+        let mut client = None;
+        let mut interval = time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+            let info = time::timeout(REQUEST_TIMEOUT, raft_info(&mut client, &addr)).await;
+            match info {
+                Ok(Ok(info)) => self.update(&addr, &info),
+                _ => client = None,
+            }
+        }
+    }
+
+    /// Records the `RAFT INFO` of the node at `addr`, and starts polling
+    /// members it knows of that are new to this supervisor.
+    fn update(self: &Arc<Self>, addr: &str, info: &str) {
+        let fields: HashMap<&str, &str> = info
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .collect();
+        let number = |name| {
+            fields
+                .get(name)
+                .and_then(|value: &&str| value.parse::<u64>().ok())
+                .unwrap_or(0)
+        };
+
+        let members: Vec<(u64, String)> = fields
+            .get("members")
+            .unwrap_or(&"")
+            .split(',')
+            .filter_map(|member| member.split_once('='))
+            .filter_map(|(id, addr)| Some((id.parse().ok()?, addr.to_string())))
+            .collect();
+
+        let mut state = self.state.lock().unwrap();
+        for (_, member) in &members {
+            if !state.nodes.contains_key(member) {
+                println!("+node {}", member);
+                state.nodes.insert(member.clone(), Node::new());
+                tokio::spawn(Arc::clone(self).monitor(member.clone()));
+            }
+        }
+
+        let node = state.nodes.get_mut(addr).unwrap();
+        let was_down = self.is_down(node);
+        node.id = Some(number("node_id"));
+        node.role = fields.get("role").unwrap_or(&"").to_string();
+        node.term = number("term");
+        node.last_log_index = number("last_log_index");
+        node.members = members;
+        node.last_ok = Some(Instant::now());
+        if was_down {
+            node.odown = false;
+            println!("-sdown {}", addr);
+        }
+
+        if node.role == "leader" {
+            let term = node.term;
+            if state
+                .leader
+                .as_ref()
+                .is_none_or(|(_, latest)| term >= *latest)
+            {
+                if state
+                    .leader
+                    .as_ref()
+                    .is_none_or(|(leader, _)| leader != addr)
+                {
+                    println!(
+                        "+switch-master {} {} term {}",
+                        self.options.name, addr, term
+                    );
+                }
+                state.leader = Some((addr.to_string(), term));
+            }
+        }
+    }
+
+    /// Looks for failed members, and for removed ones that came back.
+    async fn supervise(self: Arc<Self>) {
+        let mut interval = time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.readd_recovered().await;
+
+            for addr in self.failover_candidates() {
+                if self.agree_down(&addr).await && self.win_election(&addr).await {
+                    self.failover(&addr).await;
+                }
+            }
+        }
+    }
+
+    /// Members down as far as this supervisor knows, not being failed over
+    /// already. Members are those the leader lists.
+    fn failover_candidates(&self) -> Vec<String> {
+        let mut state = self.state.lock().unwrap();
+        let members = state
+            .leader
+            .as_ref()
+            .and_then(|(leader, _)| state.nodes.get(leader))
+            .map(|leader| leader.members.clone())
+            .unwrap_or_default();
+
+        let mut candidates = vec![];
+        for (_, addr) in members {
+            let Some(node) = state.nodes.get_mut(&addr) else {
+                continue;
+            };
+            let down = node.last_ok.unwrap_or(node.added).elapsed() > self.options.down_after;
+            let attempted = node
+                .failover_started
+                .is_some_and(|at| at.elapsed() < self.options.failover_timeout);
+            if !down {
+                node.odown = false;
+            } else if !attempted {
+                candidates.push(addr);
+            }
+        }
+        candidates
+    }
+
+    /// Asks the other supervisors whether the node at `addr` is down, and
+    /// whether they make a quorum along with this one.
+    async fn agree_down(&self, addr: &str) -> bool {
+        let epoch = self.current_epoch();
+        let replies = self.ask_sentinels(addr, epoch, "*").await;
+        let agreed = 1 + replies.iter().filter(|(down, _, _)| *down).count() as u64;
+
+        let mut state = self.state.lock().unwrap();
+        let Some(node) = state.nodes.get_mut(addr) else {
+            return false;
+        };
+        let odown = agreed >= self.options.quorum;
+        if odown && !node.odown {
+            println!("+odown {} #quorum {}/{}", addr, agreed, self.options.quorum);
+        }
+        node.odown = odown;
+        odown
+    }
+
+    /// Holds an election among supervisors for the failover of `addr`,
+    /// voting for itself. It is won with the votes of a majority of them,
+    /// and at least a quorum.
+    ///
+    /// The election is left to another supervisor that started one during
+    /// the random delay before this one.
+    async fn win_election(&self, addr: &str) -> bool {
+        let seen = self.current_epoch();
+        time::sleep(MAX_ELECTION_DELAY.mul_f64(random_fraction())).await;
+
+        let epoch = {
+            let mut state = self.state.lock().unwrap();
+            if let Some(node) = state.nodes.get_mut(addr) {
+                node.failover_started = Some(Instant::now());
+            }
+            if state.current_epoch > seen {
+                return false;
+            }
+            state.current_epoch += 1;
+            state.voted_for = Some(self.run_id.clone());
+            state.current_epoch
+        };
+        println!("+try-failover {} epoch {}", addr, epoch);
+
+        let replies = self.ask_sentinels(addr, epoch, &self.run_id).await;
+        let votes = 1 + replies
+            .iter()
+            .filter(|(_, vote, vote_epoch)| *vote == self.run_id && *vote_epoch == epoch)
+            .count() as u64;
+
+        let supervisors = self.options.sentinels.len() as u64 + 1;
+        let majority = supervisors / 2 + 1;
+        let won = votes >= majority.max(self.options.quorum);
+        if won {
+            println!("+elected-leader {} epoch {}", addr, epoch);
+        } else {
+            println!("-failover-abort-not-elected {} epoch {}", addr, epoch);
+        }
+        won
+    }
+
+    /// Fails the node at `addr` over: has another member lead if it led and
+    /// no other was elected, then removes it from the group if it has been
+    /// silent for `remove_after`.
+    ///
+    /// A node that is only unreachable from the supervisors may still be
+    /// counted in the majority, so it stays a member until then. Later
+    /// attempts, every `failover_timeout`, remove it once it is due.
+    async fn failover(&self, addr: &str) {
+        let deadline = Instant::now() + self.options.failover_timeout;
+
+        if self.leader().as_deref() == Some(addr) {
+            match self.best_replica(addr) {
+                Some(best) => {
+                    println!("+promote {} replacing {}", best, addr);
+                    let _ = request(&best, Cmd::new("RAFT").arg("PROMOTE")).await;
+                }
+                None => {
+                    println!("-failover-abort-no-good-replica {}", addr);
+                    return;
+                }
+            }
+        }
+
+        // Wait for a leader other than the failed node.
+        let leader = loop {
+            match self.leader() {
+                Some(leader) if leader != addr => break leader,
+                _ if Instant::now() > deadline => {
+                    println!("-failover-abort-no-leader {}", addr);
+                    return;
+                }
+                _ => time::sleep(POLL_INTERVAL).await,
+            }
+        };
+
+        let id = {
+            let state = self.state.lock().unwrap();
+            let Some(node) = state.nodes.get(addr) else {
+                return;
+            };
+            let due = self
+                .options
+                .remove_after
+                .is_some_and(|after| node.last_ok.unwrap_or(node.added).elapsed() > after);
+            match node.id {
+                Some(id) if due => id,
+                _ => {
+                    println!("+failover-end {} leader {}", addr, leader);
+                    return;
+                }
+            }
+        };
+
+        let cmd = Cmd::new("RAFT").arg("REMOVENODE").arg(&id);
+        match request(&leader, cmd).await {
+            Ok(_) => {
+                println!("+removed {} id {} by {}", addr, id, leader);
+                self.state
+                    .lock()
+                    .unwrap()
+                    .removed
+                    .insert(addr.to_string(), id);
+            }
+            Err(err) => println!("-failover-abort {} {}", addr, err),
+        }
+    }
+
+    /// The reachable member most up to date, other than `addr`, the lowest
+    /// id breaking ties.
+    fn best_replica(&self, addr: &str) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        let members = &state.nodes.get(addr)?.members;
+
+        members
+            .iter()
+            .filter(|(_, member)| member != addr)
+            .filter_map(|(id, member)| {
+                let node = state.nodes.get(member)?;
+                (!self.is_down(node)).then_some((node.last_log_index, *id, member))
+            })
+            .max_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)))
+            .map(|(_, _, member)| member.clone())
+    }
+
+    /// Adds nodes removed by this supervisor back to the group once they
+    /// answer again, so they catch up and count again.
+    async fn readd_recovered(&self) {
+        let (leader, recovered) = {
+            let state = self.state.lock().unwrap();
+            let recovered: Vec<(String, u64)> = state
+                .removed
+                .iter()
+                .filter(|(addr, _)| {
+                    state
+                        .nodes
+                        .get(*addr)
+                        .is_some_and(|node| !self.is_down(node))
+                })
+                .map(|(addr, id)| (addr.clone(), *id))
+                .collect();
+            (state.leader.clone(), recovered)
+        };
+        let Some((leader, _)) = leader else {
+            return;
+        };
+
+        for (addr, id) in recovered {
+            let cmd = Cmd::new("RAFT").arg("ADDNODE").arg(&id).arg(&addr);
+            match request(&leader, cmd).await {
+                Ok(_) => println!("+added {} id {} by {}", addr, id, leader),
+                // Someone else added it back already.
+                Err(err) if err.to_string().contains("already a member") => {}
+                Err(_) => continue,
+            }
+            self.state.lock().unwrap().removed.remove(&addr);
+        }
+    }
+
+    /// Sends `SENTINEL IS-MASTER-DOWN-BY-ADDR` to every other supervisor,
+    /// returning the replies of those that answered in time.
+    async fn ask_sentinels(
+        &self,
+        addr: &str,
+        epoch: u64,
+        run_id: &str,
+    ) -> Vec<(bool, String, u64)> {
+        let (host, port) = addr.rsplit_once(':').unwrap_or((addr, ""));
+        let mut requests = JoinSet::new();
+        for sentinel in &self.options.sentinels {
+            let cmd = Cmd::new("SENTINEL")
+                .arg("IS-MASTER-DOWN-BY-ADDR")
+                .arg(host)
+                .arg(port)
+                .arg(&epoch)
+                .arg(run_id);
+            let sentinel = sentinel.clone();
+            requests.spawn(async move { request(&sentinel, cmd).await });
+        }
+
+        let mut replies = vec![];
+        while let Some(res) = requests.join_next().await {
+            if let Ok(Ok(Frame::Array(parts))) = res {
+                if let [Frame::Integer(down), Frame::Bulk(vote), Frame::Integer(vote_epoch)] =
+                    &parts[..]
+                {
+                    let vote = String::from_utf8_lossy(vote).into_owned();
+                    replies.push((*down == 1, vote, *vote_epoch as u64));
+                }
+            }
+        }
+        replies
+    }
+}
+
+impl Node {
+    fn new() -> Node {
+        Node {
+            id: None,
+            role: String::new(),
+            term: 0,
+            last_log_index: 0,
+            members: vec![],
+            last_ok: None,
+            added: Instant::now(),
+            odown: false,
+            failover_started: None,
+        }
+    }
+}
+
+async fn raft_info(client: &mut Option<Client>, addr: &str) -> crate::Result<String> {
+    if client.as_ref().is_none_or(Client::is_broken) {
+        *client = Some(Client::connect(addr).await?);
+    }
+
+    let client = client.as_mut().unwrap();
+    client.query(Cmd::new("RAFT").arg("INFO")).await
+}
+
+/// Sends a single command on a new connection.
+async fn request(addr: &str, cmd: Cmd) -> crate::Result<Frame> {
+    let res = time::timeout(REQUEST_TIMEOUT, async {
+        let mut client = Client::connect(addr).await?;
+        client.query(cmd).await
+    })
+    .await;
+
+    match res {
+        Ok(res) => res,
+        Err(_) => Err("request timed out".into()),
+    }
+}
+
+/// A random identifier, 40 hex characters like Redis run ids.
+fn run_id() -> String {
+    (0..5)
+        .map(|_| format!("{:08x}", random_u64() as u32))
+        .collect()
+}
+
+/// A random number in `[0, 1)`.
+fn random_fraction() -> f64 {
+    (random_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Random enough for ids and delays, `RandomState` being seeded randomly.
+fn random_u64() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u8(0);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miniredis::protocol::connection::Connection;
+    use tokio::net::TcpListener;
+
+    const LEADER: &str = "10.0.0.1:7001";
+
+    fn sentinel(remove_after: Option<Duration>) -> Arc<Sentinel> {
+        Sentinel::new(Options {
+            bind: "127.0.0.1:0".to_string(),
+            name: "mygroup".to_string(),
+            nodes: vec![],
+            sentinels: vec![],
+            quorum: 1,
+            down_after: Duration::from_millis(100),
+            failover_timeout: Duration::from_secs(5),
+            remove_after,
+        })
+    }
+
+    /// Records a node as it would be after a `RAFT INFO`, silent for
+    /// `silent` since.
+    fn add_node(
+        sentinel: &Sentinel,
+        addr: &str,
+        id: u64,
+        last_log_index: u64,
+        members: &[(u64, &str)],
+        silent: Duration,
+    ) {
+        let mut node = Node::new();
+        node.id = Some(id);
+        node.last_log_index = last_log_index;
+        node.members = members
+            .iter()
+            .map(|(id, addr)| (*id, addr.to_string()))
+            .collect();
+        node.last_ok = Some(Instant::now() - silent);
+
+        let mut state = sentinel.state.lock().unwrap();
+        state.nodes.insert(addr.to_string(), node);
+    }
+
+    /// A node answering `+OK` to everything, returning its address and the
+    /// commands it received.
+    async fn fake_node() -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let received = Arc::new(Mutex::new(vec![]));
+
+        let log = Arc::clone(&received);
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let log = Arc::clone(&log);
+                tokio::spawn(async move {
+                    let mut connection = Connection::new(socket);
+                    while let Ok(Some(frame)) = connection.read_frame().await {
+                        log.lock().unwrap().push(frame.to_string());
+                        let _ = connection.write_frame(&Frame::Simple("OK".into())).await;
+                    }
+                });
+            }
+        });
+        (addr, received)
+    }
+
+    #[test]
+    fn one_vote_per_epoch() {
+        let sentinel = sentinel(None);
+
+        assert_eq!(
+            sentinel.is_down_by_addr(LEADER, 1, "a"),
+            (false, "a".to_string(), 1)
+        );
+        // Later candidates of the same epoch get the same answer.
+        assert_eq!(
+            sentinel.is_down_by_addr(LEADER, 1, "b"),
+            (false, "a".to_string(), 1)
+        );
+        // Checking whether a node is down does not vote.
+        assert_eq!(
+            sentinel.is_down_by_addr(LEADER, 2, "*"),
+            (false, "*".to_string(), 1)
+        );
+
+        assert_eq!(
+            sentinel.is_down_by_addr(LEADER, 2, "b"),
+            (false, "b".to_string(), 2)
+        );
+        // Stale epochs get no vote.
+        assert_eq!(
+            sentinel.is_down_by_addr(LEADER, 1, "c"),
+            (false, "*".to_string(), 2)
+        );
+    }
+
+    #[test]
+    fn nodes_are_down_after_their_silence() {
+        let sentinel = sentinel(None);
+        add_node(&sentinel, LEADER, 1, 0, &[], Duration::from_secs(1));
+        add_node(&sentinel, "10.0.0.2:7001", 2, 0, &[], Duration::ZERO);
+
+        assert!(sentinel.is_down_by_addr(LEADER, 0, "*").0);
+        assert!(!sentinel.is_down_by_addr("10.0.0.2:7001", 0, "*").0);
+        assert!(!sentinel.is_down_by_addr("10.0.0.9:7001", 0, "*").0);
+    }
+
+    #[test]
+    fn best_replica_is_the_most_up_to_date() {
+        let sentinel = sentinel(None);
+        let members = [
+            (1, LEADER),
+            (2, "10.0.0.2:7001"),
+            (3, "10.0.0.3:7001"),
+            (4, "10.0.0.4:7001"),
+            (5, "10.0.0.5:7001"),
+        ];
+        add_node(&sentinel, LEADER, 1, 20, &members, Duration::from_secs(1));
+        add_node(&sentinel, "10.0.0.2:7001", 2, 5, &[], Duration::ZERO);
+        add_node(&sentinel, "10.0.0.3:7001", 3, 9, &[], Duration::ZERO);
+        add_node(&sentinel, "10.0.0.4:7001", 4, 9, &[], Duration::ZERO);
+        // Down, however up to date.
+        add_node(
+            &sentinel,
+            "10.0.0.5:7001",
+            5,
+            15,
+            &[],
+            Duration::from_secs(1),
+        );
+
+        // The lowest id breaks the tie.
+        assert_eq!(
+            sentinel.best_replica(LEADER).as_deref(),
+            Some("10.0.0.3:7001")
+        );
+
+        add_node(
+            &sentinel,
+            "10.0.0.3:7001",
+            3,
+            9,
+            &[],
+            Duration::from_secs(1),
+        );
+        add_node(
+            &sentinel,
+            "10.0.0.4:7001",
+            4,
+            9,
+            &[],
+            Duration::from_secs(1),
+        );
+        add_node(
+            &sentinel,
+            "10.0.0.2:7001",
+            2,
+            5,
+            &[],
+            Duration::from_secs(1),
+        );
+        assert_eq!(sentinel.best_replica(LEADER), None);
+    }
+
+    /// Fails the leader over to a fake replica, which the group then
+    /// elects, returning the commands the replica received.
+    async fn fail_leader_over(
+        sentinel: &Arc<Sentinel>,
+        silent: Duration,
+    ) -> Arc<Mutex<Vec<String>>> {
+        let (replica, received) = fake_node().await;
+        let members = [(1, LEADER), (2, &replica[..])];
+        add_node(sentinel, LEADER, 1, 10, &members, silent);
+        add_node(sentinel, &replica, 2, 10, &members, Duration::ZERO);
+        sentinel.state.lock().unwrap().leader = Some((LEADER.to_string(), 1));
+
+        let failover = tokio::spawn({
+            let sentinel = Arc::clone(sentinel);
+            async move { sentinel.failover(LEADER).await }
+        });
+        time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(*received.lock().unwrap(), ["RAFT PROMOTE"]);
+
+        sentinel.state.lock().unwrap().leader = Some((replica, 2));
+        failover.await.unwrap();
+        received
+    }
+
+    #[tokio::test]
+    async fn failed_over_nodes_are_not_removed_by_default() {
+        let sentinel = sentinel(None);
+        let received = fail_leader_over(&sentinel, Duration::from_secs(1)).await;
+
+        assert_eq!(*received.lock().unwrap(), ["RAFT PROMOTE"]);
+        assert!(sentinel.state.lock().unwrap().removed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_over_nodes_are_removed_after_their_grace_period() {
+        let sentinel = sentinel(Some(Duration::from_secs(5)));
+        let received = fail_leader_over(&sentinel, Duration::from_millis(200)).await;
+        assert_eq!(*received.lock().unwrap(), ["RAFT PROMOTE"]);
+        assert!(sentinel.state.lock().unwrap().removed.is_empty());
+
+        // Another attempt once the node has been silent long enough.
+        add_node(&sentinel, LEADER, 1, 10, &[], Duration::from_secs(10));
+        sentinel.failover(LEADER).await;
+        assert_eq!(
+            *received.lock().unwrap(),
+            ["RAFT PROMOTE", "RAFT REMOVENODE 1"]
+        );
+        let removed = sentinel.state.lock().unwrap().removed.clone();
+        assert_eq!(removed, HashMap::from([(LEADER.to_string(), 1)]));
+    }
+}
@@ -0,0 +1,222 @@
+use bytes::Bytes;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+
+use miniredis::protocol::connection::Connection;
+use miniredis::protocol::frame::Frame;
+use miniredis::protocol::parse::Parse;
+
+use crate::sentinel::{Node, Sentinel};
+
+/// Answers queries about the group until the process ends.
+pub async fn run(bind: &str, sentinel: Arc<Sentinel>) -> crate::Result<()> {
+    let listener = TcpListener::bind(bind).await?;
+    println!(
+        "+monitor {} on {} quorum {}",
+        sentinel.options.name, bind, sentinel.options.quorum
+    );
+
+    loop {
+        match listener.accept().await {
+            Ok((socket, _)) => {
+                tokio::spawn(serve(socket, Arc::clone(&sentinel)));
+            }
+            // Running out of file descriptors and similar errors only affect
+            // the connection being accepted.
+            Err(err) => eprintln!("accept error: {}", err),
+        }
+    }
+}
+
+async fn serve(socket: TcpStream, sentinel: Arc<Sentinel>) {
+    let _ = socket.set_nodelay(true);
+    let mut connection = Connection::new(socket);
+
+    while let Ok(Some(frame)) = connection.read_frame().await {
+        let reply = match execute(&sentinel, frame) {
+            Ok(reply) => reply,
+            Err(err) => Frame::Error(format!("ERR {}", err)),
+        };
+        if connection.write_frame(&reply).await.is_err() {
+            return;
+        }
+    }
+}
+
+fn execute(sentinel: &Sentinel, frame: Frame) -> crate::Result<Frame> {
+    let mut parse = Parse::new(frame)?;
+    let command = parse.next_string()?.to_lowercase();
+
+    match &command[..] {
+        "ping" => Ok(Frame::Simple("PONG".to_string())),
+        "sentinel" => {
+            let subcommand = parse.next_string()?.to_lowercase();
+            sentinel_command(sentinel, &subcommand, &mut parse)
+        }
+        _ => Err(format!("unknown command '{}'", command).into()),
+    }
+}
+
+fn sentinel_command(
+    sentinel: &Sentinel,
+    subcommand: &str,
+    parse: &mut Parse,
+) -> crate::Result<Frame> {
+    let reply = match subcommand {
+        "get-master-addr-by-name" => {
+            check_name(sentinel, parse)?;
+            match sentinel.leader() {
+                Some(leader) => {
+                    let (host, port) = leader.rsplit_once(':').unwrap_or((&leader, ""));
+                    Frame::Array(vec![bulk(host), bulk(port)])
+                }
+                None => Frame::Null,
+            }
+        }
+        "masters" => {
+            parse.finish()?;
+            Frame::Array(
+                sentinel
+                    .leader()
+                    .map(|leader| master(sentinel, &leader))
+                    .into_iter()
+                    .collect(),
+            )
+        }
+        "master" => {
+            check_name(sentinel, parse)?;
+            match sentinel.leader() {
+                Some(leader) => master(sentinel, &leader),
+                None => return Err("no leader known for this group".into()),
+            }
+        }
+        "replicas" | "slaves" => {
+            check_name(sentinel, parse)?;
+            let leader = sentinel.leader();
+            Frame::Array(
+                sentinel
+                    .nodes()
+                    .into_iter()
+                    .filter(|(addr, _, _)| Some(addr) != leader.as_ref())
+                    .map(|(addr, node, down)| {
+                        Frame::Array(node_fields(&addr, &node, down, "slave"))
+                    })
+                    .collect(),
+            )
+        }
+        "sentinels" => {
+            check_name(sentinel, parse)?;
+            Frame::Array(
+                sentinel
+                    .options
+                    .sentinels
+                    .iter()
+                    .map(|addr| {
+                        let (host, port) = addr.rsplit_once(':').unwrap_or((addr, ""));
+                        Frame::Array(fields(vec![
+                            ("name", addr.clone()),
+                            ("ip", host.into()),
+                            ("port", port.into()),
+                        ]))
+                    })
+                    .collect(),
+            )
+        }
+        "is-master-down-by-addr" => {
+            let host = parse.next_string()?;
+            let port = parse.next_string()?;
+            let epoch = u64::try_from(parse.next_int()?)?;
+            let run_id = parse.next_string()?;
+            parse.finish()?;
+
+            let addr = format!("{}:{}", host, port);
+            let (down, vote, epoch) = sentinel.is_down_by_addr(&addr, epoch, &run_id);
+            Frame::Array(vec![
+                Frame::Integer(down as i64),
+                bulk(&vote),
+                Frame::Integer(epoch as i64),
+            ])
+        }
+        "myid" => {
+            parse.finish()?;
+            bulk(&sentinel.run_id)
+        }
+        _ => return Err(format!("unknown subcommand '{}'", subcommand).into()),
+    };
+    Ok(reply)
+}
+
+/// Consumes the group name, the last argument of most subcommands.
+fn check_name(sentinel: &Sentinel, parse: &mut Parse) -> crate::Result<()> {
+    let name = parse.next_string()?;
+    parse.finish()?;
+    if name != sentinel.options.name {
+        return Err("No such master with that name".into());
+    }
+    Ok(())
+}
+
+fn master(sentinel: &Sentinel, leader: &str) -> Frame {
+    let nodes = sentinel.nodes();
+    let replicas = nodes.iter().filter(|(addr, _, _)| addr != leader).count();
+    let (node, down) = nodes
+        .into_iter()
+        .find(|(addr, _, _)| addr == leader)
+        .map(|(_, node, down)| (node, down))
+        .unwrap();
+
+    let mut parts = node_fields(leader, &node, down, "master");
+    parts[1] = bulk(&sentinel.options.name);
+    parts.extend(fields(vec![
+        ("num-slaves", replicas.to_string()),
+        (
+            "num-other-sentinels",
+            sentinel.options.sentinels.len().to_string(),
+        ),
+        ("quorum", sentinel.options.quorum.to_string()),
+        (
+            "failover-timeout",
+            sentinel.options.failover_timeout.as_millis().to_string(),
+        ),
+        ("config-epoch", sentinel.current_epoch().to_string()),
+    ]));
+    Frame::Array(parts)
+}
+
+/// Fields describing a node as Redis Sentinel does, as a flat array of
+/// names and values.
+fn node_fields(addr: &str, node: &Node, down: bool, kind: &str) -> Vec<Frame> {
+    let (host, port) = addr.rsplit_once(':').unwrap_or((addr, ""));
+    let mut flags = kind.to_string();
+    if down {
+        flags.push_str(",s_down");
+    }
+    if node.odown {
+        flags.push_str(",o_down");
+    }
+
+    fields(vec![
+        ("name", addr.to_string()),
+        ("ip", host.to_string()),
+        ("port", port.to_string()),
+        ("flags", flags),
+        (
+            "run-id",
+            node.id.map(|id| id.to_string()).unwrap_or_default(),
+        ),
+        ("role-reported", node.role.clone()),
+        ("term", node.term.to_string()),
+        ("last-log-index", node.last_log_index.to_string()),
+    ])
+}
+
+fn fields(fields: Vec<(&str, String)>) -> Vec<Frame> {
+    fields
+        .into_iter()
+        .flat_map(|(name, value)| [bulk(name), bulk(&value)])
+        .collect()
+}
+
+fn bulk(value: &str) -> Frame {
+    Frame::Bulk(Bytes::copy_from_slice(value.as_bytes()))
+}
@@ -0,0 +1,120 @@
+//! Load generator for miniredis and miniminio.
+//!
+//! ```text
+//! minibench redis [options]
+//! minibench minio [options]
+//! ```
+//!
+//! Reports throughput and latency percentiles, e.g. to compare `ShardedDB`
+//! shard counts with `minibench redis --embedded --shards 1`, or its shard
+//! models with `minibench redis --embedded --shard-model owner`.
+
+mod minio;
+mod redis;
+mod stats;
+
+use std::process;
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+const USAGE: &str = "\
+Usage: minibench <redis|minio> [OPTIONS]
+
+Common options:
+  --addr <host:port>        Server to benchmark (default: the server's usual port).
+  --embedded                Benchmark an in-process server instead.
+  -c, --connections <n>     Parallel connections (redis: 50, minio: 10).
+
+redis options:
+  -n, --requests <n>        Total requests (default: 100000).
+  -P, --pipeline <n>        Requests per pipeline (default: 1).
+  -d, --value-size <size>   SET value size, e.g. 3 or 4kb (default: 3).
+  -r, --keyspace <n>        Number of distinct keys (default: 10000).
+  --get-ratio <ratio>       Share of GETs, the rest are SETs (default: 0.5).
+  --shards <n>              Shards per database of the embedded server.
+  --shard-model <model>     How the embedded server reaches its shards, mutex,
+                            rwlock or owner (default: mutex).
+
+minio options:
+  -n, --objects <n>         Objects to upload (default: 1000).
+  --object-size <size>      Object size (default: 1mb).
+  --part-size <size>        Multipart part size (default: 256kb).";
+
+/// Options as `(name, value)` pairs, flags have no value.
+pub type Args = Vec<(String, Option<String>)>;
+
+#[tokio::main]
+async fn main() {
+    let mut args = std::env::args().skip(1);
+    let target = args.next();
+
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            process::exit(1);
+        }
+    };
+
+    let res = match target.as_deref() {
+        Some("redis") => redis::run(options).await,
+        Some("minio") => minio::run(options).await,
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = res {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> std::result::Result<Args, String> {
+    const FLAGS: &[&str] = &["--embedded"];
+
+    let mut args = args.peekable();
+    let mut options = vec![];
+    while let Some(name) = args.next() {
+        if !name.starts_with('-') {
+            return Err(format!("unexpected argument '{}'", name));
+        }
+
+        let value = if FLAGS.contains(&&name[..]) {
+            None
+        } else {
+            Some(args.next().ok_or_else(|| format!("missing value for '{}'", name))?)
+        };
+        options.push((name, value));
+    }
+
+    Ok(options)
+}
+
+/// Parses a byte count with an optional unit, e.g. `100`, `4kb` or `1mb`.
+pub fn parse_size(name: &str, value: Option<String>) -> Result<usize> {
+    let invalid = || format!("invalid value for '{}'", name);
+    let value = value.ok_or_else(invalid)?.to_lowercase();
+    let digits = value.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+
+    let unit = match &value[digits.len()..] {
+        "" | "b" => 1,
+        "kb" | "k" => 1024,
+        "mb" | "m" => 1024 * 1024,
+        "gb" | "g" => 1024 * 1024 * 1024,
+        _ => return Err(invalid().into()),
+    };
+
+    let size: usize = digits.parse().map_err(|_| invalid())?;
+    Ok(size * unit)
+}
+
+/// Splits `total` requests as evenly as possible across `workers`.
+pub fn split_requests(total: usize, workers: usize) -> Vec<usize> {
+    (0..workers)
+        .map(|i| total / workers + usize::from(i < total % workers))
+        .collect()
+}
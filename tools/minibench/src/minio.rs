@@ -0,0 +1,131 @@
+use bytes::Bytes;
+use std::time::Instant;
+
+use miniminio::client::{self, MiniMinioClient};
+use miniminio::Server;
+
+use crate::stats::Latencies;
+use crate::{parse_size, split_requests, Args};
+
+/// Multipart upload workload against miniminio.
+#[derive(Debug, Clone)]
+struct Workload {
+    addr: String,
+    connections: usize,
+    objects: usize,
+    object_size: usize,
+    part_size: usize,
+    embedded: bool,
+}
+
+pub async fn run(args: Args) -> crate::Result<()> {
+    let mut workload = Workload {
+        addr: "127.0.0.1:6378".to_string(),
+        connections: 10,
+        objects: 1_000,
+        object_size: 1024 * 1024,
+        part_size: 256 * 1024,
+        embedded: false,
+    };
+
+    for (name, value) in args {
+        match &name[..] {
+            "--addr" => workload.addr = value.ok_or("missing value for '--addr'")?,
+            "-c" | "--connections" => {
+                workload.connections = value
+                    .and_then(|value| value.parse().ok())
+                    .ok_or("invalid value for '--connections'")?
+            }
+            "-n" | "--objects" => {
+                workload.objects = value
+                    .and_then(|value| value.parse().ok())
+                    .ok_or("invalid value for '--objects'")?
+            }
+            "--object-size" => workload.object_size = parse_size(&name, value)?,
+            "--part-size" => workload.part_size = parse_size(&name, value)?,
+            "--embedded" => workload.embedded = true,
+            _ => return Err(format!("unrecognized option '{}'", name).into()),
+        }
+    }
+
+    if workload.connections == 0 || workload.part_size == 0 {
+        return Err("connections and part size must be positive".into());
+    }
+
+    let server = if workload.embedded {
+        let server = Server::builder().bind("127.0.0.1:0").start().await?;
+        workload.addr = server.local_addr().to_string();
+        Some(server)
+    } else {
+        None
+    };
+
+    let parts = workload.object_size.div_ceil(workload.part_size).max(1);
+    println!("====== miniminio multipart upload ======");
+    println!(
+        "  {} connections, {} byte objects in {} parts of up to {} bytes",
+        workload.connections, workload.object_size, parts, workload.part_size
+    );
+
+    let mut clients = Vec::with_capacity(workload.connections);
+    for _ in 0..workload.connections {
+        clients.push(client::connect(&workload.addr).await?);
+    }
+
+    let start = Instant::now();
+    let tasks: Vec<_> = clients
+        .into_iter()
+        .zip(split_requests(workload.objects, workload.connections))
+        .map(|(client, objects)| tokio::spawn(worker(client, objects, workload.clone())))
+        .collect();
+
+    let mut latencies = Latencies::default();
+    for task in tasks {
+        latencies.merge(task.await??);
+    }
+
+    let elapsed = start.elapsed();
+    let secs = elapsed.as_secs_f64();
+    println!(
+        "  {} objects uploaded, {:.2} objects per second, {:.2} MB/s",
+        workload.objects,
+        workload.objects as f64 / secs,
+        (workload.objects * workload.object_size) as f64 / secs / (1024.0 * 1024.0)
+    );
+    latencies.report(elapsed);
+
+    if let Some(server) = server {
+        server.shutdown().await;
+    }
+    Ok(())
+}
+
+/// Uploads `objects` objects, recording the latency of every request:
+/// creating the upload, each part and completing it.
+async fn worker(mut client: MiniMinioClient, objects: usize, workload: Workload) -> crate::Result<Latencies> {
+    let data = Bytes::from(vec![b'x'; workload.object_size]);
+    let mut latencies = Latencies::default();
+
+    for object in 0..objects {
+        let key = format!("object-{}", object);
+
+        let start = Instant::now();
+        let upload_id = client.create_mutlipart_upload("bench", &key, "1").await?;
+        latencies.record(start.elapsed(), 1);
+
+        let mut part_order = vec![];
+        let chunks = data.chunks(workload.part_size).map(|chunk| data.slice_ref(chunk));
+        for (number, part) in chunks.enumerate() {
+            let start = Instant::now();
+            client.upload_part(&upload_id, number as u32 + 1, part).await?;
+            latencies.record(start.elapsed(), 1);
+            part_order.push((number + 1).to_string());
+        }
+
+        let start = Instant::now();
+        client.complete_multipart_upload(&upload_id, part_order).await?;
+        latencies.record(start.elapsed(), 1);
+    }
+
+    Ok(latencies)
+}
@@ -0,0 +1,162 @@
+use bytes::Bytes;
+use std::time::Instant;
+
+use miniredis::Server;
+use miniredis_client::{cmd, Client, Pipeline};
+use shared_lib::sharded_db::ShardModel;
+
+use crate::stats::{Latencies, Rng};
+use crate::{parse_size, split_requests, Args};
+
+/// GET/SET workload against miniredis.
+#[derive(Debug)]
+struct Workload {
+    addr: String,
+    connections: usize,
+    requests: usize,
+    pipeline: usize,
+    value_size: usize,
+    keyspace: u64,
+    get_ratio: f64,
+    embedded: bool,
+    shards: Option<usize>,
+    shard_model: Option<ShardModel>,
+}
+
+pub async fn run(args: Args) -> crate::Result<()> {
+    let mut workload = Workload {
+        addr: "127.0.0.1:6379".to_string(),
+        connections: 50,
+        requests: 100_000,
+        pipeline: 1,
+        value_size: 3,
+        keyspace: 10_000,
+        get_ratio: 0.5,
+        embedded: false,
+        shards: None,
+        shard_model: None,
+    };
+
+    for (name, value) in args {
+        match &name[..] {
+            "--addr" => workload.addr = value.ok_or("missing value for '--addr'")?,
+            "-c" | "--connections" => workload.connections = parse(&name, value)?,
+            "-n" | "--requests" => workload.requests = parse(&name, value)?,
+            "-P" | "--pipeline" => workload.pipeline = parse(&name, value)?,
+            "-d" | "--value-size" => workload.value_size = parse_size(&name, value)?,
+            "-r" | "--keyspace" => workload.keyspace = parse(&name, value)?,
+            "--get-ratio" => workload.get_ratio = parse(&name, value)?,
+            "--embedded" => workload.embedded = true,
+            "--shards" => workload.shards = Some(parse(&name, value)?),
+            "--shard-model" => workload.shard_model = Some(parse(&name, value)?),
+            _ => return Err(format!("unrecognized option '{}'", name).into()),
+        }
+    }
+
+    if workload.connections == 0 || workload.pipeline == 0 || workload.keyspace == 0 {
+        return Err("connections, pipeline and keyspace must be positive".into());
+    }
+    if workload.shards == Some(0) {
+        return Err("'--shards' must be positive".into());
+    }
+    if workload.shards.is_some() && !workload.embedded {
+        return Err("'--shards' only applies to an '--embedded' server".into());
+    }
+    if workload.shard_model.is_some() && !workload.embedded {
+        return Err("'--shard-model' only applies to an '--embedded' server".into());
+    }
+
+    // An in-process server, so settings such as the shard count can be
+    // compared without restarting anything.
+    let server = if workload.embedded {
+        let mut builder = Server::builder().bind("127.0.0.1:0");
+        if let Some(shards) = workload.shards {
+            builder = builder.shards(shards);
+        }
+        if let Some(model) = workload.shard_model {
+            builder = builder.shard_model(model);
+        }
+        let server = builder.start().await?;
+        workload.addr = server.local_addr().to_string();
+        Some(server)
+    } else {
+        None
+    };
+
+    println!("====== miniredis GET/SET ======");
+    println!(
+        "  {} connections, pipeline {}, {} byte values, {} keys, {:.0}% GET",
+        workload.connections,
+        workload.pipeline,
+        workload.value_size,
+        workload.keyspace,
+        workload.get_ratio * 100.0
+    );
+    if workload.embedded {
+        println!(
+            "  embedded server, {} shards, {:?} model",
+            workload.shards.unwrap_or(miniredis::state::NUM_SHARDS),
+            workload.shard_model.unwrap_or_default()
+        );
+    }
+
+    // Connect everyone first so connection setup is not part of the numbers.
+    let mut clients = Vec::with_capacity(workload.connections);
+    for _ in 0..workload.connections {
+        clients.push(Client::connect(&workload.addr).await?);
+    }
+
+    let workload = std::sync::Arc::new(workload);
+    let start = Instant::now();
+    let tasks: Vec<_> = clients
+        .into_iter()
+        .zip(split_requests(workload.requests, workload.connections))
+        .map(|(client, requests)| tokio::spawn(worker(client, requests, workload.clone())))
+        .collect();
+
+    let mut latencies = Latencies::with_capacity(workload.requests);
+    for task in tasks {
+        latencies.merge(task.await??);
+    }
+    latencies.report(start.elapsed());
+
+    if let Some(server) = server {
+        server.shutdown().await;
+    }
+    Ok(())
+}
+
+async fn worker(mut client: Client, requests: usize, workload: std::sync::Arc<Workload>) -> crate::Result<Latencies> {
+    let value = Bytes::from(vec![b'x'; workload.value_size]);
+    let mut rng = Rng::new();
+    let mut latencies = Latencies::with_capacity(requests);
+
+    let mut done = 0;
+    while done < requests {
+        let batch = workload.pipeline.min(requests - done);
+
+        let mut pipeline = Pipeline::new();
+        for _ in 0..batch {
+            let key = format!("key:{:012}", rng.below(workload.keyspace));
+            if rng.unit() < workload.get_ratio {
+                pipeline.add(cmd::get(key));
+            } else {
+                pipeline.add(cmd::set(key, &value));
+            }
+        }
+
+        let start = Instant::now();
+        pipeline.query(&mut client).await?;
+        latencies.record(start.elapsed(), batch);
+
+        done += batch;
+    }
+
+    Ok(latencies)
+}
+
+fn parse<T: std::str::FromStr>(name: &str, value: Option<String>) -> crate::Result<T> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("invalid value for '{}'", name).into())
+}
@@ -0,0 +1,103 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// Latencies of every request of a run, in microseconds.
+#[derive(Debug, Default)]
+pub struct Latencies {
+    samples: Vec<u64>,
+}
+
+impl Latencies {
+    pub fn with_capacity(capacity: usize) -> Latencies {
+        Latencies {
+            samples: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Records `count` requests that completed together after `latency`,
+    /// such as the commands of a pipeline.
+    pub fn record(&mut self, latency: Duration, count: usize) {
+        let micros = latency.as_micros() as u64;
+        self.samples.extend(std::iter::repeat_n(micros, count));
+    }
+
+    pub fn merge(&mut self, other: Latencies) {
+        self.samples.extend(other.samples);
+    }
+
+    /// Prints throughput and latency percentiles of requests that ran for
+    /// `elapsed` in total.
+    pub fn report(mut self, elapsed: Duration) {
+        self.samples.sort_unstable();
+
+        let secs = elapsed.as_secs_f64();
+        println!(
+            "  {} requests completed in {:.2} seconds",
+            self.samples.len(),
+            secs
+        );
+        println!(
+            "  throughput: {:.2} requests per second",
+            self.samples.len() as f64 / secs
+        );
+
+        if self.samples.is_empty() {
+            return;
+        }
+
+        let avg = self.samples.iter().sum::<u64>() as f64 / self.samples.len() as f64;
+        println!("  latency summary (msec):");
+        println!(
+            "  {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}",
+            "avg", "min", "p50", "p90", "p99", "p99.9", "max"
+        );
+        println!(
+            "  {:>9.3} {:>9.3} {:>9.3} {:>9.3} {:>9.3} {:>9.3} {:>9.3}",
+            avg / 1000.0,
+            self.percentile(0.0),
+            self.percentile(50.0),
+            self.percentile(90.0),
+            self.percentile(99.0),
+            self.percentile(99.9),
+            self.percentile(100.0),
+        );
+    }
+
+    /// Percentile of the sorted samples, in milliseconds.
+    fn percentile(&self, p: f64) -> f64 {
+        let rank = ((p / 100.0) * (self.samples.len() - 1) as f64).round() as usize;
+        self.samples[rank] as f64 / 1000.0
+    }
+}
+
+/// Small, fast generator for picking keys and operations. Not suitable for
+/// anything but spreading load.
+#[derive(Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// Every generator starts from a different seed.
+    pub fn new() -> Rng {
+        let mut s = RandomState::new().build_hasher();
+        s.write_u8(0);
+        Rng(s.finish() | 1)
+    }
+
+    /// xorshift64*
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
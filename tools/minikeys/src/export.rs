@@ -0,0 +1,129 @@
+use bytes::Bytes;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use miniredis_client::{cmd, Client, Cmd, Frame, Pipeline};
+
+use crate::format::{Record, Writer};
+use crate::Options;
+
+/// Keys asked for by each `SCAN`, whose values are then fetched in a single
+/// pipeline.
+const SCAN_COUNT: usize = 1000;
+
+pub async fn run(options: Options) -> crate::Result<()> {
+    let out: Box<dyn Write> = match &options.output[..] {
+        "-" => Box::new(io::stdout().lock()),
+        path => Box::new(File::create(path)?),
+    };
+    let mut writer = Writer::new(BufWriter::new(out), options.format)?;
+
+    let mut client = Client::connect(&options.addr[..]).await?;
+    let dbs = match options.db {
+        Some(db) => vec![db],
+        None => databases(&mut client).await?,
+    };
+
+    let mut exported = 0;
+    for db in dbs {
+        client.select(db).await?;
+        exported += export_db(&mut client, db, &options, &mut writer).await?;
+    }
+    writer.finish()?;
+
+    eprintln!("exported {} keys", exported);
+    Ok(())
+}
+
+/// Indexes of the databases of the server, as many as `CONFIG GET databases`
+/// reports.
+async fn databases(client: &mut Client) -> crate::Result<Vec<usize>> {
+    let count = client
+        .config_get("databases")
+        .await?
+        .into_iter()
+        .find(|(name, _)| name == "databases")
+        .and_then(|(_, value)| value.parse().ok())
+        .ok_or("the server did not report how many databases it has")?;
+    Ok((0..count).collect())
+}
+
+/// Writes the keys of the selected database `db`, returning how many.
+async fn export_db<W: Write>(
+    client: &mut Client,
+    db: usize,
+    options: &Options,
+    writer: &mut Writer<W>,
+) -> crate::Result<usize> {
+    let mut exported = 0;
+    let mut cursor = "0".to_string();
+
+    loop {
+        let mut scan = Cmd::new("SCAN").arg(&cursor);
+        if let Some(pattern) = &options.pattern {
+            scan = scan.arg("MATCH").arg(pattern);
+        }
+        let (next, keys) = parse_scan(client.query(scan.arg("COUNT").arg(&SCAN_COUNT)).await?)?;
+
+        if !keys.is_empty() {
+            let mut pipeline = Pipeline::new();
+            for key in &keys {
+                pipeline
+                    .add(Cmd::new("DUMP").arg(key))
+                    .add(cmd::pttl(key))
+                    .add(Cmd::new("TYPE").arg(key));
+            }
+            let replies = pipeline.query(client).await?;
+
+            for (key, replies) in keys.into_iter().zip(replies.chunks(3)) {
+                // Keys removed since they were scanned are skipped.
+                let (payload, ttl, type_name) = match replies {
+                    [Frame::Bulk(payload), Frame::Integer(ttl), Frame::Simple(type_name)]
+                        if *ttl != -2 =>
+                    {
+                        (payload.clone(), *ttl, type_name.clone())
+                    }
+                    [Frame::Null, ..] | [_, Frame::Integer(-2), _] => continue,
+                    replies => {
+                        let reply = replies
+                            .iter()
+                            .find(|reply| matches!(reply, Frame::Error(_)))
+                            .unwrap_or(&replies[0]);
+                        return Err(format!("exporting {:?}: {}", key, reply).into());
+                    }
+                };
+
+                writer.write(&Record {
+                    db,
+                    key,
+                    type_name,
+                    ttl: u64::try_from(ttl).ok(),
+                    payload,
+                })?;
+                exported += 1;
+            }
+        }
+
+        if next == "0" {
+            return Ok(exported);
+        }
+        cursor = next;
+    }
+}
+
+/// Splits the reply to `SCAN` into the next cursor and the keys.
+fn parse_scan(frame: Frame) -> crate::Result<(String, Vec<Bytes>)> {
+    if let Frame::Array(mut parts) = frame {
+        if let (Some(Frame::Array(keys)), Some(Frame::Bulk(cursor))) = (parts.pop(), parts.pop()) {
+            let keys = keys
+                .into_iter()
+                .map(|key| match key {
+                    Frame::Bulk(key) => Ok(key),
+                    frame => Err(format!("unexpected reply to SCAN: {}", frame)),
+                })
+                .collect::<Result<_, _>>()?;
+            return Ok((String::from_utf8(cursor.to_vec())?, keys));
+        }
+    }
+    Err("unexpected reply to SCAN".into())
+}
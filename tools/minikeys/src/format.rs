@@ -0,0 +1,297 @@
+//! Files keys are exported to.
+//!
+//! JSON lines hold an object per key. Strings are given as `value`, or as
+//! `value_base64` when not valid UTF-8, with their memcached `flags` when
+//! set. Values of module types are given as the base64 `dump` payload.
+//! Keys likewise are given as `key` or `key_base64`. `ttl` is the time to
+//! live in milliseconds, for keys that have one.
+//!
+//! ```text
+//! {"db":0,"key":"user:1","type":"string","ttl":59000,"value":"alice"}
+//! {"db":0,"key":"visits","type":"counter","dump":"AAAAB2NvdW50ZXIA..."}
+//! ```
+//!
+//! Snapshot files start with `MINIKEYS` and a version, followed by a record
+//! per key and an end marker, so truncated files are told apart:
+//!
+//! ```text
+//! record   u8 1, db u32, key, type, ttl i64 (-1 without one), payload
+//! end      u8 0
+//! ```
+//!
+//! where key, type and payload are a u32 length followed by as many bytes,
+//! and the payload is the `DUMP` of the key. Numbers are big endian.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bytes::Bytes;
+use serde_json::{json, Map, Value as Json};
+use std::io::{BufRead, Write};
+
+use miniredis::db::{Entry, Value};
+use miniredis::dump;
+use miniredis::encoding::{put_bytes, read_bytes, read_u32};
+use miniredis::module::Modules;
+
+const SNAPSHOT_MAGIC: &[u8] = b"MINIKEYS";
+
+const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    JsonLines,
+    Snapshot,
+}
+
+/// A key as exported.
+#[derive(Debug)]
+pub struct Record {
+    pub db: usize,
+    pub key: Bytes,
+    /// As reported by `TYPE`.
+    pub type_name: String,
+    /// Time to live in milliseconds.
+    pub ttl: Option<u64>,
+    /// What `DUMP` returned for the key.
+    pub payload: Bytes,
+}
+
+/// Writes records in either format.
+pub struct Writer<W: Write> {
+    out: W,
+    format: Format,
+}
+
+/// Reads records back in either format.
+pub struct Reader<R: BufRead> {
+    input: R,
+    format: Format,
+    /// Line or record number, for errors.
+    position: usize,
+    done: bool,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(mut out: W, format: Format) -> crate::Result<Writer<W>> {
+        if format == Format::Snapshot {
+            out.write_all(SNAPSHOT_MAGIC)?;
+            out.write_all(&SNAPSHOT_VERSION.to_be_bytes())?;
+        }
+        Ok(Writer { out, format })
+    }
+
+    pub fn write(&mut self, record: &Record) -> crate::Result<()> {
+        match self.format {
+            Format::JsonLines => {
+                let line = to_json(record)?;
+                writeln!(self.out, "{}", line)?;
+            }
+            Format::Snapshot => {
+                let mut buf = vec![1];
+                buf.extend_from_slice(&(record.db as u32).to_be_bytes());
+                put_bytes(&mut buf, &record.key);
+                put_bytes(&mut buf, record.type_name.as_bytes());
+                let ttl = record.ttl.map_or(-1, |ttl| ttl as i64);
+                buf.extend_from_slice(&ttl.to_be_bytes());
+                put_bytes(&mut buf, &record.payload);
+                self.out.write_all(&buf)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the end marker of snapshots and flushes.
+    pub fn finish(mut self) -> crate::Result<()> {
+        if self.format == Format::Snapshot {
+            self.out.write_all(&[0])?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(mut input: R, format: Format) -> crate::Result<Reader<R>> {
+        if format == Format::Snapshot {
+            let mut header = [0; 12];
+            input
+                .read_exact(&mut header)
+                .map_err(|_| "not a snapshot file")?;
+            if &header[..8] != SNAPSHOT_MAGIC {
+                return Err("not a snapshot file".into());
+            }
+            let version = u32::from_be_bytes(header[8..].try_into().unwrap());
+            if version > SNAPSHOT_VERSION {
+                return Err(format!("unsupported snapshot version {}", version).into());
+            }
+        }
+
+        Ok(Reader {
+            input,
+            format,
+            position: 0,
+            done: false,
+        })
+    }
+
+    /// The next record, `None` once every one was read.
+    pub fn next(&mut self) -> crate::Result<Option<Record>> {
+        if self.done {
+            return Ok(None);
+        }
+        self.position += 1;
+
+        let record = match self.format {
+            Format::JsonLines => self.next_json(),
+            Format::Snapshot => self.next_snapshot(),
+        };
+        record.map_err(|err| format!("record {}: {}", self.position, err).into())
+    }
+
+    fn next_json(&mut self) -> crate::Result<Option<Record>> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.input.read_line(&mut line)? == 0 {
+                self.done = true;
+                return Ok(None);
+            }
+            if !line.trim().is_empty() {
+                return from_json(&line).map(Some);
+            }
+            self.position += 1;
+        }
+    }
+
+    fn next_snapshot(&mut self) -> crate::Result<Option<Record>> {
+        let truncated = |_| "truncated snapshot file";
+
+        let mut tag = [0];
+        self.input.read_exact(&mut tag).map_err(truncated)?;
+        if tag[0] == 0 {
+            self.done = true;
+            return Ok(None);
+        }
+
+        let db = read_u32(&mut self.input).map_err(truncated)? as usize;
+        let key = read_bytes(&mut self.input).map_err(truncated)?;
+        let type_name =
+            String::from_utf8(read_bytes(&mut self.input).map_err(truncated)?.to_vec())?;
+        let mut ttl = [0; 8];
+        self.input.read_exact(&mut ttl).map_err(truncated)?;
+        let ttl = i64::from_be_bytes(ttl);
+        let payload = read_bytes(&mut self.input).map_err(truncated)?;
+
+        Ok(Some(Record {
+            db,
+            key,
+            type_name,
+            ttl: u64::try_from(ttl).ok(),
+            payload,
+        }))
+    }
+}
+
+fn to_json(record: &Record) -> crate::Result<String> {
+    let mut object = Map::new();
+    object.insert("db".to_string(), json!(record.db));
+    insert_bytes(&mut object, "key", &record.key);
+    object.insert("type".to_string(), json!(record.type_name));
+    if let Some(ttl) = record.ttl {
+        object.insert("ttl".to_string(), json!(ttl));
+    }
+
+    // Only strings are known to the tool, module values are loaded by their
+    // module.
+    match dump::deserialize(&record.payload, &Modules::default()) {
+        Ok(Entry {
+            value: Value::String(value),
+            flags,
+            ..
+        }) => {
+            insert_bytes(&mut object, "value", &value);
+            if flags != 0 {
+                object.insert("flags".to_string(), json!(flags));
+            }
+        }
+        _ => {
+            object.insert("dump".to_string(), json!(BASE64.encode(&record.payload)));
+        }
+    }
+
+    Ok(Json::Object(object).to_string())
+}
+
+fn from_json(line: &str) -> crate::Result<Record> {
+    let object: Map<String, Json> = serde_json::from_str(line)?;
+
+    let db = match object.get("db") {
+        Some(db) => db.as_u64().ok_or("invalid 'db'")? as usize,
+        None => 0,
+    };
+    let key = get_bytes(&object, "key")?.ok_or("missing 'key'")?;
+    let ttl = match object.get("ttl") {
+        Some(ttl) => Some(ttl.as_u64().ok_or("invalid 'ttl'")?),
+        None => None,
+    };
+
+    let (type_name, payload) = if let Some(value) = get_bytes(&object, "value")? {
+        let flags = match object.get("flags") {
+            Some(flags) => flags
+                .as_u64()
+                .and_then(|flags| u32::try_from(flags).ok())
+                .ok_or("invalid 'flags'")?,
+            None => 0,
+        };
+        let mut entry = Entry::new(Value::String(value));
+        entry.flags = flags;
+        ("string".to_string(), dump::serialize(&entry))
+    } else if let Some(data) = object.get("dump") {
+        let data = data.as_str().ok_or("invalid 'dump'")?;
+        let type_name = object
+            .get("type")
+            .and_then(Json::as_str)
+            .unwrap_or_default();
+        (type_name.to_string(), BASE64.decode(data)?.into())
+    } else {
+        return Err("missing 'value' or 'dump'".into());
+    };
+
+    Ok(Record {
+        db,
+        key,
+        type_name,
+        ttl,
+        payload,
+    })
+}
+
+/// Inserts `bytes` as `name`, or base64 encoded as `name_base64` when not
+/// valid UTF-8.
+fn insert_bytes(object: &mut Map<String, Json>, name: &str, bytes: &[u8]) {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => object.insert(name.to_string(), json!(text)),
+        Err(_) => object.insert(format!("{}_base64", name), json!(BASE64.encode(bytes))),
+    };
+}
+
+/// Reads back what `insert_bytes` inserted.
+fn get_bytes(object: &Map<String, Json>, name: &str) -> crate::Result<Option<Bytes>> {
+    if let Some(value) = object.get(name) {
+        let text = value
+            .as_str()
+            .ok_or_else(|| format!("invalid '{}'", name))?;
+        return Ok(Some(Bytes::copy_from_slice(text.as_bytes())));
+    }
+
+    let encoded_name = format!("{}_base64", name);
+    match object.get(&encoded_name) {
+        Some(value) => {
+            let encoded = value
+                .as_str()
+                .ok_or_else(|| format!("invalid '{}'", encoded_name))?;
+            Ok(Some(BASE64.decode(encoded)?.into()))
+        }
+        None => Ok(None),
+    }
+}
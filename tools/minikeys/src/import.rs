@@ -0,0 +1,103 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+use miniredis::pattern;
+use miniredis_client::{Client, Cmd, Frame, Pipeline};
+
+use crate::format::{Reader, Record};
+use crate::Options;
+
+/// Keys restored by each pipeline, at most.
+const BATCH: usize = 1000;
+
+pub async fn run(options: Options) -> crate::Result<()> {
+    let input: Box<dyn BufRead> = match &options.input[..] {
+        "-" => Box::new(io::stdin().lock()),
+        path => Box::new(BufReader::new(File::open(path)?)),
+    };
+    let mut reader = Reader::new(input, options.format)?;
+
+    let mut client = Client::connect(&options.addr[..]).await?;
+    let mut selected = None;
+    let mut batch = vec![];
+    let mut imported = 0;
+    let mut failed = 0;
+
+    while let Some(record) = reader.next()? {
+        if !wanted(&record, &options) {
+            continue;
+        }
+
+        // Every batch holds keys of a single database.
+        if selected != Some(record.db) || batch.len() == BATCH {
+            let (ok, err) = restore(&mut client, &batch, &options).await?;
+            imported += ok;
+            failed += err;
+            batch.clear();
+
+            if selected != Some(record.db) {
+                client.select(record.db).await?;
+                selected = Some(record.db);
+            }
+        }
+        batch.push(record);
+    }
+    let (ok, err) = restore(&mut client, &batch, &options).await?;
+    imported += ok;
+    failed += err;
+
+    eprintln!("imported {} keys, {} failed", imported, failed);
+    if failed > 0 {
+        return Err("some keys could not be imported".into());
+    }
+    Ok(())
+}
+
+fn wanted(record: &Record, options: &Options) -> bool {
+    options.db.is_none_or(|db| db == record.db)
+        && options
+            .pattern
+            .as_ref()
+            .is_none_or(|p| pattern::matches(p.as_bytes(), &record.key))
+}
+
+/// Restores `records` in the selected database, returning how many were
+/// restored and how many failed. Failures are reported as they are found.
+async fn restore(
+    client: &mut Client,
+    records: &[Record],
+    options: &Options,
+) -> crate::Result<(usize, usize)> {
+    if records.is_empty() {
+        return Ok((0, 0));
+    }
+
+    let mut pipeline = Pipeline::new();
+    for record in records {
+        // The time to live is sent relative to now, the expiration time
+        // in the payload is the one of the exporting server. Keys without
+        // one are restored with 0, keeping the payload's, which is unset.
+        let mut restore = Cmd::new("RESTORE")
+            .arg(&record.key)
+            .arg(&record.ttl.map_or(0, |ttl| ttl.max(1)))
+            .arg(&record.payload);
+        if options.replace {
+            restore = restore.arg("REPLACE");
+        }
+        pipeline.add(restore);
+    }
+    let replies = pipeline.query(client).await?;
+
+    let mut restored = 0;
+    let mut failed = 0;
+    for (record, reply) in records.iter().zip(replies) {
+        match reply {
+            Frame::Error(msg) => {
+                eprintln!("db {} key {:?}: {}", record.db, record.key, msg);
+                failed += 1;
+            }
+            _ => restored += 1,
+        }
+    }
+    Ok((restored, failed))
+}
@@ -0,0 +1,124 @@
+//! Exports the keyspace of a running miniredis server, and imports it back.
+//!
+//! ```text
+//! minikeys export --addr 127.0.0.1:6379 --match 'user:*' --output users.jsonl
+//! minikeys import --addr 127.0.0.1:6380 --input users.jsonl
+//! ```
+//!
+//! Keys are read with `SCAN` and `DUMP` and written back with `RESTORE`, so
+//! values of every type are exported along with their time to live. The
+//! export is not a point in time copy: keys changed while it runs may be
+//! exported before or after the change.
+//!
+//! Two formats are supported, see `format`: JSON lines, one key per line
+//! with strings in readable form so the file can be edited or sanitized,
+//! and snapshot files holding `DUMP` payloads as they are.
+
+mod export;
+mod format;
+mod import;
+
+use std::process;
+
+use format::Format;
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+const USAGE: &str = "\
+Usage: minikeys <export|import> [OPTIONS]
+
+Options:
+  --addr <host:port>        Server to export from or import to (default: 127.0.0.1:6379).
+  --format <jsonl|snapshot> Format of the file (default: jsonl).
+  --match <pattern>         Only keys matching the glob-style pattern.
+  --db <n>                  Only keys of database n (default: every database).
+  --output <file>           File to export to, - for stdout (default: -).
+  --input <file>            File to import from, - for stdin (default: -).
+  --replace                 Overwrite keys that already exist when importing.";
+
+/// What to export or import, and where.
+#[derive(Debug)]
+pub struct Options {
+    pub addr: String,
+    pub format: Format,
+    pub pattern: Option<String>,
+    pub db: Option<usize>,
+    pub output: String,
+    pub input: String,
+    pub replace: bool,
+}
+
+#[tokio::main]
+async fn main() {
+    let mut args = std::env::args().skip(1);
+    let command = args.next();
+
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            process::exit(1);
+        }
+    };
+
+    let res = match command.as_deref() {
+        Some("export") => export::run(options).await,
+        Some("import") => import::run(options).await,
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = res {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> std::result::Result<Options, String> {
+    let mut options = Options {
+        addr: "127.0.0.1:6379".to_string(),
+        format: Format::JsonLines,
+        pattern: None,
+        db: None,
+        output: "-".to_string(),
+        input: "-".to_string(),
+        replace: false,
+    };
+
+    while let Some(name) = args.next() {
+        if name == "--replace" {
+            options.replace = true;
+            continue;
+        }
+
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for '{}'", name))?;
+        match &name[..] {
+            "--addr" => options.addr = value,
+            "--format" => {
+                options.format = match &value[..] {
+                    "jsonl" => Format::JsonLines,
+                    "snapshot" => Format::Snapshot,
+                    _ => return Err(format!("unknown format '{}'", value)),
+                }
+            }
+            "--match" => options.pattern = Some(value),
+            "--db" => {
+                let db = value
+                    .parse()
+                    .map_err(|_| format!("invalid value '{}' for '{}'", value, name))?;
+                options.db = Some(db);
+            }
+            "--output" => options.output = value,
+            "--input" => options.input = value,
+            _ => return Err(format!("unknown option '{}'", name)),
+        }
+    }
+
+    Ok(options)
+}